@@ -0,0 +1,631 @@
+//! runbot-desktop 消息记录命令行工具
+//!
+//! 以只读方式打开桌面端写入的 runbot.db，桌面端（或后台服务）运行时也可以使用
+//!
+//! 用法：runbot-desktop-cli [--data-dir 目录] [--account 账号] [--db 数据库文件] <命令> [参数]
+//!
+//! 命令：
+//!   accounts                                         列出有消息记录的账号
+//!   conversations [--limit N]                        列出会话（按最后一条消息时间倒序）
+//!   tail [--user ID | --group ID] [-n N] [--follow]  显示最近的消息，--follow 持续输出新消息
+//!   search <关键词> [--limit N] [--offset N]          全文搜索（FTS5 语法）
+//!   export [--since 时间] [--until 时间] [--user ID | --group ID] [--output 文件]
+//!                                                    按时间范围导出为 JSON Lines
+//!   stats                                            消息统计（同 get_message_stats）
+//!
+//! 时间可以是 Unix 时间戳（秒）、YYYY-MM-DD 或 "YYYY-MM-DD HH:MM:SS"（本地时间）
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use chrono::{Local, NaiveDate, NaiveDateTime, TimeZone};
+use rusqlite::{params, params_from_iter, Connection, OpenFlags, Row, ToSql};
+use serde_json::Value;
+
+/// 与 tauri.conf.json 中的 identifier 一致，决定应用数据目录
+const APP_IDENTIFIER: &str = "com.niuhuan.runbot-desktop";
+/// --follow 时检查新消息的间隔
+const FOLLOW_INTERVAL: Duration = Duration::from_secs(1);
+
+struct Args {
+    data_dir: Option<PathBuf>,
+    account: Option<i64>,
+    db: Option<PathBuf>,
+    command: Command,
+}
+
+/// 按会话过滤
+#[derive(Debug, Clone, Copy)]
+enum Peer {
+    User(i64),
+    Group(i64),
+}
+
+enum Command {
+    Accounts,
+    Conversations { limit: u32 },
+    Tail { peer: Option<Peer>, count: u32, follow: bool },
+    Search { query: String, limit: u32, offset: u32 },
+    Export { since: Option<i64>, until: Option<i64>, peer: Option<Peer>, output: Option<PathBuf> },
+    Stats,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut data_dir = None;
+    let mut account = None;
+    let mut db = None;
+    let mut command: Option<String> = None;
+    let mut positional: Vec<String> = Vec::new();
+    let mut limit = None;
+    let mut offset = None;
+    let mut count = None;
+    let mut follow = false;
+    let mut peer = None;
+    let mut since = None;
+    let mut until = None;
+    let mut output = None;
+
+    let mut iter = std::env::args().skip(1);
+    while let Some(arg) = iter.next() {
+        let mut value = || iter.next().ok_or_else(|| format!("{} 缺少参数值", arg));
+        match arg.as_str() {
+            "--data-dir" => data_dir = Some(PathBuf::from(value()?)),
+            "--account" => account = Some(parse_id(&value()?)?),
+            "--db" => db = Some(PathBuf::from(value()?)),
+            "--limit" => limit = Some(parse_number(&value()?)?),
+            "--offset" => offset = Some(parse_number(&value()?)?),
+            "-n" => count = Some(parse_number(&value()?)?),
+            "--follow" | "-f" => follow = true,
+            "--user" => peer = Some(Peer::User(parse_id(&value()?)?)),
+            "--group" => peer = Some(Peer::Group(parse_id(&value()?)?)),
+            "--since" => since = Some(parse_time(&value()?)?),
+            "--until" => until = Some(parse_time(&value()?)?),
+            "--output" | "-o" => output = Some(PathBuf::from(value()?)),
+            "--help" | "-h" => return Err(usage()),
+            _ if arg.starts_with('-') => return Err(format!("未知参数: {}", arg)),
+            _ if command.is_none() => command = Some(arg),
+            _ => positional.push(arg),
+        }
+    }
+
+    let command = match command.as_deref() {
+        Some("accounts") => Command::Accounts,
+        Some("conversations") => Command::Conversations { limit: limit.unwrap_or(50) },
+        Some("tail") => Command::Tail { peer, count: count.unwrap_or(20), follow },
+        Some("search") => {
+            if positional.is_empty() {
+                return Err("search 缺少关键词".to_string());
+            }
+            Command::Search {
+                query: positional.join(" "),
+                limit: limit.unwrap_or(50),
+                offset: offset.unwrap_or(0),
+            }
+        }
+        Some("export") => Command::Export { since, until, peer, output },
+        Some("stats") => Command::Stats,
+        Some(other) => return Err(format!("未知命令: {}\n\n{}", other, usage())),
+        None => return Err(usage()),
+    };
+    Ok(Args { data_dir, account, db, command })
+}
+
+fn usage() -> String {
+    "用法：runbot-desktop-cli [--data-dir 目录] [--account 账号] [--db 数据库文件] <命令>\n\
+     命令：accounts | conversations | tail | search <关键词> | export | stats"
+        .to_string()
+}
+
+fn parse_id(value: &str) -> Result<i64, String> {
+    value.parse().map_err(|e| format!("ID 无效 {}: {}", value, e))
+}
+
+fn parse_number(value: &str) -> Result<u32, String> {
+    value.parse().map_err(|e| format!("数量无效 {}: {}", value, e))
+}
+
+/// 解析时间为秒级时间戳：Unix 时间戳、YYYY-MM-DD 或 YYYY-MM-DD HH:MM:SS（本地时间）
+fn parse_time(value: &str) -> Result<i64, String> {
+    if let Ok(timestamp) = value.parse::<i64>() {
+        return Ok(timestamp);
+    }
+    let datetime = NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S")
+        .or_else(|_| NaiveDate::parse_from_str(value, "%Y-%m-%d").map(|date| date.and_hms_opt(0, 0, 0).unwrap()))
+        .map_err(|_| format!("时间格式无效: {}", value))?;
+    Local
+        .from_local_datetime(&datetime)
+        .earliest()
+        .map(|datetime| datetime.timestamp())
+        .ok_or_else(|| format!("本地时间不存在: {}", value))
+}
+
+fn format_time(timestamp: i64) -> String {
+    Local
+        .timestamp_opt(timestamp, 0)
+        .single()
+        .map(|datetime| datetime.format("%Y-%m-%d %H:%M:%S").to_string())
+        .unwrap_or_else(|| timestamp.to_string())
+}
+
+/// 桌面端的应用数据目录（与 Tauri 的 app_data_dir 相同）
+fn default_data_dir() -> Result<PathBuf, String> {
+    let base = if cfg!(target_os = "windows") {
+        std::env::var_os("APPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        std::env::var_os("HOME").map(|home| PathBuf::from(home).join("Library/Application Support"))
+    } else {
+        std::env::var_os("XDG_DATA_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share")))
+    };
+    base.map(|base| base.join(APP_IDENTIFIER))
+        .ok_or_else(|| "无法确定应用数据目录，请使用 --data-dir 指定".to_string())
+}
+
+/// 数据目录下有消息数据库的账号（user_{账号}/runbot.db）
+fn list_accounts(data_dir: &Path) -> Result<Vec<i64>, String> {
+    let entries = std::fs::read_dir(data_dir).map_err(|e| format!("读取数据目录 {:?} 失败: {}", data_dir, e))?;
+    let mut accounts: Vec<i64> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            let account = name.strip_prefix("user_")?.parse().ok()?;
+            entry.path().join("runbot.db").exists().then_some(account)
+        })
+        .collect();
+    accounts.sort();
+    Ok(accounts)
+}
+
+/// 确定数据库文件：--db 优先，其次 --account；只有一个账号时可以省略
+fn resolve_db(args: &Args, data_dir: &Path) -> Result<PathBuf, String> {
+    if let Some(db) = &args.db {
+        return Ok(db.clone());
+    }
+    let account = match args.account {
+        Some(account) => account,
+        None => match list_accounts(data_dir)?.as_slice() {
+            [account] => *account,
+            [] => return Err(format!("{:?} 下没有消息数据库", data_dir)),
+            accounts => {
+                let accounts: Vec<String> = accounts.iter().map(i64::to_string).collect();
+                return Err(format!("有多个账号（{}），请使用 --account 指定", accounts.join("、")));
+            }
+        },
+    };
+    let path = data_dir.join(format!("user_{}", account)).join("runbot.db");
+    if !path.exists() {
+        return Err(format!("账号 {} 没有消息数据库: {:?}", account, path));
+    }
+    Ok(path)
+}
+
+/// 以只读方式打开数据库：不会创建或修改任何表，桌面端写入时最多等待 5 秒
+///
+/// 桌面端使用 WAL 模式，读取的是一致的快照，不会阻塞桌面端写入
+fn open_database(path: &Path) -> Result<Connection, String> {
+    let conn = Connection::open_with_flags(
+        path,
+        OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX | OpenFlags::SQLITE_OPEN_URI,
+    )
+    .map_err(|e| format!("打开数据库 {:?} 失败: {}", path, e))?;
+    conn.busy_timeout(Duration::from_secs(5))
+        .map_err(|e| format!("设置等待超时失败: {}", e))?;
+    conn.pragma_update(None, "query_only", true)
+        .map_err(|e| format!("设置只读失败: {}", e))?;
+
+    let journal_mode: String = conn
+        .query_row("PRAGMA journal_mode", [], |row| row.get(0))
+        .map_err(|e| format!("读取日志模式失败: {}", e))?;
+    if !journal_mode.eq_ignore_ascii_case("wal") {
+        eprintln!("提示：数据库未启用 WAL（{}），读取期间桌面端写入可能需要短暂等待", journal_mode);
+    }
+    Ok(conn)
+}
+
+/// messages 表中的一行
+struct MessageRow {
+    rowid: i64,
+    timestamp: i64,
+    post_type: String,
+    message_type: Option<String>,
+    user_id: Option<i64>,
+    group_id: Option<i64>,
+    text: Option<String>,
+    recalled: bool,
+}
+
+const MESSAGE_COLUMNS: &str =
+    "rowid, timestamp, post_type, message_type, user_id, group_id, COALESCE(raw_message, content), recalled";
+
+/// 全文搜索时与 FTS 表连接，列名需要带上表别名
+const SEARCH_COLUMNS: &str =
+    "m.rowid, m.timestamp, m.post_type, m.message_type, m.user_id, m.group_id, COALESCE(m.raw_message, m.content), m.recalled";
+
+fn message_row(row: &Row<'_>) -> rusqlite::Result<MessageRow> {
+    Ok(MessageRow {
+        rowid: row.get(0)?,
+        timestamp: row.get(1)?,
+        post_type: row.get(2)?,
+        message_type: row.get(3)?,
+        user_id: row.get(4)?,
+        group_id: row.get(5)?,
+        text: row.get(6)?,
+        recalled: row.get::<_, Option<i64>>(7)?.unwrap_or(0) != 0,
+    })
+}
+
+fn print_message(message: &MessageRow) {
+    let conversation = match (message.message_type.as_deref(), message.group_id) {
+        (_, Some(group_id)) => format!("群 {}", group_id),
+        (Some("private"), _) => format!("私聊 {}", message.user_id.unwrap_or_default()),
+        _ => message.post_type.clone(),
+    };
+    let sender = message.user_id.map(|id| id.to_string()).unwrap_or_default();
+    let text = match message.text.as_deref() {
+        Some(text) if !text.is_empty() => text.replace('\n', " "),
+        // 通知等没有文本内容的事件
+        _ => format!("[{}]", message.post_type),
+    };
+    let recalled = if message.recalled { "（已撤回）" } else { "" };
+    println!(
+        "[{}] {} {}: {}{}",
+        format_time(message.timestamp),
+        conversation,
+        sender,
+        text,
+        recalled
+    );
+}
+
+/// 按会话过滤的条件和参数
+fn peer_filter(peer: Option<Peer>) -> (String, Vec<i64>) {
+    match peer {
+        Some(Peer::User(user_id)) => (" AND message_type = 'private' AND user_id = ?".to_string(), vec![user_id]),
+        Some(Peer::Group(group_id)) => (" AND group_id = ?".to_string(), vec![group_id]),
+        None => (String::new(), Vec::new()),
+    }
+}
+
+fn query_messages(conn: &Connection, sql: &str, params: &[&dyn ToSql]) -> Result<Vec<MessageRow>, String> {
+    let mut stmt = conn.prepare(sql).map_err(|e| format!("准备查询失败: {}", e))?;
+    let rows = stmt
+        .query_map(params_from_iter(params.iter().copied()), message_row)
+        .map_err(|e| format!("执行查询失败: {}", e))?;
+    rows.collect::<rusqlite::Result<Vec<_>>>()
+        .map_err(|e| format!("读取行失败: {}", e))
+}
+
+fn conversations(conn: &Connection, limit: u32) -> Result<(), String> {
+    // 聚合查询中的裸列取自 MAX(timestamp) 所在的行，即最后一条消息
+    let mut stmt = conn
+        .prepare(
+            "SELECT message_type, COALESCE(group_id, user_id) AS peer, COUNT(*), MAX(timestamp),
+                    COALESCE(raw_message, content)
+             FROM messages
+             WHERE message_type IN ('private', 'group')
+             GROUP BY message_type, peer
+             ORDER BY MAX(timestamp) DESC
+             LIMIT ?1",
+        )
+        .map_err(|e| format!("准备查询失败: {}", e))?;
+    let rows = stmt
+        .query_map(params![limit], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, Option<i64>>(1)?,
+                row.get::<_, i64>(2)?,
+                row.get::<_, i64>(3)?,
+                row.get::<_, Option<String>>(4)?,
+            ))
+        })
+        .map_err(|e| format!("执行查询失败: {}", e))?;
+
+    for row in rows {
+        let (message_type, peer, count, last_time, last_text) = row.map_err(|e| format!("读取行失败: {}", e))?;
+        let kind = if message_type == "group" { "群" } else { "私聊" };
+        let preview: String = last_text.unwrap_or_default().replace('\n', " ").chars().take(40).collect();
+        println!(
+            "{} {}\t{} 条\t{}\t{}",
+            kind,
+            peer.unwrap_or_default(),
+            count,
+            format_time(last_time),
+            preview
+        );
+    }
+    Ok(())
+}
+
+fn tail(conn: &Connection, peer: Option<Peer>, count: u32, follow: bool) -> Result<(), String> {
+    let (filter, filter_params) = peer_filter(peer);
+    let sql = format!(
+        "SELECT {} FROM messages WHERE 1=1{} ORDER BY timestamp DESC, rowid DESC LIMIT ?",
+        MESSAGE_COLUMNS, filter
+    );
+    let mut params: Vec<&dyn ToSql> = filter_params.iter().map(|p| p as &dyn ToSql).collect();
+    params.push(&count);
+    let mut messages = query_messages(conn, &sql, &params)?;
+    messages.reverse();
+    for message in &messages {
+        print_message(message);
+    }
+    if !follow {
+        return Ok(());
+    }
+
+    // 新写入的消息 rowid 递增
+    let mut last_rowid = messages.iter().map(|m| m.rowid).max().unwrap_or(0);
+    if last_rowid == 0 {
+        last_rowid = conn
+            .query_row("SELECT COALESCE(MAX(rowid), 0) FROM messages", [], |row| row.get(0))
+            .map_err(|e| format!("执行查询失败: {}", e))?;
+    }
+    let sql = format!(
+        "SELECT {} FROM messages WHERE rowid > ?{} ORDER BY rowid",
+        MESSAGE_COLUMNS, filter
+    );
+    loop {
+        std::thread::sleep(FOLLOW_INTERVAL);
+        let mut params: Vec<&dyn ToSql> = vec![&last_rowid];
+        params.extend(filter_params.iter().map(|p| p as &dyn ToSql));
+        let messages = query_messages(conn, &sql, &params)?;
+        for message in &messages {
+            print_message(message);
+        }
+        if let Some(message) = messages.last() {
+            last_rowid = message.rowid;
+        }
+        let _ = std::io::stdout().flush();
+    }
+}
+
+fn search(conn: &Connection, query: &str, limit: u32, offset: u32) -> Result<(), String> {
+    // 与 storage::search_messages 相同的全文搜索
+    let sql = format!(
+        "SELECT {} FROM messages m
+         JOIN messages_rowid_map rmap ON m.local_message_id = rmap.local_message_id
+         JOIN messages_fts fts ON rmap.rowid = fts.rowid
+         WHERE messages_fts MATCH ?1
+         ORDER BY m.timestamp DESC
+         LIMIT ?2 OFFSET ?3",
+        SEARCH_COLUMNS
+    );
+    let messages = query_messages(conn, &sql, &[&query, &limit, &offset])?;
+    for message in &messages {
+        print_message(message);
+    }
+    eprintln!("共 {} 条", messages.len());
+    Ok(())
+}
+
+fn export(
+    conn: &Connection,
+    since: Option<i64>,
+    until: Option<i64>,
+    peer: Option<Peer>,
+    output: Option<&Path>,
+) -> Result<(), String> {
+    let (filter, filter_params) = peer_filter(peer);
+    let sql = format!(
+        "SELECT data, recalled FROM messages WHERE timestamp >= ? AND timestamp < ?{} ORDER BY timestamp, rowid",
+        filter
+    );
+    let since = since.unwrap_or(i64::MIN);
+    let until = until.unwrap_or(i64::MAX);
+    let mut params: Vec<&dyn ToSql> = vec![&since, &until];
+    params.extend(filter_params.iter().map(|p| p as &dyn ToSql));
+
+    let mut writer: Box<dyn Write> = match output {
+        Some(path) => Box::new(std::io::BufWriter::new(
+            std::fs::File::create(path).map_err(|e| format!("创建文件 {:?} 失败: {}", path, e))?,
+        )),
+        None => Box::new(std::io::BufWriter::new(std::io::stdout().lock())),
+    };
+
+    let mut stmt = conn.prepare(&sql).map_err(|e| format!("准备查询失败: {}", e))?;
+    let mut rows = stmt
+        .query(params_from_iter(params.iter().copied()))
+        .map_err(|e| format!("执行查询失败: {}", e))?;
+    let mut count = 0u64;
+    while let Some(row) = rows.next().map_err(|e| format!("读取行失败: {}", e))? {
+        let data: String = row.get(0).map_err(|e| format!("读取行失败: {}", e))?;
+        let recalled: Option<i64> = row.get(1).map_err(|e| format!("读取行失败: {}", e))?;
+        // 与 get_messages 相同：在原始数据中加上 recalled 字段
+        let line = match serde_json::from_str::<Value>(&data) {
+            Ok(mut value) => {
+                if let Some(object) = value.as_object_mut() {
+                    object.insert("recalled".to_string(), Value::Bool(recalled.unwrap_or(0) != 0));
+                }
+                value.to_string()
+            }
+            Err(_) => data,
+        };
+        writeln!(writer, "{}", line).map_err(|e| format!("写入失败: {}", e))?;
+        count += 1;
+    }
+    writer.flush().map_err(|e| format!("写入失败: {}", e))?;
+    eprintln!("已导出 {} 条", count);
+    Ok(())
+}
+
+fn stats(conn: &Connection) -> Result<(), String> {
+    // 与 storage::get_message_stats 的统计口径一致
+    let count = |sql: &str| -> Result<i64, String> {
+        conn.query_row(sql, [], |row| row.get(0))
+            .map_err(|e| format!("获取消息统计失败: {}", e))
+    };
+    let stats = serde_json::json!({
+        "total": count("SELECT COUNT(*) FROM messages")?,
+        "messages": count("SELECT COUNT(*) FROM messages WHERE post_type = 'message'")?,
+        "notices": count("SELECT COUNT(*) FROM messages WHERE post_type = 'notice'")?,
+    });
+    println!("{}", serde_json::to_string_pretty(&stats).unwrap_or_default());
+    Ok(())
+}
+
+fn run(args: Args) -> Result<(), String> {
+    let data_dir = match &args.data_dir {
+        Some(dir) => dir.clone(),
+        None => default_data_dir()?,
+    };
+
+    if let Command::Accounts = args.command {
+        for account in list_accounts(&data_dir)? {
+            println!("{}", account);
+        }
+        return Ok(());
+    }
+
+    let conn = open_database(&resolve_db(&args, &data_dir)?)?;
+    match args.command {
+        Command::Accounts => unreachable!(),
+        Command::Conversations { limit } => conversations(&conn, limit),
+        Command::Tail { peer, count, follow } => tail(&conn, peer, count, follow),
+        Command::Search { query, limit, offset } => search(&conn, &query, limit, offset),
+        Command::Export { since, until, peer, output } => export(&conn, since, until, peer, output.as_deref()),
+        Command::Stats => stats(&conn),
+    }
+}
+
+fn main() {
+    let result = parse_args().and_then(run);
+    if let Err(e) = result {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 在临时目录中创建与桌面端相同结构的 messages 表，返回目录和数据库路径
+    fn create_database(name: &str) -> (PathBuf, PathBuf) {
+        let dir = std::env::temp_dir().join(format!("runbot-cli-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("runbot.db");
+        let conn = Connection::open(&path).unwrap();
+        conn.execute_batch(
+            "PRAGMA journal_mode = WAL;
+             CREATE TABLE messages (
+                local_message_id TEXT PRIMARY KEY,
+                timestamp INTEGER NOT NULL,
+                post_type TEXT NOT NULL,
+                message_type TEXT,
+                user_id INTEGER,
+                group_id INTEGER,
+                message_id INTEGER,
+                content TEXT,
+                raw_message TEXT,
+                data TEXT NOT NULL,
+                recalled INTEGER DEFAULT 0,
+                created_at INTEGER DEFAULT (strftime('%s', 'now'))
+             );",
+        )
+        .unwrap();
+        let rows: [(&str, i64, &str, Option<i64>, i64); 4] = [
+            ("a", 100, "private", None, 0),
+            ("b", 200, "group", Some(10), 1),
+            ("c", 300, "private", None, 0),
+            ("d", 400, "group", Some(20), 0),
+        ];
+        for (id, timestamp, message_type, group_id, recalled) in rows {
+            let data = serde_json::json!({ "id": id, "time": timestamp }).to_string();
+            conn.execute(
+                "INSERT INTO messages (local_message_id, timestamp, post_type, message_type, user_id, group_id, raw_message, data, recalled)
+                 VALUES (?1, ?2, 'message', ?3, 1, ?4, ?1, ?5, ?6)",
+                params![id, timestamp, message_type, group_id, data, recalled],
+            )
+            .unwrap();
+        }
+        (dir, path)
+    }
+
+    fn exported(conn: &Connection, dir: &Path, since: Option<i64>, until: Option<i64>, peer: Option<Peer>) -> Vec<Value> {
+        let output = dir.join("export.jsonl");
+        export(conn, since, until, peer, Some(&output)).unwrap();
+        std::fs::read_to_string(&output)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    fn ids(values: &[Value]) -> Vec<&str> {
+        values.iter().map(|value| value["id"].as_str().unwrap()).collect()
+    }
+
+    #[test]
+    fn parses_timestamps_and_local_dates() {
+        assert_eq!(parse_time("1700000000").unwrap(), 1700000000);
+        let date = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap();
+        let midnight = Local.from_local_datetime(&date.and_hms_opt(0, 0, 0).unwrap()).earliest().unwrap();
+        assert_eq!(parse_time("2024-03-01").unwrap(), midnight.timestamp());
+        assert_eq!(parse_time("2024-03-01 08:30:15").unwrap(), midnight.timestamp() + 8 * 3600 + 30 * 60 + 15);
+        assert!(parse_time("2024/03/01").is_err());
+        assert!(parse_time("2024-02-30").is_err());
+        assert!(parse_time("").is_err());
+    }
+
+    #[test]
+    fn filters_by_peer() {
+        let (dir, path) = create_database("peer");
+        let conn = open_database(&path).unwrap();
+        let query = |peer| {
+            let (filter, filter_params) = peer_filter(peer);
+            let sql = format!("SELECT {} FROM messages WHERE 1=1{} ORDER BY rowid", MESSAGE_COLUMNS, filter);
+            let params: Vec<&dyn ToSql> = filter_params.iter().map(|p| p as &dyn ToSql).collect();
+            query_messages(&conn, &sql, &params)
+                .unwrap()
+                .into_iter()
+                .map(|message| message.text.unwrap())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(query(None), ["a", "b", "c", "d"]);
+        assert_eq!(query(Some(Peer::User(1))), ["a", "c"]);
+        assert_eq!(query(Some(Peer::User(2))), Vec::<String>::new());
+        assert_eq!(query(Some(Peer::Group(10))), ["b"]);
+        drop(conn);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn export_includes_since_and_excludes_until() {
+        let (dir, path) = create_database("export");
+        let conn = open_database(&path).unwrap();
+
+        assert_eq!(ids(&exported(&conn, &dir, None, None, None)), ["a", "b", "c", "d"]);
+        assert_eq!(ids(&exported(&conn, &dir, Some(200), Some(400), None)), ["b", "c"]);
+        assert_eq!(ids(&exported(&conn, &dir, Some(201), None, None)), ["c", "d"]);
+        assert_eq!(ids(&exported(&conn, &dir, None, Some(100), None)), Vec::<&str>::new());
+        assert_eq!(ids(&exported(&conn, &dir, Some(100), Some(400), Some(Peer::Group(10)))), ["b"]);
+
+        let values = exported(&conn, &dir, Some(200), Some(201), None);
+        assert_eq!(values[0]["recalled"], Value::Bool(true));
+        drop(conn);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn opens_database_read_only() {
+        let (dir, path) = create_database("readonly");
+        let conn = open_database(&path).unwrap();
+        assert!(conn
+            .execute("INSERT INTO messages (local_message_id, timestamp, post_type, data) VALUES ('e', 500, 'message', '{}')", [])
+            .is_err());
+        assert!(conn.execute("UPDATE messages SET recalled = 1", []).is_err());
+        assert!(conn.execute_batch("CREATE TABLE extra (id INTEGER)").is_err());
+        let count: i64 = conn.query_row("SELECT COUNT(*) FROM messages", [], |row| row.get(0)).unwrap();
+        assert_eq!(count, 4);
+
+        // 不会创建不存在的数据库
+        let missing = dir.join("missing.db");
+        assert!(open_database(&missing).is_err());
+        assert!(!missing.exists());
+        drop(conn);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
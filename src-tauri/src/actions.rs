@@ -0,0 +1,950 @@
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Deserializer, Serialize};
+use tauri::State;
+use crate::runbot::{self, RunbotState};
+
+/// 等待 API 响应的超时时间
+const ACTION_TIMEOUT: tokio::time::Duration = tokio::time::Duration::from_secs(10);
+
+/// OneBot API 调用错误
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ActionError {
+    /// 账号未连接或连接尚未就绪
+    NotReady { message: String },
+    /// 请求发送失败或等待响应超时
+    Request { message: String },
+    /// OneBot 实现返回失败（retcode 非 0）
+    Failed { retcode: i64, message: String, wording: String },
+    /// 响应数据与预期的结构不符
+    InvalidResponse { message: String },
+}
+
+impl fmt::Display for ActionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ActionError::NotReady { message } => write!(f, "连接未就绪: {}", message),
+            ActionError::Request { message } => write!(f, "请求失败: {}", message),
+            ActionError::Failed { retcode, message, wording } => {
+                let reason = if wording.is_empty() { message } else { wording };
+                write!(f, "调用失败 (retcode={}): {}", retcode, reason)
+            }
+            ActionError::InvalidResponse { message } => write!(f, "解析响应失败: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for ActionError {}
+
+/// 调用 OneBot API，并把响应中的 data 解析为指定类型
+pub async fn call<P, R>(
+    state: &Mutex<RunbotState>,
+    account_id: i64,
+    action: &str,
+    params: &P,
+) -> Result<R, ActionError>
+where
+    P: Serialize + ?Sized,
+    R: DeserializeOwned,
+{
+    let bot_ctx = runbot::ready_bot_ctx(state, account_id)
+        .map_err(|message| ActionError::NotReady { message })?;
+    let params = serde_json::to_value(params).map_err(|e| ActionError::Request {
+        message: format!("序列化 {} 参数失败: {}", action, e),
+    })?;
+
+    let response = bot_ctx
+        .websocket_send(action, params)
+        .await
+        .map_err(|e| ActionError::Request { message: e.to_string() })?
+        .response(ACTION_TIMEOUT)
+        .await
+        .map_err(|e| ActionError::Request { message: e.to_string() })?;
+
+    if response.retcode != 0 {
+        tracing::warn!("[actions] {} 失败: retcode={}, message={}", action, response.retcode, response.message);
+        return Err(ActionError::Failed {
+            retcode: response.retcode,
+            message: response.message,
+            wording: response.wording,
+        });
+    }
+
+    serde_json::from_value(response.data).map_err(|e| ActionError::InvalidResponse {
+        message: format!("{}: {}", action, e),
+    })
+}
+
+/// 不关心响应数据的调用（data 通常为 null）
+pub(crate) async fn call_ok<P: Serialize + ?Sized>(
+    state: &Mutex<RunbotState>,
+    account_id: i64,
+    action: &str,
+    params: &P,
+) -> Result<(), ActionError> {
+    call::<P, serde_json::Value>(state, account_id, action, params)
+        .await
+        .map(|_| ())
+}
+
+/// 不同 OneBot 实现对部分字段使用字符串或数字，统一转换为字符串
+fn string_or_number<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<String>, D::Error> {
+    Ok(match Option::<serde_json::Value>::deserialize(deserializer)? {
+        Some(serde_json::Value::String(s)) => Some(s),
+        Some(serde_json::Value::Number(n)) => Some(n.to_string()),
+        _ => None,
+    })
+}
+
+/// go-cqhttp 等实现对空列表返回 null，统一转换为空列表
+fn null_as_empty<'de, D, T>(deserializer: D) -> Result<Vec<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Ok(Option::<Vec<T>>::deserialize(deserializer)?.unwrap_or_default())
+}
+
+// ========== 通用类型 ==========
+
+/// 消息段
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MessageSegment {
+    #[serde(rename = "type")]
+    pub kind: String,
+    #[serde(default)]
+    pub data: serde_json::Map<String, serde_json::Value>,
+}
+
+/// 消息内容：CQ 码字符串或消息段数组
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum MessageContent {
+    Text(String),
+    Segments(Vec<MessageSegment>),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EmptyParams {}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MessageIdParams {
+    pub message_id: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GroupIdParams {
+    pub group_id: i64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub no_cache: Option<bool>,
+}
+
+// ========== 消息 ==========
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SendPrivateMsgParams {
+    pub user_id: i64,
+    pub message: MessageContent,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auto_escape: Option<bool>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SendGroupMsgParams {
+    pub group_id: i64,
+    pub message: MessageContent,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auto_escape: Option<bool>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SendMsgParams {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message_type: Option<String>, // private, group（为空时根据 group_id 判断）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user_id: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group_id: Option<i64>,
+    pub message: MessageContent,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auto_escape: Option<bool>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SendMsgResult {
+    pub message_id: i64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MessageSender {
+    #[serde(default)]
+    pub user_id: i64,
+    #[serde(default)]
+    pub nickname: String,
+    #[serde(default)]
+    pub card: Option<String>,
+    #[serde(default)]
+    pub sex: Option<String>,
+    #[serde(default)]
+    pub age: Option<i32>,
+    #[serde(default)]
+    pub role: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MessageDetail {
+    pub time: i64,
+    pub message_type: String,
+    pub message_id: i64,
+    #[serde(default)]
+    pub real_id: i64,
+    #[serde(default)]
+    pub sender: MessageSender,
+    pub message: MessageContent,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ForwardMsgParams {
+    pub id: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ForwardMessage {
+    /// 转发节点（NapCat 等实现使用 messages 字段）
+    #[serde(alias = "messages")]
+    pub message: Vec<serde_json::Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SendLikeParams {
+    pub user_id: i64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub times: Option<i32>,
+}
+
+/// 发送私聊消息
+#[tauri::command]
+pub async fn send_private_msg(
+    account_id: i64,
+    params: SendPrivateMsgParams,
+    state: State<'_, Arc<Mutex<RunbotState>>>,
+) -> Result<SendMsgResult, ActionError> {
+    call(&state, account_id, "send_private_msg", &params).await
+}
+
+/// 发送群消息
+#[tauri::command]
+pub async fn send_group_msg(
+    account_id: i64,
+    params: SendGroupMsgParams,
+    state: State<'_, Arc<Mutex<RunbotState>>>,
+) -> Result<SendMsgResult, ActionError> {
+    call(&state, account_id, "send_group_msg", &params).await
+}
+
+/// 发送消息
+#[tauri::command]
+pub async fn send_msg(
+    account_id: i64,
+    params: SendMsgParams,
+    state: State<'_, Arc<Mutex<RunbotState>>>,
+) -> Result<SendMsgResult, ActionError> {
+    call(&state, account_id, "send_msg", &params).await
+}
+
+/// 撤回消息
+#[tauri::command]
+pub async fn delete_msg(
+    account_id: i64,
+    params: MessageIdParams,
+    state: State<'_, Arc<Mutex<RunbotState>>>,
+) -> Result<(), ActionError> {
+    call_ok(&state, account_id, "delete_msg", &params).await
+}
+
+/// 获取消息
+#[tauri::command]
+pub async fn get_msg(
+    account_id: i64,
+    params: MessageIdParams,
+    state: State<'_, Arc<Mutex<RunbotState>>>,
+) -> Result<MessageDetail, ActionError> {
+    call(&state, account_id, "get_msg", &params).await
+}
+
+/// 获取合并转发消息
+#[tauri::command]
+pub async fn get_forward_msg(
+    account_id: i64,
+    params: ForwardMsgParams,
+    state: State<'_, Arc<Mutex<RunbotState>>>,
+) -> Result<ForwardMessage, ActionError> {
+    call(&state, account_id, "get_forward_msg", &params).await
+}
+
+/// 发送好友赞
+#[tauri::command]
+pub async fn send_like(
+    account_id: i64,
+    params: SendLikeParams,
+    state: State<'_, Arc<Mutex<RunbotState>>>,
+) -> Result<(), ActionError> {
+    call_ok(&state, account_id, "send_like", &params).await
+}
+
+// ========== 群管理 ==========
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SetGroupKickParams {
+    pub group_id: i64,
+    pub user_id: i64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reject_add_request: Option<bool>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SetGroupBanParams {
+    pub group_id: i64,
+    pub user_id: i64,
+    /// 禁言时长（秒），0 表示取消禁言
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SetGroupAnonymousBanParams {
+    pub group_id: i64,
+    /// 群消息事件中的 anonymous 对象
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub anonymous: Option<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub anonymous_flag: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GroupSwitchParams {
+    pub group_id: i64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub enable: Option<bool>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SetGroupAdminParams {
+    pub group_id: i64,
+    pub user_id: i64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub enable: Option<bool>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SetGroupCardParams {
+    pub group_id: i64,
+    pub user_id: i64,
+    /// 为空表示删除群名片
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub card: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SetGroupNameParams {
+    pub group_id: i64,
+    pub group_name: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SetGroupLeaveParams {
+    pub group_id: i64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub is_dismiss: Option<bool>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SetGroupSpecialTitleParams {
+    pub group_id: i64,
+    pub user_id: i64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub special_title: Option<String>,
+    /// 有效期（秒），-1 表示永久
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration: Option<i64>,
+}
+
+/// 群组踢人
+#[tauri::command]
+pub async fn set_group_kick(
+    account_id: i64,
+    params: SetGroupKickParams,
+    state: State<'_, Arc<Mutex<RunbotState>>>,
+) -> Result<(), ActionError> {
+    call_ok(&state, account_id, "set_group_kick", &params).await
+}
+
+/// 群组单人禁言
+#[tauri::command]
+pub async fn set_group_ban(
+    account_id: i64,
+    params: SetGroupBanParams,
+    state: State<'_, Arc<Mutex<RunbotState>>>,
+) -> Result<(), ActionError> {
+    call_ok(&state, account_id, "set_group_ban", &params).await
+}
+
+/// 群组匿名用户禁言
+#[tauri::command]
+pub async fn set_group_anonymous_ban(
+    account_id: i64,
+    params: SetGroupAnonymousBanParams,
+    state: State<'_, Arc<Mutex<RunbotState>>>,
+) -> Result<(), ActionError> {
+    if params.anonymous.is_none() && params.anonymous_flag.is_none() {
+        return Err(ActionError::Request {
+            message: "anonymous 和 anonymous_flag 至少需要提供一个".to_string(),
+        });
+    }
+    call_ok(&state, account_id, "set_group_anonymous_ban", &params).await
+}
+
+/// 群组全员禁言
+#[tauri::command]
+pub async fn set_group_whole_ban(
+    account_id: i64,
+    params: GroupSwitchParams,
+    state: State<'_, Arc<Mutex<RunbotState>>>,
+) -> Result<(), ActionError> {
+    call_ok(&state, account_id, "set_group_whole_ban", &params).await
+}
+
+/// 群组设置管理员
+#[tauri::command]
+pub async fn set_group_admin(
+    account_id: i64,
+    params: SetGroupAdminParams,
+    state: State<'_, Arc<Mutex<RunbotState>>>,
+) -> Result<(), ActionError> {
+    call_ok(&state, account_id, "set_group_admin", &params).await
+}
+
+/// 群组匿名
+#[tauri::command]
+pub async fn set_group_anonymous(
+    account_id: i64,
+    params: GroupSwitchParams,
+    state: State<'_, Arc<Mutex<RunbotState>>>,
+) -> Result<(), ActionError> {
+    call_ok(&state, account_id, "set_group_anonymous", &params).await
+}
+
+/// 设置群名片
+#[tauri::command]
+pub async fn set_group_card(
+    account_id: i64,
+    params: SetGroupCardParams,
+    state: State<'_, Arc<Mutex<RunbotState>>>,
+) -> Result<(), ActionError> {
+    call_ok(&state, account_id, "set_group_card", &params).await
+}
+
+/// 设置群名
+#[tauri::command]
+pub async fn set_group_name(
+    account_id: i64,
+    params: SetGroupNameParams,
+    state: State<'_, Arc<Mutex<RunbotState>>>,
+) -> Result<(), ActionError> {
+    call_ok(&state, account_id, "set_group_name", &params).await
+}
+
+/// 退出群组
+#[tauri::command]
+pub async fn set_group_leave(
+    account_id: i64,
+    params: SetGroupLeaveParams,
+    state: State<'_, Arc<Mutex<RunbotState>>>,
+) -> Result<(), ActionError> {
+    call_ok(&state, account_id, "set_group_leave", &params).await
+}
+
+/// 设置群组专属头衔
+#[tauri::command]
+pub async fn set_group_special_title(
+    account_id: i64,
+    params: SetGroupSpecialTitleParams,
+    state: State<'_, Arc<Mutex<RunbotState>>>,
+) -> Result<(), ActionError> {
+    call_ok(&state, account_id, "set_group_special_title", &params).await
+}
+
+// ========== 请求处理 ==========
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SetFriendAddRequestParams {
+    pub flag: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub approve: Option<bool>,
+    /// 好友备注（仅在同意时有效）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub remark: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SetGroupAddRequestParams {
+    pub flag: String,
+    /// add 或 invite，需要与请求事件中的 sub_type 一致
+    pub sub_type: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub approve: Option<bool>,
+    /// 拒绝理由（仅在拒绝时有效）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+/// 处理加好友请求
+#[tauri::command]
+pub async fn set_friend_add_request(
+    account_id: i64,
+    params: SetFriendAddRequestParams,
+    state: State<'_, Arc<Mutex<RunbotState>>>,
+) -> Result<(), ActionError> {
+    call_ok(&state, account_id, "set_friend_add_request", &params).await
+}
+
+/// 处理加群请求／邀请
+#[tauri::command]
+pub async fn set_group_add_request(
+    account_id: i64,
+    params: SetGroupAddRequestParams,
+    state: State<'_, Arc<Mutex<RunbotState>>>,
+) -> Result<(), ActionError> {
+    if params.sub_type != "add" && params.sub_type != "invite" {
+        return Err(ActionError::Request {
+            message: format!("sub_type 只能是 add 或 invite: {}", params.sub_type),
+        });
+    }
+    call_ok(&state, account_id, "set_group_add_request", &params).await
+}
+
+// ========== 账号与联系人 ==========
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoginInfo {
+    pub user_id: i64,
+    pub nickname: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StrangerInfoParams {
+    pub user_id: i64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub no_cache: Option<bool>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StrangerInfo {
+    pub user_id: i64,
+    #[serde(default)]
+    pub nickname: String,
+    #[serde(default)]
+    pub sex: Option<String>,
+    #[serde(default)]
+    pub age: Option<i32>,
+    /// QQ 等级（部分实现提供）
+    #[serde(default)]
+    pub level: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FriendInfo {
+    pub user_id: i64,
+    #[serde(default)]
+    pub nickname: String,
+    #[serde(default)]
+    pub remark: String,
+}
+
+/// 获取登录号信息
+#[tauri::command]
+pub async fn get_login_info(
+    account_id: i64,
+    state: State<'_, Arc<Mutex<RunbotState>>>,
+) -> Result<LoginInfo, ActionError> {
+    call(&state, account_id, "get_login_info", &EmptyParams {}).await
+}
+
+/// 获取陌生人信息
+#[tauri::command]
+pub async fn get_stranger_info(
+    account_id: i64,
+    params: StrangerInfoParams,
+    state: State<'_, Arc<Mutex<RunbotState>>>,
+) -> Result<StrangerInfo, ActionError> {
+    call(&state, account_id, "get_stranger_info", &params).await
+}
+
+/// 获取好友列表
+#[tauri::command]
+pub async fn get_friend_list(
+    account_id: i64,
+    state: State<'_, Arc<Mutex<RunbotState>>>,
+) -> Result<Vec<FriendInfo>, ActionError> {
+    call(&state, account_id, "get_friend_list", &EmptyParams {}).await
+}
+
+// ========== 群信息 ==========
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GroupInfo {
+    pub group_id: i64,
+    #[serde(default)]
+    pub group_name: String,
+    #[serde(default)]
+    pub member_count: Option<i64>,
+    #[serde(default)]
+    pub max_member_count: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GroupMemberInfoParams {
+    pub group_id: i64,
+    pub user_id: i64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub no_cache: Option<bool>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GroupMemberInfo {
+    pub group_id: i64,
+    pub user_id: i64,
+    #[serde(default)]
+    pub nickname: String,
+    #[serde(default)]
+    pub card: Option<String>,
+    #[serde(default)]
+    pub sex: Option<String>,
+    #[serde(default)]
+    pub age: Option<i32>,
+    #[serde(default)]
+    pub area: Option<String>,
+    #[serde(default)]
+    pub join_time: Option<i64>,
+    #[serde(default)]
+    pub last_sent_time: Option<i64>,
+    #[serde(default, deserialize_with = "string_or_number")]
+    pub level: Option<String>,
+    #[serde(default)]
+    pub role: Option<String>, // owner, admin, member
+    #[serde(default)]
+    pub unfriendly: Option<bool>,
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub title_expire_time: Option<i64>,
+    #[serde(default)]
+    pub card_changeable: Option<bool>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GroupHonorInfoParams {
+    pub group_id: i64,
+    /// talkative, performer, legend, strong_newbie, emotion 或 all
+    #[serde(rename = "type")]
+    pub honor_type: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CurrentTalkative {
+    pub user_id: i64,
+    #[serde(default)]
+    pub nickname: String,
+    #[serde(default)]
+    pub avatar: String,
+    #[serde(default)]
+    pub day_count: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HonorMember {
+    pub user_id: i64,
+    #[serde(default)]
+    pub nickname: String,
+    #[serde(default)]
+    pub avatar: String,
+    #[serde(default)]
+    pub description: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GroupHonorInfo {
+    pub group_id: i64,
+    #[serde(default)]
+    pub current_talkative: Option<CurrentTalkative>,
+    #[serde(default, deserialize_with = "null_as_empty")]
+    pub talkative_list: Vec<HonorMember>,
+    #[serde(default, deserialize_with = "null_as_empty")]
+    pub performer_list: Vec<HonorMember>,
+    #[serde(default, deserialize_with = "null_as_empty")]
+    pub legend_list: Vec<HonorMember>,
+    #[serde(default, deserialize_with = "null_as_empty")]
+    pub strong_newbie_list: Vec<HonorMember>,
+    #[serde(default, deserialize_with = "null_as_empty")]
+    pub emotion_list: Vec<HonorMember>,
+}
+
+/// 获取群信息
+#[tauri::command]
+pub async fn get_group_info(
+    account_id: i64,
+    params: GroupIdParams,
+    state: State<'_, Arc<Mutex<RunbotState>>>,
+) -> Result<GroupInfo, ActionError> {
+    call(&state, account_id, "get_group_info", &params).await
+}
+
+/// 获取群列表
+#[tauri::command]
+pub async fn get_group_list(
+    account_id: i64,
+    state: State<'_, Arc<Mutex<RunbotState>>>,
+) -> Result<Vec<GroupInfo>, ActionError> {
+    call(&state, account_id, "get_group_list", &EmptyParams {}).await
+}
+
+/// 获取群成员信息
+#[tauri::command]
+pub async fn get_group_member_info(
+    account_id: i64,
+    params: GroupMemberInfoParams,
+    state: State<'_, Arc<Mutex<RunbotState>>>,
+) -> Result<GroupMemberInfo, ActionError> {
+    call(&state, account_id, "get_group_member_info", &params).await
+}
+
+/// 获取群成员列表
+#[tauri::command]
+pub async fn get_group_member_list(
+    account_id: i64,
+    params: GroupIdParams,
+    state: State<'_, Arc<Mutex<RunbotState>>>,
+) -> Result<Vec<GroupMemberInfo>, ActionError> {
+    call(&state, account_id, "get_group_member_list", &params).await
+}
+
+/// 获取群荣誉信息
+#[tauri::command]
+pub async fn get_group_honor_info(
+    account_id: i64,
+    params: GroupHonorInfoParams,
+    state: State<'_, Arc<Mutex<RunbotState>>>,
+) -> Result<GroupHonorInfo, ActionError> {
+    call(&state, account_id, "get_group_honor_info", &params).await
+}
+
+// ========== 凭证 ==========
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DomainParams {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub domain: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Cookies {
+    pub cookies: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CsrfToken {
+    pub token: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Credentials {
+    pub cookies: String,
+    pub csrf_token: i64,
+}
+
+/// 获取 Cookies
+#[tauri::command]
+pub async fn get_cookies(
+    account_id: i64,
+    params: DomainParams,
+    state: State<'_, Arc<Mutex<RunbotState>>>,
+) -> Result<Cookies, ActionError> {
+    call(&state, account_id, "get_cookies", &params).await
+}
+
+/// 获取 CSRF Token
+#[tauri::command]
+pub async fn get_csrf_token(
+    account_id: i64,
+    state: State<'_, Arc<Mutex<RunbotState>>>,
+) -> Result<CsrfToken, ActionError> {
+    call(&state, account_id, "get_csrf_token", &EmptyParams {}).await
+}
+
+/// 获取 QQ 相关接口凭证
+#[tauri::command]
+pub async fn get_credentials(
+    account_id: i64,
+    params: DomainParams,
+    state: State<'_, Arc<Mutex<RunbotState>>>,
+) -> Result<Credentials, ActionError> {
+    call(&state, account_id, "get_credentials", &params).await
+}
+
+// ========== 文件 ==========
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GetRecordParams {
+    pub file: String,
+    /// 要转换到的格式，如 mp3、amr、wav
+    pub out_format: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GetImageParams {
+    pub file: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileInfo {
+    /// 本地文件路径
+    pub file: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct YesNo {
+    pub yes: bool,
+}
+
+/// 获取语音
+#[tauri::command]
+pub async fn get_record(
+    account_id: i64,
+    params: GetRecordParams,
+    state: State<'_, Arc<Mutex<RunbotState>>>,
+) -> Result<FileInfo, ActionError> {
+    call(&state, account_id, "get_record", &params).await
+}
+
+/// 获取图片
+#[tauri::command]
+pub async fn get_image(
+    account_id: i64,
+    params: GetImageParams,
+    state: State<'_, Arc<Mutex<RunbotState>>>,
+) -> Result<FileInfo, ActionError> {
+    call(&state, account_id, "get_image", &params).await
+}
+
+/// 检查是否可以发送图片
+#[tauri::command]
+pub async fn can_send_image(
+    account_id: i64,
+    state: State<'_, Arc<Mutex<RunbotState>>>,
+) -> Result<YesNo, ActionError> {
+    call(&state, account_id, "can_send_image", &EmptyParams {}).await
+}
+
+/// 检查是否可以发送语音
+#[tauri::command]
+pub async fn can_send_record(
+    account_id: i64,
+    state: State<'_, Arc<Mutex<RunbotState>>>,
+) -> Result<YesNo, ActionError> {
+    call(&state, account_id, "can_send_record", &EmptyParams {}).await
+}
+
+// ========== 运行状态 ==========
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BotStatus {
+    #[serde(default)]
+    pub online: Option<bool>,
+    #[serde(default)]
+    pub good: bool,
+    /// 实现自行添加的字段
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VersionInfo {
+    #[serde(default)]
+    pub app_name: String,
+    #[serde(default)]
+    pub app_version: String,
+    #[serde(default)]
+    pub protocol_version: String,
+    /// 实现自行添加的字段
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SetRestartParams {
+    /// 延迟重启（毫秒）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub delay: Option<i64>,
+}
+
+/// 获取运行状态
+#[tauri::command]
+pub async fn get_status(
+    account_id: i64,
+    state: State<'_, Arc<Mutex<RunbotState>>>,
+) -> Result<BotStatus, ActionError> {
+    call(&state, account_id, "get_status", &EmptyParams {}).await
+}
+
+/// 获取版本信息
+#[tauri::command]
+pub async fn get_version_info(
+    account_id: i64,
+    state: State<'_, Arc<Mutex<RunbotState>>>,
+) -> Result<VersionInfo, ActionError> {
+    call(&state, account_id, "get_version_info", &EmptyParams {}).await
+}
+
+/// 重启 OneBot 实现
+#[tauri::command]
+pub async fn set_restart(
+    account_id: i64,
+    params: SetRestartParams,
+    state: State<'_, Arc<Mutex<RunbotState>>>,
+) -> Result<(), ActionError> {
+    call_ok(&state, account_id, "set_restart", &params).await
+}
+
+/// 清理缓存
+#[tauri::command]
+pub async fn clean_cache(
+    account_id: i64,
+    state: State<'_, Arc<Mutex<RunbotState>>>,
+) -> Result<(), ActionError> {
+    call_ok(&state, account_id, "clean_cache", &EmptyParams {}).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn group_honor_info_accepts_null_lists() {
+        let info: GroupHonorInfo = serde_json::from_value(serde_json::json!({
+            "group_id": 1,
+            "current_talkative": null,
+            "talkative_list": null,
+            "performer_list": [{ "user_id": 2, "nickname": "a", "avatar": "", "description": "" }],
+            "legend_list": null,
+        }))
+        .unwrap();
+        assert!(info.talkative_list.is_empty());
+        assert_eq!(info.performer_list.len(), 1);
+        assert!(info.legend_list.is_empty());
+        assert!(info.emotion_list.is_empty());
+    }
+}
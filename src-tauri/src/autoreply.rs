@@ -0,0 +1,722 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use async_trait::async_trait;
+use regex::{Regex, RegexBuilder};
+use rusqlite::{params, Row};
+use runbot::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::Map;
+use tauri::State;
+use crate::AppHandle;
+use crate::actions::{self, MessageContent, SendGroupMsgParams, SendMsgResult, SendPrivateMsgParams};
+use crate::cqcode::{self, MessageChain, Segment};
+use crate::daemon::{self, ReloadTarget};
+use crate::runbot::RunbotState;
+use crate::session::Session;
+use crate::storage;
+
+/// 匹配方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchType {
+    /// 消息文本包含关键词
+    Keyword,
+    /// 消息文本匹配正则表达式
+    Regex,
+    /// 消息中 @ 了自己（pattern 不为空时还需要包含该关键词）
+    AtMe,
+}
+
+impl MatchType {
+    fn as_str(&self) -> &'static str {
+        match self {
+            MatchType::Keyword => "keyword",
+            MatchType::Regex => "regex",
+            MatchType::AtMe => "at_me",
+        }
+    }
+
+    fn parse(value: &str) -> Self {
+        match value {
+            "regex" => MatchType::Regex,
+            "at_me" => MatchType::AtMe,
+            _ => MatchType::Keyword,
+        }
+    }
+}
+
+/// 规则生效的会话范围
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReplyScope {
+    #[default]
+    All,
+    Group,
+    Private,
+}
+
+impl ReplyScope {
+    fn as_str(&self) -> &'static str {
+        match self {
+            ReplyScope::All => "all",
+            ReplyScope::Group => "group",
+            ReplyScope::Private => "private",
+        }
+    }
+
+    fn parse(value: &str) -> Self {
+        match value {
+            "group" => ReplyScope::Group,
+            "private" => ReplyScope::Private,
+            _ => ReplyScope::All,
+        }
+    }
+}
+
+/// 自动回复规则
+///
+/// 回复模板为 CQ 码格式（可以包含表情、图片等），支持变量：{sender}（群名片，没有时为昵称）、
+/// {nickname}、{card}、{user_id}、{group_id}、{message}、{time}、{date}；变量的值按纯文本插入
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AutoReplyRule {
+    /// 新建时为空
+    #[serde(default)]
+    pub id: Option<i64>,
+    pub name: String,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    pub match_type: MatchType,
+    #[serde(default)]
+    pub pattern: String,
+    #[serde(default)]
+    pub ignore_case: bool,
+    #[serde(default)]
+    pub scope: ReplyScope,
+    /// 限定的群号（群聊）或 QQ 号（私聊），为空时不限
+    #[serde(default)]
+    pub target_ids: Vec<i64>,
+    pub reply: String,
+    /// 群聊回复时 @ 发送者
+    #[serde(default)]
+    pub at_sender: bool,
+    /// 回复时引用原消息
+    #[serde(default)]
+    pub quote: bool,
+    /// 同一规则在同一会话中两次回复的最小间隔（秒）
+    #[serde(default)]
+    pub cooldown_secs: i64,
+    /// 优先级高的规则先匹配
+    #[serde(default)]
+    pub priority: i64,
+    #[serde(default)]
+    pub created_at: i64,
+    #[serde(default)]
+    pub updated_at: i64,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+impl AutoReplyRule {
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        let match_type: String = row.get("match_type")?;
+        let scope: String = row.get("scope")?;
+        let target_ids: String = row.get("target_ids")?;
+        Ok(Self {
+            id: row.get("id")?,
+            name: row.get("name")?,
+            enabled: row.get("enabled")?,
+            match_type: MatchType::parse(&match_type),
+            pattern: row.get("pattern")?,
+            ignore_case: row.get("ignore_case")?,
+            scope: ReplyScope::parse(&scope),
+            target_ids: serde_json::from_str(&target_ids).unwrap_or_default(),
+            reply: row.get("reply")?,
+            at_sender: row.get("at_sender")?,
+            quote: row.get("quote")?,
+            cooldown_secs: row.get("cooldown_secs")?,
+            priority: row.get("priority")?,
+            created_at: row.get("created_at")?,
+            updated_at: row.get("updated_at")?,
+        })
+    }
+
+    fn regex(&self) -> Result<Option<Regex>, String> {
+        if self.match_type != MatchType::Regex {
+            return Ok(None);
+        }
+        RegexBuilder::new(&self.pattern)
+            .case_insensitive(self.ignore_case)
+            .build()
+            .map(Some)
+            .map_err(|e| format!("正则表达式无效: {}", e))
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.name.trim().is_empty() {
+            return Err("规则名称不能为空".to_string());
+        }
+        if self.reply.trim().is_empty() {
+            return Err("回复内容不能为空".to_string());
+        }
+        if self.match_type != MatchType::AtMe && self.pattern.is_empty() {
+            return Err("关键词不能为空".to_string());
+        }
+        self.regex().map(|_| ())
+    }
+}
+
+/// 已加载的规则（正则表达式预先编译）
+#[derive(Debug)]
+pub struct LoadedRule {
+    rule: AutoReplyRule,
+    regex: Option<Regex>,
+}
+
+/// 收到的消息中参与匹配的信息
+struct Incoming<'a> {
+    self_id: i64,
+    group_id: Option<i64>,
+    user_id: i64,
+    text: String,
+    at_me: bool,
+    message: &'a runbot::event::Message,
+}
+
+impl LoadedRule {
+    fn matches(&self, incoming: &Incoming) -> bool {
+        let rule = &self.rule;
+        let out_of_scope = matches!(
+            (rule.scope, incoming.group_id),
+            (ReplyScope::Group, None) | (ReplyScope::Private, Some(_))
+        );
+        if out_of_scope {
+            return false;
+        }
+        if !rule.target_ids.is_empty() {
+            let target = incoming.group_id.unwrap_or(incoming.user_id);
+            if !rule.target_ids.contains(&target) {
+                return false;
+            }
+        }
+
+        let contains = |pattern: &str| {
+            if rule.ignore_case {
+                incoming.text.to_lowercase().contains(&pattern.to_lowercase())
+            } else {
+                incoming.text.contains(pattern)
+            }
+        };
+        match rule.match_type {
+            MatchType::Keyword => contains(&rule.pattern),
+            MatchType::Regex => self.regex.as_ref().is_some_and(|re| re.is_match(&incoming.text)),
+            MatchType::AtMe => incoming.at_me && (rule.pattern.is_empty() || contains(&rule.pattern)),
+        }
+    }
+}
+
+/// 按变量替换回复模板并解析为消息链，未知变量保持原样（替换后的内容不会再次替换）
+///
+/// 变量的值经过 CQ 码转义，收到的消息中的 [CQ:...] 文本不会变成消息段
+fn render(template: &str, incoming: &Incoming) -> MessageChain {
+    let sender = &incoming.message.sender;
+    let now = chrono::Local::now();
+    let variable = |name: &str| -> Option<String> {
+        Some(match name {
+            "sender" if sender.card.is_empty() => sender.nickname.clone(),
+            "sender" | "card" => sender.card.clone(),
+            "nickname" => sender.nickname.clone(),
+            "user_id" => incoming.user_id.to_string(),
+            "group_id" => incoming.group_id.map(|id| id.to_string()).unwrap_or_default(),
+            "message" => incoming.text.clone(),
+            "time" => now.format("%H:%M:%S").to_string(),
+            "date" => now.format("%Y-%m-%d").to_string(),
+            _ => return None,
+        })
+    };
+
+    let mut output = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        output.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        match after.find('}').and_then(|end| variable(&after[..end]).map(|value| (end, value))) {
+            Some((end, value)) => {
+                output.push_str(&cqcode::escape(&value, false));
+                rest = &after[end + 1..];
+            }
+            None => {
+                output.push('{');
+                rest = after;
+            }
+        }
+    }
+    output.push_str(rest);
+    MessageChain::parse(&output)
+}
+
+/// 读取账号的全部规则（按优先级从高到低）
+fn query_rules(app: &AppHandle, account_id: i64) -> Result<Vec<AutoReplyRule>, String> {
+    let conn = storage::get_connection(app, Some(account_id))?;
+    let mut stmt = conn
+        .prepare("SELECT * FROM auto_reply_rules ORDER BY priority DESC, id")
+        .map_err(|e| format!("准备查询失败: {}", e))?;
+    let rules = stmt
+        .query_map([], AutoReplyRule::from_row)
+        .map_err(|e| format!("查询自动回复规则失败: {}", e))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("读取自动回复规则失败: {}", e))?;
+    Ok(rules)
+}
+
+/// 规则修改后清除会话中的缓存，下一条消息到达时重新加载
+pub(crate) fn invalidate(state: &Mutex<RunbotState>, account_id: i64) {
+    let session = state.lock().ok().and_then(|state_guard| state_guard.sessions.get(account_id));
+    if let Some(session) = session {
+        if let Ok(mut session_guard) = session.lock() {
+            session_guard.auto_reply_rules = None;
+        }
+    }
+}
+
+/// 自动回复 Processor：排在 TauriEventProcessor 之后，按规则回复收到的消息
+#[derive(Debug)]
+pub struct AutoReplyProcessor {
+    app: AppHandle,
+    registry: Arc<Mutex<RunbotState>>,
+    state: Arc<Mutex<Session>>,
+    cooldowns: Cooldowns,
+}
+
+/// 规则的冷却时间：(规则 id, 群号或 QQ 号) -> 上次成功回复的时间
+#[derive(Debug, Default)]
+struct Cooldowns(Mutex<HashMap<(i64, i64), Instant>>);
+
+impl Cooldowns {
+    /// 规则在该会话中是否仍在冷却中
+    fn is_cooling(&self, rule: &AutoReplyRule, target: i64, now: Instant) -> bool {
+        let Some(id) = rule.id else {
+            return false;
+        };
+        self.0
+            .lock()
+            .unwrap()
+            .get(&(id, target))
+            .is_some_and(|last| now.duration_since(*last).as_secs() < rule.cooldown_secs.max(0) as u64)
+    }
+
+    /// 回复成功后开始冷却
+    fn start(&self, rule: &AutoReplyRule, target: i64, now: Instant) {
+        if let Some(id) = rule.id {
+            self.0.lock().unwrap().insert((id, target), now);
+        }
+    }
+}
+
+impl AutoReplyProcessor {
+    pub fn new(app: AppHandle, registry: Arc<Mutex<RunbotState>>, state: Arc<Mutex<Session>>) -> Self {
+        Self {
+            app,
+            registry,
+            state,
+            cooldowns: Cooldowns::default(),
+        }
+    }
+
+    /// 取出启用的规则，会话中没有缓存时从数据库加载
+    fn rules(&self, account_id: i64) -> Result<Arc<Vec<LoadedRule>>, String> {
+        if let Some(rules) = &self.state.lock().unwrap().auto_reply_rules {
+            return Ok(rules.clone());
+        }
+
+        let mut loaded = Vec::new();
+        for rule in query_rules(&self.app, account_id)? {
+            if !rule.enabled {
+                continue;
+            }
+            match rule.regex() {
+                Ok(regex) => loaded.push(LoadedRule { rule, regex }),
+                Err(e) => tracing::warn!("[AutoReplyProcessor] 跳过规则「{}」: {}", rule.name, e),
+            }
+        }
+        let loaded = Arc::new(loaded);
+        self.state.lock().unwrap().auto_reply_rules = Some(loaded.clone());
+        Ok(loaded)
+    }
+
+    async fn reply(&self, account_id: i64, rule: &AutoReplyRule, incoming: &Incoming<'_>) -> Result<i64, String> {
+        let mut chain = MessageChain::default();
+        if rule.quote {
+            chain.0.push(Segment::Reply {
+                id: incoming.message.message_id.to_string(),
+                extra: Map::new(),
+            });
+        }
+        if rule.at_sender && incoming.group_id.is_some() {
+            chain.0.push(Segment::At {
+                qq: incoming.user_id.to_string(),
+                name: None,
+                extra: Map::new(),
+            });
+            chain.0.push(Segment::text(" "));
+        }
+        chain.0.extend(render(&rule.reply, incoming).0);
+        let message = MessageContent::from(chain);
+
+        let result = match incoming.group_id {
+            Some(group_id) => {
+                let params = SendGroupMsgParams { group_id, message, auto_escape: None };
+                actions::call::<_, SendMsgResult>(&self.registry, account_id, "send_group_msg", &params).await
+            }
+            None => {
+                let params = SendPrivateMsgParams { user_id: incoming.user_id, message, auto_escape: None };
+                actions::call::<_, SendMsgResult>(&self.registry, account_id, "send_private_msg", &params).await
+            }
+        };
+        result.map(|sent| sent.message_id).map_err(|e| e.to_string())
+    }
+}
+
+#[async_trait]
+impl PostProcessor for AutoReplyProcessor {
+    fn id(&self) -> &'static str {
+        "auto_reply"
+    }
+
+    async fn process_post(
+        &self,
+        _bot_ctx: Arc<BotContext>,
+        post: &runbot::event::Post,
+    ) -> anyhow::Result<bool> {
+        let runbot::event::Post::Message(message) = post else {
+            return Ok(false);
+        };
+        let Some(account_id) = self.state.lock().unwrap().account_id else {
+            return Ok(false);
+        };
+        // 不回复自己发出的消息，避免规则互相触发
+        if message.user_id == account_id {
+            return Ok(false);
+        }
+
+        let rules = match self.rules(account_id) {
+            Ok(rules) => rules,
+            Err(e) => {
+                tracing::warn!("[AutoReplyProcessor] 加载自动回复规则失败: {}", e);
+                return Ok(false);
+            }
+        };
+        if rules.is_empty() {
+            return Ok(false);
+        }
+
+        let self_qq = account_id.to_string();
+        let mut text = String::new();
+        let mut at_me = false;
+        for data in &message.message {
+            match data {
+                MessageData::Text(segment) => text.push_str(&segment.text),
+                MessageData::At(at) if at.qq == self_qq => at_me = true,
+                _ => {}
+            }
+        }
+        let incoming = Incoming {
+            self_id: account_id,
+            group_id: matches!(message.message_type, MessageType::Group).then_some(message.group_id),
+            user_id: message.user_id,
+            text: text.trim().to_string(),
+            at_me,
+            message,
+        };
+
+        // 第一条命中的规则生效；该规则仍在冷却中时不回复
+        let Some(matched) = rules.iter().find(|loaded| loaded.matches(&incoming)) else {
+            return Ok(false);
+        };
+        let rule = &matched.rule;
+        let target = incoming.group_id.unwrap_or(incoming.user_id);
+        if self.cooldowns.is_cooling(rule, target, Instant::now()) {
+            tracing::debug!("[AutoReplyProcessor] 规则「{}」冷却中，不回复 {}", rule.name, target);
+            return Ok(false);
+        }
+
+        // 回复失败时不进入冷却，下一条命中的消息可以再次尝试
+        match self.reply(incoming.self_id, rule, &incoming).await {
+            Ok(message_id) => {
+                self.cooldowns.start(rule, target, Instant::now());
+                tracing::info!(
+                    "[AutoReplyProcessor] 规则「{}」已回复 {} (message_id={})",
+                    rule.name,
+                    target,
+                    message_id
+                )
+            }
+            Err(e) => tracing::warn!("[AutoReplyProcessor] 规则「{}」回复失败: {}", rule.name, e),
+        }
+        Ok(false)
+    }
+}
+
+/// 获取自动回复规则（按优先级从高到低）
+#[tauri::command]
+pub async fn get_auto_reply_rules(account_id: i64, app: AppHandle) -> Result<Vec<AutoReplyRule>, String> {
+    query_rules(&app, account_id)
+}
+
+/// 新建（id 为空）或更新自动回复规则，返回保存后的规则
+#[tauri::command]
+pub async fn save_auto_reply_rule(
+    account_id: i64,
+    rule: AutoReplyRule,
+    app: AppHandle,
+    state: State<'_, Arc<Mutex<RunbotState>>>,
+) -> Result<AutoReplyRule, String> {
+    rule.validate().map_err(|e| format!("规则「{}」: {}", rule.name, e))?;
+
+    let conn = storage::get_connection(&app, Some(account_id))?;
+    let target_ids = serde_json::to_string(&rule.target_ids).map_err(|e| format!("序列化规则失败: {}", e))?;
+    let id = match rule.id {
+        Some(id) => {
+            let updated = conn
+                .execute(
+                    "UPDATE auto_reply_rules SET name = ?1, enabled = ?2, match_type = ?3, pattern = ?4,
+                        ignore_case = ?5, scope = ?6, target_ids = ?7, reply = ?8, at_sender = ?9,
+                        quote = ?10, cooldown_secs = ?11, priority = ?12, updated_at = strftime('%s', 'now')
+                     WHERE id = ?13",
+                    params![
+                        rule.name,
+                        rule.enabled,
+                        rule.match_type.as_str(),
+                        rule.pattern,
+                        rule.ignore_case,
+                        rule.scope.as_str(),
+                        target_ids,
+                        rule.reply,
+                        rule.at_sender,
+                        rule.quote,
+                        rule.cooldown_secs,
+                        rule.priority,
+                        id,
+                    ],
+                )
+                .map_err(|e| format!("更新自动回复规则失败: {}", e))?;
+            if updated == 0 {
+                return Err(format!("自动回复规则不存在: {}", id));
+            }
+            id
+        }
+        None => {
+            conn.execute(
+                "INSERT INTO auto_reply_rules (name, enabled, match_type, pattern, ignore_case, scope,
+                    target_ids, reply, at_sender, quote, cooldown_secs, priority)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+                params![
+                    rule.name,
+                    rule.enabled,
+                    rule.match_type.as_str(),
+                    rule.pattern,
+                    rule.ignore_case,
+                    rule.scope.as_str(),
+                    target_ids,
+                    rule.reply,
+                    rule.at_sender,
+                    rule.quote,
+                    rule.cooldown_secs,
+                    rule.priority,
+                ],
+            )
+            .map_err(|e| format!("保存自动回复规则失败: {}", e))?;
+            conn.last_insert_rowid()
+        }
+    };
+
+    invalidate(&state, account_id);
+    daemon::notify_daemon(&app, &state, account_id, ReloadTarget::AutoReply).await?;
+    tracing::info!("[save_auto_reply_rule] 已保存自动回复规则 {}: {}", id, rule.name);
+    conn.query_row("SELECT * FROM auto_reply_rules WHERE id = ?1", params![id], AutoReplyRule::from_row)
+        .map_err(|e| format!("读取自动回复规则失败: {}", e))
+}
+
+/// 启用或停用自动回复规则
+#[tauri::command]
+pub async fn set_auto_reply_rule_enabled(
+    account_id: i64,
+    id: i64,
+    enabled: bool,
+    app: AppHandle,
+    state: State<'_, Arc<Mutex<RunbotState>>>,
+) -> Result<(), String> {
+    let conn = storage::get_connection(&app, Some(account_id))?;
+    conn.execute(
+        "UPDATE auto_reply_rules SET enabled = ?1, updated_at = strftime('%s', 'now') WHERE id = ?2",
+        params![enabled, id],
+    )
+    .map_err(|e| format!("更新自动回复规则失败: {}", e))?;
+    invalidate(&state, account_id);
+    daemon::notify_daemon(&app, &state, account_id, ReloadTarget::AutoReply).await?;
+    Ok(())
+}
+
+/// 删除自动回复规则
+#[tauri::command]
+pub async fn delete_auto_reply_rule(
+    account_id: i64,
+    id: i64,
+    app: AppHandle,
+    state: State<'_, Arc<Mutex<RunbotState>>>,
+) -> Result<(), String> {
+    let conn = storage::get_connection(&app, Some(account_id))?;
+    conn.execute("DELETE FROM auto_reply_rules WHERE id = ?1", params![id])
+        .map_err(|e| format!("删除自动回复规则失败: {}", e))?;
+    invalidate(&state, account_id);
+    daemon::notify_daemon(&app, &state, account_id, ReloadTarget::AutoReply).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use super::*;
+
+    fn rule(match_type: MatchType, pattern: &str) -> AutoReplyRule {
+        AutoReplyRule {
+            id: Some(1),
+            name: "测试".to_string(),
+            enabled: true,
+            match_type,
+            pattern: pattern.to_string(),
+            ignore_case: false,
+            scope: ReplyScope::All,
+            target_ids: Vec::new(),
+            reply: "收到".to_string(),
+            at_sender: false,
+            quote: false,
+            cooldown_secs: 0,
+            priority: 0,
+            created_at: 0,
+            updated_at: 0,
+        }
+    }
+
+    fn loaded(rule: AutoReplyRule) -> LoadedRule {
+        let regex = rule.regex().unwrap();
+        LoadedRule { rule, regex }
+    }
+
+    fn message(nickname: &str, card: &str) -> runbot::event::Message {
+        runbot::event::Message {
+            message_id: 42,
+            sender: runbot::event::Sender {
+                user_id: 30003,
+                nickname: nickname.to_string(),
+                card: card.to_string(),
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    fn incoming<'a>(message: &'a runbot::event::Message, group_id: Option<i64>, text: &str, at_me: bool) -> Incoming<'a> {
+        Incoming { self_id: 10001, group_id, user_id: 30003, text: text.to_string(), at_me, message }
+    }
+
+    #[test]
+    fn matches_keywords() {
+        let message = message("小明", "");
+        let hello = loaded(rule(MatchType::Keyword, "Hello"));
+        assert!(hello.matches(&incoming(&message, None, "Hello world", false)));
+        assert!(!hello.matches(&incoming(&message, None, "hello world", false)));
+
+        let mut ignore_case = rule(MatchType::Keyword, "Hello");
+        ignore_case.ignore_case = true;
+        assert!(loaded(ignore_case).matches(&incoming(&message, None, "HELLO", false)));
+    }
+
+    #[test]
+    fn matches_regex() {
+        let message = message("小明", "");
+        let weather = loaded(rule(MatchType::Regex, r"^(.+)天气$"));
+        assert!(weather.matches(&incoming(&message, Some(20002), "北京天气", false)));
+        assert!(!weather.matches(&incoming(&message, Some(20002), "天气怎么样", false)));
+        assert!(rule(MatchType::Regex, "(").validate().is_err());
+    }
+
+    #[test]
+    fn matches_at_me() {
+        let message = message("小明", "");
+        let any = loaded(rule(MatchType::AtMe, ""));
+        assert!(any.matches(&incoming(&message, Some(20002), "", true)));
+        assert!(!any.matches(&incoming(&message, Some(20002), "在吗", false)));
+
+        let help = loaded(rule(MatchType::AtMe, "帮助"));
+        assert!(help.matches(&incoming(&message, Some(20002), "帮助", true)));
+        assert!(!help.matches(&incoming(&message, Some(20002), "你好", true)));
+        assert!(!help.matches(&incoming(&message, Some(20002), "帮助", false)));
+    }
+
+    #[test]
+    fn matches_scope_and_targets() {
+        let message = message("小明", "");
+        let mut group_only = rule(MatchType::Keyword, "你好");
+        group_only.scope = ReplyScope::Group;
+        group_only.target_ids = vec![20002];
+        let group_only = loaded(group_only);
+        assert!(group_only.matches(&incoming(&message, Some(20002), "你好", false)));
+        assert!(!group_only.matches(&incoming(&message, Some(20003), "你好", false)));
+        assert!(!group_only.matches(&incoming(&message, None, "你好", false)));
+
+        let mut private_only = rule(MatchType::Keyword, "你好");
+        private_only.scope = ReplyScope::Private;
+        let private_only = loaded(private_only);
+        assert!(private_only.matches(&incoming(&message, None, "你好", false)));
+        assert!(!private_only.matches(&incoming(&message, Some(20002), "你好", false)));
+    }
+
+    #[test]
+    fn renders_template_as_message_chain() {
+        let with_card = message("小明", "群名片");
+        let message = message("小明", "");
+        let chain = render("[CQ:face,id=14]{sender} 说：{message}{unknown}", &incoming(&message, Some(20002), "hi", false));
+        assert_eq!(chain.to_cqcode(), "[CQ:face,id=14]小明 说：hi{unknown}");
+        assert!(matches!(chain.0[0], Segment::Face { .. }));
+
+        let chain = render("{sender}/{nickname}/{user_id}/{group_id}", &incoming(&with_card, None, "", false));
+        assert_eq!(chain.to_cqcode(), "群名片/小明/30003/");
+    }
+
+    #[test]
+    fn escapes_template_variables() {
+        // 收到的消息里的 CQ 码文本只作为文字回复，不会变成图片等消息段
+        let message = message("[CQ:at,qq=all]", "");
+        let text = "[CQ:image,file=http://example.com/a.png]&amp;";
+        let chain = render("{sender}: {message}", &incoming(&message, None, text, false));
+        assert_eq!(chain.0.len(), 1);
+        let Segment::Text { text: rendered, .. } = &chain.0[0] else {
+            panic!("应当只有文本: {:?}", chain);
+        };
+        assert_eq!(rendered, &format!("[CQ:at,qq=all]: {}", text));
+    }
+
+    #[test]
+    fn cooldown_starts_after_reply() {
+        let cooldowns = Cooldowns::default();
+        let mut rule = rule(MatchType::Keyword, "你好");
+        rule.cooldown_secs = 60;
+        let now = Instant::now();
+
+        // 没有成功回复过时不在冷却中
+        assert!(!cooldowns.is_cooling(&rule, 20002, now));
+        assert!(!cooldowns.is_cooling(&rule, 20002, now));
+
+        cooldowns.start(&rule, 20002, now);
+        assert!(cooldowns.is_cooling(&rule, 20002, now + Duration::from_secs(59)));
+        assert!(!cooldowns.is_cooling(&rule, 20002, now + Duration::from_secs(60)));
+        // 冷却按会话区分
+        assert!(!cooldowns.is_cooling(&rule, 20003, now));
+
+        rule.cooldown_secs = 0;
+        assert!(!cooldowns.is_cooling(&rule, 20002, now));
+    }
+}
@@ -1,4 +1,5 @@
-use tauri::{AppHandle, Manager};
+use tauri::Manager;
+use crate::AppHandle;
 use std::path::{Path, PathBuf};
 use std::fs;
 use std::io::Write;
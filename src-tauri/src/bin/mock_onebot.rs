@@ -0,0 +1,117 @@
+//! OneBot v11 模拟服务器
+//!
+//! 用法：mock_onebot [--host 127.0.0.1] [--port 3001] [--fixtures fixtures.json]
+//!                   [--script events.json] [--access-token TOKEN] [--heartbeat 秒]
+//!
+//! 启动后按脚本推送事件；之后从标准输入每读到一行 JSON 就作为事件推送给所有客户端
+
+use std::path::PathBuf;
+use std::time::Duration;
+use runbot_desktop_lib::mock_onebot::{self, Fixtures, MockConfig, MockServer};
+use tokio::io::{AsyncBufReadExt, BufReader};
+
+struct Args {
+    host: String,
+    port: u16,
+    fixtures: Option<PathBuf>,
+    script: Option<PathBuf>,
+    access_token: Option<String>,
+    heartbeat: Option<u64>,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut args = Args {
+        host: "127.0.0.1".to_string(),
+        port: 3001,
+        fixtures: None,
+        script: None,
+        access_token: None,
+        heartbeat: None,
+    };
+    let mut iter = std::env::args().skip(1);
+    while let Some(arg) = iter.next() {
+        let mut value = || iter.next().ok_or_else(|| format!("{} 缺少参数值", arg));
+        match arg.as_str() {
+            "--host" => args.host = value()?,
+            "--port" => args.port = value()?.parse().map_err(|e| format!("端口无效: {}", e))?,
+            "--fixtures" => args.fixtures = Some(PathBuf::from(value()?)),
+            "--script" => args.script = Some(PathBuf::from(value()?)),
+            "--access-token" => args.access_token = Some(value()?),
+            "--heartbeat" => {
+                args.heartbeat = Some(value()?.parse().map_err(|e| format!("心跳间隔无效: {}", e))?)
+            }
+            _ => return Err(format!("未知参数: {}", arg)),
+        }
+    }
+    Ok(args)
+}
+
+async fn run(args: Args) -> Result<(), String> {
+    let fixtures = match &args.fixtures {
+        Some(path) => Fixtures::load(path)?,
+        None => Fixtures::default(),
+    };
+    let script = match &args.script {
+        Some(path) => mock_onebot::load_script(path)?,
+        None => Vec::new(),
+    };
+    let config = MockConfig {
+        fixtures,
+        access_token: args.access_token,
+        heartbeat_interval: args.heartbeat.map(Duration::from_secs),
+    };
+    let server = MockServer::bind(&args.host, args.port, config).await?;
+    println!("模拟服务器已启动: {} (账号 {})", server.url(), server.self_id());
+
+    if !script.is_empty() {
+        println!("等待客户端连接后推送 {} 个脚本事件", script.len());
+        while !server.wait_connected(Duration::from_secs(3600)).await {}
+        server.run_script(script).await;
+        println!("脚本事件已全部推送");
+    }
+
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    loop {
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => break,
+            line = lines.next_line() => match line {
+                Ok(Some(line)) if line.trim().is_empty() => {}
+                Ok(Some(line)) => match serde_json::from_str(&line) {
+                    Ok(event) => server.push_event(event),
+                    Err(e) => eprintln!("解析事件失败: {}", e),
+                },
+                // 标准输入关闭后继续运行，直到 Ctrl+C
+                Ok(None) | Err(_) => {
+                    let _ = tokio::signal::ctrl_c().await;
+                    break;
+                }
+            },
+        }
+    }
+
+    server.stop().await;
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() {
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            tracing_subscriber::EnvFilter::try_from_default_env()
+                .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("info,runbot_desktop_lib=debug")),
+        )
+        .with_target(false)
+        .init();
+
+    let args = match parse_args() {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(2);
+        }
+    };
+    if let Err(e) = run(args).await {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,142 @@
+use futures_util::{SinkExt, StreamExt};
+use tokio::net::TcpListener;
+use tokio::sync::{mpsc, watch, Mutex};
+use tokio::task::JoinHandle;
+use tokio_tungstenite::tungstenite::protocol::Message as WsMessage;
+
+/// 本地回环桥接
+///
+/// runbot 的 `BotContext` 只能主动连接一个 URL，并且内部会以固定间隔自行重连。
+/// 为了让 supervisor 完全掌控与 OneBot 实现之间的连接，`BotContext` 只连接到
+/// 这个监听在 127.0.0.1 上的桥接端点，真正的上游连接由 supervisor 负责，
+/// 双方通过这里的两个通道交换原始帧。
+pub struct LoopbackBridge {
+    url: String,
+    // 投递给 runbot 的帧（上游的事件和 API 响应）
+    to_bot: mpsc::UnboundedSender<String>,
+    // runbot 发出的帧（API 调用）
+    from_bot: Mutex<mpsc::UnboundedReceiver<String>>,
+    attached: watch::Receiver<bool>,
+    task: JoinHandle<()>,
+}
+
+impl LoopbackBridge {
+    /// 在随机端口上启动桥接端点
+    pub async fn bind() -> Result<Self, String> {
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .map_err(|e| format!("启动本地桥接失败: {}", e))?;
+        let addr = listener
+            .local_addr()
+            .map_err(|e| format!("获取本地桥接地址失败: {}", e))?;
+        let url = format!("ws://{}", addr);
+
+        let (to_bot, mut to_bot_rx) = mpsc::unbounded_channel::<String>();
+        let (from_bot_tx, from_bot) = mpsc::unbounded_channel::<String>();
+        let (attached_tx, attached) = watch::channel(false);
+
+        tracing::debug!("[bridge] 本地桥接监听于 {}", url);
+
+        let task = tokio::spawn(async move {
+            // runbot 断开后会自动重连，所以这里循环 accept
+            while let Ok((stream, peer)) = listener.accept().await {
+                let ws_stream = match tokio_tungstenite::accept_async(stream).await {
+                    Ok(ws) => ws,
+                    Err(e) => {
+                        tracing::warn!("[bridge] 握手失败 ({}): {}", peer, e);
+                        continue;
+                    }
+                };
+                tracing::debug!("[bridge] runbot 已接入: {}", peer);
+                let _ = attached_tx.send(true);
+
+                let (mut sink, mut stream) = ws_stream.split();
+                loop {
+                    tokio::select! {
+                        frame = to_bot_rx.recv() => {
+                            let Some(frame) = frame else {
+                                // 桥接已被丢弃
+                                return;
+                            };
+                            if let Err(e) = sink.send(WsMessage::Text(frame.into())).await {
+                                tracing::warn!("[bridge] 投递帧到 runbot 失败: {}", e);
+                                break;
+                            }
+                        }
+                        msg = stream.next() => {
+                            match msg {
+                                Some(Ok(WsMessage::Text(text))) => {
+                                    let _ = from_bot_tx.send(text.to_string());
+                                }
+                                Some(Ok(WsMessage::Close(_))) | None => break,
+                                Some(Ok(_)) => {}
+                                Some(Err(e)) => {
+                                    tracing::warn!("[bridge] 读取 runbot 帧失败: {}", e);
+                                    break;
+                                }
+                            }
+                        }
+                    }
+                }
+
+                let _ = attached_tx.send(false);
+                tracing::debug!("[bridge] runbot 已断开: {}", peer);
+            }
+        });
+
+        Ok(Self {
+            url,
+            to_bot,
+            from_bot: Mutex::new(from_bot),
+            attached,
+            task,
+        })
+    }
+
+    /// 供 `BotContextBuilder::url` 使用的地址
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// 等待 runbot 接入桥接
+    pub async fn wait_attached(&self) {
+        let mut attached = self.attached.clone();
+        let _ = attached.wait_for(|v| *v).await;
+    }
+
+    /// 将上游收到的帧交给 runbot 处理
+    pub fn deliver(&self, frame: String) {
+        let _ = self.to_bot.send(frame);
+    }
+
+    /// 等待 runbot 发出的下一帧
+    pub async fn next_action(&self) -> Option<String> {
+        self.from_bot.lock().await.recv().await
+    }
+
+    /// 上游不可用时，直接以失败响应回复 runbot 发出的请求，避免调用方一直等到超时
+    pub fn reject_action(&self, frame: &str, reason: &str) {
+        let echo = match serde_json::from_str::<serde_json::Value>(frame) {
+            Ok(value) => value.get("echo").cloned().unwrap_or(serde_json::Value::Null),
+            Err(_) => return,
+        };
+        if echo.is_null() {
+            return;
+        }
+        let response = serde_json::json!({
+            "status": "failed",
+            "retcode": -1,
+            "data": null,
+            "message": reason,
+            "wording": reason,
+            "echo": echo,
+        });
+        self.deliver(response.to_string());
+    }
+}
+
+impl Drop for LoopbackBridge {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
@@ -0,0 +1,347 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use serde::{Deserialize, Serialize};
+use tauri::Emitter;
+use crate::AppHandle;
+use crate::session::Session;
+
+/// 连接状态机
+///
+/// 所有状态变化都必须经过 [`transition`]，由它校验转换是否合法、
+/// 更新 [`ConnectionTracker`] 并发送 `runbot-status` 事件。
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ConnectionState {
+    /// 尚未发起连接
+    #[default]
+    Idle,
+    /// 正在建立上游连接（attempt > 0 表示断线重连）
+    Connecting {
+        attempt: u32,
+        retry_in_ms: Option<u64>,
+    },
+    /// 上游已连接，正在通过 get_login_info 确认账号
+    Handshaking,
+    /// 已确认账号，可以正常收发
+    Authenticated { self_id: i64 },
+    /// 连接仍在，但心跳超时等原因导致可能不可用
+    Degraded { reason: String },
+    /// 已断开，不再自动重连
+    Disconnected { reason: DisconnectReason },
+}
+
+/// 断开原因
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "message", rename_all = "snake_case")]
+pub enum DisconnectReason {
+    /// 用户主动断开
+    Requested,
+    /// 多次重连失败后放弃
+    Failed(String),
+}
+
+impl ConnectionState {
+    /// 是否可以发送 API 请求
+    pub fn is_ready(&self) -> bool {
+        matches!(self, ConnectionState::Authenticated { .. } | ConnectionState::Degraded { .. })
+    }
+
+    /// 兼容旧版前端的粗粒度状态字符串
+    pub fn status_str(&self) -> &'static str {
+        match self {
+            ConnectionState::Idle => "disconnected",
+            ConnectionState::Connecting { attempt: 0, .. } => "connecting",
+            ConnectionState::Connecting { .. } => "reconnecting",
+            ConnectionState::Handshaking => "connecting",
+            ConnectionState::Authenticated { .. } => "connected",
+            ConnectionState::Degraded { .. } => "connected",
+            ConnectionState::Disconnected { reason: DisconnectReason::Requested } => "disconnected",
+            ConnectionState::Disconnected { reason: DisconnectReason::Failed(_) } => "failed",
+        }
+    }
+
+    fn can_transition_to(&self, next: &ConnectionState) -> bool {
+        use ConnectionState::*;
+        match (self, next) {
+            // 任何状态都可以被主动断开或放弃
+            (_, Disconnected { .. }) => true,
+            (Idle | Disconnected { .. }, Connecting { .. } | Idle) => true,
+            (Connecting { .. }, Connecting { .. } | Handshaking) => true,
+            (Handshaking, Authenticated { .. } | Connecting { .. }) => true,
+            (Authenticated { .. }, Degraded { .. } | Connecting { .. }) => true,
+            (Degraded { .. }, Authenticated { .. } | Connecting { .. }) => true,
+            _ => false,
+        }
+    }
+}
+
+/// 连接状态记录（保存在 Session 中）
+#[derive(Debug, Clone, Default)]
+pub struct ConnectionTracker {
+    pub state: ConnectionState,
+    pub last_error: Option<String>,
+    pub connected_at: Option<i64>, // 最近一次认证成功的时间（秒）
+    pub self_id: Option<i64>,      // 认证得到的 self_id
+    pub last_heartbeat: Option<(Instant, Duration)>, // 最近一次心跳的接收时间和上报的心跳间隔
+}
+
+impl ConnectionTracker {
+    pub fn is_ready(&self) -> bool {
+        self.state.is_ready()
+    }
+
+    /// 心跳是否已超时（超过两个心跳间隔未收到）；未收到过心跳时不判断
+    pub fn heartbeat_overdue(&self) -> bool {
+        match self.last_heartbeat {
+            Some((at, interval)) => at.elapsed() > interval * 2 + Duration::from_secs(1),
+            None => false,
+        }
+    }
+
+    fn apply(&mut self, next: ConnectionState) {
+        match &next {
+            ConnectionState::Authenticated { self_id } => {
+                // Degraded 恢复时保留原来的连接时间
+                if !matches!(self.state, ConnectionState::Degraded { .. }) {
+                    self.connected_at = Some(chrono::Utc::now().timestamp());
+                }
+                self.self_id = Some(*self_id);
+                self.last_error = None;
+            }
+            ConnectionState::Degraded { reason } => {
+                self.last_error = Some(reason.clone());
+            }
+            ConnectionState::Disconnected { reason } => {
+                if let DisconnectReason::Failed(error) = reason {
+                    self.last_error = Some(error.clone());
+                }
+                self.connected_at = None;
+                self.self_id = None;
+                self.last_heartbeat = None;
+            }
+            ConnectionState::Connecting { .. } | ConnectionState::Handshaking => {
+                self.connected_at = None;
+                self.last_heartbeat = None;
+            }
+            ConnectionState::Idle => {
+                self.last_error = None;
+                self.connected_at = None;
+                self.self_id = None;
+                self.last_heartbeat = None;
+            }
+        }
+        self.state = next;
+    }
+
+    /// 生成状态报告（事件和 get_runbot_status 共用）
+    pub fn report(&self, account_id: Option<i64>, message: Option<String>) -> ConnectionStatus {
+        ConnectionStatus {
+            account_id,
+            state: self.state.clone(),
+            status: self.state.status_str().to_string(),
+            message,
+            self_id: self.self_id,
+            last_error: self.last_error.clone(),
+            connected_at: self.connected_at,
+        }
+    }
+}
+
+/// 连接状态事件
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConnectionStatus {
+    pub account_id: Option<i64>, // 会话所属账号，首次认证前为空
+    pub state: ConnectionState,
+    pub status: String, // 兼容字段："connecting" | "connected" | "reconnecting" | "failed" | "disconnected"
+    pub message: Option<String>,
+    pub self_id: Option<i64>,
+    pub last_error: Option<String>,
+    pub connected_at: Option<i64>,
+}
+
+/// 记录一次错误（不改变状态），会出现在后续的状态报告中
+pub fn record_error(state: &Mutex<Session>, error: impl Into<String>) {
+    if let Ok(mut state_guard) = state.lock() {
+        state_guard.connection.last_error = Some(error.into());
+    }
+}
+
+/// 执行状态转换并通知前端；非法转换会被忽略并返回 false
+pub fn transition(
+    state: &Mutex<Session>,
+    app: &AppHandle,
+    next: ConnectionState,
+    message: Option<String>,
+) -> bool {
+    let report = {
+        let mut state_guard = match state.lock() {
+            Ok(guard) => guard,
+            Err(e) => {
+                tracing::error!("[connection] 锁定状态失败: {}", e);
+                return false;
+            }
+        };
+        let account_id = state_guard.account_id;
+        let tracker = &mut state_guard.connection;
+        if !tracker.state.can_transition_to(&next) {
+            tracing::warn!("[connection] 忽略非法状态转换 ({:?}): {:?} -> {:?}", account_id, tracker.state, next);
+            return false;
+        }
+        tracing::debug!("[connection] 状态转换 ({:?}): {:?} -> {:?}", account_id, tracker.state, next);
+        tracker.apply(next);
+        tracker.report(account_id, message)
+    };
+
+    app.emit("runbot-status", report).unwrap_or_default();
+    true
+}
+
+/// 处理心跳：记录心跳时间，并根据心跳中的账号状态在 Authenticated 和 Degraded 之间切换
+pub fn on_heartbeat(state: &Mutex<Session>, app: &AppHandle, interval_ms: i64, healthy: bool) {
+    let (current, self_id) = {
+        let mut state_guard = match state.lock() {
+            Ok(guard) => guard,
+            Err(_) => return,
+        };
+        let tracker = &mut state_guard.connection;
+        if interval_ms > 0 {
+            tracker.last_heartbeat = Some((Instant::now(), Duration::from_millis(interval_ms as u64)));
+        }
+        (tracker.state.clone(), tracker.self_id)
+    };
+
+    match current {
+        ConnectionState::Authenticated { .. } if !healthy => {
+            transition(
+                state,
+                app,
+                ConnectionState::Degraded { reason: "心跳报告账号状态异常".to_string() },
+                Some("连接异常".to_string()),
+            );
+        }
+        ConnectionState::Degraded { .. } if healthy => {
+            if let Some(self_id) = self_id {
+                transition(
+                    state,
+                    app,
+                    ConnectionState::Authenticated { self_id },
+                    Some("已连接".to_string()),
+                );
+            }
+        }
+        _ => {}
+    }
+}
+
+/// 检查心跳是否超时，超时则进入 Degraded（由 supervisor 定期调用）
+pub fn check_heartbeat(state: &Mutex<Session>, app: &AppHandle) {
+    let overdue = match state.lock() {
+        Ok(state_guard) => {
+            matches!(state_guard.connection.state, ConnectionState::Authenticated { .. })
+                && state_guard.connection.heartbeat_overdue()
+        }
+        Err(_) => false,
+    };
+    if overdue {
+        tracing::warn!("[connection] 心跳超时");
+        transition(
+            state,
+            app,
+            ConnectionState::Degraded { reason: "心跳超时".to_string() },
+            Some("连接异常".to_string()),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TestApp;
+
+    fn connecting(attempt: u32) -> ConnectionState {
+        ConnectionState::Connecting { attempt, retry_in_ms: None }
+    }
+
+    fn failed(error: &str) -> ConnectionState {
+        ConnectionState::Disconnected { reason: DisconnectReason::Failed(error.to_string()) }
+    }
+
+    #[test]
+    fn allows_connection_lifecycle() {
+        let authenticated = ConnectionState::Authenticated { self_id: 10001 };
+        let degraded = ConnectionState::Degraded { reason: "心跳超时".to_string() };
+        for (from, to) in [
+            (ConnectionState::Idle, connecting(0)),
+            (connecting(0), ConnectionState::Handshaking),
+            (connecting(1), connecting(2)),
+            (ConnectionState::Handshaking, authenticated.clone()),
+            (ConnectionState::Handshaking, connecting(1)),
+            (authenticated.clone(), degraded.clone()),
+            (degraded.clone(), authenticated.clone()),
+            (degraded.clone(), connecting(1)),
+            (authenticated.clone(), failed("断开")),
+            (failed("断开"), connecting(0)),
+            (ConnectionState::Disconnected { reason: DisconnectReason::Requested }, ConnectionState::Idle),
+        ] {
+            assert!(from.can_transition_to(&to), "{:?} -> {:?}", from, to);
+        }
+    }
+
+    #[test]
+    fn rejects_skipping_the_handshake() {
+        let authenticated = ConnectionState::Authenticated { self_id: 10001 };
+        let degraded = ConnectionState::Degraded { reason: "心跳超时".to_string() };
+        for (from, to) in [
+            (ConnectionState::Idle, authenticated.clone()),
+            (ConnectionState::Idle, ConnectionState::Handshaking),
+            (connecting(0), authenticated.clone()),
+            (connecting(0), degraded.clone()),
+            (ConnectionState::Handshaking, degraded.clone()),
+            (authenticated.clone(), ConnectionState::Handshaking),
+            (authenticated.clone(), ConnectionState::Idle),
+            (failed("断开"), authenticated.clone()),
+        ] {
+            assert!(!from.can_transition_to(&to), "{:?} -> {:?}", from, to);
+        }
+    }
+
+    #[test]
+    fn reports_last_error() {
+        let mut tracker = ConnectionTracker::default();
+        tracker.apply(connecting(0));
+        tracker.apply(ConnectionState::Handshaking);
+        tracker.apply(ConnectionState::Authenticated { self_id: 10001 });
+        let report = tracker.report(Some(10001), None);
+        assert_eq!((report.status.as_str(), report.self_id, report.last_error), ("connected", Some(10001), None));
+        assert!(report.connected_at.is_some());
+
+        tracker.apply(ConnectionState::Degraded { reason: "心跳超时".to_string() });
+        assert_eq!(tracker.report(Some(10001), None).last_error.as_deref(), Some("心跳超时"));
+
+        tracker.apply(connecting(1));
+        tracker.apply(failed("重连失败"));
+        let report = tracker.report(Some(10001), None);
+        assert_eq!(report.status, "failed");
+        assert_eq!(report.last_error.as_deref(), Some("重连失败"));
+        assert_eq!((report.self_id, report.connected_at), (None, None));
+
+        // 重新连接成功后清除错误
+        tracker.apply(connecting(0));
+        tracker.apply(ConnectionState::Handshaking);
+        tracker.apply(ConnectionState::Authenticated { self_id: 10001 });
+        assert_eq!(tracker.report(Some(10001), None).last_error, None);
+    }
+
+    #[test]
+    fn transition_ignores_illegal_changes() {
+        let app = TestApp::new();
+        let session = Mutex::new(Session::default());
+        assert!(!transition(&session, app.handle(), ConnectionState::Authenticated { self_id: 1 }, None));
+        assert_eq!(session.lock().unwrap().connection.state, ConnectionState::Idle);
+
+        assert!(transition(&session, app.handle(), connecting(0), None));
+        record_error(&session, "连接被拒绝");
+        assert_eq!(session.lock().unwrap().connection.state, connecting(0));
+        assert_eq!(session.lock().unwrap().connection.last_error.as_deref(), Some("连接被拒绝"));
+    }
+}
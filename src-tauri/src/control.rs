@@ -0,0 +1,803 @@
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use axum::body::Bytes;
+use axum::extract::ws::{Message as WsMessage, WebSocket, WebSocketUpgrade};
+use axum::extract::{Path, Query, State};
+use axum::http::{HeaderMap, StatusCode, Uri};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use futures_util::{SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tauri::{EventId, Listener};
+use crate::AppHandle;
+use tokio::net::TcpListener;
+use tokio::sync::{broadcast, mpsc, watch};
+use tokio::task::JoinHandle;
+use crate::daemon::{self, ReloadTarget};
+use crate::outbox;
+use crate::reverse;
+use crate::runbot::{self, ActionResponse, RunbotState};
+use crate::storage;
+
+/// API 调用的默认超时时间
+const ACTION_TIMEOUT: Duration = Duration::from_secs(30);
+/// 事件广播的缓冲区大小，客户端处理不过来时丢弃最早的事件
+const EVENT_BUFFER: usize = 1024;
+/// 转发给本地客户端的前端事件（界面附加到后台服务时原样重新发出）
+const FORWARDED_EVENTS: &[&str] = &[
+    "runbot-message",
+    "runbot-notice",
+    "message-sent",
+    "message-updated",
+    "outbox-state",
+    "requests-updated",
+    "schedule-state",
+    "script-log",
+    "plugin-log",
+    "relay-action",
+    "relay-clients",
+];
+
+/// 推送给本地客户端的事件
+#[derive(Debug, Clone)]
+enum ControlEvent {
+    /// OneBot 实现上报的原始事件帧
+    Raw { account_id: i64, frame: Arc<str> },
+    /// 发给前端的事件，payload 为 JSON；account_id 取自 payload 的 account_id 或 self_id
+    App {
+        event: &'static str,
+        account_id: Option<i64>,
+        payload: Arc<str>,
+    },
+}
+
+/// 本地控制接口服务器
+///
+/// 只监听 127.0.0.1，所有请求都需要访问令牌（`Authorization: Bearer <token>` 或 `access_token` 查询参数）。
+/// 对外表现为 OneBot v11 实现，外部工具可以通过桌面端登录的账号收发消息：
+///
+/// - `POST /{action}`：调用 OneBot API，请求体为参数，响应与 OneBot HTTP API 相同；
+///   带 local_message_id 的私聊／群消息与前端发送一样经过发送队列，立即返回 async
+/// - `GET /`、`/api`、`/event`（WebSocket）：正向 WebSocket，Universal／只调用 API／只推送原始事件
+/// - `GET /_runbot/messages`、`/_runbot/search`：查询本地消息记录（同 get_messages、search_messages）
+/// - `GET /_runbot/events`（WebSocket）：推送与前端相同的 runbot-message 事件
+/// - `GET /_runbot/app-events`（WebSocket）：推送发给前端的各类事件 `{event, payload}`，供附加到后台服务的界面使用
+/// - `GET /_runbot/accounts`：已连接的账号
+/// - `POST /_runbot/reload`：重新加载账号的配置，请求体为 `{"target": "auto_reply" | "plugins" | "scripts" | "schedule"}`，
+///   附加到后台服务的界面修改配置后调用
+///
+/// 多个账号在线时用 `X-Self-ID` 请求头或 `self_id` 查询参数指定账号
+#[derive(Debug)]
+pub struct ControlServer {
+    app: AppHandle,
+    addr: SocketAddr,
+    token: String,
+    events: broadcast::Sender<ControlEvent>,
+    listeners: Vec<EventId>,
+    /// 服务器停止（被丢弃）时关闭，已建立的 WebSocket 连接随之断开
+    _shutdown: watch::Sender<()>,
+    task: JoinHandle<()>,
+}
+
+#[derive(Clone)]
+struct ControlContext {
+    app: AppHandle,
+    registry: Arc<Mutex<RunbotState>>,
+    token: String,
+    events: broadcast::Sender<ControlEvent>,
+    shutdown: watch::Receiver<()>,
+}
+
+/// 本地控制接口的监听信息
+#[derive(Debug, Clone, Serialize)]
+pub struct ControlServerInfo {
+    pub addr: String,
+    pub token: String,
+}
+
+impl ControlServer {
+    /// 在 127.0.0.1 的指定端口上监听（端口为 0 时自动分配）
+    pub async fn bind(
+        app: AppHandle,
+        registry: Arc<Mutex<RunbotState>>,
+        port: u16,
+        token: String,
+    ) -> Result<Self, String> {
+        let listener = TcpListener::bind(("127.0.0.1", port))
+            .await
+            .map_err(|e| format!("监听 127.0.0.1:{} 失败: {}", port, e))?;
+        let addr = listener
+            .local_addr()
+            .map_err(|e| format!("获取监听地址失败: {}", e))?;
+
+        let (events, _) = broadcast::channel(EVENT_BUFFER);
+        let listeners = FORWARDED_EVENTS
+            .iter()
+            .map(|&name| {
+                let events = events.clone();
+                app.listen_any(name, move |event| {
+                    // 没有客户端订阅时不需要转发
+                    if events.receiver_count() == 0 {
+                        return;
+                    }
+                    let payload = event.payload();
+                    let account_id = serde_json::from_str::<Value>(payload).ok().and_then(|value| {
+                        value
+                            .get("account_id")
+                            .or_else(|| value.get("self_id"))
+                            .and_then(Value::as_i64)
+                    });
+                    let _ = events.send(ControlEvent::App { event: name, account_id, payload: payload.into() });
+                })
+            })
+            .collect();
+        let (shutdown, shutdown_rx) = watch::channel(());
+        let context = ControlContext {
+            app: app.clone(),
+            registry,
+            token: token.clone(),
+            events: events.clone(),
+            shutdown: shutdown_rx,
+        };
+        let router = Router::new()
+            .route("/", get(universal_socket))
+            .route("/api", get(api_socket))
+            .route("/event", get(event_socket))
+            .route("/_runbot/messages", get(get_messages))
+            .route("/_runbot/search", get(search_messages))
+            .route("/_runbot/events", get(message_socket))
+            .route("/_runbot/app-events", get(app_event_socket))
+            .route("/_runbot/accounts", get(get_accounts))
+            .route("/_runbot/reload", post(reload))
+            .route("/{action}", post(call_action))
+            .with_state(context);
+
+        tracing::info!("[control] 本地控制接口监听于 {}", addr);
+        let task = tokio::spawn(async move {
+            if let Err(e) = axum::serve(listener, router).await {
+                tracing::error!("[control] 本地控制接口错误: {}", e);
+            }
+        });
+
+        Ok(Self {
+            app,
+            addr,
+            token,
+            events,
+            listeners,
+            _shutdown: shutdown,
+            task,
+        })
+    }
+
+    pub fn info(&self) -> ControlServerInfo {
+        ControlServerInfo {
+            addr: self.addr.to_string(),
+            token: self.token.clone(),
+        }
+    }
+
+    /// 停止监听（已建立的 WebSocket 连接随之关闭）
+    pub async fn stop(mut self) {
+        self.task.abort();
+        let _ = (&mut self.task).await;
+    }
+}
+
+impl Drop for ControlServer {
+    fn drop(&mut self) {
+        self.task.abort();
+        for id in self.listeners.drain(..) {
+            self.app.unlisten(id);
+        }
+    }
+}
+
+fn event_sender(registry: &Mutex<RunbotState>) -> Option<broadcast::Sender<ControlEvent>> {
+    let state_guard = registry.lock().ok()?;
+    let server = state_guard.control_server.as_ref()?;
+    // 没有客户端订阅时不需要转发
+    (server.events.receiver_count() > 0).then(|| server.events.clone())
+}
+
+/// 上游推送的帧是否为事件（带 post_type）；API 调用的响应等其他帧不应转发给客户端
+pub(crate) fn is_event_frame(frame: &str) -> bool {
+    serde_json::from_str::<Value>(frame).is_ok_and(|value| value.get("post_type").is_some())
+}
+
+/// 转发 OneBot 实现上报的原始事件（控制接口未启动时忽略）
+pub fn publish_raw(registry: &Mutex<RunbotState>, account_id: i64, frame: &str) {
+    if let Some(events) = event_sender(registry) {
+        if is_event_frame(frame) {
+            let _ = events.send(ControlEvent::Raw { account_id, frame: frame.into() });
+        }
+    }
+}
+
+/// 失败响应：retcode 与 OneBot 实现的习惯一致（1400、1401、1404 等）
+fn failed(status: StatusCode, message: impl Into<String>) -> Response {
+    let message = message.into();
+    let body = json!({
+        "status": "failed",
+        "retcode": 1000 + status.as_u16() as i64,
+        "data": null,
+        "message": message,
+        "wording": message,
+    });
+    (status, Json(body)).into_response()
+}
+
+fn ok(data: Value) -> Response {
+    Json(json!({ "status": "ok", "retcode": 0, "data": data, "message": "", "wording": "" })).into_response()
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct AccountQuery {
+    self_id: Option<i64>,
+}
+
+fn check_token(context: &ControlContext, headers: &HeaderMap, uri: &Uri) -> Result<(), (StatusCode, String)> {
+    if !reverse::is_authorized_parts(headers, uri, &context.token) {
+        return Err((StatusCode::UNAUTHORIZED, "access_token 无效".to_string()));
+    }
+    Ok(())
+}
+
+/// 校验令牌并确定账号：未指定账号时使用唯一在线的账号
+fn authorize(
+    context: &ControlContext,
+    headers: &HeaderMap,
+    uri: &Uri,
+    self_id: Option<i64>,
+) -> Result<i64, (StatusCode, String)> {
+    check_token(context, headers, uri)?;
+
+    let self_id = self_id.or_else(|| {
+        headers
+            .get("X-Self-ID")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.trim().parse().ok())
+    });
+    if let Some(self_id) = self_id {
+        return Ok(self_id);
+    }
+    let accounts = context
+        .registry
+        .lock()
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("锁定状态失败: {}", e)))?
+        .sessions
+        .accounts();
+    match accounts.as_slice() {
+        [account_id] => Ok(*account_id),
+        [] => Err((StatusCode::SERVICE_UNAVAILABLE, "没有已连接的账号".to_string())),
+        _ => Err((
+            StatusCode::BAD_REQUEST,
+            "有多个账号在线，请通过 X-Self-ID 请求头或 self_id 参数指定账号".to_string(),
+        )),
+    }
+}
+
+/// 执行一次 API 调用，返回不含 echo 的响应
+async fn execute(context: &ControlContext, account_id: i64, action: &str, params: Value) -> Value {
+    let params = match params {
+        Value::Null => json!({}),
+        params => params,
+    };
+
+    // 与前端发送消息相同：带 local_message_id 的消息经过发送队列，保证送达
+    if let Some(local_message_id) = outbox::should_queue(action, &params) {
+        return match outbox::submit(&context.app, &context.registry, account_id, &local_message_id, action, &params) {
+            Ok(()) => json!({ "status": "async", "retcode": 1, "data": null, "message": "", "wording": "" }),
+            Err(e) => json!({ "status": "failed", "retcode": 1500, "data": null, "message": e, "wording": e }),
+        };
+    }
+
+    let result = async {
+        let bot_ctx = runbot::ready_bot_ctx(&context.registry, account_id)?;
+        let response = bot_ctx
+            .websocket_send(action, params)
+            .await
+            .map_err(|e| format!("发送 {} 请求失败: {}", action, e))?
+            .response(ACTION_TIMEOUT)
+            .await
+            .map_err(|e| format!("等待 {} 响应失败: {}", action, e))?;
+        Ok::<_, String>(ActionResponse::from(response))
+    }
+    .await;
+    match result.and_then(|response| serde_json::to_value(response).map_err(|e| e.to_string())) {
+        Ok(response) => response,
+        Err(e) => json!({ "status": "failed", "retcode": 1503, "data": null, "message": e, "wording": e }),
+    }
+}
+
+async fn call_action(
+    State(context): State<ControlContext>,
+    Path(action): Path<String>,
+    Query(query): Query<AccountQuery>,
+    headers: HeaderMap,
+    uri: Uri,
+    body: Bytes,
+) -> Response {
+    let account_id = match authorize(&context, &headers, &uri, query.self_id) {
+        Ok(account_id) => account_id,
+        Err((status, message)) => return failed(status, message),
+    };
+    let params = if body.iter().all(u8::is_ascii_whitespace) {
+        json!({})
+    } else {
+        match serde_json::from_slice(&body) {
+            Ok(params) => params,
+            Err(e) => return failed(StatusCode::BAD_REQUEST, format!("解析请求参数失败: {}", e)),
+        }
+    };
+    tracing::debug!("[control] 账号 {} 调用 {}", account_id, action);
+    Json(execute(&context, account_id, &action, params).await).into_response()
+}
+
+async fn get_accounts(State(context): State<ControlContext>, headers: HeaderMap, uri: Uri) -> Response {
+    if let Err((status, message)) = check_token(&context, &headers, &uri) {
+        return failed(status, message);
+    }
+    match context.registry.lock() {
+        Ok(state_guard) => ok(json!(state_guard.sessions.accounts())),
+        Err(e) => failed(StatusCode::INTERNAL_SERVER_ERROR, format!("锁定状态失败: {}", e)),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ReloadRequest {
+    target: ReloadTarget,
+}
+
+async fn reload(
+    State(context): State<ControlContext>,
+    Query(query): Query<AccountQuery>,
+    headers: HeaderMap,
+    uri: Uri,
+    body: Bytes,
+) -> Response {
+    let account_id = match authorize(&context, &headers, &uri, query.self_id) {
+        Ok(account_id) => account_id,
+        Err((status, message)) => return failed(status, message),
+    };
+    let request: ReloadRequest = match serde_json::from_slice(&body) {
+        Ok(request) => request,
+        Err(e) => return failed(StatusCode::BAD_REQUEST, format!("解析请求参数失败: {}", e)),
+    };
+    tracing::info!("[control] 账号 {} 重新加载 {:?}", account_id, request.target);
+    match daemon::reload_local(context.registry.clone(), account_id, request.target).await {
+        Ok(()) => ok(Value::Null),
+        Err(e) => failed(StatusCode::INTERNAL_SERVER_ERROR, e),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct MessagesQuery {
+    self_id: Option<i64>,
+    post_type: Option<String>,
+    user_id: Option<i64>,
+    group_id: Option<i64>,
+    limit: Option<u32>,
+    offset: Option<u32>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SearchQuery {
+    self_id: Option<i64>,
+    query: String,
+    limit: Option<u32>,
+    offset: Option<u32>,
+}
+
+/// 消息以 JSON 字符串存储，返回前解析为对象
+fn messages_response(result: Result<Vec<String>, String>) -> Response {
+    match result {
+        Ok(messages) => ok(Value::Array(
+            messages
+                .iter()
+                .filter_map(|message| serde_json::from_str(message).ok())
+                .collect(),
+        )),
+        Err(e) => failed(StatusCode::INTERNAL_SERVER_ERROR, e),
+    }
+}
+
+async fn get_messages(
+    State(context): State<ControlContext>,
+    Query(query): Query<MessagesQuery>,
+    headers: HeaderMap,
+    uri: Uri,
+) -> Response {
+    let account_id = match authorize(&context, &headers, &uri, query.self_id) {
+        Ok(account_id) => account_id,
+        Err((status, message)) => return failed(status, message),
+    };
+    messages_response(
+        storage::get_messages(
+            query.limit,
+            query.offset,
+            query.post_type,
+            query.user_id,
+            query.group_id,
+            Some(account_id),
+            context.app.clone(),
+        )
+        .await,
+    )
+}
+
+async fn search_messages(
+    State(context): State<ControlContext>,
+    Query(query): Query<SearchQuery>,
+    headers: HeaderMap,
+    uri: Uri,
+) -> Response {
+    let account_id = match authorize(&context, &headers, &uri, query.self_id) {
+        Ok(account_id) => account_id,
+        Err((status, message)) => return failed(status, message),
+    };
+    messages_response(
+        storage::search_messages(query.query, query.limit, query.offset, Some(account_id), context.app.clone()).await,
+    )
+}
+
+/// WebSocket 连接的类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SocketRole {
+    /// 推送原始事件，并可以调用 API
+    Universal,
+    /// 只调用 API
+    Api,
+    /// 只推送原始事件
+    Event,
+    /// 推送 runbot-message 事件
+    Messages,
+    /// 推送发给前端的各类事件
+    AppEvents,
+}
+
+impl SocketRole {
+    fn receives_raw(self) -> bool {
+        matches!(self, SocketRole::Universal | SocketRole::Event)
+    }
+
+    fn calls_api(self) -> bool {
+        matches!(self, SocketRole::Universal | SocketRole::Api)
+    }
+}
+
+fn upgrade(
+    context: ControlContext,
+    ws: WebSocketUpgrade,
+    query: AccountQuery,
+    headers: HeaderMap,
+    uri: Uri,
+    role: SocketRole,
+) -> Response {
+    let account_id = match authorize(&context, &headers, &uri, query.self_id) {
+        Ok(account_id) => account_id,
+        Err((status, message)) => return failed(status, message),
+    };
+    ws.on_upgrade(move |socket| serve_socket(context, socket, account_id, role))
+}
+
+async fn universal_socket(
+    State(context): State<ControlContext>,
+    ws: WebSocketUpgrade,
+    Query(query): Query<AccountQuery>,
+    headers: HeaderMap,
+    uri: Uri,
+) -> Response {
+    upgrade(context, ws, query, headers, uri, SocketRole::Universal)
+}
+
+async fn api_socket(
+    State(context): State<ControlContext>,
+    ws: WebSocketUpgrade,
+    Query(query): Query<AccountQuery>,
+    headers: HeaderMap,
+    uri: Uri,
+) -> Response {
+    upgrade(context, ws, query, headers, uri, SocketRole::Api)
+}
+
+async fn event_socket(
+    State(context): State<ControlContext>,
+    ws: WebSocketUpgrade,
+    Query(query): Query<AccountQuery>,
+    headers: HeaderMap,
+    uri: Uri,
+) -> Response {
+    upgrade(context, ws, query, headers, uri, SocketRole::Event)
+}
+
+async fn message_socket(
+    State(context): State<ControlContext>,
+    ws: WebSocketUpgrade,
+    Query(query): Query<AccountQuery>,
+    headers: HeaderMap,
+    uri: Uri,
+) -> Response {
+    upgrade(context, ws, query, headers, uri, SocketRole::Messages)
+}
+
+async fn app_event_socket(
+    State(context): State<ControlContext>,
+    ws: WebSocketUpgrade,
+    Query(query): Query<AccountQuery>,
+    headers: HeaderMap,
+    uri: Uri,
+) -> Response {
+    upgrade(context, ws, query, headers, uri, SocketRole::AppEvents)
+}
+
+async fn serve_socket(context: ControlContext, socket: WebSocket, account_id: i64, role: SocketRole) {
+    tracing::info!("[control] 本地客户端已连接: 账号 {} ({:?})", account_id, role);
+    let (mut sink, mut stream) = socket.split();
+    let mut events = context.events.subscribe();
+    let mut shutdown = context.shutdown.clone();
+    // API 调用在单独的任务中执行，响应通过这里交给发送端
+    let (responses_tx, mut responses) = mpsc::unbounded_channel::<String>();
+
+    if role.receives_raw() {
+        let lifecycle = json!({
+            "time": chrono::Utc::now().timestamp(),
+            "self_id": account_id,
+            "post_type": "meta_event",
+            "meta_event_type": "lifecycle",
+            "sub_type": "connect",
+        });
+        if sink.send(WsMessage::Text(lifecycle.to_string().into())).await.is_err() {
+            return;
+        }
+    }
+
+    loop {
+        let outgoing: String = tokio::select! {
+            _ = shutdown.changed() => break,
+            event = events.recv() => match event {
+                Ok(ControlEvent::Raw { account_id: id, frame }) if id == account_id && role.receives_raw() => {
+                    frame.to_string()
+                }
+                Ok(ControlEvent::App { event, account_id: id, payload })
+                    if role == SocketRole::Messages && event == "runbot-message" && id == Some(account_id) =>
+                {
+                    payload.to_string()
+                }
+                // 不属于任何账号的事件同样转发
+                Ok(ControlEvent::App { event, account_id: id, payload })
+                    if role == SocketRole::AppEvents && id.is_none_or(|id| id == account_id) =>
+                {
+                    format!(r#"{{"event":"{}","payload":{}}}"#, event, payload)
+                }
+                Ok(_) => continue,
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    tracing::warn!("[control] 本地客户端处理过慢，丢弃了 {} 个事件", skipped);
+                    continue;
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            },
+            Some(response) = responses.recv() => response,
+            message = stream.next() => match message {
+                Some(Ok(WsMessage::Text(text))) if role.calls_api() => {
+                    let context = context.clone();
+                    let responses_tx = responses_tx.clone();
+                    let text = text.to_string();
+                    tokio::spawn(async move {
+                        let _ = responses_tx.send(handle_frame(&context, account_id, &text).await);
+                    });
+                    continue;
+                }
+                Some(Ok(WsMessage::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => continue,
+            },
+        };
+        if sink.send(WsMessage::Text(outgoing.into())).await.is_err() {
+            break;
+        }
+    }
+    tracing::info!("[control] 本地客户端已断开: 账号 {} ({:?})", account_id, role);
+}
+
+/// 处理 WebSocket 上的 API 调用帧 `{action, params, echo}`
+async fn handle_frame(context: &ControlContext, account_id: i64, text: &str) -> String {
+    let request: Value = match serde_json::from_str(text) {
+        Ok(request) => request,
+        Err(e) => {
+            let message = format!("解析请求失败: {}", e);
+            return json!({ "status": "failed", "retcode": 1400, "data": null, "message": message, "wording": message })
+                .to_string();
+        }
+    };
+    let echo = request.get("echo").cloned().unwrap_or(Value::Null);
+    let mut response = match request.get("action").and_then(Value::as_str) {
+        Some(action) => {
+            let params = request.get("params").cloned().unwrap_or(Value::Null);
+            execute(context, account_id, action, params).await
+        }
+        None => json!({ "status": "failed", "retcode": 1400, "data": null, "message": "缺少 action", "wording": "缺少 action" }),
+    };
+    if let Some(object) = response.as_object_mut() {
+        object.insert("echo".to_string(), echo);
+    }
+    response.to_string()
+}
+
+/// 启动本地控制接口，返回监听地址和访问令牌（未指定令牌时随机生成）
+///
+/// 已在运行时会先停止旧的服务器
+#[tauri::command]
+pub async fn start_control_server(
+    port: u16,
+    token: Option<String>,
+    app: AppHandle,
+    state: tauri::State<'_, Arc<Mutex<RunbotState>>>,
+) -> Result<ControlServerInfo, String> {
+    let token = token
+        .filter(|token| !token.is_empty())
+        .unwrap_or_else(|| hex::encode(rand::random::<[u8; 16]>()));
+
+    let old_server = state
+        .lock()
+        .map_err(|e| format!("锁定状态失败: {}", e))?
+        .control_server
+        .take();
+    if let Some(old_server) = old_server {
+        old_server.stop().await;
+    }
+
+    let server = ControlServer::bind(app, state.inner().clone(), port, token).await?;
+    let info = server.info();
+    state
+        .lock()
+        .map_err(|e| format!("锁定状态失败: {}", e))?
+        .control_server = Some(server);
+
+    tracing::info!("[start_control_server] 本地控制接口已启动: {}", info.addr);
+    Ok(info)
+}
+
+/// 停止本地控制接口
+#[tauri::command]
+pub async fn stop_control_server(
+    state: tauri::State<'_, Arc<Mutex<RunbotState>>>,
+) -> Result<(), String> {
+    let server = state
+        .lock()
+        .map_err(|e| format!("锁定状态失败: {}", e))?
+        .control_server
+        .take();
+    if let Some(server) = server {
+        server.stop().await;
+        tracing::info!("[stop_control_server] 本地控制接口已停止");
+    }
+    Ok(())
+}
+
+/// 获取本地控制接口的监听地址和令牌（未启动时为空）
+#[tauri::command]
+pub async fn get_control_server(
+    state: tauri::State<'_, Arc<Mutex<RunbotState>>>,
+) -> Result<Option<ControlServerInfo>, String> {
+    let state_guard = state.lock().map_err(|e| format!("锁定状态失败: {}", e))?;
+    Ok(state_guard.control_server.as_ref().map(ControlServer::info))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_onebot::{MockConfig, MockServer};
+    use crate::session::Session;
+    use crate::test_support::{self, TestApp};
+
+    fn context(app: &TestApp) -> ControlContext {
+        ControlContext {
+            app: app.handle().clone(),
+            registry: app.registry.clone(),
+            token: "secret".to_string(),
+            events: broadcast::channel(EVENT_BUFFER).0,
+            shutdown: watch::channel(()).1,
+        }
+    }
+
+    fn register(app: &TestApp, account_id: i64) {
+        let session = Arc::new(Mutex::new(Session::default()));
+        app.registry.lock().unwrap().sessions.register(&session, account_id).unwrap();
+    }
+
+    fn bearer(token: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert("Authorization", format!("Bearer {}", token).parse().unwrap());
+        headers
+    }
+
+    #[test]
+    fn forwards_only_event_frames() {
+        assert!(is_event_frame(r#"{"post_type":"message","message_type":"private"}"#));
+        assert!(is_event_frame(r#"{"post_type":"meta_event","meta_event_type":"heartbeat"}"#));
+        assert!(!is_event_frame(r#"{"status":"ok","retcode":0,"data":null,"echo":"1"}"#));
+        assert!(!is_event_frame("not json"));
+    }
+
+    #[test]
+    fn authorize_requires_token() {
+        let app = TestApp::new();
+        let context = context(&app);
+        register(&app, 10001);
+        let uri: Uri = "/get_status".parse().unwrap();
+
+        let (status, _) = authorize(&context, &HeaderMap::new(), &uri, None).unwrap_err();
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let (status, _) = authorize(&context, &bearer("wrong"), &uri, None).unwrap_err();
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        // 令牌错误时即使指定了账号也拒绝
+        let (status, _) = authorize(&context, &bearer("wrong"), &uri, Some(10001)).unwrap_err();
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        let uri: Uri = "/get_status?access_token=secret".parse().unwrap();
+        assert_eq!(authorize(&context, &HeaderMap::new(), &uri, None), Ok(10001));
+    }
+
+    #[test]
+    fn authorize_selects_account() {
+        let app = TestApp::new();
+        let context = context(&app);
+        let uri: Uri = "/get_status".parse().unwrap();
+        let headers = bearer("secret");
+
+        // 没有在线账号
+        let (status, _) = authorize(&context, &headers, &uri, None).unwrap_err();
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+
+        // 只有一个账号时可以省略
+        register(&app, 10001);
+        assert_eq!(authorize(&context, &headers, &uri, None), Ok(10001));
+
+        // 多个账号在线时必须指定
+        register(&app, 10002);
+        let (status, _) = authorize(&context, &headers, &uri, None).unwrap_err();
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(authorize(&context, &headers, &uri, Some(10002)), Ok(10002));
+
+        let mut headers = headers;
+        headers.insert("X-Self-ID", " 10001 ".parse().unwrap());
+        assert_eq!(authorize(&context, &headers, &uri, None), Ok(10001));
+        // self_id 参数优先于请求头
+        assert_eq!(authorize(&context, &headers, &uri, Some(10002)), Ok(10002));
+    }
+
+    #[tokio::test]
+    async fn handle_frame_returns_echo() {
+        let server = MockServer::bind("127.0.0.1", 0, MockConfig::default()).await.unwrap();
+        let app = TestApp::new();
+        let session = app.connect(&server).await;
+        let context = context(&app);
+        let account_id = server.self_id();
+
+        let response: Value = serde_json::from_str(
+            &handle_frame(&context, account_id, r#"{"action":"get_status","params":{},"echo":{"id":7}}"#).await,
+        )
+        .unwrap();
+        assert_eq!(response["status"], "ok");
+        assert_eq!(response["data"]["online"], true);
+        assert_eq!(response["echo"], json!({ "id": 7 }));
+
+        // 失败的调用同样带回 echo
+        let response: Value =
+            serde_json::from_str(&handle_frame(&context, account_id, r#"{"params":{},"echo":"no-action"}"#).await).unwrap();
+        assert_eq!((response["retcode"].as_i64(), &response["echo"]), (Some(1400), &json!("no-action")));
+
+        let response: Value =
+            serde_json::from_str(&handle_frame(&context, 10002, r#"{"action":"get_status","echo":"offline"}"#).await).unwrap();
+        assert_eq!((response["retcode"].as_i64(), &response["echo"]), (Some(1503), &json!("offline")));
+
+        let response: Value = serde_json::from_str(&handle_frame(&context, account_id, "{").await).unwrap();
+        assert_eq!(response["retcode"], 1400);
+        assert!(response.get("echo").is_none());
+
+        test_support::shutdown(&session).await;
+    }
+}
@@ -0,0 +1,624 @@
+use std::collections::BTreeMap;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::{Map, Value};
+use crate::actions::{MessageContent, MessageSegment};
+
+/// 消息段（OneBot v11 及常见扩展），序列化为 {"type": ..., "data": {...}}
+///
+/// 参数值统一使用字符串，与 CQ 码格式一致；已知类型中未列出的参数（如实现扩展的字段）保存在 extra 中，
+/// 未知类型或字段不符合的消息段保存为 Unknown，原样保留
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "data", rename_all = "snake_case")]
+pub enum Segment {
+    /// 纯文本
+    Text {
+        text: String,
+        #[serde(flatten)]
+        extra: Map<String, Value>,
+    },
+    /// QQ 表情
+    Face {
+        id: String,
+        #[serde(flatten)]
+        extra: Map<String, Value>,
+    },
+    /// 图片
+    Image {
+        file: String,
+        #[serde(rename = "type", default, skip_serializing_if = "Option::is_none")]
+        kind: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        url: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        sub_type: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        summary: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        cache: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        proxy: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        timeout: Option<String>,
+        #[serde(flatten)]
+        extra: Map<String, Value>,
+    },
+    /// 语音
+    Record {
+        file: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        magic: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        url: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        cache: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        proxy: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        timeout: Option<String>,
+        #[serde(flatten)]
+        extra: Map<String, Value>,
+    },
+    /// 短视频
+    Video {
+        file: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        url: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        cache: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        proxy: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        timeout: Option<String>,
+        #[serde(flatten)]
+        extra: Map<String, Value>,
+    },
+    /// 文件（扩展）
+    File {
+        file: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        name: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        url: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        file_id: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        file_size: Option<String>,
+        #[serde(flatten)]
+        extra: Map<String, Value>,
+    },
+    /// @某人，qq 为 all 时表示全体成员
+    At {
+        qq: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        name: Option<String>,
+        #[serde(flatten)]
+        extra: Map<String, Value>,
+    },
+    /// 回复
+    Reply {
+        id: String,
+        #[serde(flatten)]
+        extra: Map<String, Value>,
+    },
+    /// 合并转发
+    Forward {
+        id: String,
+        #[serde(flatten)]
+        extra: Map<String, Value>,
+    },
+    /// JSON 消息
+    Json {
+        data: String,
+        #[serde(flatten)]
+        extra: Map<String, Value>,
+    },
+    /// XML 消息
+    Xml {
+        data: String,
+        #[serde(flatten)]
+        extra: Map<String, Value>,
+    },
+    /// 戳一戳
+    Poke {
+        #[serde(rename = "type")]
+        kind: String,
+        id: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        name: Option<String>,
+        #[serde(flatten)]
+        extra: Map<String, Value>,
+    },
+    /// 掷骰子魔法表情
+    Dice {
+        #[serde(flatten)]
+        extra: Map<String, Value>,
+    },
+    /// 猜拳魔法表情
+    Rps {
+        #[serde(flatten)]
+        extra: Map<String, Value>,
+    },
+    /// 音乐分享，type 为 custom 时使用 url、audio、title 等字段
+    Music {
+        #[serde(rename = "type")]
+        kind: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        id: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        url: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        audio: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        title: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        content: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        image: Option<String>,
+        #[serde(flatten)]
+        extra: Map<String, Value>,
+    },
+    /// 链接分享
+    Share {
+        url: String,
+        title: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        content: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        image: Option<String>,
+        #[serde(flatten)]
+        extra: Map<String, Value>,
+    },
+    /// 推荐好友或群，type 为 qq 或 group
+    Contact {
+        #[serde(rename = "type")]
+        kind: String,
+        id: String,
+        #[serde(flatten)]
+        extra: Map<String, Value>,
+    },
+    /// 位置
+    Location {
+        lat: String,
+        lon: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        title: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        content: Option<String>,
+        #[serde(flatten)]
+        extra: Map<String, Value>,
+    },
+    /// 商城表情（扩展）
+    Mface {
+        emoji_package_id: String,
+        emoji_id: String,
+        key: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        summary: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        url: Option<String>,
+        #[serde(flatten)]
+        extra: Map<String, Value>,
+    },
+    /// Markdown（扩展）
+    Markdown {
+        content: String,
+        #[serde(flatten)]
+        extra: Map<String, Value>,
+    },
+    /// 其他消息段，原样保留
+    #[serde(untagged)]
+    Unknown(UnknownSegment),
+}
+
+/// 未识别的消息段
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UnknownSegment {
+    #[serde(rename = "type")]
+    pub kind: String,
+    #[serde(default)]
+    pub data: Map<String, Value>,
+}
+
+impl Segment {
+    pub fn text(text: impl Into<String>) -> Self {
+        Segment::Text { text: text.into(), extra: Map::new() }
+    }
+
+    /// 从 JSON 消息段解析；数字和布尔参数转为字符串，缺少必需字段时作为 Unknown 保留
+    pub fn from_value(value: Value) -> Result<Self, String> {
+        let Value::Object(mut segment) = value else {
+            return Err(format!("消息段格式错误: {}", value));
+        };
+        let kind = match segment.get("type") {
+            Some(Value::String(kind)) => kind.clone(),
+            _ => return Err(format!("消息段缺少 type: {}", Value::Object(segment))),
+        };
+        let data = match segment.remove("data") {
+            Some(Value::Object(data)) => data,
+            None | Some(Value::Null) => Map::new(),
+            Some(other) => return Err(format!("消息段 data 格式错误: {}", other)),
+        };
+        let normalized: Map<String, Value> = data
+            .iter()
+            .filter_map(|(key, value)| {
+                let value = match value {
+                    Value::Null => return None,
+                    Value::Number(number) => Value::String(number.to_string()),
+                    Value::Bool(flag) => Value::String(flag.to_string()),
+                    other => other.clone(),
+                };
+                Some((key.clone(), value))
+            })
+            .collect();
+        let typed = serde_json::json!({ "type": kind, "data": normalized });
+        match serde_json::from_value::<Segment>(typed) {
+            Ok(Segment::Unknown(_)) | Err(_) => Ok(Segment::Unknown(UnknownSegment { kind, data })),
+            Ok(segment) => Ok(segment),
+        }
+    }
+
+    /// 拆分为类型和参数
+    fn into_parts(self) -> (String, Map<String, Value>) {
+        match self {
+            Segment::Unknown(unknown) => (unknown.kind, unknown.data),
+            typed => match serde_json::to_value(typed) {
+                Ok(Value::Object(mut segment)) => {
+                    let kind = segment.get("type").and_then(Value::as_str).unwrap_or_default().to_string();
+                    let data = match segment.remove("data") {
+                        Some(Value::Object(data)) => data,
+                        _ => Map::new(),
+                    };
+                    (kind, data)
+                }
+                _ => (String::new(), Map::new()),
+            },
+        }
+    }
+
+    /// 转为 CQ 码，文本段只做转义
+    pub fn to_cqcode(&self) -> String {
+        // CQ 码的文本部分没有参数，extra 无法表示
+        if let Segment::Text { text, .. } = self {
+            return escape(text, false);
+        }
+        let (kind, data) = self.clone().into_parts();
+        if kind.is_empty() {
+            return String::new();
+        }
+
+        // 参数按名称排序，保证输出稳定
+        let params: BTreeMap<String, Value> = data.into_iter().collect();
+        let mut cq_code = format!("[CQ:{}", escape(&kind, true));
+        for (key, value) in params {
+            let value = match value {
+                Value::Null => continue,
+                Value::String(value) => value,
+                other => other.to_string(),
+            };
+            cq_code.push(',');
+            cq_code.push_str(&escape(&key, true));
+            cq_code.push('=');
+            cq_code.push_str(&escape(&value, true));
+        }
+        cq_code.push(']');
+        cq_code
+    }
+}
+
+/// 消息链，序列化为消息段数组
+///
+/// 反序列化时同时接受消息段数组、单个消息段和 CQ 码字符串
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MessageChain(pub Vec<Segment>);
+
+impl MessageChain {
+    /// 从 CQ 码字符串解析
+    ///
+    /// 不完整的 CQ 码（缺少 ] 或类型为空）按纯文本处理，相邻的文本段会合并
+    pub fn parse(message: &str) -> Self {
+        let mut chain = MessageChain::default();
+        let mut rest = message;
+        while let Some(start) = rest.find("[CQ:") {
+            let Some(end) = rest[start..].find(']').map(|end| start + end) else {
+                break;
+            };
+            let mut parts = rest[start + 4..end].split(',');
+            let kind = parts.next().unwrap_or_default();
+            if kind.is_empty() {
+                chain.push_text(&unescape(&rest[..end + 1]));
+                rest = &rest[end + 1..];
+                continue;
+            }
+
+            chain.push_text(&unescape(&rest[..start]));
+            let mut data = Map::new();
+            for part in parts {
+                let (key, value) = part.split_once('=').unwrap_or((part, ""));
+                if !key.is_empty() {
+                    data.insert(unescape(key), Value::String(unescape(value)));
+                }
+            }
+            let segment = serde_json::json!({ "type": unescape(kind), "data": data });
+            if let Ok(segment) = Segment::from_value(segment) {
+                chain.0.push(segment);
+            }
+            rest = &rest[end + 1..];
+        }
+        chain.push_text(&unescape(rest));
+        chain
+    }
+
+    /// 从 JSON 解析：消息段数组、单个消息段或 CQ 码字符串
+    pub fn from_value(value: Value) -> Result<Self, String> {
+        match value {
+            Value::String(message) => Ok(Self::parse(&message)),
+            Value::Array(segments) => segments
+                .into_iter()
+                .map(Segment::from_value)
+                .collect::<Result<Vec<_>, _>>()
+                .map(MessageChain),
+            segment @ Value::Object(_) => Ok(MessageChain(vec![Segment::from_value(segment)?])),
+            other => Err(format!("消息格式错误: {}", other)),
+        }
+    }
+
+    /// 转为 CQ 码字符串
+    pub fn to_cqcode(&self) -> String {
+        self.0.iter().map(Segment::to_cqcode).collect()
+    }
+
+    fn push_text(&mut self, text: &str) {
+        if text.is_empty() {
+            return;
+        }
+        match self.0.last_mut() {
+            Some(Segment::Text { text: last, extra }) if extra.is_empty() => last.push_str(text),
+            _ => self.0.push(Segment::text(text)),
+        }
+    }
+}
+
+impl From<&[runbot::event::MessageData]> for MessageChain {
+    fn from(message: &[runbot::event::MessageData]) -> Self {
+        let segments = message
+            .iter()
+            .filter_map(|data| serde_json::to_value(data).ok())
+            .filter_map(|value| Segment::from_value(value).ok())
+            .collect();
+        MessageChain(segments)
+    }
+}
+
+impl From<Segment> for MessageSegment {
+    fn from(segment: Segment) -> Self {
+        let (kind, data) = segment.into_parts();
+        MessageSegment { kind, data }
+    }
+}
+
+/// 作为 API 参数发送时使用消息段数组，不依赖 OneBot 实现解析 CQ 码
+impl From<MessageChain> for MessageContent {
+    fn from(chain: MessageChain) -> Self {
+        MessageContent::Segments(chain.0.into_iter().map(MessageSegment::from).collect())
+    }
+}
+
+impl Serialize for MessageChain {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.0.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for MessageChain {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = Value::deserialize(deserializer)?;
+        MessageChain::from_value(value).map_err(serde::de::Error::custom)
+    }
+}
+
+/// CQ 码转义：& [ ] 在任何位置都要转义，参数中还要转义 ,
+pub fn escape(text: &str, in_param: bool) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '[' => escaped.push_str("&#91;"),
+            ']' => escaped.push_str("&#93;"),
+            ',' if in_param => escaped.push_str("&#44;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// CQ 码反转义（&amp; 最后处理，避免 &amp;#91; 被还原成 [）
+pub fn unescape(text: &str) -> String {
+    text.replace("&#91;", "[")
+        .replace("&#93;", "]")
+        .replace("&#44;", ",")
+        .replace("&amp;", "&")
+}
+
+/// 将 CQ 码字符串解析为消息段数组
+#[tauri::command]
+pub fn parse_cqcode(message: String) -> Vec<Segment> {
+    MessageChain::parse(&message).0
+}
+
+/// 将消息（消息段数组、单个消息段或 CQ 码字符串）渲染为 CQ 码字符串
+#[tauri::command]
+pub fn render_cqcode(message: Value) -> Result<String, String> {
+    MessageChain::from_value(message)
+        .map(|chain| chain.to_cqcode())
+        .map_err(|e| format!("渲染 CQ 码失败: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn round_trip(cq_code: &str) -> MessageChain {
+        let chain = MessageChain::parse(cq_code);
+        assert_eq!(chain.to_cqcode(), cq_code);
+        let value = serde_json::to_value(&chain).unwrap();
+        assert_eq!(MessageChain::from_value(value).unwrap(), chain);
+        chain
+    }
+
+    #[test]
+    fn escapes_text_and_params() {
+        let chain = MessageChain(vec![
+            Segment::text("a&b [x], y"),
+            Segment::Share {
+                url: "https://example.com/?a=1&b=[2],3".to_string(),
+                title: "标题, 带逗号".to_string(),
+                content: None,
+                image: None,
+                extra: Map::new(),
+            },
+        ]);
+        let cq_code = chain.to_cqcode();
+        assert_eq!(
+            cq_code,
+            "a&amp;b &#91;x&#93;, y[CQ:share,title=标题&#44; 带逗号,url=https://example.com/?a=1&amp;b=&#91;2&#93;&#44;3]"
+        );
+        assert_eq!(MessageChain::parse(&cq_code), chain);
+    }
+
+    #[test]
+    fn unescape_is_not_applied_twice() {
+        assert_eq!(unescape("&amp;#91;"), "&#91;");
+        let chain = MessageChain(vec![Segment::text("&#91;literal&#93;")]);
+        assert_eq!(MessageChain::parse(&chain.to_cqcode()), chain);
+    }
+
+    #[test]
+    fn round_trips_every_segment_type() {
+        for cq_code in [
+            "hello",
+            "[CQ:face,id=14]",
+            "[CQ:image,file=abc.jpg,sub_type=0,summary=&#91;动画表情&#93;,url=https://example.com/a.jpg?x=1&amp;y=2]",
+            "[CQ:record,file=a.amr,magic=0]",
+            "[CQ:video,file=a.mp4,url=https://example.com/a.mp4]",
+            "[CQ:file,file=a.zip,file_id=/abc,file_size=1024,name=a.zip]",
+            "[CQ:at,qq=all]",
+            "[CQ:at,name=群友,qq=10001]",
+            "[CQ:reply,id=-12345]",
+            "[CQ:forward,id=abcdef]",
+            "[CQ:json,data={\"app\":\"com.tencent.miniapp\"&#44;\"meta\":{\"a\":&#91;1&#44;2&#93;}}]",
+            "[CQ:xml,data=<?xml version='1.0'?><msg a=\"1&amp;2\"/>]",
+            "[CQ:poke,id=-1,type=126]",
+            "[CQ:dice]",
+            "[CQ:rps]",
+            "[CQ:music,id=28949129,type=163]",
+            "[CQ:music,audio=https://example.com/a.mp3,title=歌,type=custom,url=https://example.com]",
+            "[CQ:share,content=简介,image=https://example.com/a.png,title=标题,url=https://example.com]",
+            "[CQ:contact,id=10001,type=qq]",
+            "[CQ:location,content=地址,lat=39.9,lon=116.3,title=位置]",
+            "[CQ:mface,emoji_id=abc,emoji_package_id=1,key=k,summary=&#91;表情&#93;]",
+            "[CQ:markdown,content=# 标题\n- 列表&#44; 项]",
+            "[CQ:node,id=123]",
+            "前[CQ:reply,id=1][CQ:at,qq=10001] 后",
+        ] {
+            let chain = round_trip(cq_code);
+            let unknown = chain.0.iter().any(|segment| matches!(segment, Segment::Unknown(_)));
+            assert_eq!(unknown, cq_code.starts_with("[CQ:node"), "{}", cq_code);
+        }
+    }
+
+    #[test]
+    fn parses_into_typed_segments() {
+        let chain = MessageChain::parse("[CQ:reply,id=7][CQ:at,qq=10001] hi [CQ:face,id=14][CQ:dice]");
+        assert_eq!(
+            chain.0,
+            vec![
+                Segment::Reply { id: "7".to_string(), extra: Map::new() },
+                Segment::At { qq: "10001".to_string(), name: None, extra: Map::new() },
+                Segment::text(" hi "),
+                Segment::Face { id: "14".to_string(), extra: Map::new() },
+                Segment::Dice { extra: Map::new() },
+            ]
+        );
+    }
+
+    #[test]
+    fn unknown_and_incomplete_segments_are_preserved() {
+        let chain = MessageChain::parse("[CQ:poke,id=1][CQ:node,user_id=1,nickname=a]");
+        assert!(matches!(&chain.0[0], Segment::Unknown(unknown) if unknown.kind == "poke"));
+        assert!(matches!(&chain.0[1], Segment::Unknown(unknown) if unknown.kind == "node"));
+
+        let chain = MessageChain::parse("a[CQ:] b [CQ:face,id=1");
+        assert_eq!(chain.0, vec![Segment::text("a[CQ:] b [CQ:face,id=1")]);
+    }
+
+    #[test]
+    fn accepts_json_with_numbers_and_nested_data() {
+        let value = json!([
+            { "type": "text", "data": { "text": "hi" } },
+            { "type": "at", "data": { "qq": 10001 } },
+            { "type": "image", "data": { "file": "a.jpg", "url": null } },
+            { "type": "rps" },
+            { "type": "node", "data": { "user_id": 1, "content": [{ "type": "text", "data": { "text": "x" } }] } },
+        ]);
+        let chain = MessageChain::from_value(value.clone()).unwrap();
+        assert_eq!(chain.0[1], Segment::At { qq: "10001".to_string(), name: None, extra: Map::new() });
+        assert_eq!(chain.0[3], Segment::Rps { extra: Map::new() });
+        assert_eq!(serde_json::to_value(&chain.0[4]).unwrap(), value[4]);
+        assert_eq!(serde_json::to_value(&chain.0[3]).unwrap(), json!({ "type": "rps", "data": {} }));
+
+        let parsed: MessageChain = serde_json::from_value(json!("[CQ:face,id=1]")).unwrap();
+        assert_eq!(parsed.0, vec![Segment::Face { id: "1".to_string(), extra: Map::new() }]);
+    }
+
+    #[test]
+    fn converts_runbot_message_data() {
+        let message: Vec<runbot::event::MessageData> = vec![
+            runbot::event::MessageText::new("a[b]").into(),
+            runbot::event::MessageFace { id: "14".to_string(), sub_type: 0, raw: Value::Null }.into(),
+        ];
+        let chain = MessageChain::from(message.as_slice());
+        assert!(matches!(&chain.0[1], Segment::Face { extra, .. } if extra["sub_type"] == "0"));
+        assert_eq!(chain.to_cqcode(), "a&#91;b&#93;[CQ:face,id=14,sub_type=0]");
+    }
+
+    #[test]
+    fn keeps_extra_fields_of_typed_segments() {
+        let value = json!([
+            { "type": "face", "data": { "id": "14", "sub_type": 1 } },
+            { "type": "image", "data": { "file": "a.jpg", "file_size": "1024", "file_unique": "abc" } },
+        ]);
+        let chain = MessageChain::from_value(value).unwrap();
+        let Segment::Image { file, extra, .. } = &chain.0[1] else {
+            panic!("应解析为图片: {:?}", chain.0[1]);
+        };
+        assert_eq!(file, "a.jpg");
+        assert_eq!(extra["file_unique"], "abc");
+        assert_eq!(
+            serde_json::to_value(&chain).unwrap(),
+            json!([
+                { "type": "face", "data": { "id": "14", "sub_type": "1" } },
+                { "type": "image", "data": { "file": "a.jpg", "file_size": "1024", "file_unique": "abc" } },
+            ])
+        );
+        round_trip(&chain.to_cqcode());
+        assert_eq!(
+            chain.to_cqcode(),
+            "[CQ:face,id=14,sub_type=1][CQ:image,file=a.jpg,file_size=1024,file_unique=abc]"
+        );
+    }
+
+    #[test]
+    fn converts_to_message_content() {
+        let chain = MessageChain::parse("[CQ:reply,id=7]a&amp;b[CQ:face,id=14,sub_type=1]");
+        let content = serde_json::to_value(MessageContent::from(chain)).unwrap();
+        assert_eq!(
+            content,
+            json!([
+                { "type": "reply", "data": { "id": "7" } },
+                { "type": "text", "data": { "text": "a&b" } },
+                { "type": "face", "data": { "id": "14", "sub_type": "1" } },
+            ])
+        );
+    }
+}
@@ -0,0 +1,521 @@
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use futures_util::StreamExt;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tauri::{Emitter, Manager, State};
+use crate::AppHandle;
+use tokio_tungstenite::tungstenite::Message as WsMessage;
+use crate::control;
+use crate::reverse;
+use crate::runbot::{self, RunbotState};
+use crate::session::Session;
+use crate::storage;
+use crate::supervisor::Upstream;
+
+/// 以后台服务模式运行的命令行参数（界面版本收到时提示改用后台服务版本）
+const HEADLESS_FLAG: &str = "--headless";
+/// 指定配置文件的命令行参数（默认为应用数据目录下的 daemon.json）
+const CONFIG_FLAG: &str = "--config";
+/// 默认配置文件名
+const CONFIG_FILE: &str = "daemon.json";
+/// 后台服务运行时写入的状态文件，界面据此找到后台服务
+const STATE_FILE: &str = "daemon-state.json";
+/// 首次连接失败后重试的最长间隔
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(60);
+/// 检查账号会话是否仍然存在的间隔
+const WATCH_INTERVAL: Duration = Duration::from_secs(10);
+/// 事件转发断开后重新连接的间隔
+const BRIDGE_RETRY_DELAY: Duration = Duration::from_secs(2);
+
+/// 后台服务启动参数
+#[derive(Debug, Clone, Default)]
+pub struct HeadlessOptions {
+    /// 配置文件路径，未指定时使用应用数据目录下的 daemon.json
+    pub config: Option<PathBuf>,
+}
+
+/// 解析命令行：后台服务版本（`headless` 特性）始终以后台服务模式运行；
+/// 界面版本使用 Wry，会初始化 GTK 和 WebView，不接受 `--headless`
+pub fn headless_options() -> Result<Option<HeadlessOptions>, String> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if !cfg!(feature = "headless") {
+        if args.iter().any(|arg| arg == HEADLESS_FLAG) {
+            return Err("当前版本不支持后台服务模式，请使用 `cargo build --release --features headless` 编译后台服务版本".to_string());
+        }
+        return Ok(None);
+    }
+    let config = args
+        .iter()
+        .position(|arg| arg == CONFIG_FLAG)
+        .and_then(|index| args.get(index + 1))
+        .map(PathBuf::from);
+    Ok(Some(HeadlessOptions { config }))
+}
+
+/// 后台服务连接的账号
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AccountConfig {
+    /// 正向 WebSocket（同 connect_runbot）
+    Websocket {
+        url: String,
+        #[serde(default)]
+        access_token: Option<String>,
+    },
+    /// HTTP API + HTTP POST 上报（同 connect_runbot_http）
+    Http {
+        api_url: String,
+        #[serde(default)]
+        access_token: Option<String>,
+        #[serde(default = "default_listen_host")]
+        listen_host: String,
+        listen_port: u16,
+        #[serde(default)]
+        secret: Option<String>,
+    },
+}
+
+fn default_listen_host() -> String {
+    "127.0.0.1".to_string()
+}
+
+/// 反向 WebSocket 服务器（同 start_reverse_server）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReverseConfig {
+    #[serde(default = "default_listen_host")]
+    pub host: String,
+    pub port: u16,
+    #[serde(default)]
+    pub access_token: Option<String>,
+}
+
+/// 本地控制接口，界面和外部工具通过它访问后台服务
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ControlConfig {
+    /// 为 0 时自动分配
+    #[serde(default)]
+    pub port: u16,
+    /// 为空时每次启动随机生成
+    #[serde(default)]
+    pub token: Option<String>,
+}
+
+/// 后台服务配置文件
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DaemonConfig {
+    #[serde(default)]
+    pub accounts: Vec<AccountConfig>,
+    #[serde(default)]
+    pub reverse: Option<ReverseConfig>,
+    #[serde(default)]
+    pub control: ControlConfig,
+}
+
+/// 附加到后台服务的界面修改配置后，后台服务需要重新加载的内容
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReloadTarget {
+    /// 自动回复规则
+    AutoReply,
+    /// 插件及其授权
+    Plugins,
+    /// 脚本
+    Scripts,
+    /// 定时任务（唤醒调度循环）
+    Schedule,
+}
+
+/// 正在运行的后台服务（写入状态文件）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DaemonInfo {
+    pub pid: u32,
+    /// 本地控制接口地址
+    pub addr: String,
+    pub token: String,
+    /// 秒级时间戳
+    pub started_at: i64,
+}
+
+fn state_file(app: &AppHandle) -> Result<PathBuf, String> {
+    let mut path = storage::get_user_data_dir(app, None)?;
+    path.push(STATE_FILE);
+    Ok(path)
+}
+
+/// 写入只有当前用户可读写的文件（状态文件中有控制接口的令牌）
+///
+/// 先删除旧文件再新建，避免沿用旧文件更宽松的权限
+fn write_private(path: &Path, content: &str) -> std::io::Result<()> {
+    match std::fs::remove_file(path) {
+        Ok(()) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e),
+    }
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(path)?.write_all(content.as_bytes())
+}
+
+/// 读取配置文件；文件不存在时写入一份空配置
+fn load_config(app: &AppHandle, options: &HeadlessOptions) -> Result<DaemonConfig, String> {
+    let path = match &options.config {
+        Some(path) => path.clone(),
+        None => {
+            let mut path = storage::get_user_data_dir(app, None)?;
+            path.push(CONFIG_FILE);
+            path
+        }
+    };
+
+    if !path.exists() {
+        let config = DaemonConfig::default();
+        let content = serde_json::to_string_pretty(&config).map_err(|e| format!("序列化配置失败: {}", e))?;
+        std::fs::write(&path, content).map_err(|e| format!("写入配置文件 {:?} 失败: {}", path, e))?;
+        tracing::warn!("[daemon] 配置文件不存在，已创建空配置: {:?}", path);
+        return Ok(config);
+    }
+
+    let content = std::fs::read_to_string(&path).map_err(|e| format!("读取配置文件 {:?} 失败: {}", path, e))?;
+    let config = serde_json::from_str(&content).map_err(|e| format!("解析配置文件 {:?} 失败: {}", path, e))?;
+    tracing::info!("[daemon] 已加载配置文件: {:?}", path);
+    Ok(config)
+}
+
+/// 启动后台服务：不创建窗口，按配置文件连接账号，事件照常入库并经过全部处理器
+///
+/// 本地控制接口启动后把地址和令牌写入状态文件，界面通过 [`attach_daemon`] 附加；
+/// 收到 Ctrl+C 时退出应用
+pub fn start(app: AppHandle, options: HeadlessOptions) {
+    tauri::async_runtime::spawn(async move {
+        if let Err(e) = run(&app, &options).await {
+            tracing::error!("[daemon] 后台服务启动失败: {}", e);
+            exit(&app, 1);
+        }
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            tracing::error!("[daemon] 监听退出信号失败: {}", e);
+            return;
+        }
+        tracing::info!("[daemon] 收到退出信号");
+        exit(&app, 0);
+    });
+}
+
+/// 删除状态文件后退出进程（MockRuntime 不支持 `AppHandle::exit`）
+fn exit(app: &AppHandle, code: i32) -> ! {
+    cleanup(app);
+    std::process::exit(code)
+}
+
+async fn run(app: &AppHandle, options: &HeadlessOptions) -> Result<(), String> {
+    let config = load_config(app, options)?;
+    let state = app.state::<Arc<Mutex<RunbotState>>>();
+
+    let control = control::start_control_server(
+        config.control.port,
+        config.control.token.clone(),
+        app.clone(),
+        state.clone(),
+    )
+    .await?;
+    let info = DaemonInfo {
+        pid: std::process::id(),
+        addr: control.addr,
+        token: control.token,
+        started_at: chrono::Utc::now().timestamp(),
+    };
+    let path = state_file(app)?;
+    let content = serde_json::to_string_pretty(&info).map_err(|e| format!("序列化状态失败: {}", e))?;
+    write_private(&path, &content).map_err(|e| format!("写入状态文件 {:?} 失败: {}", path, e))?;
+    tracing::info!("[daemon] 后台服务已启动，本地控制接口: {}", info.addr);
+
+    if let Some(reverse) = config.reverse {
+        reverse::start_reverse_server(reverse.host, reverse.port, reverse.access_token, app.clone(), state).await?;
+    }
+
+    for account in config.accounts {
+        let app = app.clone();
+        tauri::async_runtime::spawn(async move { keep_connected(app, account).await });
+    }
+    Ok(())
+}
+
+/// 后台服务退出时删除状态文件
+fn cleanup(app: &AppHandle) {
+    if let Ok(path) = state_file(app) {
+        let _ = std::fs::remove_file(path);
+    }
+}
+
+/// 保持账号连接：首次连接失败时按退避间隔重试；连接建立后断线重连由 supervisor 负责，
+/// supervisor 放弃后（会话被移除）重新开始
+async fn keep_connected(app: AppHandle, account: AccountConfig) {
+    let state = app.state::<Arc<Mutex<RunbotState>>>();
+    let mut delay = Duration::from_secs(5);
+    loop {
+        let result = match account.clone() {
+            AccountConfig::Websocket { url, access_token } => {
+                runbot::connect_runbot(url, access_token, app.clone(), state.clone()).await
+            }
+            AccountConfig::Http { api_url, access_token, listen_host, listen_port, secret } => {
+                runbot::connect_runbot_http(
+                    api_url,
+                    access_token,
+                    listen_host,
+                    listen_port,
+                    secret,
+                    app.clone(),
+                    state.clone(),
+                )
+                .await
+            }
+        };
+
+        match result {
+            Ok(account_id) => {
+                tracing::info!("[daemon] 账号 {} 已连接", account_id);
+                delay = Duration::from_secs(5);
+                loop {
+                    tokio::time::sleep(WATCH_INTERVAL).await;
+                    let alive = state
+                        .lock()
+                        .map(|state_guard| state_guard.sessions.get(account_id).is_some())
+                        .unwrap_or(false);
+                    if !alive {
+                        break;
+                    }
+                }
+                tracing::warn!("[daemon] 账号 {} 的会话已结束，重新连接", account_id);
+            }
+            Err(e) => {
+                tracing::warn!("[daemon] 连接失败，{} 秒后重试: {}", delay.as_secs(), e);
+                tokio::time::sleep(delay).await;
+                delay = (delay * 2).min(MAX_RETRY_DELAY);
+            }
+        }
+    }
+}
+
+/// 读取状态文件，并确认后台服务的本地控制接口仍可连接
+async fn running_daemon(app: &AppHandle) -> Result<Option<DaemonInfo>, String> {
+    let path = state_file(app)?;
+    let Ok(content) = std::fs::read_to_string(&path) else {
+        return Ok(None);
+    };
+    let Ok(info) = serde_json::from_str::<DaemonInfo>(&content) else {
+        return Ok(None);
+    };
+    if info.pid == std::process::id() {
+        return Ok(None);
+    }
+    let probe = tokio::time::timeout(Duration::from_secs(1), tokio::net::TcpStream::connect(&info.addr)).await;
+    Ok(matches!(probe, Ok(Ok(_))).then_some(info))
+}
+
+/// 在本进程中重新加载账号的配置（后台服务收到界面的通知时调用）
+pub(crate) async fn reload_local(registry: Arc<Mutex<RunbotState>>, account_id: i64, target: ReloadTarget) -> Result<(), String> {
+    match target {
+        ReloadTarget::AutoReply => crate::autoreply::invalidate(&registry, account_id),
+        ReloadTarget::Schedule => crate::schedule::wake(&registry, account_id),
+        ReloadTarget::Plugins => tokio::task::spawn_blocking(move || crate::plugin::reload_loaded(&registry, account_id))
+            .await
+            .map_err(|e| format!("加载插件失败: {}", e))?,
+        ReloadTarget::Scripts => tokio::task::spawn_blocking(move || crate::scripting::rescan_loaded(&registry, account_id))
+            .await
+            .map_err(|e| format!("加载脚本失败: {}", e))?,
+    }
+    Ok(())
+}
+
+/// 账号附加到后台服务时通知后台服务重新加载：配置保存在共享的数据库和目录中，
+/// 但缓存、已加载的插件和脚本以及调度循环都在后台服务进程里，本地的重新加载对它们不起作用
+pub(crate) async fn notify_daemon(
+    app: &AppHandle,
+    state: &Mutex<RunbotState>,
+    account_id: i64,
+    target: ReloadTarget,
+) -> Result<(), String> {
+    if !runbot::is_attached(state, account_id) {
+        return Ok(());
+    }
+    let info = running_daemon(app)
+        .await?
+        .ok_or_else(|| "已保存，但后台服务未运行，无法通知它重新加载".to_string())?;
+
+    let body = reqwest::Client::new()
+        .post(format!("http://{}/_runbot/reload?self_id={}", info.addr, account_id))
+        .bearer_auth(&info.token)
+        .body(serde_json::json!({ "target": target }).to_string())
+        .send()
+        .await
+        .map_err(|e| format!("通知后台服务重新加载失败: {}", e))?
+        .text()
+        .await
+        .map_err(|e| format!("读取后台服务响应失败: {}", e))?;
+    let response: Value = serde_json::from_str(&body).map_err(|e| format!("解析后台服务响应失败: {}", e))?;
+    if response.get("status").and_then(Value::as_str) != Some("ok") {
+        return Err(format!("后台服务重新加载失败: {}", response));
+    }
+    tracing::info!("[daemon] 已通知后台服务重新加载 {:?}: 账号 {}", target, account_id);
+    Ok(())
+}
+
+/// 获取正在运行的后台服务（未运行时为空）
+#[tauri::command]
+pub async fn get_daemon(app: AppHandle) -> Result<Option<DaemonInfo>, String> {
+    running_daemon(&app).await
+}
+
+/// 附加到后台服务：为后台服务已连接的每个账号建立附加会话，返回这些账号
+///
+/// 附加会话不连接 OneBot 实现，API 调用通过后台服务转发，后台服务发给前端的事件在本地重新发出
+#[tauri::command]
+pub async fn attach_daemon(
+    app: AppHandle,
+    state: State<'_, Arc<Mutex<RunbotState>>>,
+) -> Result<Vec<i64>, String> {
+    let info = running_daemon(&app)
+        .await?
+        .ok_or_else(|| "后台服务未运行".to_string())?;
+
+    let body = reqwest::Client::new()
+        .get(format!("http://{}/_runbot/accounts", info.addr))
+        .bearer_auth(&info.token)
+        .send()
+        .await
+        .map_err(|e| format!("连接后台服务失败: {}", e))?
+        .text()
+        .await
+        .map_err(|e| format!("读取后台服务响应失败: {}", e))?;
+    let response: Value = serde_json::from_str(&body).map_err(|e| format!("解析后台服务响应失败: {}", e))?;
+    let accounts: Vec<i64> = response
+        .get("data")
+        .cloned()
+        .and_then(|data| serde_json::from_value(data).ok())
+        .ok_or_else(|| format!("获取后台服务账号失败: {}", response))?;
+
+    for &account_id in &accounts {
+        let existing = state
+            .lock()
+            .map_err(|e| format!("锁定状态失败: {}", e))?
+            .sessions
+            .get(account_id);
+        if existing.is_some() {
+            tracing::info!("[attach_daemon] 账号 {} 已有会话，跳过", account_id);
+            continue;
+        }
+
+        let url = format!(
+            "ws://{}/api?self_id={}&access_token={}",
+            info.addr,
+            account_id,
+            urlencoding::encode(&info.token)
+        );
+        let (session, ready) = runbot::start_session(&app, state.inner(), Upstream::Attach { url }).await?;
+        match ready.await {
+            Ok(Ok(_)) => {}
+            Ok(Err(e)) => return Err(e),
+            Err(_) => return Err("连接已取消".to_string()),
+        }
+        spawn_event_bridge(app.clone(), state.inner().clone(), session, info.clone(), account_id);
+        tracing::info!("[attach_daemon] 已附加到后台服务的账号 {}", account_id);
+    }
+    Ok(accounts)
+}
+
+/// 把后台服务发给前端的事件在本地重新发出，会话结束后停止
+fn spawn_event_bridge(
+    app: AppHandle,
+    registry: Arc<Mutex<RunbotState>>,
+    session: Arc<Mutex<Session>>,
+    info: DaemonInfo,
+    account_id: i64,
+) {
+    let url = format!(
+        "ws://{}/_runbot/app-events?self_id={}&access_token={}",
+        info.addr,
+        account_id,
+        urlencoding::encode(&info.token)
+    );
+    tauri::async_runtime::spawn(async move {
+        let is_current = || {
+            registry
+                .lock()
+                .ok()
+                .and_then(|state_guard| state_guard.sessions.get(account_id))
+                .is_some_and(|current| Arc::ptr_eq(&current, &session))
+        };
+
+        while is_current() {
+            match tokio_tungstenite::connect_async(url.as_str()).await {
+                Ok((mut ws_stream, _)) => {
+                    tracing::info!("[daemon] 已连接后台服务的事件推送: 账号 {}", account_id);
+                    let mut check = tokio::time::interval(WATCH_INTERVAL);
+                    loop {
+                        tokio::select! {
+                            message = ws_stream.next() => match message {
+                                Some(Ok(WsMessage::Text(text))) => reemit(&app, text.as_str()),
+                                Some(Ok(_)) => {}
+                                Some(Err(_)) | None => break,
+                            },
+                            _ = check.tick() => {
+                                if !is_current() {
+                                    return;
+                                }
+                            }
+                        }
+                    }
+                    tracing::warn!("[daemon] 后台服务的事件推送已断开: 账号 {}", account_id);
+                }
+                Err(e) => tracing::warn!("[daemon] 连接后台服务的事件推送失败: {}", e),
+            }
+            tokio::time::sleep(BRIDGE_RETRY_DELAY).await;
+        }
+    });
+}
+
+/// 重新发出后台服务转发的事件 `{event, payload}`
+fn reemit(app: &AppHandle, text: &str) {
+    let Ok(frame) = serde_json::from_str::<Value>(text) else {
+        return;
+    };
+    if let (Some(event), Some(payload)) = (frame.get("event").and_then(Value::as_str), frame.get("payload")) {
+        let _ = app.emit(event, payload);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn writes_state_file_readable_only_by_owner() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = std::env::temp_dir().join(format!("runbot-daemon-state-{}.json", std::process::id()));
+        std::fs::write(&path, "old").unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).unwrap();
+
+        write_private(&path, "{\"token\":\"secret\"}").unwrap();
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        let content = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(mode & 0o777, 0o600);
+        assert_eq!(content, "{\"token\":\"secret\"}");
+    }
+
+    #[test]
+    fn parses_reload_targets() {
+        let target: ReloadTarget = serde_json::from_str("\"auto_reply\"").unwrap();
+        assert_eq!(target, ReloadTarget::AutoReply);
+        assert_eq!(serde_json::to_string(&ReloadTarget::Plugins).unwrap(), "\"plugins\"");
+    }
+}
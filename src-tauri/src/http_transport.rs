@@ -0,0 +1,247 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use axum::body::Bytes;
+use axum::extract::State;
+use axum::http::{HeaderMap, StatusCode};
+use axum::routing::post;
+use axum::Router;
+use hmac::{Hmac, Mac};
+use sha1::Sha1;
+use tokio::net::TcpListener;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+/// OneBot v11 HTTP API 客户端
+///
+/// 把 runbot 发出的 `{action, params, echo}` 帧转换为 `POST {api_url}/{action}` 请求，
+/// 并把 HTTP 响应补全为带 echo 的 WebSocket 响应帧
+#[derive(Debug, Clone)]
+pub struct HttpApi {
+    client: reqwest::Client,
+    api_url: String,
+    access_token: Option<String>,
+}
+
+impl HttpApi {
+    pub fn new(api_url: &str, access_token: Option<String>) -> Result<Self, String> {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(30))
+            .build()
+            .map_err(|e| format!("创建 HTTP 客户端失败: {}", e))?;
+        Ok(Self {
+            client,
+            api_url: api_url.trim_end_matches('/').to_string(),
+            access_token,
+        })
+    }
+
+    pub fn api_url(&self) -> &str {
+        &self.api_url
+    }
+
+    /// 执行一个 API 调用帧，返回对应的响应帧
+    pub async fn call(&self, frame: &str) -> Result<String, String> {
+        let request: serde_json::Value =
+            serde_json::from_str(frame).map_err(|e| format!("解析 API 请求失败: {}", e))?;
+        let action = request
+            .get("action")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| "API 请求缺少 action".to_string())?;
+        let params = match request.get("params") {
+            Some(serde_json::Value::Null) | None => serde_json::json!({}),
+            Some(params) => params.clone(),
+        };
+        let echo = request.get("echo").cloned().unwrap_or(serde_json::Value::Null);
+
+        let mut builder = self
+            .client
+            .post(format!("{}/{}", self.api_url, action))
+            .header("Content-Type", "application/json")
+            .body(params.to_string());
+        if let Some(token) = &self.access_token {
+            builder = builder.header("Authorization", format!("Bearer {}", token));
+        }
+
+        let response = builder
+            .send()
+            .await
+            .map_err(|e| format!("HTTP API 请求失败: {}", e))?;
+        let status = response.status();
+        if !status.is_success() {
+            return Err(format!("HTTP API 请求失败: HTTP {}", status));
+        }
+        let body = response
+            .text()
+            .await
+            .map_err(|e| format!("读取 HTTP API 响应失败: {}", e))?;
+
+        let mut value: serde_json::Value =
+            serde_json::from_str(&body).map_err(|e| format!("解析 HTTP API 响应失败: {}", e))?;
+        let object = value
+            .as_object_mut()
+            .ok_or_else(|| "HTTP API 响应不是 JSON 对象".to_string())?;
+        // runbot 解析响应时要求这些字段存在
+        object.insert("echo".to_string(), echo);
+        for field in ["message", "wording"] {
+            object
+                .entry(field)
+                .or_insert_with(|| serde_json::Value::String(String::new()));
+        }
+        object.entry("data").or_insert(serde_json::Value::Null);
+        Ok(value.to_string())
+    }
+}
+
+/// OneBot v11 HTTP POST 事件接收器
+///
+/// 在本地监听 HTTP 请求，校验 `X-Signature`（HMAC-SHA1）后把事件原文交给会话
+#[derive(Debug)]
+pub struct EventReceiver {
+    addr: SocketAddr,
+    task: JoinHandle<()>,
+}
+
+#[derive(Debug)]
+struct ReceiverContext {
+    secret: Option<String>,
+    events: mpsc::UnboundedSender<String>,
+}
+
+impl EventReceiver {
+    /// 在指定地址上监听，返回接收器和事件通道
+    pub async fn bind(
+        host: &str,
+        port: u16,
+        secret: Option<String>,
+    ) -> Result<(Self, mpsc::UnboundedReceiver<String>), String> {
+        let listener = TcpListener::bind((host, port))
+            .await
+            .map_err(|e| format!("监听 {}:{} 失败: {}", host, port, e))?;
+        let addr = listener
+            .local_addr()
+            .map_err(|e| format!("获取监听地址失败: {}", e))?;
+
+        let (events_tx, events_rx) = mpsc::unbounded_channel();
+        let context = Arc::new(ReceiverContext {
+            secret,
+            events: events_tx,
+        });
+        // OneBot 实现一般直接 POST 到配置的地址，这里不区分路径
+        let app = Router::new()
+            .route("/", post(receive_event))
+            .route("/{*path}", post(receive_event))
+            .with_state(context);
+
+        tracing::info!("[http_transport] HTTP 事件接收器监听于 {}", addr);
+        let task = tokio::spawn(async move {
+            if let Err(e) = axum::serve(listener, app).await {
+                tracing::error!("[http_transport] HTTP 事件接收器错误: {}", e);
+            }
+        });
+
+        Ok((Self { addr, task }, events_rx))
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+}
+
+impl Drop for EventReceiver {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// 校验 `X-Signature: sha1=<hex>`
+fn verify_signature(secret: &str, headers: &HeaderMap, body: &[u8]) -> bool {
+    let Some(signature) = headers
+        .get("X-Signature")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("sha1="))
+    else {
+        return false;
+    };
+    let Ok(signature) = hex::decode(signature.trim()) else {
+        return false;
+    };
+    let Ok(mut mac) = Hmac::<Sha1>::new_from_slice(secret.as_bytes()) else {
+        return false;
+    };
+    mac.update(body);
+    mac.verify_slice(&signature).is_ok()
+}
+
+async fn receive_event(
+    State(context): State<Arc<ReceiverContext>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> StatusCode {
+    if let Some(secret) = &context.secret {
+        if !verify_signature(secret, &headers, &body) {
+            tracing::warn!("[http_transport] 拒绝事件: X-Signature 校验失败");
+            return StatusCode::FORBIDDEN;
+        }
+    }
+
+    let Ok(text) = String::from_utf8(body.to_vec()) else {
+        return StatusCode::BAD_REQUEST;
+    };
+    if context.events.send(text).is_err() {
+        return StatusCode::SERVICE_UNAVAILABLE;
+    }
+    // 不使用快速操作
+    StatusCode::NO_CONTENT
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BODY: &[u8] = br#"{"post_type":"meta_event","meta_event_type":"heartbeat"}"#;
+
+    fn signed(secret: &str, body: &[u8]) -> HeaderMap {
+        let mut mac = Hmac::<Sha1>::new_from_slice(secret.as_bytes()).unwrap();
+        mac.update(body);
+        let mut headers = HeaderMap::new();
+        let signature = format!("sha1={}", hex::encode(mac.finalize().into_bytes()));
+        headers.insert("X-Signature", signature.parse().unwrap());
+        headers
+    }
+
+    #[test]
+    fn accepts_valid_signature() {
+        assert!(verify_signature("secret", &signed("secret", BODY), BODY));
+    }
+
+    #[test]
+    fn rejects_wrong_or_missing_signature() {
+        assert!(!verify_signature("secret", &signed("other", BODY), BODY));
+        assert!(!verify_signature("secret", &signed("secret", b"{}"), BODY));
+        assert!(!verify_signature("secret", &HeaderMap::new(), BODY));
+
+        let mut headers = HeaderMap::new();
+        headers.insert("X-Signature", "md5=00".parse().unwrap());
+        assert!(!verify_signature("secret", &headers, BODY));
+        headers.insert("X-Signature", "sha1=not-hex".parse().unwrap());
+        assert!(!verify_signature("secret", &headers, BODY));
+    }
+
+    #[tokio::test]
+    async fn checks_signature_only_with_secret() {
+        let (events_tx, mut events_rx) = mpsc::unbounded_channel();
+        let context = Arc::new(ReceiverContext { secret: None, events: events_tx.clone() });
+        let status = receive_event(State(context), HeaderMap::new(), Bytes::from_static(BODY)).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        assert_eq!(events_rx.recv().await.unwrap().as_bytes(), BODY);
+
+        let context = Arc::new(ReceiverContext { secret: Some("secret".to_string()), events: events_tx });
+        let status = receive_event(State(context.clone()), HeaderMap::new(), Bytes::from_static(BODY)).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let status = receive_event(State(context), signed("secret", BODY), Bytes::from_static(BODY)).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        assert_eq!(events_rx.recv().await.unwrap().as_bytes(), BODY);
+        assert!(events_rx.try_recv().is_err());
+    }
+}
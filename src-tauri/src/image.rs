@@ -1,4 +1,5 @@
-use tauri::{AppHandle, Manager};
+use tauri::Manager;
+use crate::AppHandle;
 use std::path::{Path, PathBuf};
 use std::fs;
 use std::io::Write;
@@ -296,10 +297,8 @@ pub async fn download_image(
                     tracing::info!("[download_image] URL 可能已过期，尝试通过 get_image_detail API 获取新 URL: file = {}", file_id);
                     
                     // 获取 bot_ctx
-                    let bot_ctx = {
-                        let state_guard = state.lock().map_err(|e| format!("锁定状态失败: {}", e))?;
-                        state_guard.bot_ctx.clone()
-                    };
+                    // self_id 即图片所属账号，使用该账号的会话重新获取
+                    let bot_ctx = self_id.and_then(|account_id| crate::runbot::ready_bot_ctx(&state, account_id).ok());
                     
                     if let Some(bot_ctx) = bot_ctx {
                         // 直接调用异步 API（因为我们在 async 函数中）
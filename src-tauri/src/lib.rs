@@ -1,14 +1,46 @@
 // 模块声明
+pub mod mock_onebot;
 mod runbot;
+mod actions;
+mod outbox;
+mod schedule;
+mod autoreply;
+mod scripting;
+mod plugin;
+mod notice;
+mod request;
+mod review;
+mod recorder;
+mod bridge;
+mod connection;
+mod session;
+mod reverse;
+mod control;
+mod relay;
+mod daemon;
+mod http_transport;
+mod supervisor;
 mod storage;
 mod avatar;
 mod image;
 mod qface_embed;
+mod cqcode;
+#[cfg(test)]
+mod test_support;
 
 use std::sync::{Arc, Mutex, OnceLock};
 use runbot::RunbotState;
 use tauri::Manager;
 
+/// 应用使用的 Tauri 运行时；单元测试和后台服务版本（`headless` 特性）使用 MockRuntime，
+/// 不需要窗口环境就能运行会话、队列等后端逻辑
+#[cfg(not(any(test, feature = "headless")))]
+pub(crate) type Runtime = tauri::Wry;
+#[cfg(any(test, feature = "headless"))]
+pub(crate) type Runtime = tauri::test::MockRuntime;
+
+pub(crate) type AppHandle = tauri::AppHandle<Runtime>;
+
 // Learn more about Tauri commands at https://tauri.app/develop/calling-rust/
 #[tauri::command]
 fn greet(name: &str) -> String {
@@ -16,7 +48,6 @@ fn greet(name: &str) -> String {
 }
 
 static APP_DATA_DIR: OnceLock<String> = OnceLock::new();
-static CURRENT_SELF_ID: OnceLock<Arc<Mutex<Option<i64>>>> = OnceLock::new();
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
@@ -32,30 +63,48 @@ pub fn run() {
         .with_line_number(false) // 不显示行号（可选）
         .init();
     
-    tracing::info!("初始化 Tauri 应用");
+    // 后台服务版本或带 --headless 参数时以后台服务模式运行
+    let headless = match daemon::headless_options() {
+        Ok(headless) => headless,
+        Err(e) => {
+            tracing::error!("[daemon] {}", e);
+            std::process::exit(1);
+        }
+    };
+    tracing::info!("初始化 Tauri 应用{}", if headless.is_some() { "（后台服务模式）" } else { "" });
     
-    tauri::Builder::default()
+    tauri::Builder::<Runtime>::new()
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_notification::init())
         .register_uri_scheme_protocol("asset", move |_app_handle, request| {
                 let uri_str = request.uri().to_string();
                 
-                // 处理头像请求：asset://avatar/user/123456.png 或 asset://avatar/group/123456.png
+                // 处理头像请求：asset://avatar/{account}/user/123456.png 或 asset://avatar/{account}/group/123456.png
+                // （旧格式 asset://avatar/user/123456.png 使用不区分账号的缓存目录）
                 if uri_str.starts_with("asset://avatar/") {
-                    // 解析路径：asset://avatar/user/123456.png -> user/123456.png
+                    // 解析路径：asset://avatar/10001/user/123456.png -> 10001/user/123456.png
                     let path_part = uri_str.strip_prefix("asset://avatar/")
                         .unwrap_or("");
                     
                     let parts: Vec<&str> = path_part.split('/').collect();
-                    if parts.len() != 2 {
-                        return tauri::http::Response::builder()
-                            .status(400)
-                            .body("Invalid avatar URL format".as_bytes().to_vec())
-                            .unwrap();
-                    }
-                    
-                    let avatar_type = parts[0]; // "user" 或 "group"
-                    let id_str = parts[1]; // "123456.png"
+                    let (self_id, avatar_type, id_str) = match parts.as_slice() {
+                        [account, avatar_type, id_str] => match account.parse::<i64>() {
+                            Ok(account) => (Some(account), *avatar_type, *id_str),
+                            Err(_) => {
+                                return tauri::http::Response::builder()
+                                    .status(400)
+                                    .body("Invalid avatar account".as_bytes().to_vec())
+                                    .unwrap();
+                            }
+                        },
+                        [avatar_type, id_str] => (None, *avatar_type, *id_str),
+                        _ => {
+                            return tauri::http::Response::builder()
+                                .status(400)
+                                .body("Invalid avatar URL format".as_bytes().to_vec())
+                                .unwrap();
+                        }
+                    };
                     
                     // 提取 ID（去掉扩展名）
                     let id = match id_str.split('.').next() {
@@ -76,12 +125,6 @@ pub fn run() {
                         }
                     };
                     
-                    // 获取 self_id（从静态变量获取）
-                    let self_id: Option<i64> = CURRENT_SELF_ID
-                        .get()
-                        .and_then(|id| id.lock().ok())
-                        .and_then(|id| *id);
-                    
                     // 获取应用数据目录
                     let app_data_dir_str = match APP_DATA_DIR.get() {
                         Some(dir) => dir.clone(),
@@ -242,29 +285,133 @@ pub fn run() {
                         .unwrap()
                 }
             })
-        .setup(|app| {
+        .setup(move |app| {
             // 初始化应用数据目录到静态变量
             let app_data_dir = app.path().app_data_dir().unwrap();
             let app_data_dir_str = app_data_dir.to_string_lossy().to_string();
             APP_DATA_DIR.set(app_data_dir_str).expect("Failed to set app data dir");
             
-            // 初始化 self_id 静态变量
-            CURRENT_SELF_ID.set(Arc::new(Mutex::new(None))).expect("Failed to set CURRENT_SELF_ID");
+            // 后台服务模式不创建窗口
+            match headless {
+                Some(options) => daemon::start(app.handle().clone(), options),
+                None => {
+                    for window in app.config().app.windows.clone() {
+                        tauri::WebviewWindowBuilder::from_config(app.handle(), &window)?.build()?;
+                    }
+                }
+            }
             
             Ok(())
         })
         .manage(Arc::new(Mutex::new(RunbotState::default())))
+        .manage(storage::ReplayDirs::default())
         .invoke_handler(tauri::generate_handler![
             greet,
             // Runbot 命令
             runbot::connect_runbot,
+            runbot::connect_runbot_http,
+            runbot::replay_recording,
             runbot::disconnect_runbot,
             runbot::get_runbot_status,
-            runbot::get_runbot_self_id,
+            runbot::list_runbot_sessions,
             runbot::send_runbot_message,
+            runbot::call_action,
             runbot::get_forward_message,
             runbot::get_group_detail_info,
             runbot::get_group_info_ex,
+            // OneBot v11 标准 API
+            actions::send_private_msg,
+            actions::send_group_msg,
+            actions::send_msg,
+            actions::delete_msg,
+            actions::get_msg,
+            actions::get_forward_msg,
+            actions::send_like,
+            actions::set_group_kick,
+            actions::set_group_ban,
+            actions::set_group_anonymous_ban,
+            actions::set_group_whole_ban,
+            actions::set_group_admin,
+            actions::set_group_anonymous,
+            actions::set_group_card,
+            actions::set_group_name,
+            actions::set_group_leave,
+            actions::set_group_special_title,
+            actions::set_friend_add_request,
+            actions::set_group_add_request,
+            actions::get_login_info,
+            actions::get_stranger_info,
+            actions::get_friend_list,
+            actions::get_group_info,
+            actions::get_group_list,
+            actions::get_group_member_info,
+            actions::get_group_member_list,
+            actions::get_group_honor_info,
+            actions::get_cookies,
+            actions::get_csrf_token,
+            actions::get_credentials,
+            actions::get_record,
+            actions::get_image,
+            actions::can_send_image,
+            actions::can_send_record,
+            actions::get_status,
+            actions::get_version_info,
+            actions::set_restart,
+            actions::clean_cache,
+            // 发送队列命令
+            outbox::get_outbox,
+            outbox::retry_outbox_message,
+            outbox::delete_outbox_message,
+            // 自动回复规则命令
+            autoreply::get_auto_reply_rules,
+            autoreply::save_auto_reply_rule,
+            autoreply::set_auto_reply_rule_enabled,
+            autoreply::delete_auto_reply_rule,
+            // 定时消息命令
+            schedule::get_scheduled_jobs,
+            schedule::save_scheduled_job,
+            schedule::cancel_scheduled_job,
+            // 脚本命令
+            scripting::get_scripts_dir,
+            scripting::list_scripts,
+            scripting::reload_scripts,
+            scripting::get_script_logs,
+            // WASM 插件命令
+            plugin::get_plugins_dir,
+            plugin::list_plugins,
+            plugin::reload_plugins,
+            plugin::set_plugin_enabled,
+            plugin::get_plugin_logs,
+            // 通知命令
+            notice::get_notices,
+            // 请求处理命令
+            request::approve_request,
+            request::reject_request,
+            request::approve_requests,
+            request::reject_requests,
+            // 自动审核规则命令
+            review::get_review_rules,
+            review::set_review_rules,
+            // 流量录制命令
+            recorder::start_recording,
+            recorder::stop_recording,
+            recorder::list_recordings,
+            // 反向 WebSocket 命令
+            reverse::start_reverse_server,
+            reverse::stop_reverse_server,
+            reverse::get_reverse_server_addr,
+            // 本地控制接口命令
+            control::start_control_server,
+            control::stop_control_server,
+            control::get_control_server,
+            // 后台服务命令
+            daemon::get_daemon,
+            daemon::attach_daemon,
+            // 中继命令
+            relay::start_relay_server,
+            relay::stop_relay_server,
+            relay::get_relay_server,
+            relay::get_relay_actions,
             // 存储命令
             storage::save_config,
             storage::load_config,
@@ -283,6 +430,7 @@ pub fn run() {
             storage::save_request,
             storage::update_request_status,
             storage::get_requests,
+            storage::get_request_transitions,
             storage::delete_request,
             storage::clear_history_requests,
             storage::mark_request_read,
@@ -294,6 +442,9 @@ pub fn run() {
             // 图片命令
             image::check_image_cache,
             image::download_image,
+            // CQ 码命令
+            cqcode::parse_cqcode,
+            cqcode::render_cqcode,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -0,0 +1,622 @@
+use std::collections::{HashMap, HashSet};
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicI64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use futures_util::{SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{broadcast, watch, Notify};
+use tokio::task::JoinHandle;
+use tokio_tungstenite::tungstenite::handshake::server::{ErrorResponse, Request, Response};
+use tokio_tungstenite::tungstenite::http::StatusCode;
+use tokio_tungstenite::tungstenite::protocol::Message as WsMessage;
+use crate::reverse::is_authorized;
+
+/// 模拟账号的登录信息
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MockLogin {
+    pub user_id: i64,
+    pub nickname: String,
+}
+
+impl Default for MockLogin {
+    fn default() -> Self {
+        Self {
+            user_id: 10001,
+            nickname: "Mock Bot".to_string(),
+        }
+    }
+}
+
+/// 模拟服务器的数据，API 调用按这里的内容回复
+///
+/// 各列表中的对象原样作为 OneBot 响应的 data 返回，字段与 OneBot v11 一致
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Fixtures {
+    pub login: MockLogin,
+    /// get_friend_list，同时用于 get_stranger_info
+    pub friends: Vec<Value>,
+    /// get_group_list / get_group_info
+    pub groups: Vec<Value>,
+    /// 群号 -> get_group_member_list
+    pub members: HashMap<String, Vec<Value>>,
+    /// get_msg 可以查到的消息（发送和推送的消息也会加入这里）
+    pub messages: Vec<Value>,
+    /// 合并转发 id -> 消息列表，供 get_forward_msg 使用
+    pub forwards: HashMap<String, Vec<Value>>,
+    /// 其他 action 的固定响应数据，优先于内置的处理
+    pub responses: HashMap<String, Value>,
+}
+
+impl Fixtures {
+    /// 从 JSON 文件加载
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let content = std::fs::read_to_string(path).map_err(|e| format!("读取数据文件失败: {}", e))?;
+        serde_json::from_str(&content).map_err(|e| format!("解析数据文件失败: {}", e))
+    }
+}
+
+/// 脚本中的一个事件：等待 delay_ms 毫秒后推送
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScriptedEvent {
+    #[serde(default)]
+    pub delay_ms: u64,
+    pub event: Value,
+}
+
+/// 从 JSON 文件加载事件脚本（ScriptedEvent 数组）
+pub fn load_script(path: &Path) -> Result<Vec<ScriptedEvent>, String> {
+    let content = std::fs::read_to_string(path).map_err(|e| format!("读取脚本文件失败: {}", e))?;
+    serde_json::from_str(&content).map_err(|e| format!("解析脚本文件失败: {}", e))
+}
+
+/// 模拟服务器配置
+#[derive(Debug, Clone, Default)]
+pub struct MockConfig {
+    pub fixtures: Fixtures,
+    /// 设置后要求客户端携带 access_token
+    pub access_token: Option<String>,
+    /// 设置后按该间隔推送心跳事件
+    pub heartbeat_interval: Option<Duration>,
+}
+
+#[derive(Debug)]
+struct MockState {
+    fixtures: Mutex<Fixtures>,
+    access_token: Option<String>,
+    heartbeat_interval: Option<Duration>,
+    events: broadcast::Sender<String>,
+    calls: Mutex<Vec<Value>>,
+    call_notify: Notify,
+    next_message_id: AtomicI64,
+    connections: watch::Sender<usize>,
+    /// 为 false 时拒绝新的连接（模拟 OneBot 实现下线）
+    accepting: AtomicBool,
+    /// 每次递增都会断开所有已建立的连接
+    kick: watch::Sender<u64>,
+    /// 收到这些 action 的调用时不回复（模拟等待响应超时）
+    silent_actions: Mutex<HashSet<String>>,
+}
+
+/// OneBot v11 模拟服务器
+///
+/// 接受正向 WebSocket 连接，按 `Fixtures` 回复 API 调用，并可以向所有已连接的客户端推送事件。
+/// 用于在没有 QQ 实现、没有网络的环境下开发和测试
+#[derive(Debug)]
+pub struct MockServer {
+    addr: SocketAddr,
+    state: Arc<MockState>,
+    task: JoinHandle<()>,
+}
+
+impl MockServer {
+    /// 在指定地址上监听；端口为 0 时由系统分配
+    pub async fn bind(host: &str, port: u16, config: MockConfig) -> Result<Self, String> {
+        let listener = TcpListener::bind((host, port))
+            .await
+            .map_err(|e| format!("监听 {}:{} 失败: {}", host, port, e))?;
+        let addr = listener
+            .local_addr()
+            .map_err(|e| format!("获取监听地址失败: {}", e))?;
+
+        let next_message_id = config
+            .fixtures
+            .messages
+            .iter()
+            .filter_map(|message| message.get("message_id").and_then(|v| v.as_i64()))
+            .max()
+            .unwrap_or(0)
+            + 1;
+        let state = Arc::new(MockState {
+            fixtures: Mutex::new(config.fixtures),
+            access_token: config.access_token,
+            heartbeat_interval: config.heartbeat_interval,
+            events: broadcast::channel(256).0,
+            calls: Mutex::new(Vec::new()),
+            call_notify: Notify::new(),
+            next_message_id: AtomicI64::new(next_message_id),
+            connections: watch::channel(0).0,
+            accepting: AtomicBool::new(true),
+            kick: watch::channel(0).0,
+            silent_actions: Mutex::new(HashSet::new()),
+        });
+
+        tracing::info!("[mock_onebot] 模拟服务器监听于 {}", addr);
+
+        let task_state = state.clone();
+        let task = tokio::spawn(async move {
+            loop {
+                let (stream, peer) = match listener.accept().await {
+                    Ok(accepted) => accepted,
+                    Err(e) => {
+                        tracing::warn!("[mock_onebot] 接受连接失败: {}", e);
+                        continue;
+                    }
+                };
+                tokio::spawn(handle_connection(task_state.clone(), stream, peer));
+            }
+        });
+
+        Ok(Self { addr, state, task })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// 正向 WebSocket 地址
+    pub fn url(&self) -> String {
+        format!("ws://{}/", self.addr)
+    }
+
+    /// 模拟账号
+    pub fn self_id(&self) -> i64 {
+        self.state.fixtures.lock().unwrap().login.user_id
+    }
+
+    /// 修改服务器的数据，之后的 API 调用按修改后的内容回复
+    pub fn update_fixtures(&self, update: impl FnOnce(&mut Fixtures)) {
+        update(&mut self.state.fixtures.lock().unwrap());
+    }
+
+    /// 等待至少有一个客户端连接，超时返回 false
+    pub async fn wait_connected(&self, timeout: Duration) -> bool {
+        let mut connections = self.state.connections.subscribe();
+        tokio::time::timeout(timeout, connections.wait_for(|count| *count > 0))
+            .await
+            .is_ok_and(|result| result.is_ok())
+    }
+
+    /// 向所有已连接的客户端推送事件；缺少 time、self_id 时自动补全
+    pub fn push_event(&self, mut event: Value) {
+        if event.get("time").is_none() {
+            event["time"] = json!(chrono::Utc::now().timestamp());
+        }
+        if event.get("self_id").is_none() {
+            event["self_id"] = json!(self.self_id());
+        }
+        let _ = self.state.events.send(event.to_string());
+    }
+
+    /// 按脚本依次推送事件
+    pub async fn run_script(&self, script: Vec<ScriptedEvent>) {
+        for scripted in script {
+            if scripted.delay_ms > 0 {
+                tokio::time::sleep(Duration::from_millis(scripted.delay_ms)).await;
+            }
+            self.push_event(scripted.event);
+        }
+    }
+
+    /// 推送一条私聊消息，返回 message_id（之后可以通过 get_msg 查到）
+    pub fn push_private_message(&self, user_id: i64, text: &str) -> i64 {
+        let nickname = self.state.fixtures.lock().unwrap().nickname_of(user_id);
+        let message = self.state.new_message(
+            json!({
+                "message_type": "private",
+                "sub_type": "friend",
+                "user_id": user_id,
+                "sender": { "user_id": user_id, "nickname": nickname },
+            }),
+            json!([{ "type": "text", "data": { "text": text } }]),
+        );
+        let message_id = message["message_id"].as_i64().unwrap_or_default();
+        self.push_event(message);
+        message_id
+    }
+
+    /// 推送一条群消息，返回 message_id
+    pub fn push_group_message(&self, group_id: i64, user_id: i64, text: &str) -> i64 {
+        let nickname = self.state.fixtures.lock().unwrap().nickname_of(user_id);
+        let message = self.state.new_message(
+            json!({
+                "message_type": "group",
+                "sub_type": "normal",
+                "group_id": group_id,
+                "user_id": user_id,
+                "sender": { "user_id": user_id, "nickname": nickname, "card": "", "role": "member" },
+            }),
+            json!([{ "type": "text", "data": { "text": text } }]),
+        );
+        let message_id = message["message_id"].as_i64().unwrap_or_default();
+        self.push_event(message);
+        message_id
+    }
+
+    /// 推送好友申请
+    pub fn push_friend_request(&self, user_id: i64, comment: &str, flag: &str) {
+        self.push_event(json!({
+            "post_type": "request",
+            "request_type": "friend",
+            "user_id": user_id,
+            "comment": comment,
+            "flag": flag,
+        }));
+    }
+
+    /// 推送加群申请（sub_type 为 add）或入群邀请（sub_type 为 invite）
+    pub fn push_group_request(&self, group_id: i64, user_id: i64, sub_type: &str, comment: &str, flag: &str) {
+        self.push_event(json!({
+            "post_type": "request",
+            "request_type": "group",
+            "sub_type": sub_type,
+            "group_id": group_id,
+            "user_id": user_id,
+            "comment": comment,
+            "flag": flag,
+        }));
+    }
+
+    /// 已收到的 API 调用（`{action, params}`，按收到的顺序）
+    pub fn calls(&self) -> Vec<Value> {
+        self.state.calls.lock().unwrap().clone()
+    }
+
+    /// 等待指定 action 的调用（包括已经收到的），返回其 params；超时返回 None
+    pub async fn wait_for_call(&self, action: &str, timeout: Duration) -> Option<Value> {
+        let find = || {
+            self.state
+                .calls
+                .lock()
+                .unwrap()
+                .iter()
+                .find(|call| call["action"] == action)
+                .map(|call| call["params"].clone())
+        };
+        let wait = async {
+            loop {
+                let notified = self.state.call_notify.notified();
+                tokio::pin!(notified);
+                notified.as_mut().enable();
+                if let Some(params) = find() {
+                    return params;
+                }
+                notified.await;
+            }
+        };
+        tokio::time::timeout(timeout, wait).await.ok()
+    }
+
+    /// 是否接受新的连接；设为 false 后客户端重连会被拒绝，直到重新设为 true
+    pub fn set_accepting(&self, accepting: bool) {
+        self.state.accepting.store(accepting, Ordering::SeqCst);
+    }
+
+    /// 记录但不回复该 action 的调用，调用方只能等到超时
+    pub fn set_silent(&self, action: &str) {
+        self.state.silent_actions.lock().unwrap().insert(action.to_string());
+    }
+
+    /// 断开所有已建立的连接（监听继续，客户端可以重新连接）
+    pub fn disconnect_all(&self) {
+        self.state.kick.send_modify(|generation| *generation += 1);
+    }
+
+    /// 停止监听；已建立的连接随之关闭
+    pub async fn stop(mut self) {
+        self.disconnect_all();
+        self.task.abort();
+        let _ = (&mut self.task).await;
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+impl Fixtures {
+    fn nickname_of(&self, user_id: i64) -> String {
+        self.friends
+            .iter()
+            .find(|friend| friend["user_id"] == user_id)
+            .and_then(|friend| friend["nickname"].as_str())
+            .unwrap_or_default()
+            .to_string()
+    }
+
+    fn find_group(&self, group_id: i64) -> Option<&Value> {
+        self.groups.iter().find(|group| group["group_id"] == group_id)
+    }
+
+    fn group_members(&self, group_id: i64) -> Vec<Value> {
+        self.members.get(&group_id.to_string()).cloned().unwrap_or_default()
+    }
+}
+
+impl MockState {
+    /// 生成一条完整的消息事件并保存，供 get_msg 查询
+    fn new_message(&self, mut message: Value, segments: Value) -> Value {
+        let message_id = self.next_message_id.fetch_add(1, Ordering::SeqCst);
+        let raw_message = segments
+            .as_array()
+            .map(|segments| {
+                segments
+                    .iter()
+                    .filter_map(|segment| segment["data"]["text"].as_str())
+                    .collect::<String>()
+            })
+            .unwrap_or_else(|| segments.as_str().unwrap_or_default().to_string());
+
+        let mut fixtures = self.fixtures.lock().unwrap();
+        message["post_type"] = json!("message");
+        message["self_id"] = json!(fixtures.login.user_id);
+        message["time"] = json!(chrono::Utc::now().timestamp());
+        message["message_id"] = json!(message_id);
+        message["real_id"] = json!(message_id);
+        message["message_seq"] = json!(message_id);
+        message["message"] = segments;
+        message["raw_message"] = json!(raw_message);
+        message["font"] = json!(14);
+        message["message_format"] = json!("array");
+        fixtures.messages.push(message.clone());
+        message
+    }
+
+    /// 处理一次 API 调用，返回响应帧
+    fn handle_call(&self, frame: &str) -> Option<String> {
+        let request: Value = serde_json::from_str(frame).ok()?;
+        let action = request["action"].as_str()?.to_string();
+        let params = match request.get("params") {
+            Some(Value::Null) | None => json!({}),
+            Some(params) => params.clone(),
+        };
+        let echo = request.get("echo").cloned().unwrap_or(Value::Null);
+
+        tracing::debug!("[mock_onebot] 收到 API 调用: {} {}", action, params);
+        if self.silent_actions.lock().unwrap().contains(&action) {
+            self.calls.lock().unwrap().push(json!({ "action": action, "params": params }));
+            self.call_notify.notify_waiters();
+            return None;
+        }
+        let result = self.respond(&action, &params);
+        self.calls.lock().unwrap().push(json!({ "action": action, "params": params }));
+        self.call_notify.notify_waiters();
+
+        let response = match result {
+            Ok(data) => json!({
+                "status": "ok",
+                "retcode": 0,
+                "data": data,
+                "message": "",
+                "wording": "",
+                "echo": echo,
+            }),
+            Err((retcode, message)) => json!({
+                "status": "failed",
+                "retcode": retcode,
+                "data": null,
+                "message": message,
+                "wording": message,
+                "echo": echo,
+            }),
+        };
+        Some(response.to_string())
+    }
+
+    fn respond(&self, action: &str, params: &Value) -> Result<Value, (i64, String)> {
+        let group_id = params["group_id"].as_i64().unwrap_or_default();
+        let user_id = params["user_id"].as_i64().unwrap_or_default();
+
+        if let Some(data) = self.fixtures.lock().unwrap().responses.get(action) {
+            return Ok(data.clone());
+        }
+
+        match action {
+            "send_private_msg" | "send_group_msg" | "send_msg" => {
+                let is_group = action == "send_group_msg"
+                    || (action == "send_msg"
+                        && (params["message_type"] == "group"
+                            || (params["message_type"].is_null() && !params["group_id"].is_null())));
+                let (self_id, nickname) = {
+                    let fixtures = self.fixtures.lock().unwrap();
+                    (fixtures.login.user_id, fixtures.login.nickname.clone())
+                };
+                let sender = json!({ "user_id": self_id, "nickname": nickname });
+                let header = if is_group {
+                    json!({
+                        "message_type": "group",
+                        "sub_type": "normal",
+                        "group_id": group_id,
+                        "user_id": self_id,
+                        "target_id": group_id,
+                        "sender": sender,
+                    })
+                } else {
+                    json!({
+                        "message_type": "private",
+                        "sub_type": "friend",
+                        "user_id": self_id,
+                        "target_id": user_id,
+                        "sender": sender,
+                    })
+                };
+                let message_id = self.new_message(header, params["message"].clone())["message_id"].clone();
+                Ok(json!({ "message_id": message_id }))
+            }
+            "get_msg" => {
+                let message_id = params["message_id"].as_i64().unwrap_or_default();
+                self.fixtures
+                    .lock()
+                    .unwrap()
+                    .messages
+                    .iter()
+                    .find(|message| message["message_id"] == message_id)
+                    .cloned()
+                    .ok_or_else(|| (100, format!("消息 {} 不存在", message_id)))
+            }
+            "get_forward_msg" => {
+                let id = params["id"]
+                    .as_str()
+                    .or_else(|| params["message_id"].as_str())
+                    .unwrap_or_default()
+                    .to_string();
+                self.fixtures
+                    .lock()
+                    .unwrap()
+                    .forwards
+                    .get(&id)
+                    .map(|messages| json!({ "messages": messages }))
+                    .ok_or_else(|| (100, format!("合并转发 {} 不存在", id)))
+            }
+            "get_login_info" => {
+                let login = self.fixtures.lock().unwrap().login.clone();
+                Ok(json!({ "user_id": login.user_id, "nickname": login.nickname }))
+            }
+            "get_friend_list" => Ok(json!(self.fixtures.lock().unwrap().friends)),
+            "get_group_list" => Ok(json!(self.fixtures.lock().unwrap().groups)),
+            "get_group_info" => self
+                .fixtures
+                .lock()
+                .unwrap()
+                .find_group(group_id)
+                .cloned()
+                .ok_or_else(|| (100, format!("群 {} 不存在", group_id))),
+            "get_group_member_list" => Ok(json!(self.fixtures.lock().unwrap().group_members(group_id))),
+            "get_group_member_info" => self
+                .fixtures
+                .lock()
+                .unwrap()
+                .group_members(group_id)
+                .into_iter()
+                .find(|member| member["user_id"] == user_id)
+                .ok_or_else(|| (100, format!("群 {} 中没有成员 {}", group_id, user_id))),
+            "get_stranger_info" => {
+                let fixtures = self.fixtures.lock().unwrap();
+                Ok(fixtures
+                    .friends
+                    .iter()
+                    .find(|friend| friend["user_id"] == user_id)
+                    .cloned()
+                    .unwrap_or_else(|| json!({ "user_id": user_id, "nickname": "", "sex": "unknown", "age": 0 })))
+            }
+            "get_status" => Ok(json!({ "online": true, "good": true })),
+            "get_version_info" => Ok(json!({
+                "app_name": "mock_onebot",
+                "app_version": env!("CARGO_PKG_VERSION"),
+                "protocol_version": "v11",
+            })),
+            // 没有返回数据的操作类 API 一律视为成功
+            _ if action.starts_with("set_") || action.starts_with("delete_") || action == "send_like" => {
+                Ok(Value::Null)
+            }
+            _ => Err((1404, format!("不支持的 API: {}", action))),
+        }
+    }
+
+    fn meta_event(&self, meta_event_type: &str, extra: Value) -> String {
+        let mut event = json!({
+            "post_type": "meta_event",
+            "meta_event_type": meta_event_type,
+            "time": chrono::Utc::now().timestamp(),
+            "self_id": self.fixtures.lock().unwrap().login.user_id,
+        });
+        if let (Some(event), Some(extra)) = (event.as_object_mut(), extra.as_object()) {
+            event.extend(extra.clone());
+        }
+        event.to_string()
+    }
+}
+
+fn reject(status: StatusCode, reason: &str) -> ErrorResponse {
+    let mut response = ErrorResponse::new(Some(reason.to_string()));
+    *response.status_mut() = status;
+    response
+}
+
+async fn handle_connection(state: Arc<MockState>, stream: TcpStream, peer: SocketAddr) {
+    // 回调签名由 tungstenite 规定
+    #[allow(clippy::result_large_err)]
+    let callback = |request: &Request, response: Response| -> Result<Response, ErrorResponse> {
+        if !state.accepting.load(Ordering::SeqCst) {
+            return Err(reject(StatusCode::SERVICE_UNAVAILABLE, "Service Unavailable"));
+        }
+        if let Some(access_token) = &state.access_token {
+            if !is_authorized(request, access_token) {
+                tracing::warn!("[mock_onebot] 拒绝连接 ({}): access_token 无效", peer);
+                return Err(reject(StatusCode::UNAUTHORIZED, "Unauthorized"));
+            }
+        }
+        Ok(response)
+    };
+
+    let ws_stream = match tokio_tungstenite::accept_hdr_async(stream, callback).await {
+        Ok(ws_stream) => ws_stream,
+        Err(e) => {
+            tracing::warn!("[mock_onebot] 握手失败 ({}): {}", peer, e);
+            return;
+        }
+    };
+    tracing::info!("[mock_onebot] 客户端已连接: {}", peer);
+
+    let (mut sink, mut stream) = ws_stream.split();
+    let mut events = state.events.subscribe();
+    let mut kick = state.kick.subscribe();
+    state.connections.send_modify(|count| *count += 1);
+
+    let lifecycle = state.meta_event("lifecycle", json!({ "sub_type": "connect" }));
+    let mut alive = sink.send(WsMessage::Text(lifecycle.into())).await.is_ok();
+
+    let interval = state.heartbeat_interval.unwrap_or(Duration::from_secs(3600));
+    let mut heartbeat = tokio::time::interval_at(tokio::time::Instant::now() + interval, interval);
+
+    while alive {
+        let outgoing = tokio::select! {
+            msg = stream.next() => match msg {
+                Some(Ok(WsMessage::Text(text))) => state.handle_call(&text),
+                Some(Ok(WsMessage::Close(_))) | None | Some(Err(_)) => break,
+                Some(Ok(_)) => None,
+            },
+            event = events.recv() => match event {
+                Ok(event) => Some(event),
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    tracing::warn!("[mock_onebot] 客户端 {} 跳过了 {} 个事件", peer, skipped);
+                    None
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            },
+            _ = kick.changed() => {
+                let _ = sink.send(WsMessage::Close(None)).await;
+                break;
+            }
+            _ = heartbeat.tick(), if state.heartbeat_interval.is_some() => Some(state.meta_event(
+                "heartbeat",
+                json!({
+                    "status": { "online": true, "good": true },
+                    "interval": interval.as_millis() as i64,
+                }),
+            )),
+        };
+        if let Some(frame) = outgoing {
+            alive = sink.send(WsMessage::Text(frame.into())).await.is_ok();
+        }
+    }
+
+    state.connections.send_modify(|count| *count -= 1);
+    tracing::info!("[mock_onebot] 客户端已断开: {}", peer);
+}
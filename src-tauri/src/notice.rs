@@ -0,0 +1,503 @@
+use rusqlite::{params, Row};
+use serde::{Deserialize, Serialize};
+use tauri::Emitter;
+use crate::AppHandle;
+use crate::storage;
+
+/// 通知事件（OneBot v11），保留事件自带的时间和全部字段
+///
+/// runbot 解析出的通知会丢弃部分字段（例如禁言时长），所以这里直接从上游原始帧解析
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "notice_type", rename_all = "snake_case")]
+pub enum Notice {
+    /// 群文件上传
+    GroupUpload {
+        time: i64,
+        self_id: i64,
+        group_id: i64,
+        user_id: i64,
+        file: UploadedFile,
+    },
+    /// 群管理员变动
+    GroupAdmin {
+        time: i64,
+        self_id: i64,
+        sub_type: AdminChange,
+        group_id: i64,
+        user_id: i64,
+    },
+    /// 群成员减少
+    GroupDecrease {
+        time: i64,
+        self_id: i64,
+        sub_type: DecreaseKind,
+        group_id: i64,
+        operator_id: i64,
+        user_id: i64,
+    },
+    /// 群成员增加
+    GroupIncrease {
+        time: i64,
+        self_id: i64,
+        sub_type: IncreaseKind,
+        group_id: i64,
+        operator_id: i64,
+        user_id: i64,
+    },
+    /// 群禁言（duration 为禁言秒数，解除禁言时为 0）
+    GroupBan {
+        time: i64,
+        self_id: i64,
+        sub_type: BanKind,
+        group_id: i64,
+        operator_id: i64,
+        user_id: i64,
+        #[serde(default)]
+        duration: i64,
+    },
+    /// 好友添加
+    FriendAdd {
+        time: i64,
+        self_id: i64,
+        user_id: i64,
+    },
+    /// 群消息撤回
+    GroupRecall {
+        time: i64,
+        self_id: i64,
+        group_id: i64,
+        user_id: i64,
+        operator_id: i64,
+        message_id: i64,
+    },
+    /// 好友消息撤回
+    FriendRecall {
+        time: i64,
+        self_id: i64,
+        user_id: i64,
+        message_id: i64,
+    },
+    /// 群内提示（戳一戳、运气王、荣誉变更）
+    Notify(Notify),
+    /// 无法识别的通知，保留原始数据
+    Unknown {
+        time: i64,
+        self_id: i64,
+        data: serde_json::Value,
+    },
+}
+
+/// 群内提示事件
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "sub_type", rename_all = "snake_case")]
+pub enum Notify {
+    /// 戳一戳（私聊戳一戳时没有 group_id）
+    Poke {
+        time: i64,
+        self_id: i64,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        group_id: Option<i64>,
+        user_id: i64,
+        target_id: i64,
+    },
+    /// 群红包运气王
+    LuckyKing {
+        time: i64,
+        self_id: i64,
+        group_id: i64,
+        user_id: i64,
+        target_id: i64,
+    },
+    /// 群成员荣誉变更
+    Honor {
+        time: i64,
+        self_id: i64,
+        group_id: i64,
+        honor_type: HonorType,
+        user_id: i64,
+    },
+}
+
+/// 上传的群文件
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UploadedFile {
+    pub id: String,
+    pub name: String,
+    pub size: i64,
+    pub busid: i64,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AdminChange {
+    Set,
+    Unset,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DecreaseKind {
+    Leave,
+    Kick,
+    KickMe,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IncreaseKind {
+    Approve,
+    Invite,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BanKind {
+    Ban,
+    LiftBan,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HonorType {
+    Talkative,
+    Performer,
+    Emotion,
+}
+
+impl Notice {
+    /// 从原始事件解析通知，字段不符合标准时作为 Unknown 保留原始数据
+    pub fn parse(value: &serde_json::Value) -> Self {
+        match serde_json::from_value(value.clone()) {
+            Ok(notice) => notice,
+            Err(e) => {
+                tracing::debug!("[Notice::parse] 无法识别的通知 ({}): {}", e, value);
+                Notice::Unknown {
+                    time: value["time"].as_i64().unwrap_or_default(),
+                    self_id: value["self_id"].as_i64().unwrap_or_default(),
+                    data: value.clone(),
+                }
+            }
+        }
+    }
+
+    pub fn time(&self) -> i64 {
+        match self {
+            Notice::GroupUpload { time, .. }
+            | Notice::GroupAdmin { time, .. }
+            | Notice::GroupDecrease { time, .. }
+            | Notice::GroupIncrease { time, .. }
+            | Notice::GroupBan { time, .. }
+            | Notice::FriendAdd { time, .. }
+            | Notice::GroupRecall { time, .. }
+            | Notice::FriendRecall { time, .. }
+            | Notice::Unknown { time, .. } => *time,
+            Notice::Notify(notify) => match notify {
+                Notify::Poke { time, .. } | Notify::LuckyKing { time, .. } | Notify::Honor { time, .. } => *time,
+            },
+        }
+    }
+
+    pub fn self_id(&self) -> i64 {
+        match self {
+            Notice::GroupUpload { self_id, .. }
+            | Notice::GroupAdmin { self_id, .. }
+            | Notice::GroupDecrease { self_id, .. }
+            | Notice::GroupIncrease { self_id, .. }
+            | Notice::GroupBan { self_id, .. }
+            | Notice::FriendAdd { self_id, .. }
+            | Notice::GroupRecall { self_id, .. }
+            | Notice::FriendRecall { self_id, .. }
+            | Notice::Unknown { self_id, .. } => *self_id,
+            Notice::Notify(notify) => match notify {
+                Notify::Poke { self_id, .. }
+                | Notify::LuckyKing { self_id, .. }
+                | Notify::Honor { self_id, .. } => *self_id,
+            },
+        }
+    }
+
+    pub fn notice_type(&self) -> &str {
+        match self {
+            Notice::GroupUpload { .. } => "group_upload",
+            Notice::GroupAdmin { .. } => "group_admin",
+            Notice::GroupDecrease { .. } => "group_decrease",
+            Notice::GroupIncrease { .. } => "group_increase",
+            Notice::GroupBan { .. } => "group_ban",
+            Notice::FriendAdd { .. } => "friend_add",
+            Notice::GroupRecall { .. } => "group_recall",
+            Notice::FriendRecall { .. } => "friend_recall",
+            Notice::Notify(_) => "notify",
+            Notice::Unknown { data, .. } => data["notice_type"].as_str().unwrap_or("unknown"),
+        }
+    }
+
+    pub fn sub_type(&self) -> Option<String> {
+        let value = match self {
+            Notice::GroupAdmin { sub_type, .. } => serde_json::to_value(sub_type).ok()?,
+            Notice::GroupDecrease { sub_type, .. } => serde_json::to_value(sub_type).ok()?,
+            Notice::GroupIncrease { sub_type, .. } => serde_json::to_value(sub_type).ok()?,
+            Notice::GroupBan { sub_type, .. } => serde_json::to_value(sub_type).ok()?,
+            Notice::Notify(notify) => {
+                return Some(
+                    match notify {
+                        Notify::Poke { .. } => "poke",
+                        Notify::LuckyKing { .. } => "lucky_king",
+                        Notify::Honor { .. } => "honor",
+                    }
+                    .to_string(),
+                )
+            }
+            Notice::Unknown { data, .. } => data["sub_type"].clone(),
+            _ => return None,
+        };
+        value.as_str().map(|s| s.to_string())
+    }
+
+    pub fn group_id(&self) -> Option<i64> {
+        match self {
+            Notice::GroupUpload { group_id, .. }
+            | Notice::GroupAdmin { group_id, .. }
+            | Notice::GroupDecrease { group_id, .. }
+            | Notice::GroupIncrease { group_id, .. }
+            | Notice::GroupBan { group_id, .. }
+            | Notice::GroupRecall { group_id, .. } => Some(*group_id),
+            Notice::Notify(Notify::Poke { group_id, .. }) => *group_id,
+            Notice::Notify(Notify::LuckyKing { group_id, .. })
+            | Notice::Notify(Notify::Honor { group_id, .. }) => Some(*group_id),
+            Notice::Unknown { data, .. } => data["group_id"].as_i64(),
+            Notice::FriendAdd { .. } | Notice::FriendRecall { .. } => None,
+        }
+    }
+
+    pub fn user_id(&self) -> Option<i64> {
+        match self {
+            Notice::GroupUpload { user_id, .. }
+            | Notice::GroupAdmin { user_id, .. }
+            | Notice::GroupDecrease { user_id, .. }
+            | Notice::GroupIncrease { user_id, .. }
+            | Notice::GroupBan { user_id, .. }
+            | Notice::FriendAdd { user_id, .. }
+            | Notice::GroupRecall { user_id, .. }
+            | Notice::FriendRecall { user_id, .. } => Some(*user_id),
+            Notice::Notify(notify) => match notify {
+                Notify::Poke { user_id, .. }
+                | Notify::LuckyKing { user_id, .. }
+                | Notify::Honor { user_id, .. } => Some(*user_id),
+            },
+            Notice::Unknown { data, .. } => data["user_id"].as_i64(),
+        }
+    }
+
+    pub fn operator_id(&self) -> Option<i64> {
+        match self {
+            Notice::GroupDecrease { operator_id, .. }
+            | Notice::GroupIncrease { operator_id, .. }
+            | Notice::GroupBan { operator_id, .. }
+            | Notice::GroupRecall { operator_id, .. } => Some(*operator_id),
+            Notice::Unknown { data, .. } => data["operator_id"].as_i64(),
+            _ => None,
+        }
+    }
+
+}
+
+/// 已保存的通知（同时作为 runbot-notice 事件的内容）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NoticeRecord {
+    pub id: i64,
+    pub account_id: i64,
+    pub notice: Notice,
+}
+
+impl NoticeRecord {
+    fn from_row(account_id: i64, row: &Row) -> rusqlite::Result<Self> {
+        let data: String = row.get("data")?;
+        let notice = match serde_json::from_str::<serde_json::Value>(&data) {
+            Ok(value) => Notice::parse(&value),
+            Err(_) => Notice::Unknown {
+                time: row.get("time")?,
+                self_id: account_id,
+                data: serde_json::Value::String(data),
+            },
+        };
+        Ok(Self {
+            id: row.get("id")?,
+            account_id,
+            notice,
+        })
+    }
+}
+
+/// 处理上游的原始帧：通知事件写入 notices 表并发送 runbot-notice 事件
+///
+/// 其它帧直接忽略，由 runbot 继续处理
+pub fn on_raw_event(app: &AppHandle, frame: &str) {
+    let Ok(value) = serde_json::from_str::<serde_json::Value>(frame) else {
+        return;
+    };
+    if value["post_type"].as_str() != Some("notice") {
+        return;
+    }
+    let notice = Notice::parse(&value);
+    let account_id = notice.self_id();
+    if account_id <= 0 {
+        tracing::warn!("[on_raw_event] 通知缺少 self_id，跳过保存: {}", frame);
+        return;
+    }
+    match save(app, account_id, &notice) {
+        Ok(id) => {
+            let record = NoticeRecord { id, account_id, notice };
+            app.emit("runbot-notice", record).unwrap_or_default();
+        }
+        Err(e) => tracing::warn!("[on_raw_event] 保存通知失败: {}", e),
+    }
+}
+
+/// 保存通知，返回记录 ID
+fn save(app: &AppHandle, account_id: i64, notice: &Notice) -> Result<i64, String> {
+    let conn = storage::get_connection(app, Some(account_id))?;
+    let data = serde_json::to_string(notice).map_err(|e| format!("序列化通知失败: {}", e))?;
+    conn.execute(
+        "INSERT INTO notices (time, notice_type, sub_type, group_id, user_id, operator_id, data)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        params![
+            notice.time(),
+            notice.notice_type(),
+            notice.sub_type(),
+            notice.group_id(),
+            notice.user_id(),
+            notice.operator_id(),
+            data
+        ],
+    )
+    .map_err(|e| format!("插入通知失败: {}", e))?;
+    Ok(conn.last_insert_rowid())
+}
+
+/// 获取通知（按事件时间倒序，可按类型、群、用户筛选）
+#[tauri::command]
+pub async fn get_notices(
+    account_id: i64,
+    notice_type: Option<String>,
+    group_id: Option<i64>,
+    user_id: Option<i64>,
+    limit: Option<u32>,
+    offset: Option<u32>,
+    app: AppHandle,
+) -> Result<Vec<NoticeRecord>, String> {
+    let conn = storage::get_connection(&app, Some(account_id))?;
+
+    let mut query = "SELECT id, time, data FROM notices WHERE 1=1".to_string();
+    let mut params: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
+    if let Some(notice_type) = notice_type {
+        query.push_str(" AND notice_type = ?");
+        params.push(Box::new(notice_type));
+    }
+    if let Some(group_id) = group_id {
+        query.push_str(" AND group_id = ?");
+        params.push(Box::new(group_id));
+    }
+    if let Some(user_id) = user_id {
+        query.push_str(" AND (user_id = ? OR operator_id = ?)");
+        params.push(Box::new(user_id));
+        params.push(Box::new(user_id));
+    }
+    query.push_str(" ORDER BY time DESC, id DESC LIMIT ? OFFSET ?");
+    params.push(Box::new(limit.unwrap_or(100)));
+    params.push(Box::new(offset.unwrap_or(0)));
+
+    let mut stmt = conn
+        .prepare(&query)
+        .map_err(|e| format!("准备查询失败: {}", e))?;
+    let records = stmt
+        .query_map(
+            rusqlite::params_from_iter(params.iter().map(|p| p.as_ref())),
+            |row| NoticeRecord::from_row(account_id, row),
+        )
+        .map_err(|e| format!("查询通知失败: {}", e))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("读取通知失败: {}", e))?;
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use super::*;
+
+    fn notice(notice_type: &str, fields: serde_json::Value) -> Notice {
+        let mut value = json!({ "time": 1700000000, "self_id": 10001, "post_type": "notice", "notice_type": notice_type });
+        value.as_object_mut().unwrap().extend(fields.as_object().unwrap().clone());
+        Notice::parse(&value)
+    }
+
+    #[test]
+    fn keeps_ban_duration() {
+        let ban = notice("group_ban", json!({ "sub_type": "ban", "group_id": 20002, "operator_id": 30001, "user_id": 30003, "duration": 600 }));
+        assert!(matches!(ban, Notice::GroupBan { sub_type: BanKind::Ban, duration: 600, .. }));
+        assert_eq!((ban.group_id(), ban.user_id(), ban.operator_id()), (Some(20002), Some(30003), Some(30001)));
+
+        // 解除禁言时可能不带 duration
+        let lift = notice("group_ban", json!({ "sub_type": "lift_ban", "group_id": 20002, "operator_id": 30001, "user_id": 30003 }));
+        assert!(matches!(lift, Notice::GroupBan { sub_type: BanKind::LiftBan, duration: 0, .. }));
+        assert_eq!(lift.sub_type().as_deref(), Some("lift_ban"));
+    }
+
+    #[test]
+    fn parses_admin_changes() {
+        let set = notice("group_admin", json!({ "sub_type": "set", "group_id": 20002, "user_id": 30003 }));
+        assert!(matches!(set, Notice::GroupAdmin { sub_type: AdminChange::Set, group_id: 20002, user_id: 30003, .. }));
+        let unset = notice("group_admin", json!({ "sub_type": "unset", "group_id": 20002, "user_id": 30003 }));
+        assert!(matches!(unset, Notice::GroupAdmin { sub_type: AdminChange::Unset, .. }));
+        assert_eq!((unset.notice_type(), unset.sub_type().as_deref()), ("group_admin", Some("unset")));
+    }
+
+    #[test]
+    fn keeps_recalled_message_id() {
+        let group = notice("group_recall", json!({ "group_id": 20002, "user_id": 30003, "operator_id": 30001, "message_id": 123 }));
+        assert!(matches!(group, Notice::GroupRecall { message_id: 123, operator_id: 30001, .. }));
+        let friend = notice("friend_recall", json!({ "user_id": 30003, "message_id": 456 }));
+        assert!(matches!(friend, Notice::FriendRecall { message_id: 456, user_id: 30003, .. }));
+        assert_eq!(friend.group_id(), None);
+    }
+
+    #[test]
+    fn parses_group_notify() {
+        let poke = notice("notify", json!({ "sub_type": "poke", "group_id": 20002, "user_id": 30003, "target_id": 10001 }));
+        assert!(matches!(poke, Notice::Notify(Notify::Poke { group_id: Some(20002), target_id: 10001, .. })));
+        assert_eq!((poke.notice_type(), poke.sub_type().as_deref()), ("notify", Some("poke")));
+
+        let lucky_king = notice("notify", json!({ "sub_type": "lucky_king", "group_id": 20002, "user_id": 30001, "target_id": 30003 }));
+        assert!(matches!(lucky_king, Notice::Notify(Notify::LuckyKing { user_id: 30001, target_id: 30003, .. })));
+
+        let honor = notice("notify", json!({ "sub_type": "honor", "group_id": 20002, "honor_type": "talkative", "user_id": 30003 }));
+        assert!(matches!(honor, Notice::Notify(Notify::Honor { honor_type: HonorType::Talkative, .. })));
+        assert_eq!(honor.group_id(), Some(20002));
+    }
+
+    #[test]
+    fn parses_private_poke_without_group() {
+        let poke = notice("notify", json!({ "sub_type": "poke", "user_id": 30003, "target_id": 10001 }));
+        assert!(matches!(poke, Notice::Notify(Notify::Poke { group_id: None, user_id: 30003, .. })));
+        assert_eq!((poke.group_id(), poke.time(), poke.self_id()), (None, 1700000000, 10001));
+    }
+
+    #[test]
+    fn falls_back_to_unknown() {
+        // 未知的通知类型、未知的子类型和缺少字段都保留原始数据
+        for (notice_type, fields) in [
+            ("group_card", json!({ "group_id": 20002, "user_id": 30003, "card_new": "新名片" })),
+            ("notify", json!({ "sub_type": "title", "group_id": 20002, "user_id": 30003 })),
+            ("group_ban", json!({ "sub_type": "ban", "group_id": 20002 })),
+        ] {
+            let parsed = notice(notice_type, fields.clone());
+            let Notice::Unknown { time, self_id, data } = &parsed else {
+                panic!("{} 应当无法识别: {:?}", notice_type, parsed);
+            };
+            assert_eq!((*time, *self_id), (1700000000, 10001));
+            assert_eq!(data["group_id"], fields["group_id"]);
+            assert_eq!(parsed.notice_type(), notice_type);
+            assert_eq!(parsed.group_id(), Some(20002));
+        }
+    }
+}
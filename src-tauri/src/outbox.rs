@@ -0,0 +1,550 @@
+use std::sync::{Arc, Mutex};
+use rusqlite::{params, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+use tauri::{Emitter, State};
+use crate::AppHandle;
+use crate::actions::{self, ActionError, SendMsgResult};
+use crate::runbot::{self, RunbotState};
+use crate::storage;
+
+/// 单条消息最多尝试发送的次数
+const MAX_ATTEMPTS: i64 = 5;
+
+/// 发送队列中消息的状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OutboxState {
+    Pending,
+    Sending,
+    Sent,
+    Failed,
+}
+
+impl OutboxState {
+    fn as_str(&self) -> &'static str {
+        match self {
+            OutboxState::Pending => "pending",
+            OutboxState::Sending => "sending",
+            OutboxState::Sent => "sent",
+            OutboxState::Failed => "failed",
+        }
+    }
+
+    fn parse(value: &str) -> Self {
+        match value {
+            "sending" => OutboxState::Sending,
+            "sent" => OutboxState::Sent,
+            "failed" => OutboxState::Failed,
+            _ => OutboxState::Pending,
+        }
+    }
+}
+
+/// 发送队列中的一条消息（同时作为 outbox-state 事件的内容）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OutboxEntry {
+    pub account_id: i64,
+    pub local_message_id: String,
+    pub action: String,
+    pub params: serde_json::Value,
+    pub state: OutboxState,
+    pub attempts: i64,
+    pub last_error: Option<String>,
+    pub message_id: Option<i64>,
+    pub created_at: i64,
+}
+
+impl OutboxEntry {
+    fn from_row(account_id: i64, row: &Row) -> rusqlite::Result<Self> {
+        let params: String = row.get("params")?;
+        let state: String = row.get("state")?;
+        Ok(Self {
+            account_id,
+            local_message_id: row.get("local_message_id")?,
+            action: row.get("action")?,
+            params: serde_json::from_str(&params).unwrap_or(serde_json::Value::Null),
+            state: OutboxState::parse(&state),
+            attempts: row.get("attempts")?,
+            last_error: row.get("last_error")?,
+            message_id: row.get("message_id")?,
+            created_at: row.get("created_at")?,
+        })
+    }
+}
+
+/// 把消息加入发送队列（同一 local_message_id 重复加入时重新排队）
+fn enqueue(
+    app: &AppHandle,
+    account_id: i64,
+    local_message_id: &str,
+    action: &str,
+    params: &serde_json::Value,
+) -> Result<OutboxEntry, String> {
+    let conn = storage::get_connection(app, Some(account_id))?;
+    conn.execute(
+        "INSERT INTO outbox (local_message_id, action, params, state, attempts)
+         VALUES (?1, ?2, ?3, 'pending', 0)
+         ON CONFLICT(local_message_id) DO UPDATE SET
+            action = excluded.action,
+            params = excluded.params,
+            state = 'pending',
+            attempts = 0,
+            last_error = NULL,
+            updated_at = strftime('%s', 'now')",
+        params![local_message_id, action, params.to_string()],
+    )
+    .map_err(|e| format!("加入发送队列失败: {}", e))?;
+
+    conn.query_row(
+        "SELECT * FROM outbox WHERE local_message_id = ?1",
+        params![local_message_id],
+        |row| OutboxEntry::from_row(account_id, row),
+    )
+    .map_err(|e| format!("读取发送队列失败: {}", e))
+}
+
+/// 取出队首待发送的消息（上次发送中途退出的消息同样重新发送）
+fn next_entry(app: &AppHandle, account_id: i64) -> Result<Option<OutboxEntry>, String> {
+    let conn = storage::get_connection(app, Some(account_id))?;
+    conn.query_row(
+        "SELECT * FROM outbox WHERE state IN ('pending', 'sending') ORDER BY id LIMIT 1",
+        [],
+        |row| OutboxEntry::from_row(account_id, row),
+    )
+    .optional()
+    .map_err(|e| format!("读取发送队列失败: {}", e))
+}
+
+/// 保存消息状态并通知前端
+fn save_entry(app: &AppHandle, entry: &OutboxEntry) {
+    let result = storage::get_connection(app, Some(entry.account_id)).and_then(|conn| {
+        conn.execute(
+            "UPDATE outbox SET state = ?1, attempts = ?2, last_error = ?3, message_id = ?4,
+                updated_at = strftime('%s', 'now')
+             WHERE local_message_id = ?5",
+            params![
+                entry.state.as_str(),
+                entry.attempts,
+                entry.last_error,
+                entry.message_id,
+                entry.local_message_id,
+            ],
+        )
+        .map_err(|e| format!("更新发送队列失败: {}", e))
+    });
+    if let Err(e) = result {
+        tracing::warn!("[outbox] {}", e);
+    }
+    let _ = app.emit("outbox-state", entry);
+}
+
+/// 是否通过发送队列发送：只有带 local_message_id 的私聊／群消息需要保证送达
+pub fn should_queue(action: &str, params: &serde_json::Value) -> Option<String> {
+    if action != "send_private_msg" && action != "send_group_msg" {
+        return None;
+    }
+    params
+        .get("local_message_id")
+        .and_then(|v| v.as_str())
+        .map(|s| s.to_string())
+}
+
+/// 把消息加入发送队列，连接可用时立即开始发送
+pub fn submit(
+    app: &AppHandle,
+    registry: &Arc<Mutex<RunbotState>>,
+    account_id: i64,
+    local_message_id: &str,
+    action: &str,
+    params: &serde_json::Value,
+) -> Result<(), String> {
+    let entry = enqueue(app, account_id, local_message_id, action, params)?;
+    tracing::info!("[outbox] 消息已加入发送队列: {} ({})", local_message_id, action);
+    let _ = app.emit("outbox-state", &entry);
+    request_flush(app.clone(), registry.clone(), account_id);
+    Ok(())
+}
+
+/// 请求发送队列中的消息；同一账号同时只有一个发送任务，正在发送时只做标记
+pub fn request_flush(app: AppHandle, registry: Arc<Mutex<RunbotState>>, account_id: i64) {
+    let Some(session) = registry
+        .lock()
+        .ok()
+        .and_then(|state_guard| state_guard.sessions.get(account_id))
+    else {
+        return;
+    };
+
+    {
+        let Ok(mut session_guard) = session.lock() else {
+            return;
+        };
+        if let Some(rerun) = session_guard.outbox_flush.as_mut() {
+            *rerun = true;
+            return;
+        }
+        session_guard.outbox_flush = Some(false);
+    }
+
+    tokio::spawn(async move {
+        loop {
+            flush(&app, &registry, account_id).await;
+
+            let Ok(mut session_guard) = session.lock() else {
+                return;
+            };
+            if session_guard.outbox_flush == Some(true) {
+                session_guard.outbox_flush = Some(false);
+                continue;
+            }
+            session_guard.outbox_flush = None;
+            return;
+        }
+    });
+}
+
+/// 按顺序发送队列中的消息，直到队列为空或连接不可用
+async fn flush(app: &AppHandle, registry: &Mutex<RunbotState>, account_id: i64) {
+    loop {
+        let entry = match next_entry(app, account_id) {
+            Ok(Some(entry)) => entry,
+            Ok(None) => return,
+            Err(e) => {
+                tracing::warn!("[outbox] {}", e);
+                return;
+            }
+        };
+        if !send_entry(app, registry, entry).await {
+            return;
+        }
+    }
+}
+
+/// 发送单条消息（暂时失败时退避重试）；返回 false 表示连接不可用，停止发送
+async fn send_entry(app: &AppHandle, registry: &Mutex<RunbotState>, mut entry: OutboxEntry) -> bool {
+    // local_message_id 等字段只在本地使用，不发给 OneBot 实现
+    let mut params = entry.params.clone();
+    if let Some(object) = params.as_object_mut() {
+        object.remove("local_message_id");
+        object.remove("need_reload");
+    }
+
+    loop {
+        entry.attempts += 1;
+        entry.state = OutboxState::Sending;
+        save_entry(app, &entry);
+
+        let result = actions::call::<_, SendMsgResult>(registry, entry.account_id, &entry.action, &params).await;
+        match result {
+            Ok(result) => {
+                entry.state = OutboxState::Sent;
+                entry.message_id = Some(result.message_id);
+                entry.last_error = None;
+                save_entry(app, &entry);
+                tracing::info!("[outbox] 消息发送成功: {} -> {}", entry.local_message_id, result.message_id);
+                on_sent(app, registry, &entry, result.message_id).await;
+                return true;
+            }
+            // 连接断开：保持排队，等待重连后再发送
+            Err(ActionError::NotReady { message }) => {
+                entry.attempts -= 1;
+                entry.state = OutboxState::Pending;
+                entry.last_error = Some(message);
+                save_entry(app, &entry);
+                return false;
+            }
+            // 等待响应超时或响应无法解析：消息可能已经送达，重发可能导致重复，交给用户决定
+            Err(e @ (ActionError::Request { .. } | ActionError::InvalidResponse { .. })) => {
+                tracing::warn!("[outbox] 消息发送结果未知: {}: {}", entry.local_message_id, e);
+                entry.state = OutboxState::Failed;
+                entry.last_error = Some(format!("发送结果未知，消息可能已送达: {}", e));
+                save_entry(app, &entry);
+                return true;
+            }
+            Err(e) => {
+                tracing::warn!("[outbox] 消息发送失败 ({}/{}): {}: {}", entry.attempts, MAX_ATTEMPTS, entry.local_message_id, e);
+                entry.last_error = Some(e.to_string());
+                if !is_transient(&e) || entry.attempts >= MAX_ATTEMPTS {
+                    entry.state = OutboxState::Failed;
+                    save_entry(app, &entry);
+                    return true;
+                }
+                entry.state = OutboxState::Pending;
+                save_entry(app, &entry);
+
+                let delay = 2u64.saturating_pow(entry.attempts as u32).min(30);
+                tokio::time::sleep(tokio::time::Duration::from_secs(delay)).await;
+            }
+        }
+    }
+}
+
+/// 失败是否是暂时的（重试可能成功）
+///
+/// OneBot v11 没有定义表示暂时失败的 retcode，参数错误、权限不足、不支持的 API 等重试也不会成功；
+/// 这里只认 OneBot 12 的网络错误（33xxx）和请求过于频繁（36xxx）
+fn is_transient(e: &ActionError) -> bool {
+    match e {
+        ActionError::Failed { retcode, .. } => matches!(retcode / 1000, 33 | 36),
+        _ => false,
+    }
+}
+
+/// 发送成功后：更新本地消息的 message_id，需要时重新获取完整消息内容
+async fn on_sent(app: &AppHandle, registry: &Mutex<RunbotState>, entry: &OutboxEntry, message_id: i64) {
+    let account_id = entry.account_id;
+    let local_message_id = entry.local_message_id.clone();
+
+    if let Err(e) = storage::update_message_id(local_message_id.clone(), message_id, Some(account_id), app.clone()).await {
+        tracing::warn!("更新消息 message_id 失败: {}", e);
+        return;
+    }
+
+    // 如果需要重新加载（包含图片），获取完整消息内容
+    let need_reload = entry
+        .params
+        .get("need_reload")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false);
+    if need_reload {
+        let full_message = match runbot::ready_bot_ctx(registry, account_id) {
+            Ok(bot_ctx) => bot_ctx.get_msg(message_id).await.ok(),
+            Err(_) => None,
+        };
+        if let Some(full_message) = full_message {
+            // 将 Message 转换为 CQ 码格式的字符串
+            let message_str = full_message.raw_message.clone();
+
+            // 更新数据库中的消息内容
+            if let Err(e) = storage::update_message_content(
+                local_message_id.clone(),
+                message_str.clone(),
+                message_str.clone(),
+                Some(account_id),
+                app.clone(),
+            ).await {
+                tracing::warn!("更新消息内容失败: {}", e);
+            }
+
+            // 发送事件通知前端更新消息内容
+            #[derive(serde::Serialize, Clone)]
+            struct MessageUpdatedEvent {
+                account_id: i64,
+                local_message_id: String,
+                message_id: i64,
+                message: String,
+                raw_message: String,
+            }
+            let _ = app.emit("message-updated", MessageUpdatedEvent {
+                account_id,
+                local_message_id: local_message_id.clone(),
+                message_id,
+                message: message_str.clone(),
+                raw_message: message_str,
+            });
+        } else {
+            tracing::warn!("获取完整消息内容失败: message_id={}", message_id);
+        }
+    }
+
+    // 发送事件通知前端消息已发送成功
+    #[derive(serde::Serialize, Clone)]
+    struct MessageSentEvent {
+        account_id: i64,
+        local_message_id: String,
+        message_id: i64,
+    }
+    let _ = app.emit("message-sent", MessageSentEvent {
+        account_id,
+        local_message_id,
+        message_id,
+    });
+}
+
+/// 获取发送队列（默认只返回未发送成功的消息）
+#[tauri::command]
+pub async fn get_outbox(
+    account_id: i64,
+    include_sent: Option<bool>,
+    app: AppHandle,
+) -> Result<Vec<OutboxEntry>, String> {
+    let conn = storage::get_connection(&app, Some(account_id))?;
+    let sql = if include_sent.unwrap_or(false) {
+        "SELECT * FROM outbox ORDER BY id"
+    } else {
+        "SELECT * FROM outbox WHERE state != 'sent' ORDER BY id"
+    };
+    let mut stmt = conn
+        .prepare(sql)
+        .map_err(|e| format!("准备查询失败: {}", e))?;
+    let entries = stmt
+        .query_map([], |row| OutboxEntry::from_row(account_id, row))
+        .map_err(|e| format!("查询发送队列失败: {}", e))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("读取发送队列失败: {}", e))?;
+    Ok(entries)
+}
+
+/// 重新发送失败的消息
+#[tauri::command]
+pub async fn retry_outbox_message(
+    account_id: i64,
+    local_message_id: String,
+    app: AppHandle,
+    state: State<'_, Arc<Mutex<RunbotState>>>,
+) -> Result<(), String> {
+    let conn = storage::get_connection(&app, Some(account_id))?;
+    let updated = conn
+        .execute(
+            "UPDATE outbox SET state = 'pending', attempts = 0, last_error = NULL,
+                updated_at = strftime('%s', 'now')
+             WHERE local_message_id = ?1 AND state = 'failed'",
+            params![local_message_id],
+        )
+        .map_err(|e| format!("更新发送队列失败: {}", e))?;
+    if updated == 0 {
+        return Err(format!("消息不在失败状态: {}", local_message_id));
+    }
+
+    tracing::info!("[retry_outbox_message] 重新发送: {}", local_message_id);
+    request_flush(app, state.inner().clone(), account_id);
+    Ok(())
+}
+
+/// 从发送队列中移除消息（不再发送）
+#[tauri::command]
+pub async fn delete_outbox_message(
+    account_id: i64,
+    local_message_id: String,
+    app: AppHandle,
+) -> Result<(), String> {
+    let conn = storage::get_connection(&app, Some(account_id))?;
+    conn.execute(
+        "DELETE FROM outbox WHERE local_message_id = ?1 AND state != 'sending'",
+        params![local_message_id],
+    )
+    .map_err(|e| format!("删除发送队列消息失败: {}", e))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use super::*;
+    use crate::mock_onebot::{MockConfig, MockServer};
+    use crate::test_support::{self, TestApp};
+
+    #[tokio::test]
+    async fn flushes_queued_messages_in_order_after_reconnect() {
+        let server = MockServer::bind("127.0.0.1", 0, MockConfig::default()).await.unwrap();
+        let app = TestApp::new();
+        let session = app.connect(&server).await;
+        let account_id = server.self_id();
+
+        server.set_accepting(false);
+        server.disconnect_all();
+        test_support::wait_connection(&session, false).await;
+
+        for i in 1..=3 {
+            let local_message_id = format!("local-{}", i);
+            let params = json!({
+                "user_id": 30003,
+                "message": format!("第 {} 条", i),
+                "local_message_id": local_message_id,
+            });
+            submit(app.handle(), &app.registry, account_id, &local_message_id, "send_private_msg", &params).unwrap();
+        }
+
+        // 断线期间消息保持排队
+        let queued = get_outbox(account_id, None, app.handle().clone()).await.unwrap();
+        assert_eq!(queued.len(), 3);
+        assert!(queued.iter().all(|entry| entry.state == OutboxState::Pending));
+        assert!(server.calls().iter().all(|call| call["action"] != "send_private_msg"));
+
+        server.set_accepting(true);
+        let sent = test_support::wait_for_calls(&server, "send_private_msg", 3).await;
+        let messages: Vec<&str> = sent.iter().map(|params| params["message"].as_str().unwrap()).collect();
+        assert_eq!(messages, ["第 1 条", "第 2 条", "第 3 条"]);
+        // 只在本地使用的字段不会发给 OneBot 实现
+        assert!(sent.iter().all(|params| params.get("local_message_id").is_none()));
+
+        let wait = async {
+            loop {
+                let entries = get_outbox(account_id, Some(true), app.handle().clone()).await.unwrap();
+                if entries.iter().all(|entry| entry.state == OutboxState::Sent) {
+                    return entries;
+                }
+                tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+            }
+        };
+        let entries = tokio::time::timeout(test_support::TIMEOUT, wait).await.unwrap();
+        assert!(entries.iter().all(|entry| entry.attempts == 1 && entry.message_id.is_some()));
+
+        test_support::shutdown(&session).await;
+    }
+
+    async fn wait_finished(app: &TestApp, account_id: i64, local_message_id: &str) -> OutboxEntry {
+        let wait = async {
+            loop {
+                let entries = get_outbox(account_id, Some(true), app.handle().clone()).await.unwrap();
+                let entry = entries.into_iter().find(|entry| entry.local_message_id == local_message_id);
+                if let Some(entry) = entry.filter(|entry| matches!(entry.state, OutboxState::Sent | OutboxState::Failed)) {
+                    return entry;
+                }
+                tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+            }
+        };
+        tokio::time::timeout(test_support::TIMEOUT, wait).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn does_not_retry_deterministic_failures() {
+        let server = MockServer::bind("127.0.0.1", 0, MockConfig::default()).await.unwrap();
+        let app = TestApp::new();
+        let session = app.connect(&server).await;
+        let account_id = server.self_id();
+
+        // 模拟服务器对不支持的 API 返回 retcode 1404
+        let params = json!({ "user_id": 30003, "message": "你好" });
+        submit(app.handle(), &app.registry, account_id, "local-1", "send_unknown_msg", &params).unwrap();
+        let entry = wait_finished(&app, account_id, "local-1").await;
+        assert_eq!((entry.state, entry.attempts), (OutboxState::Failed, 1));
+        assert!(entry.last_error.unwrap().contains("1404"));
+        assert_eq!(server.calls().iter().filter(|call| call["action"] == "send_unknown_msg").count(), 1);
+
+        test_support::shutdown(&session).await;
+    }
+
+    #[tokio::test]
+    async fn does_not_resend_when_result_is_unknown() {
+        let server = MockServer::bind("127.0.0.1", 0, MockConfig::default()).await.unwrap();
+        // 实现已经发出消息，但响应中没有 message_id
+        server.update_fixtures(|fixtures| {
+            fixtures.responses.insert("send_private_msg".to_string(), json!({}));
+        });
+        let app = TestApp::new();
+        let session = app.connect(&server).await;
+        let account_id = server.self_id();
+
+        let params = json!({ "user_id": 30003, "message": "你好" });
+        submit(app.handle(), &app.registry, account_id, "local-1", "send_private_msg", &params).unwrap();
+        let entry = wait_finished(&app, account_id, "local-1").await;
+        assert_eq!((entry.state, entry.attempts), (OutboxState::Failed, 1));
+        assert!(entry.last_error.unwrap().contains("发送结果未知"));
+        assert_eq!(server.calls().iter().filter(|call| call["action"] == "send_private_msg").count(), 1);
+
+        test_support::shutdown(&session).await;
+    }
+
+    #[test]
+    fn retries_only_transient_failures() {
+        let failed = |retcode| ActionError::Failed { retcode, message: String::new(), wording: String::new() };
+        assert!(is_transient(&failed(33000)));
+        assert!(is_transient(&failed(36000)));
+        for retcode in [100, 102, 1400, 1403, 1404, 10003] {
+            assert!(!is_transient(&failed(retcode)), "{}", retcode);
+        }
+        assert!(!is_transient(&ActionError::Request { message: "等待响应超时".to_string() }));
+        assert!(!is_transient(&ActionError::InvalidResponse { message: String::new() }));
+    }
+}
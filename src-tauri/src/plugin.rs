@@ -0,0 +1,990 @@
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use async_trait::async_trait;
+use rusqlite::{params, OptionalExtension};
+use runbot::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use tauri::{Emitter, State};
+use crate::AppHandle;
+use wasmtime::{Caller, Config, Engine, Linker, Memory, Module, Store, StoreLimits, StoreLimitsBuilder, Trap, TypedFunc};
+use crate::actions;
+use crate::daemon::{self, ReloadTarget};
+use crate::runbot::RunbotState;
+use crate::scripting::{self, PostQueue, MAX_QUEUED_POSTS};
+use crate::session::Session;
+use crate::storage;
+
+/// 插件目录中的清单文件名
+const MANIFEST_FILE: &str = "manifest.json";
+/// 单次调用（init 或 on_event）可以消耗的燃料，超出后中止执行
+const FUEL_PER_CALL: u64 = 200_000_000;
+/// 插件线性内存上限
+const MAX_MEMORY: usize = 64 << 20;
+/// 单次从插件内存读取的数据上限
+const MAX_BUFFER: usize = 1 << 20;
+/// 键值存储中单个键、值的长度上限
+const MAX_KEY: usize = 256;
+const MAX_VALUE: usize = 64 << 10;
+/// 每个账号保留的插件日志条数
+const MAX_LOGS: usize = 500;
+/// 单次调用（init 或 on_event）中最多发送的消息数
+const MAX_SENDS_PER_CALL: usize = 5;
+/// 每个插件每分钟最多发送的消息数
+const MAX_SENDS_PER_MINUTE: usize = 20;
+const SEND_WINDOW: Duration = Duration::from_secs(60);
+
+/// 宿主函数的返回码
+const OK: i32 = 0;
+const ERR_DENIED: i32 = -1;
+const ERR_INVALID: i32 = -2;
+const ERR_FAILED: i32 = -3;
+const ERR_RATE_LIMITED: i32 = -5;
+
+/// 发送消息权限
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SendPermission {
+    /// 可以向触发事件的群（或私聊对象）发送消息
+    pub reply: bool,
+    /// 可以发送消息的群
+    pub groups: Vec<i64>,
+    /// 可以发送私聊消息的 QQ 号
+    pub users: Vec<i64>,
+}
+
+/// 插件清单中声明的权限
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Permissions {
+    /// 可以接收的事件：message、message_sent、notice、request、meta_event
+    pub events: Vec<String>,
+    /// 发送消息，None 表示不能发送
+    pub send_message: Option<SendPermission>,
+    /// 键值存储
+    pub storage: bool,
+}
+
+/// 插件清单（插件目录中的 manifest.json）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PluginManifest {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub version: String,
+    #[serde(default)]
+    pub description: String,
+    /// 插件目录中的 wasm 文件名
+    #[serde(default = "default_module")]
+    pub module: String,
+    #[serde(default)]
+    pub permissions: Permissions,
+}
+
+fn default_module() -> String {
+    "plugin.wasm".to_string()
+}
+
+/// 插件状态
+#[derive(Debug, Clone, Serialize)]
+pub struct PluginInfo {
+    /// 插件目录名，授权和键值存储都按它区分（清单中的 id 只用于显示）
+    pub id: String,
+    pub dir: String,
+    pub manifest: Option<PluginManifest>,
+    /// 清单和模块的 SHA-256，启用时需要传回，确认用户同意的就是当前的插件
+    pub fingerprint: Option<String>,
+    pub enabled: bool,
+    /// 授权时的清单和模块与当前一致；任何一个变化后都需要重新授权
+    pub granted: bool,
+    pub loaded: bool,
+    pub error: Option<String>,
+}
+
+/// 插件日志（同时作为 plugin-log 事件的内容）
+#[derive(Debug, Clone, Serialize)]
+pub struct PluginLog {
+    pub account_id: i64,
+    pub plugin: String,
+    /// info 或 error
+    pub level: String,
+    pub message: String,
+    /// 毫秒时间戳
+    pub time: i64,
+}
+
+#[derive(Debug, Clone)]
+struct LogSink {
+    app: AppHandle,
+    account_id: i64,
+    logs: Arc<Mutex<VecDeque<PluginLog>>>,
+}
+
+impl LogSink {
+    fn log(&self, plugin: &str, level: &str, message: String) {
+        match level {
+            "error" => tracing::warn!("[plugin:{}] {}", plugin, message),
+            _ => tracing::info!("[plugin:{}] {}", plugin, message),
+        }
+        let entry = PluginLog {
+            account_id: self.account_id,
+            plugin: plugin.to_string(),
+            level: level.to_string(),
+            message,
+            time: chrono::Utc::now().timestamp_millis(),
+        };
+        {
+            let mut logs = self.logs.lock().unwrap();
+            if logs.len() >= MAX_LOGS {
+                logs.pop_front();
+            }
+            logs.push_back(entry.clone());
+        }
+        let _ = self.app.emit("plugin-log", entry);
+    }
+}
+
+/// 插件 Store 中的宿主状态，宿主函数只能通过它访问外部
+struct HostState {
+    /// 插件目录名
+    plugin_id: String,
+    permissions: Permissions,
+    account_id: i64,
+    app: AppHandle,
+    registry: Arc<Mutex<RunbotState>>,
+    runtime: tokio::runtime::Handle,
+    sink: LogSink,
+    /// 当前事件所在的会话：("group", 群号) 或 ("private", QQ 号)
+    reply_target: Option<(&'static str, i64)>,
+    send_quota: SendQuota,
+    limits: StoreLimits,
+}
+
+impl HostState {
+    fn log(&self, level: &str, message: String) {
+        self.sink.log(&self.plugin_id, level, message);
+    }
+
+    fn can_send(&self, target_type: &str, id: i64) -> bool {
+        let Some(permission) = &self.permissions.send_message else {
+            return false;
+        };
+        if permission.reply && self.reply_target.is_some_and(|(t, i)| t == target_type && i == id) {
+            return true;
+        }
+        match target_type {
+            "group" => permission.groups.contains(&id),
+            "private" => permission.users.contains(&id),
+            _ => false,
+        }
+    }
+}
+
+/// 插件发送消息的频率限制：每次事件回调最多 MAX_SENDS_PER_CALL 条，每分钟最多 MAX_SENDS_PER_MINUTE 条
+///
+/// 等待发送结果时不消耗燃料，只靠燃料无法限制插件在一次调用中循环发送
+#[derive(Default)]
+struct SendQuota {
+    /// 本次调用中已发送的消息数
+    sends_this_call: usize,
+    /// 最近一分钟内发送消息的时间
+    recent_sends: VecDeque<Instant>,
+}
+
+impl SendQuota {
+    /// 开始新的一次事件回调
+    fn begin_call(&mut self) {
+        self.sends_this_call = 0;
+    }
+
+    /// 记录一次发送；超出单次调用或每分钟的发送次数时返回 false
+    fn take(&mut self, now: Instant) -> bool {
+        while self.recent_sends.front().is_some_and(|sent| now.duration_since(*sent) >= SEND_WINDOW) {
+            self.recent_sends.pop_front();
+        }
+        if self.sends_this_call >= MAX_SENDS_PER_CALL || self.recent_sends.len() >= MAX_SENDS_PER_MINUTE {
+            return false;
+        }
+        self.sends_this_call += 1;
+        self.recent_sends.push_back(now);
+        true
+    }
+}
+
+/// 插件调用 send_message 时传入的 JSON
+#[derive(Debug, Deserialize)]
+struct SendRequest {
+    /// group 或 private
+    #[serde(rename = "type")]
+    target_type: String,
+    id: i64,
+    /// 消息文本或消息段数组
+    message: Value,
+}
+
+fn memory(caller: &mut Caller<'_, HostState>) -> wasmtime::Result<Memory> {
+    caller
+        .get_export("memory")
+        .and_then(|export| export.into_memory())
+        .ok_or_else(|| wasmtime::Error::msg("插件没有导出 memory"))
+}
+
+fn read_bytes(caller: &mut Caller<'_, HostState>, ptr: i32, len: i32) -> wasmtime::Result<Vec<u8>> {
+    let len = len as u32 as usize;
+    if len > MAX_BUFFER {
+        return Err(wasmtime::Error::msg(format!("数据过长: {} 字节", len)));
+    }
+    let mut buf = vec![0; len];
+    memory(caller)?.read(&*caller, ptr as u32 as usize, &mut buf)?;
+    Ok(buf)
+}
+
+fn read_string(caller: &mut Caller<'_, HostState>, ptr: i32, len: i32) -> wasmtime::Result<String> {
+    String::from_utf8(read_bytes(caller, ptr, len)?).map_err(|e| wasmtime::Error::msg(format!("字符串不是 UTF-8: {}", e)))
+}
+
+/// 通过插件导出的 alloc 分配内存并写入数据，返回 (ptr << 32) | len
+fn write_to_plugin(caller: &mut Caller<'_, HostState>, data: &[u8]) -> wasmtime::Result<i64> {
+    let alloc = caller
+        .get_export("alloc")
+        .and_then(|export| export.into_func())
+        .ok_or_else(|| wasmtime::Error::msg("插件没有导出 alloc"))?
+        .typed::<i32, i32>(&*caller)?;
+    let ptr = alloc.call(&mut *caller, data.len() as i32)?;
+    memory(caller)?.write(&mut *caller, ptr as u32 as usize, data)?;
+    Ok(((ptr as u32 as i64) << 32) | data.len() as i64)
+}
+
+fn storage_get(state: &HostState, key: &str) -> Result<Option<Vec<u8>>, String> {
+    let conn = storage::get_connection(&state.app, Some(state.account_id))?;
+    conn.query_row(
+        "SELECT value FROM plugin_storage WHERE plugin_id = ?1 AND key = ?2",
+        params![state.plugin_id, key],
+        |row| row.get(0),
+    )
+    .optional()
+    .map_err(|e| format!("读取插件存储失败: {}", e))
+}
+
+fn storage_set(state: &HostState, key: &str, value: &[u8]) -> Result<(), String> {
+    let conn = storage::get_connection(&state.app, Some(state.account_id))?;
+    conn.execute(
+        "INSERT INTO plugin_storage (plugin_id, key, value, updated_at) VALUES (?1, ?2, ?3, strftime('%s', 'now'))
+         ON CONFLICT(plugin_id, key) DO UPDATE SET value = excluded.value, updated_at = excluded.updated_at",
+        params![state.plugin_id, key, value],
+    )
+    .map_err(|e| format!("写入插件存储失败: {}", e))?;
+    Ok(())
+}
+
+fn storage_delete(state: &HostState, key: &str) -> Result<(), String> {
+    let conn = storage::get_connection(&state.app, Some(state.account_id))?;
+    conn.execute(
+        "DELETE FROM plugin_storage WHERE plugin_id = ?1 AND key = ?2",
+        params![state.plugin_id, key],
+    )
+    .map_err(|e| format!("删除插件存储失败: {}", e))?;
+    Ok(())
+}
+
+fn read_key(caller: &mut Caller<'_, HostState>, ptr: i32, len: i32) -> wasmtime::Result<Result<String, i32>> {
+    if !caller.data().permissions.storage {
+        caller.data().log("error", "没有键值存储权限".to_string());
+        return Ok(Err(ERR_DENIED));
+    }
+    if len as u32 as usize > MAX_KEY {
+        caller.data().log("error", format!("键过长: {} 字节", len));
+        return Ok(Err(ERR_INVALID));
+    }
+    Ok(Ok(read_string(caller, ptr, len)?))
+}
+
+/// 注册插件可以导入的宿主函数（模块名 runbot）
+///
+/// - log(ptr, len)：输出一行日志
+/// - send_message(ptr, len) -> i32：参数为 JSON {"type": "group" | "private", "id": 号码, "message": 文本或消息段}；
+///   每次调用最多发送 MAX_SENDS_PER_CALL 条，每分钟最多 MAX_SENDS_PER_MINUTE 条，超出时返回 -5
+/// - storage_get(key_ptr, key_len) -> i64：返回 (ptr << 32) | len，值不存在时返回 -4
+/// - storage_set(key_ptr, key_len, value_ptr, value_len) -> i32
+/// - storage_delete(key_ptr, key_len) -> i32
+///
+/// 返回 0 表示成功，-1 没有权限，-2 参数无效，-3 执行失败，-5 发送过于频繁
+fn add_host_functions(linker: &mut Linker<HostState>) -> wasmtime::Result<()> {
+    linker.func_wrap("runbot", "log", |mut caller: Caller<'_, HostState>, ptr: i32, len: i32| {
+        let message = read_string(&mut caller, ptr, len)?;
+        caller.data().log("info", message);
+        Ok(())
+    })?;
+
+    linker.func_wrap(
+        "runbot",
+        "send_message",
+        |mut caller: Caller<'_, HostState>, ptr: i32, len: i32| -> wasmtime::Result<i32> {
+            let bytes = read_bytes(&mut caller, ptr, len)?;
+            let state = caller.data_mut();
+            let request: SendRequest = match serde_json::from_slice(&bytes) {
+                Ok(request) => request,
+                Err(e) => {
+                    state.log("error", format!("send_message 参数无效: {}", e));
+                    return Ok(ERR_INVALID);
+                }
+            };
+            let (action, params) = match request.target_type.as_str() {
+                "group" => ("send_group_msg", json!({ "group_id": request.id, "message": request.message })),
+                "private" => ("send_private_msg", json!({ "user_id": request.id, "message": request.message })),
+                other => {
+                    state.log("error", format!("send_message 类型无效: {}", other));
+                    return Ok(ERR_INVALID);
+                }
+            };
+            if !state.can_send(&request.target_type, request.id) {
+                state.log("error", format!("没有向 {} {} 发送消息的权限", request.target_type, request.id));
+                return Ok(ERR_DENIED);
+            }
+            if !state.send_quota.take(Instant::now()) {
+                state.log("error", "发送消息过于频繁，已丢弃".to_string());
+                return Ok(ERR_RATE_LIMITED);
+            }
+            let result = state
+                .runtime
+                .block_on(actions::call::<_, Value>(&state.registry, state.account_id, action, &params));
+            match result {
+                Ok(_) => Ok(OK),
+                Err(e) => {
+                    state.log("error", format!("发送消息失败: {}", e));
+                    Ok(ERR_FAILED)
+                }
+            }
+        },
+    )?;
+
+    linker.func_wrap(
+        "runbot",
+        "storage_get",
+        |mut caller: Caller<'_, HostState>, ptr: i32, len: i32| -> wasmtime::Result<i64> {
+            let key = match read_key(&mut caller, ptr, len)? {
+                Ok(key) => key,
+                Err(code) => return Ok(code as i64),
+            };
+            match storage_get(caller.data(), &key) {
+                Ok(Some(value)) => write_to_plugin(&mut caller, &value),
+                Ok(None) => Ok(-4),
+                Err(e) => {
+                    caller.data().log("error", e);
+                    Ok(ERR_FAILED as i64)
+                }
+            }
+        },
+    )?;
+
+    linker.func_wrap(
+        "runbot",
+        "storage_set",
+        |mut caller: Caller<'_, HostState>, key_ptr: i32, key_len: i32, value_ptr: i32, value_len: i32| -> wasmtime::Result<i32> {
+            let key = match read_key(&mut caller, key_ptr, key_len)? {
+                Ok(key) => key,
+                Err(code) => return Ok(code),
+            };
+            if value_len as u32 as usize > MAX_VALUE {
+                caller.data().log("error", format!("值过长: {} 字节", value_len));
+                return Ok(ERR_INVALID);
+            }
+            let value = read_bytes(&mut caller, value_ptr, value_len)?;
+            match storage_set(caller.data(), &key, &value) {
+                Ok(()) => Ok(OK),
+                Err(e) => {
+                    caller.data().log("error", e);
+                    Ok(ERR_FAILED)
+                }
+            }
+        },
+    )?;
+
+    linker.func_wrap(
+        "runbot",
+        "storage_delete",
+        |mut caller: Caller<'_, HostState>, ptr: i32, len: i32| -> wasmtime::Result<i32> {
+            let key = match read_key(&mut caller, ptr, len)? {
+                Ok(key) => key,
+                Err(code) => return Ok(code),
+            };
+            match storage_delete(caller.data(), &key) {
+                Ok(()) => Ok(OK),
+                Err(e) => {
+                    caller.data().log("error", e);
+                    Ok(ERR_FAILED)
+                }
+            }
+        },
+    )?;
+
+    Ok(())
+}
+
+fn describe_error(e: wasmtime::Error) -> String {
+    match e.downcast_ref::<Trap>() {
+        Some(Trap::OutOfFuel) => "执行超出计算额度".to_string(),
+        _ => format!("{:#}", e),
+    }
+}
+
+/// 已实例化的插件
+///
+/// 插件需要导出 memory、alloc(len) -> ptr 和 on_event(ptr, len)，可选导出 init()；
+/// 事件以 JSON 写入插件内存，结构与脚本中的 post 相同
+struct PluginInstance {
+    store: Store<HostState>,
+    memory: Memory,
+    alloc: TypedFunc<i32, i32>,
+    on_event: TypedFunc<(i32, i32), ()>,
+}
+
+impl PluginInstance {
+    fn on_event(&mut self, event: &[u8], reply_target: Option<(&'static str, i64)>) -> wasmtime::Result<()> {
+        self.store.data_mut().reply_target = reply_target;
+        self.store.data_mut().send_quota.begin_call();
+        self.store.set_fuel(FUEL_PER_CALL)?;
+        let ptr = self.alloc.call(&mut self.store, event.len() as i32)?;
+        self.memory.write(&mut self.store, ptr as u32 as usize, event)?;
+        self.on_event.call(&mut self.store, (ptr, event.len() as i32))
+    }
+}
+
+struct Plugin {
+    id: String,
+    dir: PathBuf,
+    manifest: Option<PluginManifest>,
+    fingerprint: Option<String>,
+    enabled: bool,
+    granted: bool,
+    error: Option<String>,
+    instance: Option<Mutex<PluginInstance>>,
+    queue: Arc<PostQueue<QueuedEvent>>,
+}
+
+/// 等待插件处理的事件：序列化后的事件和可以回复的对象
+type QueuedEvent = (Arc<Vec<u8>>, Option<(&'static str, i64)>);
+
+impl Plugin {
+    fn info(&self) -> PluginInfo {
+        PluginInfo {
+            id: self.id.clone(),
+            dir: self.dir.to_string_lossy().to_string(),
+            manifest: self.manifest.clone(),
+            fingerprint: self.fingerprint.clone(),
+            enabled: self.enabled,
+            granted: self.granted,
+            loaded: self.instance.is_some(),
+            error: self.error.clone(),
+        }
+    }
+
+    fn accepts(&self, category: &str) -> bool {
+        self.manifest
+            .as_ref()
+            .is_some_and(|manifest| manifest.permissions.events.iter().any(|event| event == category))
+    }
+}
+
+/// 插件可以订阅的事件类型
+fn event_category(post: &runbot::event::Post) -> Option<&'static str> {
+    match post {
+        runbot::event::Post::Message(_) => Some("message"),
+        runbot::event::Post::MessageSent(_) => Some("message_sent"),
+        runbot::event::Post::Notice(_) => Some("notice"),
+        runbot::event::Post::Request(_) => Some("request"),
+        runbot::event::Post::MetaEvent(_) => Some("meta_event"),
+        _ => None,
+    }
+}
+
+/// 事件所在的会话：有群号时为群，否则为对方 QQ 号
+fn reply_target(event: &Value) -> Option<(&'static str, i64)> {
+    let id = |key: &str| event.get(key).and_then(Value::as_i64).filter(|id| *id > 0);
+    id("group_id")
+        .map(|group_id| ("group", group_id))
+        .or_else(|| id("user_id").map(|user_id| ("private", user_id)))
+}
+
+fn plugins_dir(app: &AppHandle, account_id: i64) -> Result<PathBuf, String> {
+    let mut path = storage::get_user_data_dir(app, Some(account_id))?;
+    path.push("plugins");
+    std::fs::create_dir_all(&path).map_err(|e| format!("创建插件目录失败: {}", e))?;
+    Ok(path)
+}
+
+/// 插件内容的指纹：清单和模块文件的 SHA-256
+fn fingerprint(manifest: &[u8], module: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update((manifest.len() as u64).to_le_bytes());
+    hasher.update(manifest);
+    hasher.update(module);
+    hex::encode(hasher.finalize())
+}
+
+/// 读取插件的授权：(是否启用, 授权时的指纹)
+fn load_grant(app: &AppHandle, account_id: i64, plugin_id: &str) -> Result<Option<(bool, String)>, String> {
+    let conn = storage::get_connection(app, Some(account_id))?;
+    conn.query_row(
+        "SELECT enabled, fingerprint FROM plugins WHERE plugin_id = ?1",
+        params![plugin_id],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )
+    .optional()
+    .map_err(|e| format!("读取插件授权失败: {}", e))
+}
+
+/// 一个账号的插件运行环境
+///
+/// 插件放在用户数据目录的 plugins 文件夹中，每个插件一个子目录（manifest.json 和 wasm 模块）；
+/// 插件只有在用户按清单授权并启用后才会加载。授权按子目录记录并绑定清单和模块的内容，
+/// 其中任何一个变化后都需要重新授权，新放入的插件也无法通过声明相同的 id 冒用其他插件的授权和存储
+pub struct PluginHost {
+    account_id: i64,
+    dir: PathBuf,
+    app: AppHandle,
+    registry: Arc<Mutex<RunbotState>>,
+    engine: Engine,
+    sink: LogSink,
+    /// None 表示尚未加载
+    plugins: Mutex<Option<Vec<Arc<Plugin>>>>,
+}
+
+impl std::fmt::Debug for PluginHost {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PluginHost")
+            .field("account_id", &self.account_id)
+            .field("dir", &self.dir)
+            .finish()
+    }
+}
+
+impl PluginHost {
+    fn new(app: &AppHandle, registry: Arc<Mutex<RunbotState>>, account_id: i64) -> Result<Self, String> {
+        let mut config = Config::new();
+        config.consume_fuel(true);
+        let engine = Engine::new(&config).map_err(|e| format!("创建插件引擎失败: {}", e))?;
+        Ok(Self {
+            account_id,
+            dir: plugins_dir(app, account_id)?,
+            app: app.clone(),
+            registry,
+            engine,
+            sink: LogSink {
+                app: app.clone(),
+                account_id,
+                logs: Arc::new(Mutex::new(VecDeque::new())),
+            },
+            plugins: Mutex::new(None),
+        })
+    }
+
+    /// 获取插件列表，尚未加载时先加载（在阻塞线程中调用）
+    fn plugins(&self) -> Vec<Arc<Plugin>> {
+        let mut plugins = self.plugins.lock().unwrap();
+        plugins.get_or_insert_with(|| self.load_all()).clone()
+    }
+
+    /// 丢弃已加载的插件并重新加载（在阻塞线程中调用）
+    fn reload(&self) -> Vec<Arc<Plugin>> {
+        let mut plugins = self.plugins.lock().unwrap();
+        plugins.take();
+        plugins.get_or_insert_with(|| self.load_all()).clone()
+    }
+
+    fn load_all(&self) -> Vec<Arc<Plugin>> {
+        let mut dirs: Vec<PathBuf> = match std::fs::read_dir(&self.dir) {
+            Ok(entries) => entries.flatten().map(|entry| entry.path()).filter(|path| path.is_dir()).collect(),
+            Err(e) => {
+                tracing::warn!("[plugin] 读取插件目录失败 {:?}: {}", self.dir, e);
+                return Vec::new();
+            }
+        };
+        dirs.sort();
+
+        let mut plugins: Vec<Arc<Plugin>> = Vec::new();
+        for dir in dirs {
+            let plugin = self.load(&dir);
+            if let Some(e) = &plugin.error {
+                self.sink.log(&plugin.id, "error", e.clone());
+            } else if plugin.instance.is_some() {
+                self.sink.log(&plugin.id, "info", "插件已加载".to_string());
+            }
+            plugins.push(Arc::new(plugin));
+        }
+        plugins
+    }
+
+    fn load(&self, dir: &Path) -> Plugin {
+        let mut plugin = Plugin {
+            id: dir.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default(),
+            dir: dir.to_path_buf(),
+            manifest: None,
+            fingerprint: None,
+            enabled: false,
+            granted: false,
+            error: None,
+            instance: None,
+            queue: Arc::new(PostQueue::default()),
+        };
+
+        // 清单和模块只读取一次：计算指纹和加载的是同一份内容
+        let files = std::fs::read(dir.join(MANIFEST_FILE))
+            .map_err(|e| format!("读取清单失败: {}", e))
+            .and_then(|manifest_bytes| {
+                let manifest = serde_json::from_slice::<PluginManifest>(&manifest_bytes)
+                    .map_err(|e| format!("解析清单失败: {}", e))?;
+                if Path::new(&manifest.module).file_name().and_then(|name| name.to_str()) != Some(manifest.module.as_str()) {
+                    return Err(format!("模块文件名无效: {}", manifest.module));
+                }
+                let module = std::fs::read(dir.join(&manifest.module)).map_err(|e| format!("读取模块失败: {}", e))?;
+                Ok((manifest, fingerprint(&manifest_bytes, &module), module))
+            });
+        let (manifest, fingerprint, module) = match files {
+            Ok(files) => files,
+            Err(e) => {
+                plugin.error = Some(e);
+                return plugin;
+            }
+        };
+        plugin.manifest = Some(manifest.clone());
+        plugin.fingerprint = Some(fingerprint.clone());
+
+        match load_grant(&self.app, self.account_id, &plugin.id) {
+            Ok(Some((enabled, granted_fingerprint))) => {
+                plugin.enabled = enabled;
+                plugin.granted = granted_fingerprint == fingerprint;
+            }
+            Ok(None) => {}
+            Err(e) => {
+                plugin.error = Some(e);
+                return plugin;
+            }
+        }
+        if !plugin.enabled || !plugin.granted {
+            return plugin;
+        }
+
+        match self.instantiate(&plugin.id, &manifest, &module) {
+            Ok(instance) => plugin.instance = Some(Mutex::new(instance)),
+            Err(e) => plugin.error = Some(e),
+        }
+        plugin
+    }
+
+    fn instantiate(&self, plugin_id: &str, manifest: &PluginManifest, module: &[u8]) -> Result<PluginInstance, String> {
+        let module = Module::new(&self.engine, module).map_err(|e| format!("加载模块失败: {:#}", e))?;
+
+        let mut linker = Linker::new(&self.engine);
+        add_host_functions(&mut linker).map_err(|e| format!("注册宿主函数失败: {}", e))?;
+
+        let state = HostState {
+            plugin_id: plugin_id.to_string(),
+            permissions: manifest.permissions.clone(),
+            account_id: self.account_id,
+            app: self.app.clone(),
+            registry: self.registry.clone(),
+            runtime: tokio::runtime::Handle::current(),
+            sink: self.sink.clone(),
+            reply_target: None,
+            send_quota: SendQuota::default(),
+            limits: StoreLimitsBuilder::new().memory_size(MAX_MEMORY).instances(1).build(),
+        };
+        let mut store = Store::new(&self.engine, state);
+        store.limiter(|state| &mut state.limits);
+        store.set_fuel(FUEL_PER_CALL).map_err(|e| format!("设置燃料失败: {}", e))?;
+
+        // 插件只能导入上面注册的宿主函数，导入其他函数（如 WASI）会实例化失败
+        let instance = linker
+            .instantiate(&mut store, &module)
+            .map_err(|e| format!("实例化失败: {}", describe_error(e)))?;
+        let memory = instance
+            .get_memory(&mut store, "memory")
+            .ok_or_else(|| "插件没有导出 memory".to_string())?;
+        let alloc = instance
+            .get_typed_func::<i32, i32>(&mut store, "alloc")
+            .map_err(|e| format!("插件没有导出 alloc: {}", e))?;
+        let on_event = instance
+            .get_typed_func::<(i32, i32), ()>(&mut store, "on_event")
+            .map_err(|e| format!("插件没有导出 on_event: {}", e))?;
+        if let Ok(init) = instance.get_typed_func::<(), ()>(&mut store, "init") {
+            init.call(&mut store, ()).map_err(|e| format!("init 执行失败: {}", describe_error(e)))?;
+        }
+
+        Ok(PluginInstance { store, memory, alloc, on_event })
+    }
+}
+
+/// 取得会话的插件环境，没有时创建（插件在第一次使用时加载）
+fn host_for(
+    app: &AppHandle,
+    registry: &Arc<Mutex<RunbotState>>,
+    session: &Mutex<Session>,
+    account_id: i64,
+) -> Result<Arc<PluginHost>, String> {
+    let mut session_guard = session.lock().map_err(|e| format!("锁定会话失败: {}", e))?;
+    if let Some(host) = &session_guard.plugin_host {
+        return Ok(host.clone());
+    }
+    let host = Arc::new(PluginHost::new(app, registry.clone(), account_id)?);
+    session_guard.plugin_host = Some(host.clone());
+    Ok(host)
+}
+
+/// 重新加载会话中已加载的插件（在阻塞线程中调用），尚未加载时不需要处理
+pub(crate) fn reload_loaded(state: &Mutex<RunbotState>, account_id: i64) {
+    let session = state.lock().ok().and_then(|state_guard| state_guard.sessions.get(account_id));
+    let host = session.and_then(|session| session.lock().ok().and_then(|session_guard| session_guard.plugin_host.clone()));
+    if let Some(host) = host {
+        tracing::info!("[plugin] 重新加载插件: {:?}", host.dir);
+        host.reload();
+    }
+}
+
+/// 插件 Processor：把事件交给订阅了该类型事件的插件
+#[derive(Debug)]
+pub struct PluginProcessor {
+    app: AppHandle,
+    registry: Arc<Mutex<RunbotState>>,
+    state: Arc<Mutex<Session>>,
+}
+
+impl PluginProcessor {
+    pub fn new(app: AppHandle, registry: Arc<Mutex<RunbotState>>, state: Arc<Mutex<Session>>) -> Self {
+        Self { app, registry, state }
+    }
+}
+
+#[async_trait]
+impl PostProcessor for PluginProcessor {
+    fn id(&self) -> &'static str {
+        "plugin"
+    }
+
+    async fn process_post(
+        &self,
+        _bot_ctx: Arc<BotContext>,
+        post: &runbot::event::Post,
+    ) -> anyhow::Result<bool> {
+        let Some(category) = event_category(post) else {
+            return Ok(false);
+        };
+        let Some(account_id) = self.state.lock().unwrap().account_id else {
+            return Ok(false);
+        };
+        let host = match host_for(&self.app, &self.registry, &self.state, account_id) {
+            Ok(host) => host,
+            Err(e) => {
+                tracing::warn!("[PluginProcessor] {}", e);
+                return Ok(false);
+            }
+        };
+
+        let load_host = host.clone();
+        let plugins: Vec<Arc<Plugin>> = tokio::task::spawn_blocking(move || load_host.plugins())
+            .await
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|plugin| plugin.instance.is_some() && plugin.accepts(category))
+            .collect();
+        if plugins.is_empty() {
+            return Ok(false);
+        }
+
+        let event = match scripting::post_to_json(post) {
+            Ok(event) => event,
+            Err(e) => {
+                tracing::warn!("[PluginProcessor] {}", e);
+                return Ok(false);
+            }
+        };
+        let target = reply_target(&event);
+        let event = Arc::new(event.to_string().into_bytes());
+        for plugin in plugins {
+            // 插件在阻塞线程中执行，同一插件的事件按顺序逐个处理
+            let queue = plugin.queue.clone();
+            let sink = host.sink.clone();
+            let id = plugin.id.clone();
+            let queued = queue.push((event.clone(), target), move |(event, target)| {
+                let Some(instance) = &plugin.instance else {
+                    return;
+                };
+                let result = instance.lock().unwrap().on_event(&event, target);
+                if let Err(e) = result {
+                    sink.log(&plugin.id, "error", format!("on_event 执行失败: {}", describe_error(e)));
+                }
+            });
+            if !queued {
+                host.sink.log(&id, "error", format!("待处理的事件超过 {} 条，已丢弃新事件", MAX_QUEUED_POSTS));
+            }
+        }
+        Ok(false)
+    }
+}
+
+fn connected_session(state: &Mutex<RunbotState>, account_id: i64) -> Result<Arc<Mutex<Session>>, String> {
+    state
+        .lock()
+        .map_err(|e| format!("锁定状态失败: {}", e))?
+        .sessions
+        .get(account_id)
+        .ok_or_else(|| format!("账号 {} 未连接", account_id))
+}
+
+/// 获取账号的插件目录
+#[tauri::command]
+pub async fn get_plugins_dir(account_id: i64, app: AppHandle) -> Result<String, String> {
+    Ok(plugins_dir(&app, account_id)?.to_string_lossy().to_string())
+}
+
+/// 获取插件列表（账号需已连接）
+#[tauri::command]
+pub async fn list_plugins(
+    account_id: i64,
+    app: AppHandle,
+    state: State<'_, Arc<Mutex<RunbotState>>>,
+) -> Result<Vec<PluginInfo>, String> {
+    let session = connected_session(&state, account_id)?;
+    let host = host_for(&app, state.inner(), &session, account_id)?;
+    tokio::task::spawn_blocking(move || host.plugins().iter().map(|plugin| plugin.info()).collect())
+        .await
+        .map_err(|e| format!("加载插件失败: {}", e))
+}
+
+/// 重新加载全部插件（插件内存中的状态会被重置）
+#[tauri::command]
+pub async fn reload_plugins(
+    account_id: i64,
+    app: AppHandle,
+    state: State<'_, Arc<Mutex<RunbotState>>>,
+) -> Result<Vec<PluginInfo>, String> {
+    let session = connected_session(&state, account_id)?;
+    let host = host_for(&app, state.inner(), &session, account_id)?;
+    tracing::info!("[reload_plugins] 重新加载插件: {:?}", host.dir);
+    let plugins = tokio::task::spawn_blocking(move || host.reload().iter().map(|plugin| plugin.info()).collect())
+        .await
+        .map_err(|e| format!("加载插件失败: {}", e))?;
+    daemon::notify_daemon(&app, &state, account_id, ReloadTarget::Plugins).await?;
+    Ok(plugins)
+}
+
+/// 启用或停用插件；启用即表示同意清单中当前声明的全部权限
+///
+/// 启用时需要传入界面上显示的指纹，插件在此期间变化时拒绝启用，避免同意的不是看到的插件
+#[tauri::command]
+pub async fn set_plugin_enabled(
+    account_id: i64,
+    plugin_id: String,
+    enabled: bool,
+    fingerprint: Option<String>,
+    app: AppHandle,
+    state: State<'_, Arc<Mutex<RunbotState>>>,
+) -> Result<Vec<PluginInfo>, String> {
+    tracing::info!("[set_plugin_enabled] plugin_id={}, enabled={}", plugin_id, enabled);
+    let session = connected_session(&state, account_id)?;
+    let host = host_for(&app, state.inner(), &session, account_id)?;
+
+    let load_host = host.clone();
+    let plugins = tokio::task::spawn_blocking(move || load_host.plugins())
+        .await
+        .map_err(|e| format!("加载插件失败: {}", e))?;
+    let plugin = plugins
+        .iter()
+        .find(|plugin| plugin.id == plugin_id)
+        .ok_or_else(|| format!("插件不存在: {}", plugin_id))?;
+
+    let conn = storage::get_connection(&app, Some(account_id))?;
+    if enabled {
+        let (Some(manifest), Some(current)) = (&plugin.manifest, &plugin.fingerprint) else {
+            return Err(format!("插件无法加载: {}", plugin.error.clone().unwrap_or_default()));
+        };
+        if fingerprint.as_ref() != Some(current) {
+            return Err("插件内容已变化，请重新确认权限后再启用".to_string());
+        }
+        let permissions = serde_json::to_string(&manifest.permissions).map_err(|e| format!("序列化权限失败: {}", e))?;
+        conn.execute(
+            "INSERT INTO plugins (plugin_id, fingerprint, enabled, permissions, updated_at)
+             VALUES (?1, ?2, 1, ?3, strftime('%s', 'now'))
+             ON CONFLICT(plugin_id) DO UPDATE SET
+                fingerprint = excluded.fingerprint,
+                enabled = 1,
+                permissions = excluded.permissions,
+                updated_at = excluded.updated_at",
+            params![plugin_id, current, permissions],
+        )
+    } else {
+        conn.execute(
+            "UPDATE plugins SET enabled = 0, updated_at = strftime('%s', 'now') WHERE plugin_id = ?1",
+            params![plugin_id],
+        )
+    }
+    .map_err(|e| format!("保存插件授权失败: {}", e))?;
+
+    let plugins = tokio::task::spawn_blocking(move || host.reload().iter().map(|plugin| plugin.info()).collect())
+        .await
+        .map_err(|e| format!("加载插件失败: {}", e))?;
+    daemon::notify_daemon(&app, &state, account_id, ReloadTarget::Plugins).await?;
+    Ok(plugins)
+}
+
+/// 获取最近的插件日志
+#[tauri::command]
+pub async fn get_plugin_logs(
+    account_id: i64,
+    state: State<'_, Arc<Mutex<RunbotState>>>,
+) -> Result<Vec<PluginLog>, String> {
+    let session = connected_session(&state, account_id)?;
+    let host = session
+        .lock()
+        .map_err(|e| format!("锁定会话失败: {}", e))?
+        .plugin_host
+        .clone();
+    Ok(host
+        .map(|host| host.sink.logs.lock().unwrap().iter().cloned().collect())
+        .unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn limits_sends_per_call() {
+        let mut quota = SendQuota::default();
+        let now = Instant::now();
+        for _ in 0..MAX_SENDS_PER_CALL {
+            assert!(quota.take(now));
+        }
+        assert!(!quota.take(now));
+
+        quota.begin_call();
+        assert!(quota.take(now));
+    }
+
+    #[test]
+    fn limits_sends_per_minute() {
+        let mut quota = SendQuota::default();
+        let start = Instant::now();
+        for _ in 0..MAX_SENDS_PER_MINUTE {
+            quota.begin_call();
+            assert!(quota.take(start));
+        }
+        quota.begin_call();
+        assert!(!quota.take(start + Duration::from_secs(30)));
+        // 一分钟后之前的发送记录过期
+        assert!(quota.take(start + SEND_WINDOW));
+    }
+
+    #[test]
+    fn fingerprint_covers_manifest_and_module() {
+        let base = fingerprint(b"{\"id\":\"a\"}", b"wasm");
+        assert_eq!(base, fingerprint(b"{\"id\":\"a\"}", b"wasm"));
+        assert_ne!(base, fingerprint(b"{\"id\":\"b\"}", b"wasm"));
+        assert_ne!(base, fingerprint(b"{\"id\":\"a\"}", b"wasm2"));
+        // 清单和模块的分界不同，指纹也不同
+        assert_ne!(fingerprint(b"ab", b"c"), fingerprint(b"a", b"bc"));
+    }
+}
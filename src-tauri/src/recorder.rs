@@ -0,0 +1,294 @@
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tauri::State;
+use crate::AppHandle;
+use tokio::sync::mpsc;
+use crate::runbot::RunbotState;
+use crate::storage;
+
+/// 帧的方向
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Direction {
+    /// 上游推送给客户端的帧（事件和 API 响应）
+    Inbound,
+    /// 客户端发给上游的帧（API 调用）
+    Outbound,
+}
+
+/// 录制文件中的一行
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordEntry {
+    /// 毫秒时间戳
+    pub time: i64,
+    pub direction: Direction,
+    /// 原始帧内容
+    pub frame: String,
+}
+
+/// 会话流量录制器：把上游收发的原始帧按行写入 JSON Lines 文件
+///
+/// 写文件在后台任务中进行，录制器被丢弃后写完剩余的帧并关闭文件
+#[derive(Debug)]
+pub struct Recorder {
+    path: PathBuf,
+    lines: mpsc::UnboundedSender<String>,
+}
+
+impl Recorder {
+    pub fn start(path: PathBuf) -> Result<Self, String> {
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .map_err(|e| format!("创建录制文件失败: {}", e))?;
+        let (lines, mut rx) = mpsc::unbounded_channel::<String>();
+
+        let file_path = path.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut writer = std::io::BufWriter::new(file);
+            while let Some(line) = rx.blocking_recv() {
+                let result = writeln!(writer, "{}", line).and_then(|_| writer.flush());
+                if let Err(e) = result {
+                    tracing::error!("[recorder] 写入录制文件失败 {:?}: {}", file_path, e);
+                    return;
+                }
+            }
+            tracing::info!("[recorder] 录制结束: {:?}", file_path);
+        });
+
+        tracing::info!("[recorder] 开始录制: {:?}", path);
+        Ok(Self { path, lines })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn record(&self, direction: Direction, frame: &str) {
+        let entry = RecordEntry {
+            time: chrono::Utc::now().timestamp_millis(),
+            direction,
+            frame: frame.to_string(),
+        };
+        if let Ok(line) = serde_json::to_string(&entry) {
+            let _ = self.lines.send(line);
+        }
+    }
+}
+
+/// 已加载的录制文件，供回放使用
+///
+/// 入站帧中带 echo 的是 API 响应，按 echo 与出站的调用配对；其余入站帧作为事件按时间顺序回放
+#[derive(Debug, Default)]
+pub struct Recording {
+    path: PathBuf,
+    events: Vec<RecordEntry>,
+    /// (action, params, 响应帧)，按录制顺序排列，每个响应只使用一次
+    responses: Vec<(String, Value, String)>,
+    self_id: Option<i64>,
+}
+
+/// 解析 API 调用帧，返回 (action, params, echo)
+fn parse_call(frame: &str) -> Option<(String, Value, Value)> {
+    let value: Value = serde_json::from_str(frame).ok()?;
+    let action = value.get("action")?.as_str()?.to_string();
+    let params = match value.get("params") {
+        Some(Value::Null) | None => serde_json::json!({}),
+        Some(params) => params.clone(),
+    };
+    let echo = value.get("echo").cloned().unwrap_or(Value::Null);
+    Some((action, params, echo))
+}
+
+impl Recording {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let file = std::fs::File::open(path).map_err(|e| format!("打开录制文件失败: {}", e))?;
+
+        let mut recording = Recording {
+            path: path.to_path_buf(),
+            ..Default::default()
+        };
+        // echo -> (action, params)
+        let mut calls: HashMap<String, (String, Value)> = HashMap::new();
+
+        for (index, line) in BufReader::new(file).lines().enumerate() {
+            let line = line.map_err(|e| format!("读取录制文件失败: {}", e))?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let entry: RecordEntry = serde_json::from_str(&line)
+                .map_err(|e| format!("解析录制文件第 {} 行失败: {}", index + 1, e))?;
+
+            match entry.direction {
+                Direction::Outbound => {
+                    if let Some((action, params, echo)) = parse_call(&entry.frame) {
+                        calls.insert(echo.to_string(), (action, params));
+                    }
+                }
+                Direction::Inbound => {
+                    let Ok(value) = serde_json::from_str::<Value>(&entry.frame) else {
+                        continue;
+                    };
+                    if value.get("post_type").is_none() {
+                        let echo = value.get("echo").cloned().unwrap_or(Value::Null);
+                        if let Some((action, params)) = calls.remove(&echo.to_string()) {
+                            recording.responses.push((action, params, entry.frame));
+                        }
+                        continue;
+                    }
+                    if recording.self_id.is_none() {
+                        recording.self_id = value.get("self_id").and_then(|v| v.as_i64());
+                    }
+                    recording.events.push(entry);
+                }
+            }
+        }
+
+        tracing::info!(
+            "[recorder] 加载录制文件 {:?}: {} 个事件，{} 个 API 响应",
+            path,
+            recording.events.len(),
+            recording.responses.len()
+        );
+        Ok(recording)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// 取出按时间顺序排列的事件
+    pub fn take_events(&mut self) -> Vec<RecordEntry> {
+        std::mem::take(&mut self.events)
+    }
+
+    /// 为回放中的 API 调用查找录制的响应（action 和 params 都相同），并替换为本次调用的 echo
+    ///
+    /// 录制开始于连接之后时没有 get_login_info 的响应，此时按事件中的 self_id 构造
+    pub fn respond(&mut self, frame: &str) -> Option<String> {
+        let (action, params, echo) = parse_call(frame)?;
+
+        let index = self
+            .responses
+            .iter()
+            .position(|(a, p, _)| *a == action && *p == params);
+        let mut response: Value = match index {
+            Some(index) => {
+                let (_, _, response) = self.responses.remove(index);
+                serde_json::from_str(&response).ok()?
+            }
+            None if action == "get_login_info" => serde_json::json!({
+                "status": "ok",
+                "retcode": 0,
+                "data": { "user_id": self.self_id?, "nickname": "" },
+            }),
+            None => return None,
+        };
+        response["echo"] = echo;
+        Some(response.to_string())
+    }
+}
+
+/// 录制文件信息
+#[derive(Debug, Clone, Serialize)]
+pub struct RecordingFile {
+    pub name: String,
+    pub path: String,
+    pub size: u64,
+    /// 最后修改时间（秒）
+    pub modified: i64,
+}
+
+fn recordings_dir(app: &AppHandle, account_id: i64) -> Result<PathBuf, String> {
+    let mut path = storage::get_user_data_dir(app, Some(account_id))?;
+    path.push("recordings");
+    std::fs::create_dir_all(&path).map_err(|e| format!("创建录制目录失败: {}", e))?;
+    Ok(path)
+}
+
+/// 开始录制账号连接上的原始帧，返回录制文件路径；已在录制时返回当前文件
+#[tauri::command]
+pub async fn start_recording(
+    account_id: i64,
+    app: AppHandle,
+    state: State<'_, Arc<Mutex<RunbotState>>>,
+) -> Result<String, String> {
+    let session = state
+        .lock()
+        .map_err(|e| format!("锁定状态失败: {}", e))?
+        .sessions
+        .get(account_id)
+        .ok_or_else(|| format!("账号 {} 未连接", account_id))?;
+
+    let mut session_guard = session.lock().map_err(|e| format!("锁定会话失败: {}", e))?;
+    if let Some(recorder) = &session_guard.recorder {
+        return Ok(recorder.path().to_string_lossy().to_string());
+    }
+
+    let mut path = recordings_dir(&app, account_id)?;
+    path.push(format!("{}.jsonl", chrono::Local::now().format("%Y%m%d_%H%M%S")));
+    let recorder = Recorder::start(path)?;
+    let path = recorder.path().to_string_lossy().to_string();
+    session_guard.recorder = Some(Arc::new(recorder));
+    Ok(path)
+}
+
+/// 停止录制，返回录制文件路径；未在录制时返回 None
+#[tauri::command]
+pub async fn stop_recording(
+    account_id: i64,
+    state: State<'_, Arc<Mutex<RunbotState>>>,
+) -> Result<Option<String>, String> {
+    let session = state
+        .lock()
+        .map_err(|e| format!("锁定状态失败: {}", e))?
+        .sessions
+        .get(account_id);
+    let Some(session) = session else {
+        return Ok(None);
+    };
+
+    let recorder = session
+        .lock()
+        .map_err(|e| format!("锁定会话失败: {}", e))?
+        .recorder
+        .take();
+    Ok(recorder.map(|recorder| recorder.path().to_string_lossy().to_string()))
+}
+
+/// 列出账号的录制文件（按修改时间倒序）
+#[tauri::command]
+pub async fn list_recordings(account_id: i64, app: AppHandle) -> Result<Vec<RecordingFile>, String> {
+    let dir = recordings_dir(&app, account_id)?;
+    let entries = std::fs::read_dir(&dir).map_err(|e| format!("读取录制目录失败: {}", e))?;
+
+    let mut files = Vec::new();
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.extension().and_then(|ext| ext.to_str()) != Some("jsonl") {
+            continue;
+        }
+        let Ok(metadata) = entry.metadata() else {
+            continue;
+        };
+        let modified = metadata
+            .modified()
+            .ok()
+            .and_then(|time| time.duration_since(std::time::UNIX_EPOCH).ok())
+            .map(|duration| duration.as_secs() as i64)
+            .unwrap_or(0);
+        files.push(RecordingFile {
+            name: entry.file_name().to_string_lossy().to_string(),
+            path: path.to_string_lossy().to_string(),
+            size: metadata.len(),
+            modified,
+        });
+    }
+    files.sort_by_key(|file| std::cmp::Reverse(file.modified));
+    Ok(files)
+}
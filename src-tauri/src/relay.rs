@@ -0,0 +1,595 @@
+use std::collections::VecDeque;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use axum::extract::ws::{Message as WsMessage, WebSocket, WebSocketUpgrade};
+use axum::extract::{ConnectInfo, Query, State as AxumState};
+use axum::http::{HeaderMap, StatusCode, Uri};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::Router;
+use futures_util::{SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tauri::{Emitter, State};
+use crate::AppHandle;
+use tokio::net::TcpListener;
+use tokio::sync::{broadcast, mpsc, watch};
+use tokio::task::JoinHandle;
+use crate::reverse;
+use crate::runbot::{self, ActionResponse, RunbotState};
+
+/// 转发 API 调用的超时时间
+const ACTION_TIMEOUT: Duration = Duration::from_secs(30);
+/// 事件广播的缓冲区大小，下游处理不过来时丢弃最早的事件
+const EVENT_BUFFER: usize = 1024;
+/// 每个账号保留的转发记录条数
+const MAX_ACTIONS: usize = 500;
+
+/// 下游客户端（同时作为 relay-clients 事件的内容）
+#[derive(Debug, Clone, Serialize)]
+pub struct RelayClient {
+    pub id: u64,
+    /// 连接时的 name 参数，没有时使用 User-Agent
+    pub name: String,
+    pub addr: String,
+    /// universal、api 或 event
+    pub role: String,
+    /// 秒级时间戳
+    pub connected_at: i64,
+}
+
+/// 下游客户端发起的一次 API 调用（同时作为 relay-action 事件的内容）
+#[derive(Debug, Clone, Serialize)]
+pub struct RelayAction {
+    pub account_id: i64,
+    pub client_id: u64,
+    pub client_name: String,
+    pub action: String,
+    pub params: Value,
+    /// 响应状态：ok、failed 或 async
+    pub status: String,
+    pub retcode: i64,
+    pub message: String,
+    pub elapsed_ms: i64,
+    /// 毫秒时间戳
+    pub time: i64,
+}
+
+/// 中继服务器的状态
+#[derive(Debug, Clone, Serialize)]
+pub struct RelayStatus {
+    pub addr: String,
+    pub clients: Vec<RelayClient>,
+}
+
+/// 中继服务器：把已连接的账号重新作为正向 WebSocket OneBot v11 实现提供给下游的机器人框架
+///
+/// 上游上报的原始事件原样转发给所有下游客户端，下游的 API 调用通过会话的 BotContext 发送，
+/// 响应带上下游自己的 echo 只回给发起调用的客户端。
+/// 服务器属于会话，断开账号时随会话一起停止
+pub struct RelayServer {
+    addr: SocketAddr,
+    events: broadcast::Sender<Arc<str>>,
+    clients: Arc<Mutex<Vec<RelayClient>>>,
+    actions: Arc<Mutex<VecDeque<RelayAction>>>,
+    /// 服务器停止时关闭，已建立的连接随之断开
+    shutdown: Mutex<Option<watch::Sender<()>>>,
+    task: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl std::fmt::Debug for RelayServer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RelayServer")
+            .field("addr", &self.addr)
+            .finish()
+    }
+}
+
+#[derive(Clone)]
+struct RelayContext {
+    app: AppHandle,
+    registry: Arc<Mutex<RunbotState>>,
+    account_id: i64,
+    access_token: Option<String>,
+    events: broadcast::Sender<Arc<str>>,
+    clients: Arc<Mutex<Vec<RelayClient>>>,
+    actions: Arc<Mutex<VecDeque<RelayAction>>>,
+    next_client_id: Arc<AtomicU64>,
+    shutdown: watch::Receiver<()>,
+}
+
+impl RelayServer {
+    /// 在指定地址上监听
+    pub async fn bind(
+        app: AppHandle,
+        registry: Arc<Mutex<RunbotState>>,
+        account_id: i64,
+        host: &str,
+        port: u16,
+        access_token: Option<String>,
+    ) -> Result<Self, String> {
+        // 下游客户端可以用账号发送任意消息，对外开放时必须校验令牌
+        if access_token.is_none() && !is_loopback_host(host) {
+            return Err(format!("监听非本机地址 {} 时必须设置 access_token", host));
+        }
+        let listener = TcpListener::bind((host, port))
+            .await
+            .map_err(|e| format!("监听 {}:{} 失败: {}", host, port, e))?;
+        let addr = listener
+            .local_addr()
+            .map_err(|e| format!("获取监听地址失败: {}", e))?;
+
+        let (events, _) = broadcast::channel(EVENT_BUFFER);
+        let (shutdown, shutdown_rx) = watch::channel(());
+        let clients = Arc::new(Mutex::new(Vec::new()));
+        let actions = Arc::new(Mutex::new(VecDeque::new()));
+        let context = RelayContext {
+            app,
+            registry,
+            account_id,
+            access_token,
+            events: events.clone(),
+            clients: clients.clone(),
+            actions: actions.clone(),
+            next_client_id: Arc::new(AtomicU64::new(1)),
+            shutdown: shutdown_rx,
+        };
+        let router = Router::new()
+            .route("/", get(universal_socket))
+            .route("/api", get(api_socket))
+            .route("/event", get(event_socket))
+            .with_state(context);
+
+        tracing::info!("[relay] 账号 {} 的中继服务器监听于 {}", account_id, addr);
+        let task = tokio::spawn(async move {
+            let service = router.into_make_service_with_connect_info::<SocketAddr>();
+            if let Err(e) = axum::serve(listener, service).await {
+                tracing::error!("[relay] 中继服务器错误: {}", e);
+            }
+        });
+
+        Ok(Self {
+            addr,
+            events,
+            clients,
+            actions,
+            shutdown: Mutex::new(Some(shutdown)),
+            task: Mutex::new(Some(task)),
+        })
+    }
+
+    /// 断开所有下游客户端，停止监听并等待监听端口释放
+    ///
+    /// 转发事件时其他地方可能仍持有这个服务器，不能依赖 Drop 及时释放端口
+    pub async fn stop(&self) {
+        self.shutdown.lock().unwrap().take();
+        let task = self.task.lock().unwrap().take();
+        if let Some(task) = task {
+            task.abort();
+            let _ = task.await;
+        }
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// 把上游上报的原始事件转发给下游客户端（API 调用的响应等不带 post_type 的帧不转发）
+    pub fn publish(&self, frame: &str) {
+        if self.events.receiver_count() > 0 && crate::control::is_event_frame(frame) {
+            let _ = self.events.send(frame.into());
+        }
+    }
+
+    pub fn status(&self) -> RelayStatus {
+        RelayStatus {
+            addr: self.addr.to_string(),
+            clients: self.clients.lock().unwrap().clone(),
+        }
+    }
+
+    pub fn actions(&self) -> Vec<RelayAction> {
+        self.actions.lock().unwrap().iter().cloned().collect()
+    }
+}
+
+/// 监听地址是否只能从本机访问
+fn is_loopback_host(host: &str) -> bool {
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    host.eq_ignore_ascii_case("localhost") || host.parse::<IpAddr>().is_ok_and(|ip| ip.is_loopback())
+}
+
+impl Drop for RelayServer {
+    fn drop(&mut self) {
+        if let Some(task) = self.task.get_mut().ok().and_then(Option::take) {
+            task.abort();
+        }
+    }
+}
+
+impl RelayContext {
+    fn emit_clients(&self) {
+        let clients = self.clients.lock().unwrap().clone();
+        let _ = self.app.emit(
+            "relay-clients",
+            json!({ "account_id": self.account_id, "clients": clients }),
+        );
+    }
+
+    fn record(&self, entry: RelayAction) {
+        {
+            let mut actions = self.actions.lock().unwrap();
+            if actions.len() >= MAX_ACTIONS {
+                actions.pop_front();
+            }
+            actions.push_back(entry.clone());
+        }
+        let _ = self.app.emit("relay-action", entry);
+    }
+}
+
+/// 下游连接的类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Role {
+    Universal,
+    Api,
+    Event,
+}
+
+impl Role {
+    fn as_str(self) -> &'static str {
+        match self {
+            Role::Universal => "universal",
+            Role::Api => "api",
+            Role::Event => "event",
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct ConnectQuery {
+    name: Option<String>,
+}
+
+fn upgrade(
+    context: RelayContext,
+    ws: WebSocketUpgrade,
+    remote: SocketAddr,
+    query: ConnectQuery,
+    headers: HeaderMap,
+    uri: Uri,
+    role: Role,
+) -> Response {
+    if let Some(access_token) = &context.access_token {
+        if !reverse::is_authorized_parts(&headers, &uri, access_token) {
+            tracing::warn!("[relay] 拒绝来自 {} 的连接: access_token 无效", remote);
+            return (StatusCode::UNAUTHORIZED, "access_token 无效").into_response();
+        }
+    }
+
+    let id = context.next_client_id.fetch_add(1, Ordering::Relaxed);
+    let name = query
+        .name
+        .filter(|name| !name.is_empty())
+        .or_else(|| {
+            headers
+                .get("User-Agent")
+                .and_then(|v| v.to_str().ok())
+                .map(str::to_string)
+        })
+        .unwrap_or_else(|| format!("客户端 {}", id));
+    let client = RelayClient {
+        id,
+        name,
+        addr: remote.to_string(),
+        role: role.as_str().to_string(),
+        connected_at: chrono::Utc::now().timestamp(),
+    };
+    ws.on_upgrade(move |socket| serve_client(context, socket, client, role))
+}
+
+async fn universal_socket(
+    AxumState(context): AxumState<RelayContext>,
+    ws: WebSocketUpgrade,
+    ConnectInfo(remote): ConnectInfo<SocketAddr>,
+    Query(query): Query<ConnectQuery>,
+    headers: HeaderMap,
+    uri: Uri,
+) -> Response {
+    upgrade(context, ws, remote, query, headers, uri, Role::Universal)
+}
+
+async fn api_socket(
+    AxumState(context): AxumState<RelayContext>,
+    ws: WebSocketUpgrade,
+    ConnectInfo(remote): ConnectInfo<SocketAddr>,
+    Query(query): Query<ConnectQuery>,
+    headers: HeaderMap,
+    uri: Uri,
+) -> Response {
+    upgrade(context, ws, remote, query, headers, uri, Role::Api)
+}
+
+async fn event_socket(
+    AxumState(context): AxumState<RelayContext>,
+    ws: WebSocketUpgrade,
+    ConnectInfo(remote): ConnectInfo<SocketAddr>,
+    Query(query): Query<ConnectQuery>,
+    headers: HeaderMap,
+    uri: Uri,
+) -> Response {
+    upgrade(context, ws, remote, query, headers, uri, Role::Event)
+}
+
+async fn serve_client(context: RelayContext, socket: WebSocket, client: RelayClient, role: Role) {
+    tracing::info!("[relay] 下游客户端已连接: {} ({}, {})", client.name, client.addr, client.role);
+    context.clients.lock().unwrap().push(client.clone());
+    context.emit_clients();
+
+    let (mut sink, mut stream) = socket.split();
+    let mut events = context.events.subscribe();
+    let mut shutdown = context.shutdown.clone();
+    // API 调用并发执行，响应通过这里交给发送端
+    let (responses_tx, mut responses) = mpsc::unbounded_channel::<String>();
+
+    if role != Role::Api {
+        let lifecycle = json!({
+            "time": chrono::Utc::now().timestamp(),
+            "self_id": context.account_id,
+            "post_type": "meta_event",
+            "meta_event_type": "lifecycle",
+            "sub_type": "connect",
+        });
+        let _ = sink.send(WsMessage::Text(lifecycle.to_string().into())).await;
+    }
+
+    loop {
+        let outgoing: String = tokio::select! {
+            _ = shutdown.changed() => break,
+            event = events.recv(), if role != Role::Api => match event {
+                Ok(frame) => frame.to_string(),
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    tracing::warn!("[relay] {} 处理过慢，丢弃了 {} 个事件", client.name, skipped);
+                    continue;
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            },
+            Some(response) = responses.recv() => response,
+            message = stream.next() => match message {
+                Some(Ok(WsMessage::Text(text))) if role != Role::Event => {
+                    let context = context.clone();
+                    let client = client.clone();
+                    let responses_tx = responses_tx.clone();
+                    tokio::spawn(async move {
+                        let _ = responses_tx.send(forward(&context, &client, text.as_str()).await);
+                    });
+                    continue;
+                }
+                Some(Ok(WsMessage::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => continue,
+            },
+        };
+        if sink.send(WsMessage::Text(outgoing.into())).await.is_err() {
+            break;
+        }
+    }
+
+    context.clients.lock().unwrap().retain(|c| c.id != client.id);
+    context.emit_clients();
+    tracing::info!("[relay] 下游客户端已断开: {} ({})", client.name, client.addr);
+}
+
+/// 转发一次 API 调用，返回带下游 echo 的响应帧
+async fn forward(context: &RelayContext, client: &RelayClient, text: &str) -> String {
+    let started = Instant::now();
+    let request: Value = serde_json::from_str(text).unwrap_or(Value::Null);
+    let echo = request.get("echo").cloned().unwrap_or(Value::Null);
+    let action = request
+        .get("action")
+        .and_then(Value::as_str)
+        .unwrap_or_default()
+        .to_string();
+    let params = match request.get("params") {
+        Some(Value::Null) | None => json!({}),
+        Some(params) => params.clone(),
+    };
+
+    let result = async {
+        if action.is_empty() {
+            return Err("缺少 action".to_string());
+        }
+        let bot_ctx = runbot::ready_bot_ctx(&context.registry, context.account_id)?;
+        let response = bot_ctx
+            .websocket_send(&action, params.clone())
+            .await
+            .map_err(|e| format!("发送 {} 请求失败: {}", action, e))?
+            .response(ACTION_TIMEOUT)
+            .await
+            .map_err(|e| format!("等待 {} 响应失败: {}", action, e))?;
+        Ok(ActionResponse::from(response))
+    }
+    .await;
+    let response = result.unwrap_or_else(|e| ActionResponse {
+        status: "failed".to_string(),
+        retcode: 1503,
+        data: Value::Null,
+        message: e.clone(),
+        wording: e,
+    });
+
+    context.record(RelayAction {
+        account_id: context.account_id,
+        client_id: client.id,
+        client_name: client.name.clone(),
+        action,
+        params,
+        status: response.status.clone(),
+        retcode: response.retcode,
+        message: response.message.clone(),
+        elapsed_ms: started.elapsed().as_millis() as i64,
+        time: chrono::Utc::now().timestamp_millis(),
+    });
+
+    let mut frame = serde_json::to_value(&response).unwrap_or_default();
+    if let Some(object) = frame.as_object_mut() {
+        object.insert("echo".to_string(), echo);
+    }
+    frame.to_string()
+}
+
+/// 为账号启动中继服务器，返回实际监听的地址
+///
+/// 已在运行时会先停止旧的服务器（已连接的下游客户端随之断开）
+#[tauri::command]
+pub async fn start_relay_server(
+    account_id: i64,
+    host: String,
+    port: u16,
+    access_token: Option<String>,
+    app: AppHandle,
+    state: State<'_, Arc<Mutex<RunbotState>>>,
+) -> Result<String, String> {
+    // 空字符串视为不校验
+    let access_token = access_token.filter(|token| !token.is_empty());
+
+    let session = state
+        .lock()
+        .map_err(|e| format!("锁定状态失败: {}", e))?
+        .sessions
+        .get(account_id)
+        .ok_or_else(|| format!("账号 {} 未连接", account_id))?;
+    // 先停止旧的服务器，以便重新使用同一端口
+    let old = session.lock().map_err(|e| format!("锁定会话失败: {}", e))?.relay.take();
+    if let Some(old) = old {
+        old.stop().await;
+    }
+
+    let server = RelayServer::bind(app, state.inner().clone(), account_id, &host, port, access_token).await?;
+    let addr = server.local_addr().to_string();
+    session
+        .lock()
+        .map_err(|e| format!("锁定会话失败: {}", e))?
+        .relay = Some(Arc::new(server));
+
+    tracing::info!("[start_relay_server] 账号 {} 的中继服务器已启动: {}", account_id, addr);
+    Ok(addr)
+}
+
+/// 停止账号的中继服务器
+#[tauri::command]
+pub async fn stop_relay_server(
+    account_id: i64,
+    state: State<'_, Arc<Mutex<RunbotState>>>,
+) -> Result<(), String> {
+    let session = state
+        .lock()
+        .map_err(|e| format!("锁定状态失败: {}", e))?
+        .sessions
+        .get(account_id);
+    let Some(session) = session else {
+        return Ok(());
+    };
+
+    let server = session
+        .lock()
+        .map_err(|e| format!("锁定会话失败: {}", e))?
+        .relay
+        .take();
+    if let Some(server) = server {
+        server.stop().await;
+        tracing::info!("[stop_relay_server] 账号 {} 的中继服务器已停止", account_id);
+    }
+    Ok(())
+}
+
+fn relay_server(state: &Mutex<RunbotState>, account_id: i64) -> Result<Option<Arc<RelayServer>>, String> {
+    let session = state
+        .lock()
+        .map_err(|e| format!("锁定状态失败: {}", e))?
+        .sessions
+        .get(account_id);
+    let Some(session) = session else {
+        return Ok(None);
+    };
+    let relay = session
+        .lock()
+        .map_err(|e| format!("锁定会话失败: {}", e))?
+        .relay
+        .clone();
+    Ok(relay)
+}
+
+/// 获取中继服务器的地址和已连接的下游客户端（未启动时为空）
+#[tauri::command]
+pub async fn get_relay_server(
+    account_id: i64,
+    state: State<'_, Arc<Mutex<RunbotState>>>,
+) -> Result<Option<RelayStatus>, String> {
+    Ok(relay_server(&state, account_id)?.map(|server| server.status()))
+}
+
+/// 获取下游客户端最近的 API 调用记录（按时间顺序）
+#[tauri::command]
+pub async fn get_relay_actions(
+    account_id: i64,
+    state: State<'_, Arc<Mutex<RunbotState>>>,
+) -> Result<Vec<RelayAction>, String> {
+    Ok(relay_server(&state, account_id)?
+        .map(|server| server.actions())
+        .unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TestApp;
+
+    #[test]
+    fn detects_loopback_hosts() {
+        assert!(is_loopback_host("127.0.0.1"));
+        assert!(is_loopback_host("localhost"));
+        assert!(is_loopback_host("::1"));
+        assert!(is_loopback_host("[::1]"));
+        assert!(!is_loopback_host("0.0.0.0"));
+        assert!(!is_loopback_host("::"));
+        assert!(!is_loopback_host("192.168.1.2"));
+        assert!(!is_loopback_host("example.com"));
+    }
+
+    #[tokio::test]
+    async fn stop_releases_port_while_still_referenced() {
+        let app = TestApp::new();
+        let server = RelayServer::bind(app.handle().clone(), app.registry.clone(), 10001, "127.0.0.1", 0, None)
+            .await
+            .map(Arc::new)
+            .unwrap();
+        let port = server.local_addr().port();
+        let (mut socket, _) = tokio_tungstenite::connect_async(format!("ws://127.0.0.1:{}/event", port))
+            .await
+            .unwrap();
+        // 连接后先收到 lifecycle 事件
+        assert!(matches!(socket.next().await, Some(Ok(_))));
+
+        // 正在转发事件的地方仍持有服务器
+        let publisher = server.clone();
+        server.stop().await;
+        let rebound = RelayServer::bind(app.handle().clone(), app.registry.clone(), 10001, "127.0.0.1", port, None)
+            .await
+            .unwrap();
+        assert_eq!(rebound.local_addr().port(), port);
+
+        // 已连接的客户端被断开
+        let closed = async {
+            loop {
+                match socket.next().await {
+                    Some(Ok(message)) if !message.is_close() => continue,
+                    _ => return,
+                }
+            }
+        };
+        tokio::time::timeout(crate::test_support::TIMEOUT, closed).await.unwrap();
+        publisher.publish(r#"{"post_type":"message"}"#);
+    }
+}
@@ -0,0 +1,523 @@
+use std::sync::{Arc, Mutex};
+use rusqlite::{params, OptionalExtension};
+use serde::Serialize;
+use tauri::{Emitter, State};
+use crate::AppHandle;
+use crate::actions::{
+    self, ActionError, GroupIdParams, GroupInfo, SetFriendAddRequestParams, SetGroupAddRequestParams, StrangerInfo,
+    StrangerInfoParams,
+};
+use crate::review::{self, Decision};
+use crate::runbot::RunbotState;
+use crate::storage;
+
+/// 请求中用户、群的显示名称（查询失败的字段为空）
+#[derive(Debug, Default)]
+struct ResolvedNames {
+    user_name: Option<String>,
+    group_name: Option<String>,
+}
+
+impl ResolvedNames {
+    /// 所有需要的名称都已查到
+    fn complete(&self, group_id: Option<i64>) -> bool {
+        self.user_name.is_some() && (group_id.is_none() || self.group_name.is_some())
+    }
+}
+
+/// requests-updated 事件内容（请求列表有变化，前端需重新加载）
+#[derive(Debug, Clone, Serialize)]
+struct RequestsUpdated {
+    account_id: i64,
+}
+
+fn user_placeholder(user_id: i64) -> String {
+    format!("用户 {}", user_id)
+}
+
+fn group_placeholder(group_id: i64) -> String {
+    format!("群 {}", group_id)
+}
+
+/// 通过 get_stranger_info / get_group_info 查询名称
+///
+/// 群邀请请求中的 user_id 即邀请人，所以查到的用户名就是邀请人的昵称
+async fn resolve_names(
+    registry: &Mutex<RunbotState>,
+    account_id: i64,
+    user_id: i64,
+    group_id: Option<i64>,
+) -> ResolvedNames {
+    let mut names = ResolvedNames::default();
+
+    let params = StrangerInfoParams { user_id, no_cache: None };
+    match actions::call::<_, StrangerInfo>(registry, account_id, "get_stranger_info", &params).await {
+        Ok(info) if !info.nickname.is_empty() => names.user_name = Some(info.nickname),
+        Ok(_) => tracing::debug!("[resolve_names] 用户 {} 昵称为空", user_id),
+        Err(e) => tracing::warn!("[resolve_names] 查询用户 {} 失败: {}", user_id, e),
+    }
+
+    if let Some(group_id) = group_id {
+        let params = GroupIdParams { group_id, no_cache: None };
+        match actions::call::<_, GroupInfo>(registry, account_id, "get_group_info", &params).await {
+            Ok(info) if !info.group_name.is_empty() => names.group_name = Some(info.group_name),
+            Ok(_) => tracing::debug!("[resolve_names] 群 {} 名称为空", group_id),
+            Err(e) => tracing::warn!("[resolve_names] 查询群 {} 失败: {}", group_id, e),
+        }
+    }
+
+    names
+}
+
+/// 请求事件中需要保存和审核的字段
+struct RequestFields<'a> {
+    request_type: &'static str,
+    sub_type: Option<&'a str>,
+    time: i64,
+    user_id: i64,
+    group_id: Option<i64>,
+    comment: &'a str,
+    flag: &'a str,
+}
+
+impl<'a> RequestFields<'a> {
+    fn from_request(request: &'a runbot::event::Request) -> Option<Self> {
+        match request {
+            runbot::event::Request::Friend(req) => Some(Self {
+                request_type: "friend",
+                sub_type: None,
+                time: req.time,
+                user_id: req.user_id,
+                group_id: None,
+                comment: &req.comment,
+                flag: &req.flag,
+            }),
+            runbot::event::Request::Group(req) => Some(Self {
+                request_type: "group",
+                sub_type: Some(match &req.sub_type {
+                    runbot::event::GroupRequestSubType::Add => "add",
+                    runbot::event::GroupRequestSubType::Invite => "invite",
+                    runbot::event::GroupRequestSubType::Unknown(s) => s.as_str(),
+                }),
+                time: req.time,
+                user_id: req.user_id,
+                group_id: Some(req.group_id),
+                comment: &req.comment,
+                flag: &req.flag,
+            }),
+            runbot::event::Request::Unknown(_) => None,
+        }
+    }
+}
+
+/// 处理收到的好友/群请求：先保存，再按自动审核规则处理
+pub async fn on_request(
+    app: &AppHandle,
+    registry: &Mutex<RunbotState>,
+    account_id: i64,
+    request: &runbot::event::Request,
+) -> Result<(), String> {
+    let Some(fields) = RequestFields::from_request(request) else {
+        return Ok(());
+    };
+    store(app, registry, account_id, &fields).await?;
+    auto_review(app, registry, account_id, &fields).await
+}
+
+/// 保存收到的好友/群请求，保存前先查询真实的用户名和群名
+///
+/// 查询失败时使用占位名称，并标记为未解析，等下次连接成功后再补全
+async fn store(
+    app: &AppHandle,
+    registry: &Mutex<RunbotState>,
+    account_id: i64,
+    fields: &RequestFields<'_>,
+) -> Result<(), String> {
+    let RequestFields { request_type, sub_type, time, user_id, group_id, comment, flag } = *fields;
+
+    let names = resolve_names(registry, account_id, user_id, group_id).await;
+    let names_resolved = names.complete(group_id);
+    let user_name = names.user_name.unwrap_or_else(|| user_placeholder(user_id));
+    let group_name = group_id.map(|id| names.group_name.unwrap_or_else(|| group_placeholder(id)));
+
+    let request_data = serde_json::json!({
+        "id": format!("{}_{}_{}", request_type, flag, time),
+        "time": time,
+        "request_type": request_type,
+        "sub_type": sub_type,
+        "user_id": user_id,
+        "user_name": user_name,
+        "nickname": user_name,
+        "comment": comment,
+        "flag": flag,
+        "group_id": group_id,
+        "group_name": group_name,
+        "status": "pending",
+        "is_read": false,
+        "names_resolved": names_resolved,
+    });
+
+    tracing::info!(
+        "[request::store] 保存请求: flag={}, account_id={}, request_type={}, names_resolved={}",
+        flag,
+        account_id,
+        request_type,
+        names_resolved
+    );
+    storage::save_request(request_data.to_string(), Some(account_id), app.clone()).await?;
+    Ok(())
+}
+
+/// 按自动审核规则处理请求，并把命中的规则和处理结果记录到 requests 表
+async fn auto_review(
+    app: &AppHandle,
+    registry: &Mutex<RunbotState>,
+    account_id: i64,
+    fields: &RequestFields<'_>,
+) -> Result<(), String> {
+    let rules = review::load_rules(app, account_id).await?;
+    if rules.is_empty() {
+        return Ok(());
+    }
+    let candidate = review::Candidate {
+        request_type: fields.request_type,
+        sub_type: fields.sub_type,
+        group_id: fields.group_id,
+        user_id: fields.user_id,
+        comment: fields.comment,
+    };
+    let Some(rule) = review::evaluate(registry, account_id, rules, &candidate).await else {
+        return Ok(());
+    };
+
+    tracing::info!(
+        "[auto_review] 请求 {} 命中规则「{}」，处理方式: {}",
+        fields.flag,
+        rule.name,
+        rule.decision.as_str()
+    );
+    let handled_by = format!("rule:{}", rule.name);
+    let result = handle(app, registry, account_id, fields.flag, &rule.decision, &handled_by).await;
+    if let Err(e) = &result {
+        tracing::warn!("[auto_review] 自动处理请求 {} 失败: {}", fields.flag, e);
+    }
+
+    let conn = storage::get_connection(app, Some(account_id))?;
+    conn.execute(
+        "UPDATE requests SET review_rule = ?1, review_action = ?2, review_error = ?3 WHERE flag = ?4",
+        params![rule.name, rule.decision.as_str(), result.err(), fields.flag],
+    )
+    .map_err(|e| format!("记录审核结果失败: {}", e))?;
+    Ok(())
+}
+
+/// 名称尚未查询到的请求：(flag, user_id, group_id)
+fn unresolved_requests(app: &AppHandle, account_id: i64) -> Result<Vec<(String, i64, Option<i64>)>, String> {
+    let conn = storage::get_connection(app, Some(account_id))?;
+    let mut stmt = conn
+        .prepare("SELECT flag, user_id, group_id FROM requests WHERE names_resolved = 0")
+        .map_err(|e| format!("准备查询失败: {}", e))?;
+    let rows = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+        .map_err(|e| format!("查询请求失败: {}", e))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("读取请求失败: {}", e))?;
+    Ok(rows)
+}
+
+/// 补全之前查询失败、仍使用占位名称的请求
+pub async fn backfill_names(app: &AppHandle, registry: &Mutex<RunbotState>, account_id: i64) -> Result<usize, String> {
+    let pending = unresolved_requests(app, account_id)?;
+
+    let mut updated = 0;
+    for (flag, user_id, group_id) in pending {
+        let names = resolve_names(registry, account_id, user_id, group_id).await;
+        if names.user_name.is_none() && names.group_name.is_none() {
+            continue;
+        }
+        let names_resolved = names.complete(group_id);
+        let conn = storage::get_connection(app, Some(account_id))?;
+        conn.execute(
+            "UPDATE requests SET
+                user_name = COALESCE(?1, user_name),
+                nickname = COALESCE(?1, nickname),
+                group_name = COALESCE(?2, group_name),
+                names_resolved = ?3
+             WHERE flag = ?4",
+            params![names.user_name, names.group_name, names_resolved as i64, flag],
+        )
+        .map_err(|e| format!("更新请求名称失败: {}", e))?;
+        updated += 1;
+    }
+
+    Ok(updated)
+}
+
+/// 在后台标记已过期的请求并补全请求名称，有更新时通知前端重新加载请求列表
+pub fn spawn_backfill(app: AppHandle, registry: Arc<Mutex<RunbotState>>, account_id: i64) {
+    tokio::spawn(async move {
+        let expired = match storage::get_connection(&app, Some(account_id))
+            .and_then(|conn| storage::expire_stale_requests(&conn))
+        {
+            Ok(expired) => expired,
+            Err(e) => {
+                tracing::warn!("[spawn_backfill] 标记过期请求失败: {}", e);
+                0
+            }
+        };
+        if expired > 0 {
+            tracing::info!("[spawn_backfill] 已将 {} 条请求标记为过期", expired);
+        }
+
+        let updated = match backfill_names(&app, &registry, account_id).await {
+            Ok(updated) => updated,
+            Err(e) => {
+                tracing::warn!("[spawn_backfill] 补全请求名称失败: {}", e);
+                0
+            }
+        };
+        if updated > 0 {
+            tracing::info!("[spawn_backfill] 已补全 {} 条请求的名称", updated);
+        }
+
+        if expired > 0 || updated > 0 {
+            app.emit("requests-updated", RequestsUpdated { account_id })
+                .unwrap_or_default();
+        }
+    });
+}
+
+/// 批量处理请求时单个请求的结果
+#[derive(Debug, Clone, Serialize)]
+pub struct RequestResult {
+    pub flag: String,
+    pub ok: bool,
+    pub error: Option<String>,
+}
+
+/// 同意或拒绝一个请求：调用 OneBot API 成功后才更新数据库中的状态
+///
+/// handled_by 记录处理人（manual 或 rule:规则名）；Decision::Pending 不做任何处理
+async fn handle(
+    app: &AppHandle,
+    registry: &Mutex<RunbotState>,
+    account_id: i64,
+    flag: &str,
+    decision: &Decision,
+    handled_by: &str,
+) -> Result<(), String> {
+    let (approve, note) = match decision {
+        Decision::Approve { remark } => (true, remark.clone()),
+        Decision::Reject { reason } => (false, reason.clone()),
+        Decision::Pending => return Ok(()),
+    };
+
+    let conn = storage::get_connection(app, Some(account_id))?;
+    let (request_type, sub_type, status) = conn
+        .query_row(
+            "SELECT request_type, sub_type, status FROM requests WHERE flag = ?1",
+            params![flag],
+            |row| Ok((row.get::<_, String>(0)?, row.get::<_, Option<String>>(1)?, row.get::<_, String>(2)?)),
+        )
+        .optional()
+        .map_err(|e| format!("查询请求失败: {}", e))?
+        .ok_or_else(|| format!("请求不存在: {}", flag))?;
+    if status != "pending" {
+        return Err(format!("请求已处理: {}", status));
+    }
+
+    // remark 为好友备注（仅同意好友请求时有效），reason 为拒绝理由（仅拒绝群请求时有效）
+    let result = if request_type == "friend" {
+        let params = SetFriendAddRequestParams {
+            flag: flag.to_string(),
+            approve: Some(approve),
+            remark: note.clone().filter(|_| approve),
+        };
+        actions::call_ok(registry, account_id, "set_friend_add_request", &params).await
+    } else {
+        let params = SetGroupAddRequestParams {
+            flag: flag.to_string(),
+            sub_type: sub_type.unwrap_or_else(|| "add".to_string()),
+            approve: Some(approve),
+            reason: note.clone().filter(|_| !approve),
+        };
+        actions::call_ok(registry, account_id, "set_group_add_request", &params).await
+    };
+    if let Err(e) = result {
+        // flag 已失效（请求过期或已在其它设备处理）时服务器上的请求已不存在
+        if is_stale_flag(&e) {
+            storage::transition_request(&conn, flag, Some("pending"), "expired", Some("server"), Some(&e.to_string()))?;
+        }
+        return Err(e.to_string());
+    }
+
+    // 只更新仍为 pending 的请求，避免覆盖并发处理的结果
+    let status = if approve { "approved" } else { "rejected" };
+    storage::transition_request(&conn, flag, Some("pending"), status, Some(handled_by), note.as_deref())?;
+
+    tracing::info!("[request::handle] 请求 {} 已{}", flag, if approve { "同意" } else { "拒绝" });
+    Ok(())
+}
+
+/// OneBot 实现因为 flag 无效而拒绝处理请求
+///
+/// 各实现没有统一的 retcode，只认已知实现的错误信息（go-cqhttp 的 FLAG_NOT_FOUND 和 FLAG_HAS_BEEN_CHECKED），
+/// 其它失败都可能是暂时的，请求保持待处理
+fn is_stale_flag(error: &ActionError) -> bool {
+    let ActionError::Failed { message, .. } = error else {
+        return false;
+    };
+    STALE_FLAG_MESSAGES.contains(&message.trim())
+}
+
+const STALE_FLAG_MESSAGES: &[&str] = &["FLAG_NOT_FOUND", "FLAG_HAS_BEEN_CHECKED"];
+
+/// 批量处理请求（逐个处理，单个失败不影响其它请求）
+async fn handle_batch(
+    app: &AppHandle,
+    registry: &Mutex<RunbotState>,
+    account_id: i64,
+    flags: Vec<String>,
+    decision: Decision,
+) -> Vec<RequestResult> {
+    let mut results = Vec::with_capacity(flags.len());
+    for flag in flags {
+        let result = handle(app, registry, account_id, &flag, &decision, "manual").await;
+        results.push(RequestResult {
+            flag,
+            ok: result.is_ok(),
+            error: result.err(),
+        });
+    }
+    app.emit("requests-updated", RequestsUpdated { account_id })
+        .unwrap_or_default();
+    results
+}
+
+/// 同意请求
+#[tauri::command]
+pub async fn approve_request(
+    account_id: i64,
+    flag: String,
+    remark: Option<String>,
+    app: AppHandle,
+    state: State<'_, Arc<Mutex<RunbotState>>>,
+) -> Result<(), String> {
+    handle(&app, &state, account_id, &flag, &Decision::Approve { remark }, "manual")
+        .await
+        .map_err(|e| format!("同意请求失败: {}", e))
+}
+
+/// 拒绝请求
+#[tauri::command]
+pub async fn reject_request(
+    account_id: i64,
+    flag: String,
+    reason: Option<String>,
+    app: AppHandle,
+    state: State<'_, Arc<Mutex<RunbotState>>>,
+) -> Result<(), String> {
+    handle(&app, &state, account_id, &flag, &Decision::Reject { reason }, "manual")
+        .await
+        .map_err(|e| format!("拒绝请求失败: {}", e))
+}
+
+/// 批量同意请求
+#[tauri::command]
+pub async fn approve_requests(
+    account_id: i64,
+    flags: Vec<String>,
+    remark: Option<String>,
+    app: AppHandle,
+    state: State<'_, Arc<Mutex<RunbotState>>>,
+) -> Result<Vec<RequestResult>, String> {
+    Ok(handle_batch(&app, &state, account_id, flags, Decision::Approve { remark }).await)
+}
+
+/// 批量拒绝请求
+#[tauri::command]
+pub async fn reject_requests(
+    account_id: i64,
+    flags: Vec<String>,
+    reason: Option<String>,
+    app: AppHandle,
+    state: State<'_, Arc<Mutex<RunbotState>>>,
+) -> Result<Vec<RequestResult>, String> {
+    Ok(handle_batch(&app, &state, account_id, flags, Decision::Reject { reason }).await)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_onebot::{MockConfig, MockServer};
+    use crate::test_support::{self, TestApp};
+
+    fn failed(message: &str, wording: &str) -> ActionError {
+        ActionError::Failed { retcode: 100, message: message.to_string(), wording: wording.to_string() }
+    }
+
+    #[test]
+    fn stale_flag_is_detected_from_failure_reason() {
+        assert!(is_stale_flag(&failed("FLAG_NOT_FOUND", "FLAG不存在")));
+        assert!(is_stale_flag(&failed("FLAG_HAS_BEEN_CHECKED", "消息已被处理")));
+        assert!(!is_stale_flag(&failed("权限不足", "")));
+        assert!(!is_stale_flag(&ActionError::Request { message: "FLAG_NOT_FOUND".to_string() }));
+    }
+
+    #[test]
+    fn generic_failure_is_not_stale_flag() {
+        // 只是提到 flag、invalid 或 not found 的失败不一定是请求失效
+        for (message, wording) in [
+            ("Invalid parameter", ""),
+            ("flag无效", ""),
+            ("", "请求已失效"),
+            ("user not found", ""),
+            ("群不存在", ""),
+        ] {
+            assert!(!is_stale_flag(&failed(message, wording)), "{} {}", message, wording);
+        }
+    }
+
+    async fn wait_for_request(app: &TestApp, account_id: i64, resolved: bool) -> serde_json::Value {
+        let wait = async {
+            loop {
+                let requests = storage::get_requests(None, None, None, None, Some(account_id), app.handle().clone())
+                    .await
+                    .unwrap();
+                let request = requests
+                    .first()
+                    .map(|request| serde_json::from_str::<serde_json::Value>(request).unwrap());
+                if let Some(request) = request.filter(|request| (request["user_name"] == "新朋友") == resolved) {
+                    return request;
+                }
+                tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+            }
+        };
+        tokio::time::timeout(test_support::TIMEOUT, wait).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn backfills_names_after_reconnect() {
+        let server = MockServer::bind("127.0.0.1", 0, MockConfig::default()).await.unwrap();
+        let app = TestApp::new();
+        let session = app.connect(&server).await;
+        let account_id = server.self_id();
+
+        // 查不到昵称时先用占位名称保存
+        server.push_friend_request(50005, "你好", "flag-1");
+        let request = wait_for_request(&app, account_id, false).await;
+        assert_eq!(request["flag"], "flag-1");
+        assert_eq!(request["status"], "pending");
+
+        server.update_fixtures(|fixtures| {
+            fixtures.friends.push(serde_json::json!({ "user_id": 50005, "nickname": "新朋友", "remark": "" }));
+        });
+        server.disconnect_all();
+        test_support::wait_connection(&session, false).await;
+
+        let request = wait_for_request(&app, account_id, true).await;
+        assert_eq!(request["flag"], "flag-1");
+
+        test_support::shutdown(&session).await;
+    }
+}
@@ -0,0 +1,312 @@
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use sha2::{Digest, Sha256};
+use tauri::State;
+use crate::AppHandle;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio_tungstenite::tungstenite::handshake::server::{ErrorResponse, Request, Response};
+use tokio_tungstenite::tungstenite::http::{HeaderMap, StatusCode, Uri};
+use tokio_tungstenite::MaybeTlsStream;
+use crate::runbot::{self, RunbotState};
+use crate::supervisor::Upstream;
+
+/// 反向 WebSocket 服务器
+///
+/// OneBot 实现（NapCat、Lagrange、LLOneBot 等）以 Universal 角色连接进来后，
+/// 每个账号对应一个会话，事件同样经过 `TauriEventProcessor` 处理。
+/// 同一账号断线重连时，新的连接会交给原来的会话继续使用。
+#[derive(Debug)]
+pub struct ReverseServer {
+    addr: SocketAddr,
+    task: JoinHandle<()>,
+}
+
+impl ReverseServer {
+    /// 在指定地址上监听
+    pub async fn bind(
+        app: AppHandle,
+        state: Arc<Mutex<RunbotState>>,
+        host: &str,
+        port: u16,
+        access_token: Option<String>,
+    ) -> Result<Self, String> {
+        let listener = TcpListener::bind((host, port))
+            .await
+            .map_err(|e| format!("监听 {}:{} 失败: {}", host, port, e))?;
+        let addr = listener
+            .local_addr()
+            .map_err(|e| format!("获取监听地址失败: {}", e))?;
+
+        tracing::info!("[reverse] 反向 WebSocket 服务器监听于 {}", addr);
+
+        let task = tokio::spawn(async move {
+            loop {
+                let (stream, peer) = match listener.accept().await {
+                    Ok(accepted) => accepted,
+                    Err(e) => {
+                        tracing::warn!("[reverse] 接受连接失败: {}", e);
+                        continue;
+                    }
+                };
+                tokio::spawn(handle_connection(
+                    app.clone(),
+                    state.clone(),
+                    stream,
+                    peer,
+                    access_token.clone(),
+                ));
+            }
+        });
+
+        Ok(Self { addr, task })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// 停止监听并等待监听端口释放
+    pub async fn stop(mut self) {
+        self.task.abort();
+        let _ = (&mut self.task).await;
+    }
+}
+
+impl Drop for ReverseServer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// 握手时从请求头中得到的信息
+#[derive(Debug, Default)]
+struct Handshake {
+    self_id: Option<i64>,
+}
+
+/// 校验访问令牌：支持 `Authorization: Bearer <token>`（以及旧版的 `Token <token>`）和 `access_token` 查询参数
+pub(crate) fn is_authorized(request: &Request, access_token: &str) -> bool {
+    is_authorized_parts(request.headers(), request.uri(), access_token)
+}
+
+/// 同 [`is_authorized`]，直接使用请求头和 URI（供本地控制接口使用）
+pub(crate) fn is_authorized_parts(headers: &HeaderMap, uri: &Uri, access_token: &str) -> bool {
+    if let Some(value) = headers.get("Authorization").and_then(|v| v.to_str().ok()) {
+        let token = value
+            .strip_prefix("Bearer ")
+            .or_else(|| value.strip_prefix("Token "))
+            .map(str::trim);
+        return token.is_some_and(|token| token_eq(token, access_token));
+    }
+
+    uri
+        .query()
+        .map(|query| {
+            query.split('&').any(|pair| {
+                pair.strip_prefix("access_token=")
+                    .map(|token| urlencoding::decode(token).map(|t| token_eq(&t, access_token)).unwrap_or(false))
+                    .unwrap_or(false)
+            })
+        })
+        .unwrap_or(false)
+}
+
+/// 以固定时间比较令牌：先取哈希再逐字节比较，耗时与令牌内容和长度无关
+fn token_eq(token: &str, access_token: &str) -> bool {
+    let token = Sha256::digest(token.as_bytes());
+    let access_token = Sha256::digest(access_token.as_bytes());
+    token
+        .iter()
+        .zip(access_token.iter())
+        .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+        == 0
+}
+
+fn reject(status: StatusCode, reason: &str) -> ErrorResponse {
+    let mut response = ErrorResponse::new(Some(reason.to_string()));
+    *response.status_mut() = status;
+    response
+}
+
+async fn handle_connection(
+    app: AppHandle,
+    state: Arc<Mutex<RunbotState>>,
+    stream: TcpStream,
+    peer: SocketAddr,
+    access_token: Option<String>,
+) {
+    let mut handshake = Handshake::default();
+    // 回调签名由 tungstenite 规定
+    #[allow(clippy::result_large_err)]
+    let callback = |request: &Request, response: Response| -> Result<Response, ErrorResponse> {
+        if let Some(access_token) = &access_token {
+            if !is_authorized(request, access_token) {
+                tracing::warn!("[reverse] 拒绝连接 ({}): access_token 无效", peer);
+                return Err(reject(StatusCode::UNAUTHORIZED, "Unauthorized"));
+            }
+        }
+
+        // 只有 Universal 连接可以同时收事件和调用 API
+        let role = request.headers().get("X-Client-Role").and_then(|v| v.to_str().ok());
+        if let Some(role) = role {
+            if !role.eq_ignore_ascii_case("Universal") {
+                tracing::warn!("[reverse] 拒绝连接 ({}): 不支持的 X-Client-Role: {}", peer, role);
+                return Err(reject(StatusCode::BAD_REQUEST, "Only Universal client role is supported"));
+            }
+        }
+
+        handshake.self_id = request
+            .headers()
+            .get("X-Self-ID")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.trim().parse::<i64>().ok());
+        Ok(response)
+    };
+
+    let ws_stream = match tokio_tungstenite::accept_hdr_async(MaybeTlsStream::Plain(stream), callback).await {
+        Ok(ws_stream) => ws_stream,
+        Err(e) => {
+            tracing::warn!("[reverse] 握手失败 ({}): {}", peer, e);
+            return;
+        }
+    };
+    tracing::info!("[reverse] OneBot 实现已接入: {} (X-Self-ID: {:?})", peer, handshake.self_id);
+
+    // 已有该账号的反向会话时，把连接交给它
+    let mut ws_stream = ws_stream;
+    if let Some(self_id) = handshake.self_id {
+        let incoming = state
+            .lock()
+            .ok()
+            .and_then(|state_guard| state_guard.sessions.get(self_id))
+            .and_then(|session| session.lock().ok().and_then(|s| s.incoming.clone()));
+        if let Some(incoming) = incoming {
+            match incoming.send(ws_stream) {
+                Ok(()) => {
+                    tracing::info!("[reverse] 账号 {} 重新接入", self_id);
+                    return;
+                }
+                Err(e) => ws_stream = e.0,
+            }
+        }
+    }
+
+    // 否则新建会话
+    let (incoming_tx, incoming_rx) = mpsc::unbounded_channel();
+    let _ = incoming_tx.send(ws_stream);
+    let upstream = Upstream::Accept {
+        incoming: tokio::sync::Mutex::new(incoming_rx),
+    };
+    let (session, ready) = match runbot::start_session(&app, &state, upstream).await {
+        Ok(started) => started,
+        Err(e) => {
+            tracing::error!("[reverse] 创建会话失败 ({}): {}", peer, e);
+            return;
+        }
+    };
+    if let Ok(mut session_guard) = session.lock() {
+        session_guard.incoming = Some(incoming_tx);
+    }
+
+    match ready.await {
+        Ok(Ok(self_id)) => tracing::info!("[reverse] 账号 {} 已连接 ({})", self_id, peer),
+        Ok(Err(e)) => tracing::warn!("[reverse] 会话认证失败 ({}): {}", peer, e),
+        Err(_) => {}
+    }
+}
+
+/// 启动反向 WebSocket 服务器，返回实际监听的地址
+///
+/// 已在运行时会先停止旧的服务器（已接入的会话不受影响）
+#[tauri::command]
+pub async fn start_reverse_server(
+    host: String,
+    port: u16,
+    access_token: Option<String>,
+    app: AppHandle,
+    state: State<'_, Arc<Mutex<RunbotState>>>,
+) -> Result<String, String> {
+    // 空字符串视为不校验
+    let access_token = access_token.filter(|token| !token.is_empty());
+
+    let old_server = state
+        .lock()
+        .map_err(|e| format!("锁定状态失败: {}", e))?
+        .reverse_server
+        .take();
+    if let Some(old_server) = old_server {
+        old_server.stop().await;
+    }
+
+    let server = ReverseServer::bind(app, state.inner().clone(), &host, port, access_token).await?;
+    let addr = server.local_addr().to_string();
+
+    state
+        .lock()
+        .map_err(|e| format!("锁定状态失败: {}", e))?
+        .reverse_server = Some(server);
+
+    tracing::info!("[start_reverse_server] 反向 WebSocket 服务器已启动: {}", addr);
+    Ok(addr)
+}
+
+/// 停止反向 WebSocket 服务器
+///
+/// 已接入的连接会继续使用，断开后不再等待重新接入
+#[tauri::command]
+pub async fn stop_reverse_server(
+    state: State<'_, Arc<Mutex<RunbotState>>>,
+) -> Result<(), String> {
+    let server = {
+        let mut state_guard = state.lock().map_err(|e| format!("锁定状态失败: {}", e))?;
+        let server = state_guard.reverse_server.take();
+        if server.is_some() {
+            state_guard.sessions.close_incoming();
+        }
+        server
+    };
+    if let Some(server) = server {
+        server.stop().await;
+        tracing::info!("[stop_reverse_server] 反向 WebSocket 服务器已停止");
+    }
+    Ok(())
+}
+
+/// 获取反向 WebSocket 服务器的监听地址（未启动时为空）
+#[tauri::command]
+pub async fn get_reverse_server_addr(
+    state: State<'_, Arc<Mutex<RunbotState>>>,
+) -> Result<Option<String>, String> {
+    let state_guard = state.lock().map_err(|e| format!("锁定状态失败: {}", e))?;
+    Ok(state_guard
+        .reverse_server
+        .as_ref()
+        .map(|server| server.local_addr().to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn authorized(header: Option<&str>, uri: &str) -> bool {
+        let mut headers = HeaderMap::new();
+        if let Some(header) = header {
+            headers.insert("Authorization", header.parse().unwrap());
+        }
+        is_authorized_parts(&headers, &uri.parse().unwrap(), "s3cret")
+    }
+
+    #[test]
+    fn checks_access_token() {
+        assert!(authorized(Some("Bearer s3cret"), "/"));
+        assert!(authorized(Some("Token s3cret"), "/"));
+        assert!(authorized(None, "/?name=bot&access_token=s3cret"));
+        assert!(!authorized(Some("Bearer s3cre"), "/"));
+        assert!(!authorized(Some("Bearer s3cret2"), "/"));
+        assert!(!authorized(Some("s3cret"), "/?access_token=s3cret"));
+        assert!(!authorized(None, "/?access_token=other"));
+        assert!(!authorized(None, "/"));
+    }
+}
@@ -0,0 +1,409 @@
+use std::sync::Mutex;
+use regex::RegexBuilder;
+use serde::{Deserialize, Serialize};
+use crate::AppHandle;
+use crate::actions::{self, StrangerInfo, StrangerInfoParams};
+use crate::runbot::RunbotState;
+use crate::storage;
+
+/// 自动审核规则在配置存储中的 key（按账号保存）
+const RULES_CONFIG_KEY: &str = "request_review_rules";
+
+/// 请求自动审核规则
+///
+/// 所有已设置的条件都满足时规则命中；规则按列表顺序匹配，第一条命中的规则生效
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReviewRule {
+    pub name: String,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    /// friend 或 group，为空时匹配所有请求
+    #[serde(default)]
+    pub request_type: Option<String>,
+    /// 群请求的 add 或 invite，为空时匹配所有
+    #[serde(default)]
+    pub sub_type: Option<String>,
+    /// 限定的群号，为空时匹配所有群
+    #[serde(default)]
+    pub group_ids: Vec<i64>,
+    #[serde(default)]
+    pub comment: Option<CommentMatch>,
+    #[serde(default)]
+    pub min_age: Option<i32>,
+    #[serde(default)]
+    pub max_age: Option<i32>,
+    #[serde(default)]
+    pub min_level: Option<i64>,
+    pub decision: Decision,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+/// 验证消息的匹配方式
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum CommentMatch {
+    /// 完整匹配（忽略首尾空白）
+    Exact {
+        text: String,
+        #[serde(default)]
+        ignore_case: bool,
+    },
+    /// 正则匹配
+    Regex {
+        pattern: String,
+        #[serde(default)]
+        ignore_case: bool,
+    },
+    /// 入群问题的答案在列表中（验证消息为「问题：…\n答案：…」时只比较答案部分）
+    Answers {
+        answers: Vec<String>,
+        #[serde(default)]
+        ignore_case: bool,
+    },
+}
+
+/// 规则命中后的处理方式
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum Decision {
+    Approve {
+        /// 好友备注（仅好友请求有效）
+        #[serde(default)]
+        remark: Option<String>,
+    },
+    Reject {
+        /// 拒绝理由（仅群请求有效）
+        #[serde(default)]
+        reason: Option<String>,
+    },
+    /// 保持待处理，交给人工审核
+    Pending,
+}
+
+impl Decision {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Decision::Approve { .. } => "approve",
+            Decision::Reject { .. } => "reject",
+            Decision::Pending => "pending",
+        }
+    }
+}
+
+/// 参与规则匹配的请求信息
+pub struct Candidate<'a> {
+    pub request_type: &'a str,
+    pub sub_type: Option<&'a str>,
+    pub group_id: Option<i64>,
+    pub user_id: i64,
+    pub comment: &'a str,
+}
+
+impl CommentMatch {
+    fn matches(&self, comment: &str) -> bool {
+        match self {
+            CommentMatch::Exact { text, ignore_case } => eq(comment.trim(), text.trim(), *ignore_case),
+            CommentMatch::Regex { pattern, ignore_case } => RegexBuilder::new(pattern)
+                .case_insensitive(*ignore_case)
+                .build()
+                .map(|re| re.is_match(comment))
+                .unwrap_or(false),
+            CommentMatch::Answers { answers, ignore_case } => {
+                let answer = comment
+                    .rsplit_once("答案：")
+                    .or_else(|| comment.rsplit_once("答案:"))
+                    .map(|(_, answer)| answer)
+                    .unwrap_or(comment)
+                    .trim();
+                answers.iter().any(|a| eq(answer, a.trim(), *ignore_case))
+            }
+        }
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if let CommentMatch::Regex { pattern, .. } = self {
+            RegexBuilder::new(pattern)
+                .build()
+                .map_err(|e| format!("正则表达式无效: {}", e))?;
+        }
+        Ok(())
+    }
+}
+
+fn eq(a: &str, b: &str, ignore_case: bool) -> bool {
+    if ignore_case {
+        a.to_lowercase() == b.to_lowercase()
+    } else {
+        a == b
+    }
+}
+
+impl ReviewRule {
+    /// 是否需要通过 get_stranger_info 获取年龄、等级
+    fn needs_stranger_info(&self) -> bool {
+        self.min_age.is_some() || self.max_age.is_some() || self.min_level.is_some()
+    }
+
+    /// 不依赖陌生人信息的条件
+    fn matches_request(&self, candidate: &Candidate) -> bool {
+        if let Some(request_type) = &self.request_type {
+            if request_type != candidate.request_type {
+                return false;
+            }
+        }
+        if let Some(sub_type) = &self.sub_type {
+            if Some(sub_type.as_str()) != candidate.sub_type {
+                return false;
+            }
+        }
+        if !self.group_ids.is_empty()
+            && !candidate.group_id.is_some_and(|id| self.group_ids.contains(&id))
+        {
+            return false;
+        }
+        if let Some(comment) = &self.comment {
+            if !comment.matches(candidate.comment) {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// 年龄、等级条件（获取不到对应信息时视为不满足）
+    fn matches_stranger(&self, stranger: Option<&StrangerInfo>) -> bool {
+        if !self.needs_stranger_info() {
+            return true;
+        }
+        let Some(stranger) = stranger else {
+            return false;
+        };
+        let age_ok = match (self.min_age, self.max_age, stranger.age) {
+            (None, None, _) => true,
+            (_, _, None) => false,
+            (min, max, Some(age)) => min.is_none_or(|min| age >= min) && max.is_none_or(|max| age <= max),
+        };
+        let level_ok = match (self.min_level, stranger.level) {
+            (None, _) => true,
+            (Some(_), None) => false,
+            (Some(min), Some(level)) => level >= min,
+        };
+        age_ok && level_ok
+    }
+}
+
+/// 读取账号的自动审核规则
+pub async fn load_rules(app: &AppHandle, account_id: i64) -> Result<Vec<ReviewRule>, String> {
+    match storage::load_config(RULES_CONFIG_KEY.to_string(), Some(account_id), app.clone()).await? {
+        Some(content) => serde_json::from_str(&content).map_err(|e| format!("解析审核规则失败: {}", e)),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// 按顺序匹配规则，返回第一条命中的规则
+///
+/// 只有存在需要年龄、等级的候选规则时才会调用 get_stranger_info
+pub async fn evaluate(
+    registry: &Mutex<RunbotState>,
+    account_id: i64,
+    rules: Vec<ReviewRule>,
+    candidate: &Candidate<'_>,
+) -> Option<ReviewRule> {
+    let candidates = request_candidates(rules, candidate);
+
+    let stranger = if candidates.iter().any(ReviewRule::needs_stranger_info) {
+        let params = StrangerInfoParams {
+            user_id: candidate.user_id,
+            no_cache: Some(true),
+        };
+        match actions::call::<_, StrangerInfo>(registry, account_id, "get_stranger_info", &params).await {
+            Ok(info) => Some(info),
+            Err(e) => {
+                tracing::warn!("[review::evaluate] 获取用户 {} 信息失败: {}", candidate.user_id, e);
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    first_match(candidates, stranger.as_ref())
+}
+
+/// 已启用且满足请求条件（不含年龄、等级）的规则，保持原顺序
+fn request_candidates(rules: Vec<ReviewRule>, candidate: &Candidate) -> Vec<ReviewRule> {
+    rules
+        .into_iter()
+        .filter(|rule| rule.enabled && rule.matches_request(candidate))
+        .collect()
+}
+
+/// 候选规则中第一条满足年龄、等级条件的规则（列表顺序即优先级）
+fn first_match(candidates: Vec<ReviewRule>, stranger: Option<&StrangerInfo>) -> Option<ReviewRule> {
+    candidates
+        .into_iter()
+        .find(|rule| rule.matches_stranger(stranger))
+}
+
+/// 获取自动审核规则
+#[tauri::command]
+pub async fn get_review_rules(account_id: i64, app: AppHandle) -> Result<Vec<ReviewRule>, String> {
+    load_rules(&app, account_id).await
+}
+
+/// 保存自动审核规则（整体替换，保存前校验正则表达式）
+#[tauri::command]
+pub async fn set_review_rules(
+    account_id: i64,
+    rules: Vec<ReviewRule>,
+    app: AppHandle,
+) -> Result<(), String> {
+    for rule in &rules {
+        if let Some(comment) = &rule.comment {
+            comment
+                .validate()
+                .map_err(|e| format!("规则「{}」: {}", rule.name, e))?;
+        }
+    }
+    let content = serde_json::to_string(&rules).map_err(|e| format!("序列化审核规则失败: {}", e))?;
+    storage::save_config(RULES_CONFIG_KEY.to_string(), content, Some(account_id), app).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(name: &str, decision: Decision) -> ReviewRule {
+        ReviewRule {
+            name: name.to_string(),
+            enabled: true,
+            request_type: None,
+            sub_type: None,
+            group_ids: Vec::new(),
+            comment: None,
+            min_age: None,
+            max_age: None,
+            min_level: None,
+            decision,
+        }
+    }
+
+    fn candidate(comment: &str) -> Candidate<'_> {
+        Candidate {
+            request_type: "group",
+            sub_type: Some("add"),
+            group_id: Some(100),
+            user_id: 10001,
+            comment,
+        }
+    }
+
+    fn stranger(age: Option<i32>, level: Option<i64>) -> StrangerInfo {
+        StrangerInfo {
+            user_id: 10001,
+            nickname: String::new(),
+            sex: None,
+            age,
+            level,
+        }
+    }
+
+    #[test]
+    fn exact_comment_ignores_whitespace_and_optionally_case() {
+        let exact = CommentMatch::Exact { text: "Hello".to_string(), ignore_case: false };
+        assert!(exact.matches("  Hello\n"));
+        assert!(!exact.matches("hello"));
+        let exact = CommentMatch::Exact { text: "Hello".to_string(), ignore_case: true };
+        assert!(exact.matches("HELLO"));
+        assert!(!exact.matches("Hello there"));
+    }
+
+    #[test]
+    fn regex_comment_matches_and_invalid_pattern_never_matches() {
+        let regex = CommentMatch::Regex { pattern: r"^\d{6}$".to_string(), ignore_case: false };
+        assert!(regex.matches("123456"));
+        assert!(!regex.matches("12345a"));
+        let regex = CommentMatch::Regex { pattern: "rust".to_string(), ignore_case: true };
+        assert!(regex.matches("I like RUST"));
+
+        let invalid = CommentMatch::Regex { pattern: "(".to_string(), ignore_case: false };
+        assert!(!invalid.matches("("));
+        assert!(invalid.validate().is_err());
+    }
+
+    #[test]
+    fn answers_compare_only_the_answer_part() {
+        let answers = CommentMatch::Answers {
+            answers: vec!["Rust".to_string(), "铁锈".to_string()],
+            ignore_case: true,
+        };
+        assert!(answers.matches("问题：你喜欢什么语言？\n答案：rust"));
+        assert!(answers.matches("问题:?\n答案: 铁锈 "));
+        assert!(answers.matches("Rust"));
+        assert!(!answers.matches("问题：Rust？\n答案：Go"));
+    }
+
+    #[test]
+    fn request_conditions_must_all_match() {
+        let mut r = rule("r", Decision::Pending);
+        assert!(r.matches_request(&candidate("")));
+
+        r.request_type = Some("friend".to_string());
+        assert!(!r.matches_request(&candidate("")));
+        r.request_type = Some("group".to_string());
+        r.sub_type = Some("invite".to_string());
+        assert!(!r.matches_request(&candidate("")));
+        r.sub_type = Some("add".to_string());
+        r.group_ids = vec![200];
+        assert!(!r.matches_request(&candidate("")));
+        r.group_ids = vec![100, 200];
+        assert!(r.matches_request(&candidate("")));
+
+        r.comment = Some(CommentMatch::Exact { text: "ok".to_string(), ignore_case: false });
+        assert!(!r.matches_request(&candidate("no")));
+        assert!(r.matches_request(&candidate("ok")));
+    }
+
+    #[test]
+    fn stranger_conditions_require_the_information() {
+        let mut r = rule("r", Decision::Pending);
+        assert!(r.matches_stranger(None));
+
+        r.min_age = Some(18);
+        r.max_age = Some(30);
+        assert!(!r.matches_stranger(None));
+        assert!(!r.matches_stranger(Some(&stranger(None, None))));
+        assert!(!r.matches_stranger(Some(&stranger(Some(17), None))));
+        assert!(r.matches_stranger(Some(&stranger(Some(18), None))));
+        assert!(r.matches_stranger(Some(&stranger(Some(30), None))));
+        assert!(!r.matches_stranger(Some(&stranger(Some(31), None))));
+
+        r.min_level = Some(16);
+        assert!(!r.matches_stranger(Some(&stranger(Some(20), None))));
+        assert!(!r.matches_stranger(Some(&stranger(Some(20), Some(15)))));
+        assert!(r.matches_stranger(Some(&stranger(Some(20), Some(16)))));
+    }
+
+    #[test]
+    fn first_matching_rule_in_order_wins() {
+        let mut young = rule("young", Decision::Reject { reason: None });
+        young.max_age = Some(15);
+        let mut disabled = rule("disabled", Decision::Reject { reason: None });
+        disabled.enabled = false;
+        let mut keyword = rule("keyword", Decision::Approve { remark: None });
+        keyword.comment = Some(CommentMatch::Regex { pattern: "暗号".to_string(), ignore_case: false });
+        let fallback = rule("fallback", Decision::Pending);
+        let rules = vec![disabled, young, keyword, fallback];
+
+        let pick = |comment: &str, age: Option<i32>| {
+            let candidates = request_candidates(rules.clone(), &candidate(comment));
+            first_match(candidates, Some(&stranger(age, None))).map(|rule| rule.name)
+        };
+        assert_eq!(pick("暗号", Some(12)).as_deref(), Some("young"));
+        assert_eq!(pick("暗号", Some(20)).as_deref(), Some("keyword"));
+        assert_eq!(pick("你好", Some(20)).as_deref(), Some("fallback"));
+        assert_eq!(pick("你好", None).as_deref(), Some("fallback"));
+    }
+}
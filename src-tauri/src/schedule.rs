@@ -0,0 +1,748 @@
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use chrono::{Local, TimeZone};
+use rusqlite::{params, Row};
+use serde::{Deserialize, Serialize};
+use tauri::{Emitter, State};
+use crate::AppHandle;
+use crate::cqcode::MessageChain;
+use tokio::sync::Notify;
+use crate::daemon::{self, ReloadTarget};
+use crate::outbox;
+use crate::runbot::RunbotState;
+use crate::storage;
+
+/// 到点后多久以内仍按时发送；超过这个时间视为错过，按 misfire 策略处理
+const MISFIRE_GRACE_SECS: i64 = 60;
+/// 调度循环最长的休眠时间（用于检查会话是否已经结束）
+const MAX_SLEEP_SECS: i64 = 60;
+
+/// 发送目标
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TargetType {
+    Group,
+    Private,
+}
+
+impl TargetType {
+    fn as_str(&self) -> &'static str {
+        match self {
+            TargetType::Group => "group",
+            TargetType::Private => "private",
+        }
+    }
+
+    fn parse(value: &str) -> Self {
+        match value {
+            "private" => TargetType::Private,
+            _ => TargetType::Group,
+        }
+    }
+}
+
+/// 错过发送时间（例如程序未运行）时的处理方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MisfirePolicy {
+    /// 立即补发一次（错过多次也只补发一次）
+    #[default]
+    CatchUp,
+    /// 跳过错过的发送，等待下一次
+    Skip,
+}
+
+impl MisfirePolicy {
+    fn as_str(&self) -> &'static str {
+        match self {
+            MisfirePolicy::CatchUp => "catch_up",
+            MisfirePolicy::Skip => "skip",
+        }
+    }
+
+    fn parse(value: &str) -> Self {
+        match value {
+            "skip" => MisfirePolicy::Skip,
+            _ => MisfirePolicy::CatchUp,
+        }
+    }
+}
+
+/// 任务状态
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobState {
+    /// 等待发送
+    #[default]
+    Scheduled,
+    /// 单次任务已发送（交给发送队列）
+    Completed,
+    /// 单次任务错过发送时间并按策略跳过
+    Missed,
+    /// 已取消
+    Cancelled,
+    /// 无法继续调度（例如加入发送队列失败）
+    Failed,
+}
+
+impl JobState {
+    fn as_str(&self) -> &'static str {
+        match self {
+            JobState::Scheduled => "scheduled",
+            JobState::Completed => "completed",
+            JobState::Missed => "missed",
+            JobState::Cancelled => "cancelled",
+            JobState::Failed => "failed",
+        }
+    }
+
+    fn parse(value: &str) -> Self {
+        match value {
+            "completed" => JobState::Completed,
+            "missed" => JobState::Missed,
+            "cancelled" => JobState::Cancelled,
+            "failed" => JobState::Failed,
+            _ => JobState::Scheduled,
+        }
+    }
+}
+
+/// 定时消息任务（同时作为 schedule-state 事件的内容）
+///
+/// run_at 与 cron 二选一：run_at 为单次发送的时间（秒级时间戳）；cron 为周期表达式（本地时间），
+/// 支持 5 段（分 时 日 月 周）或带秒的 6、7 段写法，星期建议使用英文缩写（如 MON-FRI）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScheduledJob {
+    #[serde(default)]
+    pub account_id: i64,
+    /// 新建时为空
+    #[serde(default)]
+    pub id: Option<i64>,
+    #[serde(default)]
+    pub name: String,
+    pub target_type: TargetType,
+    pub target_id: i64,
+    /// 消息内容（CQ 码格式，发送时解析为消息段）
+    pub message: String,
+    #[serde(default)]
+    pub run_at: Option<i64>,
+    #[serde(default)]
+    pub cron: Option<String>,
+    #[serde(default)]
+    pub misfire: MisfirePolicy,
+    #[serde(default)]
+    pub state: JobState,
+    #[serde(default)]
+    pub next_run_at: Option<i64>,
+    #[serde(default)]
+    pub last_run_at: Option<i64>,
+    /// 最近一次发送生成的本地消息 ID，可用于关联发送队列的状态
+    #[serde(default)]
+    pub last_local_message_id: Option<String>,
+    #[serde(default)]
+    pub last_error: Option<String>,
+    #[serde(default)]
+    pub run_count: i64,
+    #[serde(default)]
+    pub created_at: i64,
+    #[serde(default)]
+    pub updated_at: i64,
+}
+
+impl ScheduledJob {
+    fn from_row(account_id: i64, row: &Row) -> rusqlite::Result<Self> {
+        let target_type: String = row.get("target_type")?;
+        let misfire: String = row.get("misfire")?;
+        let state: String = row.get("state")?;
+        Ok(Self {
+            account_id,
+            id: row.get("id")?,
+            name: row.get("name")?,
+            target_type: TargetType::parse(&target_type),
+            target_id: row.get("target_id")?,
+            message: row.get("message")?,
+            run_at: row.get("run_at")?,
+            cron: row.get("cron")?,
+            misfire: MisfirePolicy::parse(&misfire),
+            state: JobState::parse(&state),
+            next_run_at: row.get("next_run_at")?,
+            last_run_at: row.get("last_run_at")?,
+            last_local_message_id: row.get("last_local_message_id")?,
+            last_error: row.get("last_error")?,
+            run_count: row.get("run_count")?,
+            created_at: row.get("created_at")?,
+            updated_at: row.get("updated_at")?,
+        })
+    }
+
+    /// 检查任务，返回从 now 起的下一次发送时间
+    fn validate(&self, now: i64) -> Result<i64, String> {
+        if self.target_id <= 0 {
+            return Err("发送目标无效".to_string());
+        }
+        if self.message.trim().is_empty() {
+            return Err("消息内容不能为空".to_string());
+        }
+        match (&self.cron, self.run_at) {
+            (Some(cron), None) => next_cron_run(cron, now)?.ok_or_else(|| "cron 表达式没有之后的发送时间".to_string()),
+            (None, Some(run_at)) if run_at <= now => Err("发送时间已过".to_string()),
+            (None, Some(run_at)) => Ok(run_at),
+            _ => Err("run_at 与 cron 需要且只能设置一个".to_string()),
+        }
+    }
+}
+
+/// 解析 cron 表达式（5 段写法补上秒）
+fn parse_cron(expression: &str) -> Result<cron::Schedule, String> {
+    let expression = expression.trim();
+    let expression = if expression.split_whitespace().count() == 5 {
+        format!("0 {}", expression)
+    } else {
+        expression.to_string()
+    };
+    cron::Schedule::from_str(&expression).map_err(|e| format!("cron 表达式无效: {}", e))
+}
+
+/// 计算 after 之后（不含）的下一次发送时间
+fn next_cron_run(expression: &str, after: i64) -> Result<Option<i64>, String> {
+    let schedule = parse_cron(expression)?;
+    let after = Local
+        .timestamp_opt(after, 0)
+        .single()
+        .ok_or_else(|| format!("时间无效: {}", after))?;
+    Ok(schedule.after(&after).next().map(|time| time.timestamp()))
+}
+
+fn query_jobs(app: &AppHandle, account_id: i64, include_finished: bool) -> Result<Vec<ScheduledJob>, String> {
+    let conn = storage::get_connection(app, Some(account_id))?;
+    let sql = if include_finished {
+        "SELECT * FROM scheduled_jobs ORDER BY next_run_at IS NULL, next_run_at, id"
+    } else {
+        "SELECT * FROM scheduled_jobs WHERE state = 'scheduled' ORDER BY next_run_at, id"
+    };
+    let mut stmt = conn.prepare(sql).map_err(|e| format!("准备查询失败: {}", e))?;
+    let jobs = stmt
+        .query_map([], |row| ScheduledJob::from_row(account_id, row))
+        .map_err(|e| format!("查询定时任务失败: {}", e))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("读取定时任务失败: {}", e))?;
+    Ok(jobs)
+}
+
+fn load_job(app: &AppHandle, account_id: i64, id: i64) -> Result<ScheduledJob, String> {
+    let conn = storage::get_connection(app, Some(account_id))?;
+    conn.query_row("SELECT * FROM scheduled_jobs WHERE id = ?1", params![id], |row| {
+        ScheduledJob::from_row(account_id, row)
+    })
+    .map_err(|e| format!("读取定时任务失败: {}", e))
+}
+
+/// 保存调度结果并通知前端
+///
+/// 只有任务仍是本次处理的那一次（状态为 scheduled 且 next_run_at 仍为 due）时才写入调度结果；
+/// 处理期间任务被修改或取消时只记录发送结果，保留用户修改后的状态和发送时间
+fn save_run(app: &AppHandle, job: &ScheduledJob, due: i64, fired: bool) {
+    let Some(id) = job.id else {
+        return;
+    };
+    let result = storage::get_connection(app, Some(job.account_id)).and_then(|conn| {
+        let updated = conn
+            .execute(
+                "UPDATE scheduled_jobs SET state = ?1, next_run_at = ?2, last_run_at = ?3,
+                    last_local_message_id = ?4, last_error = ?5, run_count = ?6,
+                    updated_at = strftime('%s', 'now')
+                 WHERE id = ?7 AND state = 'scheduled' AND next_run_at = ?8",
+                params![
+                    job.state.as_str(),
+                    job.next_run_at,
+                    job.last_run_at,
+                    job.last_local_message_id,
+                    job.last_error,
+                    job.run_count,
+                    id,
+                    due,
+                ],
+            )
+            .map_err(|e| format!("更新定时任务失败: {}", e))?;
+        if updated > 0 {
+            return Ok(false);
+        }
+        conn.execute(
+            "UPDATE scheduled_jobs SET last_run_at = ?1, last_local_message_id = ?2, last_error = ?3,
+                run_count = run_count + ?4, updated_at = strftime('%s', 'now')
+             WHERE id = ?5",
+            params![
+                job.last_run_at,
+                job.last_local_message_id,
+                job.last_error,
+                i64::from(fired),
+                id,
+            ],
+        )
+        .map_err(|e| format!("更新定时任务失败: {}", e))?;
+        Ok(true)
+    });
+    match result {
+        Ok(false) => {
+            let _ = app.emit("schedule-state", job);
+        }
+        Ok(true) => {
+            tracing::info!("[scheduler] 定时任务 {} 在处理期间已被修改，保留修改后的任务", id);
+            match load_job(app, job.account_id, id) {
+                Ok(current) => {
+                    let _ = app.emit("schedule-state", &current);
+                }
+                Err(e) => tracing::warn!("[scheduler] {}", e),
+            }
+        }
+        Err(e) => tracing::warn!("[scheduler] {}", e),
+    }
+}
+
+/// 发送一条定时消息：与前端发送消息相同，先写入本地消息记录，再交给发送队列
+///
+/// 本地记录保存规范化后的 CQ 码，发给 OneBot 实现的是消息段数组
+fn fire(
+    app: &AppHandle,
+    registry: &Arc<Mutex<RunbotState>>,
+    job: &ScheduledJob,
+    now: i64,
+) -> Result<String, String> {
+    let account_id = job.account_id;
+    let (action, target_key, user_id, group_id) = match job.target_type {
+        TargetType::Group => ("send_group_msg", "group_id", account_id, Some(job.target_id)),
+        TargetType::Private => ("send_private_msg", "user_id", job.target_id, None),
+    };
+
+    let chain = MessageChain::parse(&job.message);
+    let raw_message = chain.to_cqcode();
+    let mut local_message = serde_json::json!({
+        "time": now,
+        "self_id": account_id,
+        "post_type": "message_sent",
+        "message_type": job.target_type.as_str(),
+        "user_id": user_id,
+        "group_id": group_id,
+        "message": raw_message,
+        "raw_message": raw_message,
+    });
+    let local_message_id = storage::store_event(app, account_id, &mut local_message)?;
+
+    let params = serde_json::json!({
+        target_key: job.target_id,
+        "message": chain,
+        "local_message_id": local_message_id,
+    });
+    outbox::submit(app, registry, account_id, &local_message_id, action, &params)?;
+    Ok(local_message_id)
+}
+
+/// 到期的任务是否按 misfire 策略跳过：超过宽限时间才算错过，错过时 catch_up 仍补发一次
+fn is_skipped(misfire: MisfirePolicy, due: i64, now: i64) -> bool {
+    now - due > MISFIRE_GRACE_SECS && misfire == MisfirePolicy::Skip
+}
+
+/// 根据本次处理的结果计算任务的下一次发送时间和状态
+fn advance(job: &mut ScheduledJob, now: i64, skipped: bool) {
+    match &job.cron {
+        Some(cron) => match next_cron_run(cron, now) {
+            Ok(Some(next)) => job.next_run_at = Some(next),
+            Ok(None) => {
+                job.next_run_at = None;
+                job.state = JobState::Completed;
+            }
+            Err(e) => {
+                job.next_run_at = None;
+                job.state = JobState::Failed;
+                job.last_error = Some(e);
+            }
+        },
+        None => {
+            job.next_run_at = None;
+            job.state = if skipped {
+                JobState::Missed
+            } else if job.last_error.is_none() {
+                JobState::Completed
+            } else {
+                JobState::Failed
+            };
+        }
+    }
+}
+
+/// 处理一个到期的任务
+fn run_job(app: &AppHandle, registry: &Arc<Mutex<RunbotState>>, mut job: ScheduledJob, now: i64) {
+    let due = job.next_run_at.unwrap_or(now);
+    let skipped = is_skipped(job.misfire, due, now);
+    let mut fired = false;
+
+    if skipped {
+        tracing::info!("[scheduler] 定时任务 {} 错过发送时间 {}，已跳过", job.id.unwrap_or_default(), due);
+        job.last_error = Some(format!("错过发送时间 {}，已跳过", due));
+    } else {
+        match fire(app, registry, &job, now) {
+            Ok(local_message_id) => {
+                tracing::info!("[scheduler] 定时任务 {} 已加入发送队列: {}", job.id.unwrap_or_default(), local_message_id);
+                job.last_run_at = Some(now);
+                job.last_local_message_id = Some(local_message_id);
+                job.last_error = None;
+                job.run_count += 1;
+                fired = true;
+            }
+            Err(e) => {
+                tracing::warn!("[scheduler] 定时任务 {} 发送失败: {}", job.id.unwrap_or_default(), e);
+                job.last_error = Some(e);
+            }
+        }
+    }
+
+    advance(&mut job, now, skipped);
+    save_run(app, &job, due, fired);
+}
+
+/// 处理所有到期的任务，返回之后最早的发送时间
+fn run_due(app: &AppHandle, registry: &Arc<Mutex<RunbotState>>, account_id: i64) -> Result<Option<i64>, String> {
+    let now = chrono::Utc::now().timestamp();
+    let due: Vec<ScheduledJob> = query_jobs(app, account_id, false)?
+        .into_iter()
+        .filter(|job| job.next_run_at.is_some_and(|next| next <= now))
+        .collect();
+    for job in due {
+        run_job(app, registry, job, now);
+    }
+
+    let conn = storage::get_connection(app, Some(account_id))?;
+    conn.query_row(
+        "SELECT MIN(next_run_at) FROM scheduled_jobs WHERE state = 'scheduled'",
+        [],
+        |row| row.get(0),
+    )
+    .map_err(|e| format!("查询定时任务失败: {}", e))
+}
+
+/// 启动账号的定时任务调度（账号认证成功后调用，已在运行时忽略）
+///
+/// 调度随会话结束而停止；程序未运行期间错过的发送在下次连接时按 misfire 策略处理
+pub fn start(app: AppHandle, registry: Arc<Mutex<RunbotState>>, account_id: i64) {
+    let Some(session) = registry
+        .lock()
+        .ok()
+        .and_then(|state_guard| state_guard.sessions.get(account_id))
+    else {
+        return;
+    };
+    let wake = {
+        let Ok(mut session_guard) = session.lock() else {
+            return;
+        };
+        if session_guard.scheduler.is_some() {
+            return;
+        }
+        let wake = Arc::new(Notify::new());
+        session_guard.scheduler = Some(wake.clone());
+        wake
+    };
+
+    tracing::info!("[scheduler] 启动账号 {} 的定时任务调度", account_id);
+    tokio::spawn(async move {
+        loop {
+            let current = registry
+                .lock()
+                .ok()
+                .and_then(|state_guard| state_guard.sessions.get(account_id))
+                .is_some_and(|current| Arc::ptr_eq(&current, &session));
+            if !current {
+                break;
+            }
+
+            let now = chrono::Utc::now().timestamp();
+            let delay = match run_due(&app, &registry, account_id) {
+                Ok(Some(next)) => (next - now).clamp(0, MAX_SLEEP_SECS),
+                Ok(None) => MAX_SLEEP_SECS,
+                Err(e) => {
+                    tracing::warn!("[scheduler] {}", e);
+                    MAX_SLEEP_SECS
+                }
+            };
+            tokio::select! {
+                _ = tokio::time::sleep(tokio::time::Duration::from_secs(delay as u64)) => {}
+                _ = wake.notified() => {}
+            }
+        }
+        tracing::info!("[scheduler] 账号 {} 的定时任务调度已停止", account_id);
+    });
+}
+
+/// 任务修改后唤醒调度循环，重新计算休眠时间
+pub(crate) fn wake(state: &Mutex<RunbotState>, account_id: i64) {
+    let session = state.lock().ok().and_then(|state_guard| state_guard.sessions.get(account_id));
+    if let Some(session) = session {
+        if let Some(wake) = session.lock().ok().and_then(|session_guard| session_guard.scheduler.clone()) {
+            wake.notify_one();
+        }
+    }
+}
+
+/// 获取定时任务（默认只返回等待发送的任务）
+#[tauri::command]
+pub async fn get_scheduled_jobs(
+    account_id: i64,
+    include_finished: Option<bool>,
+    app: AppHandle,
+) -> Result<Vec<ScheduledJob>, String> {
+    query_jobs(&app, account_id, include_finished.unwrap_or(false))
+}
+
+/// 新建（id 为空）或修改定时任务，返回保存后的任务；修改已结束的任务会重新开始调度
+#[tauri::command]
+pub async fn save_scheduled_job(
+    account_id: i64,
+    job: ScheduledJob,
+    app: AppHandle,
+    state: State<'_, Arc<Mutex<RunbotState>>>,
+) -> Result<ScheduledJob, String> {
+    let now = chrono::Utc::now().timestamp();
+    let next_run_at = job.validate(now)?;
+
+    let conn = storage::get_connection(&app, Some(account_id))?;
+    let id = match job.id {
+        Some(id) => {
+            let updated = conn
+                .execute(
+                    "UPDATE scheduled_jobs SET name = ?1, target_type = ?2, target_id = ?3, message = ?4,
+                        run_at = ?5, cron = ?6, misfire = ?7, state = 'scheduled', next_run_at = ?8,
+                        last_error = NULL, updated_at = strftime('%s', 'now')
+                     WHERE id = ?9",
+                    params![
+                        job.name,
+                        job.target_type.as_str(),
+                        job.target_id,
+                        job.message,
+                        job.run_at,
+                        job.cron,
+                        job.misfire.as_str(),
+                        next_run_at,
+                        id,
+                    ],
+                )
+                .map_err(|e| format!("更新定时任务失败: {}", e))?;
+            if updated == 0 {
+                return Err(format!("定时任务不存在: {}", id));
+            }
+            id
+        }
+        None => {
+            conn.execute(
+                "INSERT INTO scheduled_jobs (name, target_type, target_id, message, run_at, cron, misfire, next_run_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                params![
+                    job.name,
+                    job.target_type.as_str(),
+                    job.target_id,
+                    job.message,
+                    job.run_at,
+                    job.cron,
+                    job.misfire.as_str(),
+                    next_run_at,
+                ],
+            )
+            .map_err(|e| format!("保存定时任务失败: {}", e))?;
+            conn.last_insert_rowid()
+        }
+    };
+
+    tracing::info!("[save_scheduled_job] 已保存定时任务 {}，下次发送时间 {}", id, next_run_at);
+    let job = load_job(&app, account_id, id)?;
+    let _ = app.emit("schedule-state", &job);
+    wake(&state, account_id);
+    daemon::notify_daemon(&app, &state, account_id, ReloadTarget::Schedule).await?;
+    Ok(job)
+}
+
+/// 取消定时任务
+#[tauri::command]
+pub async fn cancel_scheduled_job(
+    account_id: i64,
+    id: i64,
+    app: AppHandle,
+    state: State<'_, Arc<Mutex<RunbotState>>>,
+) -> Result<ScheduledJob, String> {
+    let conn = storage::get_connection(&app, Some(account_id))?;
+    let updated = conn
+        .execute(
+            "UPDATE scheduled_jobs SET state = 'cancelled', next_run_at = NULL, updated_at = strftime('%s', 'now')
+             WHERE id = ?1 AND state = 'scheduled'",
+            params![id],
+        )
+        .map_err(|e| format!("取消定时任务失败: {}", e))?;
+    if updated == 0 {
+        return Err(format!("定时任务不存在或已结束: {}", id));
+    }
+
+    tracing::info!("[cancel_scheduled_job] 已取消定时任务 {}", id);
+    let job = load_job(&app, account_id, id)?;
+    let _ = app.emit("schedule-state", &job);
+    wake(&state, account_id);
+    daemon::notify_daemon(&app, &state, account_id, ReloadTarget::Schedule).await?;
+    Ok(job)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TestApp;
+
+    const NOW: i64 = 1_700_000_000;
+
+    fn job(run_at: Option<i64>, cron: Option<&str>) -> ScheduledJob {
+        let mut job: ScheduledJob = serde_json::from_value(serde_json::json!({
+            "target_type": "group",
+            "target_id": 10001,
+            "message": "早上好",
+        }))
+        .unwrap();
+        job.run_at = run_at;
+        job.cron = cron.map(str::to_string);
+        job
+    }
+
+    #[test]
+    fn parses_cron_expressions() {
+        assert!(parse_cron("*/5 * * * *").is_ok());
+        assert!(parse_cron(" 30 */5 * * * * ").is_ok());
+        assert!(parse_cron("0 9 * * MON-FRI").is_ok());
+        assert!(parse_cron("61 * * * *").is_err());
+        assert!(parse_cron("every day").is_err());
+    }
+
+    #[test]
+    fn computes_next_cron_run_after_time() {
+        // 时区偏移都是 5 分钟的整数倍，结果与本地时区无关
+        assert_eq!(next_cron_run("*/5 * * * *", NOW).unwrap(), Some(1_700_000_100));
+        assert_eq!(next_cron_run("*/5 * * * *", 1_700_000_100).unwrap(), Some(1_700_000_400));
+        assert_eq!(next_cron_run("*/10 * * * * *", NOW).unwrap(), Some(1_700_000_010));
+        assert_eq!(next_cron_run("0 0 0 1 1 * 2000", NOW).unwrap(), None);
+        assert!(next_cron_run("bad", NOW).is_err());
+    }
+
+    #[test]
+    fn validates_jobs() {
+        assert_eq!(job(Some(NOW + 60), None).validate(NOW), Ok(NOW + 60));
+        assert_eq!(job(None, Some("*/5 * * * *")).validate(NOW), Ok(1_700_000_100));
+        assert!(job(Some(NOW), None).validate(NOW).is_err());
+        assert!(job(None, None).validate(NOW).is_err());
+        assert!(job(Some(NOW + 60), Some("*/5 * * * *")).validate(NOW).is_err());
+        assert!(job(None, Some("0 0 0 1 1 * 2000")).validate(NOW).is_err());
+
+        let mut empty = job(Some(NOW + 60), None);
+        empty.message = "  ".to_string();
+        assert!(empty.validate(NOW).is_err());
+        let mut no_target = job(Some(NOW + 60), None);
+        no_target.target_id = 0;
+        assert!(no_target.validate(NOW).is_err());
+    }
+
+    #[test]
+    fn skips_only_missed_jobs_with_skip_policy() {
+        let due = NOW - MISFIRE_GRACE_SECS;
+        assert!(!is_skipped(MisfirePolicy::Skip, due, NOW));
+        assert!(is_skipped(MisfirePolicy::Skip, due - 1, NOW));
+        assert!(!is_skipped(MisfirePolicy::CatchUp, due - 1, NOW));
+        assert!(!is_skipped(MisfirePolicy::CatchUp, NOW - 86_400, NOW));
+    }
+
+    #[test]
+    fn advances_one_shot_jobs() {
+        let mut sent = job(Some(NOW), None);
+        advance(&mut sent, NOW, false);
+        assert_eq!((sent.state, sent.next_run_at), (JobState::Completed, None));
+
+        let mut skipped = job(Some(NOW - 3_600), None);
+        skipped.last_error = Some("错过发送时间".to_string());
+        advance(&mut skipped, NOW, true);
+        assert_eq!((skipped.state, skipped.next_run_at), (JobState::Missed, None));
+
+        let mut failed = job(Some(NOW), None);
+        failed.last_error = Some("账号未连接".to_string());
+        advance(&mut failed, NOW, false);
+        assert_eq!(failed.state, JobState::Failed);
+    }
+
+    #[test]
+    fn advances_cron_jobs() {
+        // 错过多次也只补发一次，下一次从当前时间算起
+        let mut recurring = job(None, Some("*/5 * * * *"));
+        recurring.next_run_at = Some(NOW - 3_600);
+        advance(&mut recurring, NOW, false);
+        assert_eq!((recurring.state, recurring.next_run_at), (JobState::Scheduled, Some(1_700_000_100)));
+
+        let mut skipped = job(None, Some("*/5 * * * *"));
+        advance(&mut skipped, NOW, true);
+        assert_eq!((skipped.state, skipped.next_run_at), (JobState::Scheduled, Some(1_700_000_100)));
+
+        let mut expired = job(None, Some("0 0 0 1 1 * 2000"));
+        advance(&mut expired, NOW, false);
+        assert_eq!((expired.state, expired.next_run_at), (JobState::Completed, None));
+    }
+
+    fn insert(app: &TestApp, job: &ScheduledJob, next_run_at: i64) -> ScheduledJob {
+        let conn = storage::get_connection(app.handle(), Some(1)).unwrap();
+        conn.execute(
+            "INSERT INTO scheduled_jobs (name, target_type, target_id, message, run_at, cron, misfire, next_run_at)
+             VALUES ('', 'group', ?1, ?2, ?3, ?4, 'catch_up', ?5)",
+            params![job.target_id, job.message, job.run_at, job.cron, next_run_at],
+        )
+        .unwrap();
+        load_job(app.handle(), 1, conn.last_insert_rowid()).unwrap()
+    }
+
+    #[test]
+    fn saves_run_of_unchanged_job() {
+        let app = TestApp::new();
+        let mut job = insert(&app, &job(None, Some("*/5 * * * *")), NOW);
+        job.last_run_at = Some(NOW);
+        job.run_count = 1;
+        advance(&mut job, NOW, false);
+        save_run(app.handle(), &job, NOW, true);
+
+        let saved = load_job(app.handle(), 1, job.id.unwrap()).unwrap();
+        assert_eq!(saved.next_run_at, Some(1_700_000_100));
+        assert_eq!((saved.last_run_at, saved.run_count), (Some(NOW), 1));
+    }
+
+    #[test]
+    fn keeps_job_changed_while_running() {
+        let app = TestApp::new();
+        let mut job = insert(&app, &job(Some(NOW), None), NOW);
+        let id = job.id.unwrap();
+
+        // 发送期间用户把任务改到了明天
+        let conn = storage::get_connection(app.handle(), Some(1)).unwrap();
+        conn.execute(
+            "UPDATE scheduled_jobs SET run_at = ?1, next_run_at = ?1 WHERE id = ?2",
+            params![NOW + 86_400, id],
+        )
+        .unwrap();
+
+        job.last_run_at = Some(NOW);
+        job.last_local_message_id = Some("local".to_string());
+        job.run_count = 1;
+        advance(&mut job, NOW, false);
+        save_run(app.handle(), &job, NOW, true);
+
+        let saved = load_job(app.handle(), 1, id).unwrap();
+        assert_eq!((saved.state, saved.next_run_at), (JobState::Scheduled, Some(NOW + 86_400)));
+        assert_eq!(saved.last_local_message_id.as_deref(), Some("local"));
+        assert_eq!(saved.run_count, 1);
+
+        // 已取消的任务不会被改回
+        conn.execute("UPDATE scheduled_jobs SET state = 'cancelled', next_run_at = NULL WHERE id = ?1", params![id])
+            .unwrap();
+        let mut stale = saved.clone();
+        stale.next_run_at = Some(NOW + 86_400);
+        advance(&mut stale, NOW + 86_400, false);
+        save_run(app.handle(), &stale, NOW + 86_400, false);
+        let saved = load_job(app.handle(), 1, id).unwrap();
+        assert_eq!((saved.state, saved.next_run_at), (JobState::Cancelled, None));
+    }
+}
@@ -0,0 +1,776 @@
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+use async_trait::async_trait;
+use rhai::{CallFnOptions, Dynamic, Engine, EvalAltResult, Map, Position, Scope, AST};
+use runbot::prelude::*;
+use serde::Serialize;
+use serde_json::Value;
+use tauri::{Emitter, State};
+use crate::AppHandle;
+use crate::actions;
+use crate::daemon::{self, ReloadTarget};
+use crate::runbot::RunbotState;
+use crate::session::Session;
+use crate::storage;
+
+/// 脚本没有设置 timeout_ms 时单次执行的超时时间
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(3);
+/// 两次检查脚本目录变化的最小间隔
+const SCAN_INTERVAL: Duration = Duration::from_secs(2);
+/// 每个账号保留的脚本日志条数
+const MAX_LOGS: usize = 500;
+/// 每个脚本（或插件）最多积压的待处理事件数，超过后丢弃新事件
+pub(crate) const MAX_QUEUED_POSTS: usize = 100;
+
+/// 脚本日志（同时作为 script-log 事件的内容）
+#[derive(Debug, Clone, Serialize)]
+pub struct ScriptLog {
+    pub account_id: i64,
+    pub script: String,
+    /// info、debug 或 error
+    pub level: String,
+    pub message: String,
+    /// 毫秒时间戳
+    pub time: i64,
+}
+
+/// 脚本状态
+#[derive(Debug, Clone, Serialize)]
+pub struct ScriptInfo {
+    pub name: String,
+    pub path: String,
+    /// 编译或初始化失败时的错误
+    pub error: Option<String>,
+    pub timeout_ms: u64,
+    /// 是否定义了 on_post(post)
+    pub handles_posts: bool,
+}
+
+/// 日志输出：写入缓冲区并通知前端
+#[derive(Debug, Clone)]
+struct LogSink {
+    app: AppHandle,
+    account_id: i64,
+    logs: Arc<Mutex<VecDeque<ScriptLog>>>,
+}
+
+impl LogSink {
+    fn log(&self, script: &str, level: &str, message: String) {
+        match level {
+            "error" => tracing::warn!("[script:{}] {}", script, message),
+            _ => tracing::info!("[script:{}] {}", script, message),
+        }
+        let entry = ScriptLog {
+            account_id: self.account_id,
+            script: script.to_string(),
+            level: level.to_string(),
+            message,
+            time: chrono::Utc::now().timestamp_millis(),
+        };
+        {
+            let mut logs = self.logs.lock().unwrap();
+            if logs.len() >= MAX_LOGS {
+                logs.pop_front();
+            }
+            logs.push_back(entry.clone());
+        }
+        let _ = self.app.emit("script-log", entry);
+    }
+}
+
+/// 单个脚本（或插件）的事件队列
+///
+/// 同一时间最多只有一个阻塞线程按到达顺序处理队列中的事件，积压超过 MAX_QUEUED_POSTS 条时拒绝新事件
+pub(crate) struct PostQueue<T> {
+    state: Mutex<QueueState<T>>,
+}
+
+struct QueueState<T> {
+    posts: VecDeque<T>,
+    running: bool,
+}
+
+impl<T> Default for PostQueue<T> {
+    fn default() -> Self {
+        Self {
+            state: Mutex::new(QueueState { posts: VecDeque::new(), running: false }),
+        }
+    }
+}
+
+impl<T: Send + 'static> PostQueue<T> {
+    /// 加入一个事件，没有线程在处理时启动一个（用 handle 处理，直到队列为空）；队列已满时返回 false
+    pub(crate) fn push(self: &Arc<Self>, post: T, handle: impl Fn(T) + Send + 'static) -> bool {
+        {
+            let mut state = self.state.lock().unwrap();
+            if state.posts.len() >= MAX_QUEUED_POSTS {
+                return false;
+            }
+            state.posts.push_back(post);
+            if state.running {
+                return true;
+            }
+            state.running = true;
+        }
+
+        let queue = self.clone();
+        tokio::task::spawn_blocking(move || loop {
+            let post = {
+                let mut state = queue.state.lock().unwrap();
+                let post = state.posts.pop_front();
+                state.running = post.is_some();
+                post
+            };
+            match post {
+                Some(post) => handle(post),
+                None => return,
+            }
+        });
+        true
+    }
+}
+
+/// 已加载的脚本
+///
+/// 每个脚本使用独立的 Engine；on_post 按顺序执行，this 绑定到脚本自己的状态对象，可以在多次调用之间保存数据
+struct Script {
+    name: String,
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    error: Option<String>,
+    timeout: Duration,
+    handles_posts: bool,
+    runtime: Option<Mutex<ScriptRuntime>>,
+    queue: Arc<PostQueue<Dynamic>>,
+}
+
+struct ScriptRuntime {
+    engine: Engine,
+    ast: AST,
+    scope: Scope<'static>,
+    state: Dynamic,
+    deadline: Arc<Mutex<Instant>>,
+}
+
+/// 把 OneBot 调用的结果转换为脚本中的异常
+fn script_error(message: String) -> Box<EvalAltResult> {
+    message.into()
+}
+
+/// 创建脚本引擎，注册 OneBot 调用和日志函数
+fn build_engine(
+    host: &ScriptHost,
+    name: &str,
+    deadline: Arc<Mutex<Instant>>,
+) -> Engine {
+    let mut engine = Engine::new();
+    engine.set_max_call_levels(32);
+
+    let progress_deadline = deadline.clone();
+    engine.on_progress(move |_| {
+        if Instant::now() > *progress_deadline.lock().unwrap() {
+            Some(Dynamic::from("执行超时"))
+        } else {
+            None
+        }
+    });
+
+    let sink = host.sink.clone();
+    let script = name.to_string();
+    engine.on_print(move |message| sink.log(&script, "info", message.to_string()));
+    let sink = host.sink.clone();
+    let script = name.to_string();
+    engine.on_debug(move |message, _, _| sink.log(&script, "debug", message.to_string()));
+    let sink = host.sink.clone();
+    let script = name.to_string();
+    engine.register_fn("log", move |message: &str| sink.log(&script, "info", message.to_string()));
+
+    let account_id = host.account_id;
+    engine.register_fn("self_id", move || account_id);
+
+    // 脚本在阻塞线程中执行，OneBot 调用通过 runtime handle 等待结果；
+    // 等待期间 on_progress 不会被调用，所以单独按剩余时间限制等待
+    let call = {
+        let registry = host.registry.clone();
+        let handle = tokio::runtime::Handle::current();
+        Arc::new(move |action: &str, params: Value| -> Result<Dynamic, Box<EvalAltResult>> {
+            let remaining = deadline.lock().unwrap().saturating_duration_since(Instant::now());
+            let data = handle
+                .block_on(tokio::time::timeout(
+                    remaining,
+                    actions::call::<_, Value>(&registry, account_id, action, &params),
+                ))
+                .map_err(|_| EvalAltResult::ErrorTerminated(Dynamic::from("执行超时"), Position::NONE))?
+                .map_err(|e| script_error(format!("{} {}", action, e)))?;
+            rhai::serde::to_dynamic(data)
+        })
+    };
+
+    let f = call.clone();
+    engine.register_fn("call_action", move |action: &str, params: Map| {
+        let params = rhai::serde::from_dynamic::<Value>(&Dynamic::from_map(params))?;
+        f(action, params)
+    });
+    let f = call.clone();
+    engine.register_fn("call_action", move |action: &str| f(action, serde_json::json!({})));
+    let f = call.clone();
+    engine.register_fn("send_private_msg", move |user_id: i64, message: Dynamic| {
+        let message = rhai::serde::from_dynamic::<Value>(&message)?;
+        f("send_private_msg", serde_json::json!({ "user_id": user_id, "message": message }))
+    });
+    let f = call;
+    engine.register_fn("send_group_msg", move |group_id: i64, message: Dynamic| {
+        let message = rhai::serde::from_dynamic::<Value>(&message)?;
+        f("send_group_msg", serde_json::json!({ "group_id": group_id, "message": message }))
+    });
+
+    engine
+}
+
+/// 把 Post 展开为 JSON 对象：kind 为类型路径（如 Message、Notice.GroupRecall、Request.Friend），其余为事件字段
+///
+/// 脚本和插件看到的事件都是这个结构
+pub(crate) fn post_to_json(post: &runbot::event::Post) -> Result<Value, String> {
+    let mut value = serde_json::to_value(post).map_err(|e| format!("序列化事件失败: {}", e))?;
+    let mut kind = Vec::new();
+    loop {
+        let inner = match value.as_object() {
+            Some(object) if object.len() == 1 => object.iter().next().and_then(|(key, inner)| {
+                let typed = key.starts_with(|c: char| c.is_ascii_uppercase());
+                (typed && inner.is_object()).then(|| (key.clone(), inner.clone()))
+            }),
+            _ => None,
+        };
+        match inner {
+            Some((key, inner)) => {
+                kind.push(key);
+                value = inner;
+            }
+            None => break,
+        }
+    }
+    if let Some(object) = value.as_object_mut() {
+        object.insert("kind".to_string(), Value::String(kind.join(".")));
+    }
+    Ok(value)
+}
+
+fn post_to_dynamic(post: &runbot::event::Post) -> Result<Dynamic, String> {
+    rhai::serde::to_dynamic(post_to_json(post)?).map_err(|e| format!("转换事件失败: {}", e))
+}
+
+impl Script {
+    fn load(host: &ScriptHost, path: &Path, modified: Option<SystemTime>) -> Script {
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let mut script = Script {
+            name: name.clone(),
+            path: path.to_path_buf(),
+            modified,
+            error: None,
+            timeout: DEFAULT_TIMEOUT,
+            handles_posts: false,
+            runtime: None,
+            queue: Arc::new(PostQueue::default()),
+        };
+
+        let deadline = Arc::new(Mutex::new(Instant::now() + DEFAULT_TIMEOUT));
+        let engine = build_engine(host, &name, deadline.clone());
+        let result = engine
+            .compile_file(path.to_path_buf())
+            .map_err(|e| format!("编译失败: {}", e))
+            .and_then(|ast| {
+                // 执行顶层语句完成初始化；顶层的 timeout_ms 变量用于设置超时时间
+                let mut scope = Scope::new();
+                engine
+                    .run_ast_with_scope(&mut scope, &ast)
+                    .map_err(|e| format!("初始化失败: {}", e))?;
+                Ok((ast, scope))
+            });
+
+        match result {
+            Ok((ast, scope)) => {
+                if let Some(timeout_ms) = scope.get_value::<i64>("timeout_ms") {
+                    script.timeout = Duration::from_millis(timeout_ms.max(1) as u64);
+                }
+                script.handles_posts = ast
+                    .iter_functions()
+                    .any(|f| f.name == "on_post" && f.params.len() == 1);
+                script.runtime = Some(Mutex::new(ScriptRuntime {
+                    engine,
+                    ast,
+                    scope,
+                    state: Dynamic::from_map(Map::new()),
+                    deadline,
+                }));
+                host.sink.log(&name, "info", "脚本已加载".to_string());
+            }
+            Err(e) => {
+                host.sink.log(&name, "error", e.clone());
+                script.error = Some(e);
+            }
+        }
+        script
+    }
+
+    /// 调用 on_post（在阻塞线程中执行）
+    fn on_post(&self, post: Dynamic) -> Result<(), String> {
+        let Some(runtime) = &self.runtime else {
+            return Ok(());
+        };
+        let mut runtime = runtime.lock().unwrap();
+        let ScriptRuntime { engine, ast, scope, state, deadline } = &mut *runtime;
+        *deadline.lock().unwrap() = Instant::now() + self.timeout;
+        let options = CallFnOptions::new().eval_ast(false).bind_this_ptr(state);
+        engine
+            .call_fn_with_options::<Dynamic>(options, scope, ast, "on_post", (post,))
+            .map(|_| ())
+            .map_err(|e| match *e {
+                EvalAltResult::ErrorTerminated(..) => format!("执行超时（{} 毫秒）", self.timeout.as_millis()),
+                e => e.to_string(),
+            })
+    }
+
+    fn info(&self) -> ScriptInfo {
+        ScriptInfo {
+            name: self.name.clone(),
+            path: self.path.to_string_lossy().to_string(),
+            error: self.error.clone(),
+            timeout_ms: self.timeout.as_millis() as u64,
+            handles_posts: self.handles_posts,
+        }
+    }
+}
+
+/// 一个账号的脚本运行环境
+///
+/// 脚本放在用户数据目录的 scripts 文件夹中（*.rhai），文件新增、修改、删除后自动重新加载：
+/// 后台每隔 SCAN_INTERVAL 检查一次脚本目录，收到事件时也会检查
+pub struct ScriptHost {
+    account_id: i64,
+    dir: PathBuf,
+    registry: Arc<Mutex<RunbotState>>,
+    sink: LogSink,
+    scripts: Mutex<Vec<Arc<Script>>>,
+    last_scan: Mutex<Option<Instant>>,
+}
+
+impl std::fmt::Debug for ScriptHost {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ScriptHost")
+            .field("account_id", &self.account_id)
+            .field("dir", &self.dir)
+            .finish()
+    }
+}
+
+fn scripts_dir(app: &AppHandle, account_id: i64) -> Result<PathBuf, String> {
+    let mut path = storage::get_user_data_dir(app, Some(account_id))?;
+    path.push("scripts");
+    std::fs::create_dir_all(&path).map_err(|e| format!("创建脚本目录失败: {}", e))?;
+    Ok(path)
+}
+
+impl ScriptHost {
+    fn new(app: &AppHandle, registry: Arc<Mutex<RunbotState>>, account_id: i64) -> Result<Self, String> {
+        Ok(Self {
+            account_id,
+            dir: scripts_dir(app, account_id)?,
+            registry,
+            sink: LogSink {
+                app: app.clone(),
+                account_id,
+                logs: Arc::new(Mutex::new(VecDeque::new())),
+            },
+            scripts: Mutex::new(Vec::new()),
+            last_scan: Mutex::new(None),
+        })
+    }
+
+    /// 检查脚本目录，重新加载有变化的脚本；force 为 false 时两次检查至少间隔 SCAN_INTERVAL
+    fn scan(&self, force: bool) {
+        {
+            let mut last_scan = self.last_scan.lock().unwrap();
+            if !force && last_scan.is_some_and(|last| last.elapsed() < SCAN_INTERVAL) {
+                return;
+            }
+            *last_scan = Some(Instant::now());
+        }
+
+        let mut files: Vec<(PathBuf, Option<SystemTime>)> = match std::fs::read_dir(&self.dir) {
+            Ok(entries) => entries
+                .flatten()
+                .map(|entry| entry.path())
+                .filter(|path| path.extension().and_then(|ext| ext.to_str()) == Some("rhai"))
+                .map(|path| {
+                    let modified = std::fs::metadata(&path).and_then(|m| m.modified()).ok();
+                    (path, modified)
+                })
+                .collect(),
+            Err(e) => {
+                tracing::warn!("[scripting] 读取脚本目录失败 {:?}: {}", self.dir, e);
+                return;
+            }
+        };
+        files.sort();
+
+        let current = self.scripts.lock().unwrap().clone();
+        let unchanged = current.len() == files.len()
+            && current
+                .iter()
+                .zip(&files)
+                .all(|(script, (path, modified))| script.path == *path && script.modified == *modified);
+        if unchanged && !force {
+            return;
+        }
+
+        let scripts = files
+            .into_iter()
+            .map(|(path, modified)| {
+                match current.iter().find(|script| script.path == path && script.modified == modified) {
+                    Some(script) if !force => script.clone(),
+                    _ => Arc::new(Script::load(self, &path, modified)),
+                }
+            })
+            .collect();
+        *self.scripts.lock().unwrap() = scripts;
+    }
+
+    /// 后台定时检查脚本目录，没有事件时修改的脚本同样会重新加载；运行环境被释放后停止
+    fn spawn_watcher(self: &Arc<Self>) {
+        let host = Arc::downgrade(self);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(SCAN_INTERVAL);
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                interval.tick().await;
+                let Some(host) = host.upgrade() else {
+                    return;
+                };
+                let _ = tokio::task::spawn_blocking(move || host.scan(false)).await;
+            }
+        });
+    }
+
+    fn infos(&self) -> Vec<ScriptInfo> {
+        self.scripts.lock().unwrap().iter().map(|script| script.info()).collect()
+    }
+}
+
+/// 取得会话的脚本环境，没有时创建并加载脚本
+fn host_for(
+    app: &AppHandle,
+    registry: &Arc<Mutex<RunbotState>>,
+    session: &Mutex<Session>,
+    account_id: i64,
+) -> Result<Arc<ScriptHost>, String> {
+    let mut session_guard = session.lock().map_err(|e| format!("锁定会话失败: {}", e))?;
+    if let Some(host) = &session_guard.script_host {
+        return Ok(host.clone());
+    }
+    let host = Arc::new(ScriptHost::new(app, registry.clone(), account_id)?);
+    host.spawn_watcher();
+    session_guard.script_host = Some(host.clone());
+    Ok(host)
+}
+
+/// 重新检查会话中已加载的脚本（在阻塞线程中调用），尚未加载时不需要处理
+pub(crate) fn rescan_loaded(state: &Mutex<RunbotState>, account_id: i64) {
+    let session = state.lock().ok().and_then(|state_guard| state_guard.sessions.get(account_id));
+    let host = session.and_then(|session| session.lock().ok().and_then(|session_guard| session_guard.script_host.clone()));
+    if let Some(host) = host {
+        tracing::info!("[scripting] 重新加载脚本: {:?}", host.dir);
+        host.scan(true);
+    }
+}
+
+/// 脚本 Processor：把事件交给定义了 on_post(post) 的脚本处理
+#[derive(Debug)]
+pub struct ScriptProcessor {
+    app: AppHandle,
+    registry: Arc<Mutex<RunbotState>>,
+    state: Arc<Mutex<Session>>,
+}
+
+impl ScriptProcessor {
+    pub fn new(app: AppHandle, registry: Arc<Mutex<RunbotState>>, state: Arc<Mutex<Session>>) -> Self {
+        Self { app, registry, state }
+    }
+}
+
+#[async_trait]
+impl PostProcessor for ScriptProcessor {
+    fn id(&self) -> &'static str {
+        "script"
+    }
+
+    async fn process_post(
+        &self,
+        _bot_ctx: Arc<BotContext>,
+        post: &runbot::event::Post,
+    ) -> anyhow::Result<bool> {
+        if let runbot::event::Post::Response(_) = post {
+            return Ok(false);
+        }
+        let Some(account_id) = self.state.lock().unwrap().account_id else {
+            return Ok(false);
+        };
+        let host = match host_for(&self.app, &self.registry, &self.state, account_id) {
+            Ok(host) => host,
+            Err(e) => {
+                tracing::warn!("[ScriptProcessor] {}", e);
+                return Ok(false);
+            }
+        };
+
+        let scan_host = host.clone();
+        let _ = tokio::task::spawn_blocking(move || scan_host.scan(false)).await;
+        let scripts: Vec<Arc<Script>> = host
+            .scripts
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|script| script.handles_posts)
+            .cloned()
+            .collect();
+        if scripts.is_empty() {
+            return Ok(false);
+        }
+
+        let post = match post_to_dynamic(post) {
+            Ok(post) => post,
+            Err(e) => {
+                tracing::warn!("[ScriptProcessor] {}", e);
+                return Ok(false);
+            }
+        };
+        for script in scripts {
+            // 脚本在阻塞线程中按顺序执行，不影响其他事件的处理
+            let queue = script.queue.clone();
+            let sink = host.sink.clone();
+            let name = script.name.clone();
+            let queued = queue.push(post.clone(), move |post| {
+                if let Err(e) = script.on_post(post) {
+                    sink.log(&script.name, "error", format!("on_post 执行失败: {}", e));
+                }
+            });
+            if !queued {
+                host.sink.log(&name, "error", format!("待处理的事件超过 {} 条，已丢弃新事件", MAX_QUEUED_POSTS));
+            }
+        }
+        Ok(false)
+    }
+}
+
+fn connected_session(state: &Mutex<RunbotState>, account_id: i64) -> Result<Arc<Mutex<Session>>, String> {
+    state
+        .lock()
+        .map_err(|e| format!("锁定状态失败: {}", e))?
+        .sessions
+        .get(account_id)
+        .ok_or_else(|| format!("账号 {} 未连接", account_id))
+}
+
+/// 获取账号的脚本目录
+#[tauri::command]
+pub async fn get_scripts_dir(account_id: i64, app: AppHandle) -> Result<String, String> {
+    Ok(scripts_dir(&app, account_id)?.to_string_lossy().to_string())
+}
+
+/// 获取已加载的脚本（账号需已连接）
+#[tauri::command]
+pub async fn list_scripts(
+    account_id: i64,
+    app: AppHandle,
+    state: State<'_, Arc<Mutex<RunbotState>>>,
+) -> Result<Vec<ScriptInfo>, String> {
+    let session = connected_session(&state, account_id)?;
+    let host = host_for(&app, state.inner(), &session, account_id)?;
+    tokio::task::spawn_blocking(move || {
+        host.scan(false);
+        host.infos()
+    })
+    .await
+    .map_err(|e| format!("加载脚本失败: {}", e))
+}
+
+/// 重新加载全部脚本（脚本状态会被重置）
+#[tauri::command]
+pub async fn reload_scripts(
+    account_id: i64,
+    app: AppHandle,
+    state: State<'_, Arc<Mutex<RunbotState>>>,
+) -> Result<Vec<ScriptInfo>, String> {
+    let session = connected_session(&state, account_id)?;
+    let host = host_for(&app, state.inner(), &session, account_id)?;
+    tracing::info!("[reload_scripts] 重新加载脚本: {:?}", host.dir);
+    let scripts = tokio::task::spawn_blocking(move || {
+        host.scan(true);
+        host.infos()
+    })
+    .await
+    .map_err(|e| format!("加载脚本失败: {}", e))?;
+    daemon::notify_daemon(&app, &state, account_id, ReloadTarget::Scripts).await?;
+    Ok(scripts)
+}
+
+/// 获取最近的脚本日志
+#[tauri::command]
+pub async fn get_script_logs(
+    account_id: i64,
+    state: State<'_, Arc<Mutex<RunbotState>>>,
+) -> Result<Vec<ScriptLog>, String> {
+    let session = connected_session(&state, account_id)?;
+    let host = session
+        .lock()
+        .map_err(|e| format!("锁定会话失败: {}", e))?
+        .script_host
+        .clone();
+    Ok(host
+        .map(|host| host.sink.logs.lock().unwrap().iter().cloned().collect())
+        .unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use super::*;
+    use crate::mock_onebot::{MockConfig, MockServer};
+    use crate::test_support::{self, TestApp};
+
+    fn write_script(host: &ScriptHost, name: &str, source: &str) -> PathBuf {
+        let path = host.dir.join(name);
+        std::fs::write(&path, source).unwrap();
+        path
+    }
+
+    /// 在阻塞线程中执行（脚本引擎需要 tokio runtime handle 调用 OneBot API）
+    async fn blocking<T: Send + 'static>(f: impl FnOnce() -> T + Send + 'static) -> T {
+        tokio::task::spawn_blocking(f).await.unwrap()
+    }
+
+    fn script(host: &ScriptHost, name: &str) -> Arc<Script> {
+        host.scripts.lock().unwrap().iter().find(|script| script.name == name).cloned().unwrap()
+    }
+
+    #[tokio::test]
+    async fn stops_infinite_loop_at_deadline() {
+        let app = TestApp::new();
+        let host = Arc::new(ScriptHost::new(app.handle(), app.registry.clone(), 10001).unwrap());
+        write_script(&host, "loop.rhai", "let timeout_ms = 100;\nfn on_post(post) { loop {} }");
+
+        let scan_host = host.clone();
+        blocking(move || scan_host.scan(true)).await;
+        let script = script(&host, "loop.rhai");
+        assert_eq!(script.timeout, Duration::from_millis(100));
+
+        let started = Instant::now();
+        let result = blocking(move || script.on_post(Dynamic::UNIT)).await;
+        assert_eq!(result.unwrap_err(), "执行超时（100 毫秒）");
+        assert!(started.elapsed() < Duration::from_secs(2));
+    }
+
+    #[tokio::test]
+    async fn call_action_waits_only_for_remaining_time() {
+        let server = MockServer::bind("127.0.0.1", 0, MockConfig::default()).await.unwrap();
+        server.set_silent("get_status");
+        let app = TestApp::new();
+        let session = app.connect(&server).await;
+        let host = Arc::new(ScriptHost::new(app.handle(), app.registry.clone(), server.self_id()).unwrap());
+        write_script(&host, "call.rhai", "let timeout_ms = 300;\nfn on_post(post) { call_action(\"get_status\"); }");
+
+        let scan_host = host.clone();
+        blocking(move || scan_host.scan(true)).await;
+        let script = script(&host, "call.rhai");
+        let started = Instant::now();
+        let result = blocking(move || script.on_post(Dynamic::UNIT)).await;
+        // 服务器不回复，脚本在自己的超时时间到达时结束，而不是等到 OneBot 调用本身超时
+        assert_eq!(result.unwrap_err(), "执行超时（300 毫秒）");
+        assert!(started.elapsed() >= Duration::from_millis(300));
+        assert!(started.elapsed() < Duration::from_secs(2));
+        assert_eq!(test_support::wait_for_calls(&server, "get_status", 1).await.len(), 1);
+
+        test_support::shutdown(&session).await;
+    }
+
+    #[tokio::test]
+    async fn rescan_reloads_changed_files() {
+        let app = TestApp::new();
+        let host = Arc::new(ScriptHost::new(app.handle(), app.registry.clone(), 10001).unwrap());
+        let changed = write_script(&host, "a.rhai", "let x = 1;");
+        write_script(&host, "b.rhai", "fn on_post(post) {}");
+        let removed = write_script(&host, "c.rhai", "let x = 1;");
+
+        let scan_host = host.clone();
+        blocking(move || scan_host.scan(true)).await;
+        assert_eq!(host.infos().len(), 3);
+        assert!(!script(&host, "a.rhai").handles_posts);
+        let unchanged = script(&host, "b.rhai");
+
+        // 修改时间变化的文件重新加载，新增的加载，删除的移除，没有变化的保持原来的状态
+        std::fs::write(&changed, "fn on_post(post) {}").unwrap();
+        let modified = SystemTime::now() + Duration::from_secs(10);
+        std::fs::File::options().write(true).open(&changed).unwrap().set_modified(modified).unwrap();
+        std::fs::remove_file(&removed).unwrap();
+        write_script(&host, "d.rhai", "fn on_post(post) {");
+
+        // 检查间隔内不重新扫描
+        let scan_host = host.clone();
+        blocking(move || scan_host.scan(false)).await;
+        assert_eq!(host.infos().len(), 3);
+
+        *host.last_scan.lock().unwrap() = None;
+        let scan_host = host.clone();
+        blocking(move || scan_host.scan(false)).await;
+        let names: Vec<String> = host.infos().into_iter().map(|info| info.name).collect();
+        assert_eq!(names, ["a.rhai", "b.rhai", "d.rhai"]);
+        assert!(script(&host, "a.rhai").handles_posts);
+        assert!(Arc::ptr_eq(&script(&host, "b.rhai"), &unchanged));
+        assert!(script(&host, "d.rhai").error.as_ref().unwrap().starts_with("编译失败"));
+    }
+
+    #[tokio::test]
+    async fn queue_handles_posts_in_order_and_drops_backlog() {
+        let queue = Arc::new(PostQueue::default());
+        let (release_tx, release_rx) = std::sync::mpsc::channel::<()>();
+        let release_rx = Arc::new(Mutex::new(release_rx));
+        let handled = Arc::new(Mutex::new(Vec::new()));
+        let running = Arc::new(AtomicUsize::new(0));
+
+        let handler = || {
+            let release_rx = release_rx.clone();
+            let handled = handled.clone();
+            let running = running.clone();
+            move |post: usize| {
+                assert_eq!(running.fetch_add(1, Ordering::SeqCst), 0, "同一队列不能并发处理");
+                release_rx.lock().unwrap().recv().unwrap();
+                handled.lock().unwrap().push(post);
+                running.fetch_sub(1, Ordering::SeqCst);
+            }
+        };
+        // 第一个事件被取出处理后，队列中最多再积压 MAX_QUEUED_POSTS 个
+        assert!(queue.push(0, handler()));
+        while !queue.state.lock().unwrap().posts.is_empty() {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        for post in 1..=MAX_QUEUED_POSTS {
+            assert!(queue.push(post, handler()));
+        }
+        assert!(!queue.push(MAX_QUEUED_POSTS + 1, handler()));
+
+        for _ in 0..=MAX_QUEUED_POSTS {
+            release_tx.send(()).unwrap();
+        }
+        let wait = async {
+            while queue.state.lock().unwrap().running {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+        };
+        tokio::time::timeout(test_support::TIMEOUT, wait).await.unwrap();
+        assert_eq!(*handled.lock().unwrap(), (0..=MAX_QUEUED_POSTS).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,153 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use runbot::prelude::*;
+use crate::autoreply::LoadedRule;
+use crate::connection::ConnectionTracker;
+use crate::plugin::PluginHost;
+use crate::recorder::Recorder;
+use crate::relay::RelayServer;
+use crate::scripting::ScriptHost;
+use crate::storage::ReplayDir;
+use tokio::sync::{mpsc, Notify};
+use crate::supervisor::{SupervisorHandle, UpstreamStream};
+
+/// 单个账号的连接会话
+#[derive(Debug, Clone, Default)]
+pub struct Session {
+    pub account_id: Option<i64>, // 首次认证后确定的账号（self_id），之后不再改变
+    pub connection: ConnectionTracker, // 连接状态（只能通过 connection::transition 修改）
+    pub bot_ctx: Option<Arc<BotContext>>,
+    pub supervisor: Option<Arc<SupervisorHandle>>, // 负责重连的后台任务
+    pub attached: bool, // 附加到后台服务的会话：事件、发送队列和定时消息由后台服务处理
+    pub incoming: Option<mpsc::UnboundedSender<UpstreamStream>>, // 反向连接会话：用于交付重新接入的连接
+    pub outbox_flush: Option<bool>, // 发送队列：Some 表示正在发送，为 true 时发送完后需要再检查一次
+    pub scheduler: Option<Arc<Notify>>, // 定时任务调度：Some 表示调度循环正在运行，通知后重新检查到期任务
+    pub replay_dir: Option<Arc<ReplayDir>>, // 回放会话：账号数据改为读写的临时目录，会话结束时删除
+    pub recorder: Option<Arc<Recorder>>, // 流量录制：Some 表示正在录制上游收发的原始帧
+    pub relay: Option<Arc<RelayServer>>, // 中继服务器：Some 表示正在把事件转发给下游客户端
+    pub auto_reply_rules: Option<Arc<Vec<LoadedRule>>>, // 启用的自动回复规则缓存，None 表示需要从数据库重新加载
+    pub script_host: Option<Arc<ScriptHost>>, // 脚本运行环境，收到第一个事件或打开脚本页面时创建
+    pub plugin_host: Option<Arc<PluginHost>>, // WASM 插件运行环境，创建方式同上
+}
+
+/// 会话注册表：已认证的会话按 self_id 索引，未完成首次认证的会话单独存放
+#[derive(Debug, Default)]
+pub struct SessionRegistry {
+    sessions: HashMap<i64, Arc<Mutex<Session>>>,
+    pending: Vec<Arc<Mutex<Session>>>,
+}
+
+impl SessionRegistry {
+    /// 添加一个尚未认证的会话
+    pub fn add_pending(&mut self, session: Arc<Mutex<Session>>) {
+        self.pending.push(session);
+    }
+
+    /// 首次认证成功后，把会话登记到对应账号下；该账号已有其他会话时返回错误
+    pub fn register(&mut self, session: &Arc<Mutex<Session>>, account_id: i64) -> Result<(), String> {
+        if let Some(existing) = self.sessions.get(&account_id) {
+            if !Arc::ptr_eq(existing, session) {
+                return Err(format!("账号 {} 已在其他会话中连接", account_id));
+            }
+        }
+        self.pending.retain(|s| !Arc::ptr_eq(s, session));
+        self.sessions.insert(account_id, session.clone());
+        Ok(())
+    }
+
+    /// 移除会话（无论是否已认证）
+    pub fn remove(&mut self, session: &Arc<Mutex<Session>>) {
+        self.pending.retain(|s| !Arc::ptr_eq(s, session));
+        self.sessions.retain(|_, s| !Arc::ptr_eq(s, session));
+    }
+
+    /// 按账号取出已认证的会话
+    pub fn take(&mut self, account_id: i64) -> Option<Arc<Mutex<Session>>> {
+        self.sessions.remove(&account_id)
+    }
+
+    /// 取出所有未完成首次认证的会话
+    pub fn take_pending(&mut self) -> Vec<Arc<Mutex<Session>>> {
+        std::mem::take(&mut self.pending)
+    }
+
+    /// 停止接收反向连接：等待重新接入的反向会话会因此结束
+    pub fn close_incoming(&self) {
+        for session in self.all() {
+            if let Ok(mut session_guard) = session.lock() {
+                session_guard.incoming = None;
+            }
+        }
+    }
+
+    pub fn get(&self, account_id: i64) -> Option<Arc<Mutex<Session>>> {
+        self.sessions.get(&account_id).cloned()
+    }
+
+    /// 已认证的账号（按账号排序）
+    pub fn accounts(&self) -> Vec<i64> {
+        let mut account_ids: Vec<i64> = self.sessions.keys().copied().collect();
+        account_ids.sort();
+        account_ids
+    }
+
+    /// 所有会话（已认证的按账号排序，未认证的排在最后）
+    pub fn all(&self) -> Vec<Arc<Mutex<Session>>> {
+        self.accounts()
+            .into_iter()
+            .filter_map(|id| self.sessions.get(&id).cloned())
+            .chain(self.pending.iter().cloned())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session_for(account_id: i64) -> Arc<Mutex<Session>> {
+        Arc::new(Mutex::new(Session { account_id: Some(account_id), ..Default::default() }))
+    }
+
+    #[test]
+    fn keeps_accounts_separate() {
+        let mut registry = SessionRegistry::default();
+        let first = session_for(10002);
+        let second = session_for(10001);
+        registry.add_pending(first.clone());
+        registry.add_pending(second.clone());
+        registry.register(&first, 10002).unwrap();
+        registry.register(&second, 10001).unwrap();
+
+        assert_eq!(registry.accounts(), vec![10001, 10002]);
+        assert!(Arc::ptr_eq(&registry.get(10001).unwrap(), &second));
+        assert!(Arc::ptr_eq(&registry.get(10002).unwrap(), &first));
+        assert!(registry.take_pending().is_empty());
+
+        // 同一账号不能被另一个会话占用，重复登记同一个会话则没有影响
+        assert!(registry.register(&session_for(10001), 10001).is_err());
+        registry.register(&second, 10001).unwrap();
+        assert!(Arc::ptr_eq(&registry.get(10001).unwrap(), &second));
+    }
+
+    #[test]
+    fn removing_one_account_keeps_the_other() {
+        let mut registry = SessionRegistry::default();
+        let first = session_for(10001);
+        let second = session_for(10002);
+        let pending = Arc::new(Mutex::new(Session::default()));
+        registry.register(&first, 10001).unwrap();
+        registry.register(&second, 10002).unwrap();
+        registry.add_pending(pending.clone());
+
+        registry.remove(&first);
+        assert_eq!(registry.accounts(), vec![10002]);
+        assert!(registry.get(10001).is_none());
+        assert!(Arc::ptr_eq(&registry.get(10002).unwrap(), &second));
+        assert_eq!(registry.all().len(), 2);
+
+        assert!(Arc::ptr_eq(&registry.take(10002).unwrap(), &second));
+        assert!(registry.accounts().is_empty());
+        assert!(Arc::ptr_eq(&registry.take_pending()[0], &pending));
+    }
+}
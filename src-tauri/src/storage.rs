@@ -1,7 +1,10 @@
-use tauri::{AppHandle, Manager};
-use rusqlite::{Connection, Result as SqlResult, params};
+use tauri::Manager;
+use crate::AppHandle;
+use rusqlite::{Connection, OptionalExtension, Result as SqlResult, params};
 use serde_json::Value;
+use std::collections::HashMap;
 use std::path::PathBuf;
+use std::sync::{Arc, Mutex, Weak};
 use sha2::{Sha256, Digest};
 use base64::{Engine as _, engine::general_purpose};
 
@@ -17,8 +20,63 @@ fn ensure_app_data_dir(app: &AppHandle) -> Result<PathBuf, String> {
     Ok(dir)
 }
 
+/// 回放使用的临时数据目录：回放的事件不能写进该账号真实的数据库
+///
+/// 在应用数据目录的 replay 子目录下随机命名，由回放会话持有，释放时删除
+#[derive(Debug)]
+pub struct ReplayDir {
+    path: PathBuf,
+}
+
+impl ReplayDir {
+    /// 为回放的账号创建临时数据目录，在返回值释放前该账号的数据都读写这个目录
+    pub(crate) fn create(app: &AppHandle, self_id: i64) -> Result<Arc<Self>, String> {
+        let dirs = app
+            .try_state::<ReplayDirs>()
+            .ok_or_else(|| "回放数据目录索引未初始化".to_string())?;
+        let mut path = ensure_app_data_dir(app)?;
+        path.push("replay");
+        std::fs::create_dir_all(&path)
+            .map_err(|e| format!("创建回放数据目录失败: {}", e))?;
+        path.push(format!("user_{}_{}", self_id, hex::encode(rand::random::<[u8; 8]>())));
+        std::fs::create_dir(&path)
+            .map_err(|e| format!("创建回放数据目录失败: {}", e))?;
+
+        let dir = Arc::new(Self { path });
+        dirs.0.lock().unwrap().insert(self_id, Arc::downgrade(&dir));
+        Ok(dir)
+    }
+}
+
+impl Drop for ReplayDir {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_dir_all(&self.path) {
+            tracing::warn!("删除回放数据目录失败: {}", e);
+        }
+    }
+}
+
+/// 正在回放的账号 -> 回放会话持有的数据目录
+///
+/// 只用于按账号查找，目录本身随会话释放；查找时不需要锁定会话，避免和持有会话锁的调用方互相等待
+#[derive(Debug, Default)]
+pub struct ReplayDirs(Mutex<HashMap<i64, Weak<ReplayDir>>>);
+
+fn replay_dir(app: &AppHandle, self_id: i64) -> Option<PathBuf> {
+    let dirs = app.try_state::<ReplayDirs>()?;
+    let mut dirs = dirs.0.lock().unwrap();
+    let dir = dirs.get(&self_id)?.upgrade();
+    if dir.is_none() {
+        dirs.remove(&self_id);
+    }
+    dir.map(|dir| dir.path.clone())
+}
+
 /// 获取用户数据目录（根据 self_id）
-fn get_user_data_dir(app: &AppHandle, self_id: Option<i64>) -> Result<PathBuf, String> {
+pub(crate) fn get_user_data_dir(app: &AppHandle, self_id: Option<i64>) -> Result<PathBuf, String> {
+    if let Some(path) = self_id.and_then(|uid| replay_dir(app, uid)) {
+        return Ok(path);
+    }
     let mut path = ensure_app_data_dir(app)?;
     
     if let Some(uid) = self_id {
@@ -37,6 +95,27 @@ fn get_db_path(app: &AppHandle, self_id: Option<i64>) -> Result<PathBuf, String>
     Ok(path)
 }
 
+/// 请求表结构（旧版本迁移时也使用这里的定义重建）
+const REQUESTS_TABLE_SQL: &str = "CREATE TABLE IF NOT EXISTS requests (
+    id TEXT PRIMARY KEY,
+    timestamp INTEGER NOT NULL,
+    request_type TEXT NOT NULL,
+    sub_type TEXT,
+    user_id INTEGER NOT NULL,
+    user_name TEXT NOT NULL,
+    nickname TEXT,
+    comment TEXT NOT NULL,
+    flag TEXT NOT NULL UNIQUE,
+    group_id INTEGER,
+    group_name TEXT,
+    status TEXT NOT NULL DEFAULT 'pending',
+    is_read INTEGER NOT NULL DEFAULT 0,
+    created_at INTEGER DEFAULT (strftime('%s', 'now'))
+)";
+
+/// 待处理请求在服务器端失效的时间（秒），超过后标记为 expired
+const REQUEST_EXPIRE_SECS: i64 = 7 * 24 * 60 * 60;
+
 /// 初始化数据库（创建表和索引）
 fn init_database(conn: &Connection) -> SqlResult<()> {
     // 创建消息表（使用 localMessageId 作为主键）
@@ -116,30 +195,39 @@ fn init_database(conn: &Connection) -> SqlResult<()> {
         [],
     )?;
 
-    // 创建请求表
-    // 注意: 使用 user_id 和 group_id 组合来确保唯一性
-    // 好友请求: user_id 唯一 (group_id 为 NULL)
-    // 群请求: (group_id, user_id) 组合唯一
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS requests (
-            id TEXT PRIMARY KEY,
-            timestamp INTEGER NOT NULL,
-            request_type TEXT NOT NULL,
-            sub_type TEXT,
-            user_id INTEGER NOT NULL,
-            user_name TEXT NOT NULL,
-            nickname TEXT,
-            comment TEXT NOT NULL,
-            flag TEXT NOT NULL,
-            group_id INTEGER,
-            group_name TEXT,
-            status TEXT NOT NULL DEFAULT 'pending',
-            is_read INTEGER NOT NULL DEFAULT 0,
-            created_at INTEGER DEFAULT (strftime('%s', 'now')),
-            UNIQUE(user_id, group_id)
-        )",
+    // 同一 message_id 的消息只保留一条（并发写入、重连补发时由唯一索引保证）
+    // 旧数据库可能已有重复记录，首次建索引前保留最早的一条
+    let unique_index_exists: bool = conn.query_row(
+        "SELECT COUNT(*) FROM sqlite_master WHERE type = 'index' AND name = 'idx_messages_message_id'",
         [],
-    )?;
+        |row| row.get(0),
+    ).unwrap_or(0) > 0;
+
+    if !unique_index_exists {
+        // 残留的全文搜索索引项没有对应的 rowid 映射，搜索时不会被关联到
+        conn.execute_batch(
+            "BEGIN;
+             CREATE TEMP TABLE duplicate_messages AS
+                 SELECT local_message_id FROM messages
+                 WHERE message_id IS NOT NULL AND post_type IN ('message', 'message_sent')
+                   AND rowid NOT IN (
+                       SELECT MIN(rowid) FROM messages
+                       WHERE message_id IS NOT NULL AND post_type IN ('message', 'message_sent')
+                       GROUP BY message_id
+                   );
+             DELETE FROM messages_rowid_map
+             WHERE local_message_id IN (SELECT local_message_id FROM duplicate_messages);
+             DELETE FROM messages
+             WHERE local_message_id IN (SELECT local_message_id FROM duplicate_messages);
+             DROP TABLE duplicate_messages;
+             CREATE UNIQUE INDEX IF NOT EXISTS idx_messages_message_id ON messages(message_id)
+             WHERE message_id IS NOT NULL AND post_type IN ('message', 'message_sent');
+             COMMIT;",
+        )?;
+    }
+
+    // 创建请求表（每个 flag 一行，同一用户的多次请求都会保留）
+    conn.execute(REQUESTS_TABLE_SQL, [])?;
 
     // 检查并添加 is_read 字段（如果表已存在但没有该字段）
     let is_read_exists: bool = conn.query_row(
@@ -155,6 +243,86 @@ fn init_database(conn: &Connection) -> SqlResult<()> {
         )?;
     }
 
+    // 旧版本的请求表使用 UNIQUE(user_id, group_id)，新请求会覆盖旧请求，这里重建为按 flag 唯一
+    let legacy_unique: bool = conn.query_row(
+        "SELECT COUNT(*) FROM sqlite_master
+         WHERE type = 'table' AND name = 'requests' AND sql LIKE '%UNIQUE(user_id, group_id)%'",
+        [],
+        |row| row.get(0),
+    ).unwrap_or(0) > 0;
+
+    if legacy_unique {
+        conn.execute_batch(&format!(
+            "BEGIN;
+             ALTER TABLE requests RENAME TO requests_legacy;
+             {};
+             INSERT OR IGNORE INTO requests (
+                 id, timestamp, request_type, sub_type, user_id, user_name, nickname,
+                 comment, flag, group_id, group_name, status, is_read, created_at
+             )
+             SELECT id, timestamp, request_type, sub_type, user_id, user_name, nickname,
+                 comment, flag, group_id, group_name, status, is_read, created_at
+             FROM requests_legacy;
+             DROP TABLE requests_legacy;
+             COMMIT;",
+            REQUESTS_TABLE_SQL
+        ))?;
+    }
+
+    // 检查并添加 names_resolved 字段（用户名、群名是否已查询到，未查到时使用占位名称）
+    let names_resolved_exists: bool = conn.query_row(
+        "SELECT COUNT(*) FROM pragma_table_info('requests') WHERE name='names_resolved'",
+        [],
+        |row| row.get(0),
+    ).unwrap_or(0) > 0;
+    
+    if !names_resolved_exists {
+        conn.execute(
+            "ALTER TABLE requests ADD COLUMN names_resolved INTEGER NOT NULL DEFAULT 0",
+            [],
+        )?;
+    }
+
+    // 自动审核记录：命中的规则、处理方式、执行失败时的错误
+    // 处理记录：最后一次状态变更的处理人（manual、rule:规则名、server）和时间
+    for (column, definition) in [
+        ("review_rule", "TEXT"),
+        ("review_action", "TEXT"),
+        ("review_error", "TEXT"),
+        ("handled_by", "TEXT"),
+        ("handled_at", "INTEGER"),
+    ] {
+        let exists: bool = conn.query_row(
+            "SELECT COUNT(*) FROM pragma_table_info('requests') WHERE name=?1",
+            params![column],
+            |row| row.get(0),
+        ).unwrap_or(0) > 0;
+        if !exists {
+            conn.execute(
+                &format!("ALTER TABLE requests ADD COLUMN {} {}", column, definition),
+                [],
+            )?;
+        }
+    }
+
+    // 创建请求状态变更记录表
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS request_transitions (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            flag TEXT NOT NULL,
+            from_status TEXT,
+            to_status TEXT NOT NULL,
+            handled_by TEXT,
+            note TEXT,
+            created_at INTEGER DEFAULT (strftime('%s', 'now'))
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_request_transitions_flag ON request_transitions(flag)",
+        [],
+    )?;
+
     // 创建请求表索引
     conn.execute(
         "CREATE INDEX IF NOT EXISTS idx_requests_timestamp ON requests(timestamp DESC)",
@@ -177,11 +345,145 @@ fn init_database(conn: &Connection) -> SqlResult<()> {
         [],
     )?;
 
+    // 创建通知表（time 为事件自带的时间，data 为完整的通知 JSON）
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS notices (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            time INTEGER NOT NULL,
+            notice_type TEXT NOT NULL,
+            sub_type TEXT,
+            group_id INTEGER,
+            user_id INTEGER,
+            operator_id INTEGER,
+            data TEXT NOT NULL,
+            created_at INTEGER DEFAULT (strftime('%s', 'now'))
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_notices_time ON notices(time DESC)",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_notices_group_id ON notices(group_id)",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_notices_user_id ON notices(user_id)",
+        [],
+    )?;
+
+    // 创建发送队列表（按 id 顺序发送）
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS outbox (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            local_message_id TEXT NOT NULL UNIQUE,
+            action TEXT NOT NULL,
+            params TEXT NOT NULL,
+            state TEXT NOT NULL DEFAULT 'pending',
+            attempts INTEGER NOT NULL DEFAULT 0,
+            last_error TEXT,
+            message_id INTEGER,
+            created_at INTEGER DEFAULT (strftime('%s', 'now')),
+            updated_at INTEGER DEFAULT (strftime('%s', 'now'))
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_outbox_state ON outbox(state)",
+        [],
+    )?;
+
+    // 创建自动回复规则表（按 priority 从高到低匹配）
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS auto_reply_rules (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL,
+            enabled INTEGER NOT NULL DEFAULT 1,
+            match_type TEXT NOT NULL,
+            pattern TEXT NOT NULL DEFAULT '',
+            ignore_case INTEGER NOT NULL DEFAULT 0,
+            scope TEXT NOT NULL DEFAULT 'all',
+            target_ids TEXT NOT NULL DEFAULT '[]',
+            reply TEXT NOT NULL,
+            at_sender INTEGER NOT NULL DEFAULT 0,
+            quote INTEGER NOT NULL DEFAULT 0,
+            cooldown_secs INTEGER NOT NULL DEFAULT 0,
+            priority INTEGER NOT NULL DEFAULT 0,
+            created_at INTEGER DEFAULT (strftime('%s', 'now')),
+            updated_at INTEGER DEFAULT (strftime('%s', 'now'))
+        )",
+        [],
+    )?;
+
+    // 创建定时消息任务表（run_at 与 cron 二选一，next_run_at 为下次发送时间）
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS scheduled_jobs (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL DEFAULT '',
+            target_type TEXT NOT NULL,
+            target_id INTEGER NOT NULL,
+            message TEXT NOT NULL,
+            run_at INTEGER,
+            cron TEXT,
+            misfire TEXT NOT NULL DEFAULT 'catch_up',
+            state TEXT NOT NULL DEFAULT 'scheduled',
+            next_run_at INTEGER,
+            last_run_at INTEGER,
+            last_local_message_id TEXT,
+            last_error TEXT,
+            run_count INTEGER NOT NULL DEFAULT 0,
+            created_at INTEGER DEFAULT (strftime('%s', 'now')),
+            updated_at INTEGER DEFAULT (strftime('%s', 'now'))
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_scheduled_jobs_next_run ON scheduled_jobs(state, next_run_at)",
+        [],
+    )?;
+
+    // 旧的插件授权按清单中的 id 记录，任何插件都可以声明同样的 id 冒用授权，需要重新授权
+    let legacy_plugins: bool = conn.query_row(
+        "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = 'plugins'
+            AND NOT EXISTS (SELECT 1 FROM pragma_table_info('plugins') WHERE name = 'fingerprint')",
+        [],
+        |row| row.get(0),
+    ).unwrap_or(0) > 0;
+    if legacy_plugins {
+        conn.execute("DROP TABLE plugins", [])?;
+    }
+
+    // 创建插件授权表：按插件目录名记录，fingerprint 为用户同意时清单和模块的 SHA-256，
+    // permissions 为当时清单中声明的权限
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS plugins (
+            plugin_id TEXT PRIMARY KEY,
+            fingerprint TEXT NOT NULL,
+            enabled INTEGER NOT NULL DEFAULT 0,
+            permissions TEXT NOT NULL,
+            updated_at INTEGER DEFAULT (strftime('%s', 'now'))
+        )",
+        [],
+    )?;
+
+    // 创建插件键值存储表（plugin_id 为插件目录名）
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS plugin_storage (
+            plugin_id TEXT NOT NULL,
+            key TEXT NOT NULL,
+            value BLOB NOT NULL,
+            updated_at INTEGER DEFAULT (strftime('%s', 'now')),
+            PRIMARY KEY (plugin_id, key)
+        )",
+        [],
+    )?;
+
     Ok(())
 }
 
 /// 获取数据库连接（用户特定）
-fn get_connection(app: &AppHandle, self_id: Option<i64>) -> Result<Connection, String> {
+pub(crate) fn get_connection(app: &AppHandle, self_id: Option<i64>) -> Result<Connection, String> {
     let db_path = get_db_path(app, self_id)?;
     
     tracing::info!("🔌 get_connection: self_id={:?}, db_path={:?}", self_id, db_path);
@@ -189,6 +491,12 @@ fn get_connection(app: &AppHandle, self_id: Option<i64>) -> Result<Connection, S
     let conn = Connection::open(&db_path)
         .map_err(|e| format!("打开数据库失败: {}", e))?;
     
+    // WAL 模式下读取不阻塞写入（命令行工具、附加到后台服务的界面会同时打开数据库）
+    conn.pragma_update(None, "journal_mode", "WAL")
+        .map_err(|e| format!("设置 WAL 模式失败: {}", e))?;
+    conn.busy_timeout(std::time::Duration::from_secs(5))
+        .map_err(|e| format!("设置等待超时失败: {}", e))?;
+    
     // 初始化数据库（如果还没有初始化）
     init_database(&conn)
         .map_err(|e| format!("初始化数据库失败: {}", e))?;
@@ -283,9 +591,15 @@ pub async fn save_message(
     app: AppHandle,
 ) -> Result<String, String> {
     let conn = get_connection(&app, self_id)?;
-    
+    insert_message(&conn, &message_data)
+}
+
+/// 写入一条消息（消息 JSON 中必须带 localMessageId）
+///
+/// localMessageId 已存在时更新该记录；message_id 与已有消息重复时不写入，返回已有消息的 localMessageId
+fn insert_message(conn: &Connection, message_data: &str) -> Result<String, String> {
     // 解析 JSON 数据
-    let msg: Value = serde_json::from_str(&message_data)
+    let msg: Value = serde_json::from_str(message_data)
         .map_err(|e| format!("解析 JSON 失败: {}", e))?;
     
     // 获取或生成 localMessageId
@@ -306,12 +620,23 @@ pub async fn save_message(
     let content = msg["message"].as_str().map(|s| s.to_string());
     let raw_message = msg["raw_message"].as_str().map(|s| s.to_string());
     
-    // 插入或更新消息（使用 INSERT OR REPLACE）
-    conn.execute(
-        "INSERT OR REPLACE INTO messages (
+    // 插入或更新消息（按 localMessageId 更新，message_id 重复时忽略）
+    let inserted = conn.execute(
+        "INSERT INTO messages (
             local_message_id, timestamp, post_type, message_type, user_id, group_id,
             message_id, content, raw_message, data
-        ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+        ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)
+        ON CONFLICT(local_message_id) DO UPDATE SET
+            timestamp = excluded.timestamp,
+            post_type = excluded.post_type,
+            message_type = excluded.message_type,
+            user_id = excluded.user_id,
+            group_id = excluded.group_id,
+            message_id = excluded.message_id,
+            content = excluded.content,
+            raw_message = excluded.raw_message,
+            data = excluded.data
+        ON CONFLICT DO NOTHING",
         params![
             local_message_id,
             timestamp,
@@ -326,6 +651,19 @@ pub async fn save_message(
         ],
     )
     .map_err(|e| format!("插入消息失败: {}", e))?;
+
+    if inserted == 0 {
+        if let Some(message_id) = message_id {
+            return conn
+                .query_row(
+                    "SELECT local_message_id FROM messages
+                     WHERE message_id = ?1 AND post_type IN ('message', 'message_sent')",
+                    params![message_id],
+                    |row| row.get::<_, String>(0),
+                )
+                .map_err(|e| format!("查询消息失败: {}", e));
+        }
+    }
     
     // 获取或创建 rowid 映射
     let row_id = match conn.query_row::<i64, _, _>(
@@ -374,6 +712,42 @@ pub async fn save_message(
     Ok(local_message_id)
 }
 
+/// 生成本地消息 ID（UUID v4 格式，与前端生成的格式保持一致）
+fn new_local_message_id() -> String {
+    let mut bytes: [u8; 16] = rand::random();
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex = hex::encode(bytes);
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[0..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..32]
+    )
+}
+
+/// 持久化收到的事件（消息、通知），返回 localMessageId
+///
+/// 带 message_id 的消息按 message_id 去重（唯一索引）：已存在时直接返回已有记录的 localMessageId，
+/// 避免重连补发或自身发送回显导致重复入库。
+pub(crate) fn store_event(
+    app: &AppHandle,
+    self_id: i64,
+    data: &mut Value,
+) -> Result<String, String> {
+    let conn = get_connection(app, Some(self_id))?;
+
+    let local_message_id = new_local_message_id();
+    if let Some(obj) = data.as_object_mut() {
+        obj.insert("localMessageId".to_string(), Value::String(local_message_id.clone()));
+    }
+    let message_data = serde_json::to_string(data)
+        .map_err(|e| format!("序列化消息数据失败: {}", e))?;
+    insert_message(&conn, &message_data)
+}
+
 /// 更新消息的 message_id（用户特定）
 #[tauri::command]
 pub async fn update_message_id(
@@ -383,6 +757,19 @@ pub async fn update_message_id(
     app: AppHandle,
 ) -> Result<(), String> {
     let conn = get_connection(&app, self_id)?;
+
+    // 发送回显可能已先以同一 message_id 入库，保留本地记录（界面持有它的 localMessageId）
+    let echoed: Option<String> = conn
+        .query_row(
+            "SELECT local_message_id FROM messages
+             WHERE message_id = ?1 AND post_type IN ('message', 'message_sent') AND local_message_id != ?2",
+            params![message_id, local_message_id],
+            |row| row.get(0),
+        )
+        .ok();
+    if let Some(echoed) = echoed {
+        remove_message(&conn, &echoed)?;
+    }
     
     // 更新消息的 message_id
     conn.execute(
@@ -594,7 +981,11 @@ pub async fn delete_message(
     app: AppHandle,
 ) -> Result<(), String> {
     let conn = get_connection(&app, self_id)?;
-    
+    remove_message(&conn, &local_message_id)
+}
+
+/// 删除一条消息及其全文搜索索引
+fn remove_message(conn: &Connection, local_message_id: &str) -> Result<(), String> {
     // 获取 rowid
     let row_id: Option<i64> = conn.query_row(
         "SELECT rowid FROM messages_rowid_map WHERE local_message_id = ?1",
@@ -603,12 +994,13 @@ pub async fn delete_message(
     ).ok();
     
     if let Some(rid) = row_id {
-        // 删除全文搜索索引
+        // 删除全文搜索索引（外部内容表按 rowid 删除可能失败，忽略；
+        // 残留的索引项没有对应的 rowid 映射，搜索时不会被关联到）
         conn.execute(
             "DELETE FROM messages_fts WHERE rowid = ?1",
             params![rid],
         )
-        .map_err(|e| format!("删除全文搜索索引失败: {}", e))?;
+        .ok();
         
         // 删除 rowid 映射
         conn.execute(
@@ -720,7 +1112,16 @@ pub async fn mark_message_recalled(
     app: AppHandle,
 ) -> Result<(), String> {
     let conn = get_connection(&app, self_id)?;
-    
+    recall_message(&conn, message_id)
+}
+
+/// 通过 message_id 标记消息为已撤回（供后端事件处理直接调用）
+pub(crate) fn mark_recalled(app: &AppHandle, self_id: i64, message_id: i64) -> Result<(), String> {
+    let conn = get_connection(app, Some(self_id))?;
+    recall_message(&conn, message_id)
+}
+
+fn recall_message(conn: &Connection, message_id: i64) -> Result<(), String> {
     // 通过 message_id 标记消息为已撤回
     let affected = conn.execute(
         "UPDATE messages SET recalled = 1 WHERE message_id = ?1",
@@ -785,6 +1186,9 @@ pub async fn save_request(
     let conn = get_connection(&app, self_id)?;
     
     tracing::info!("✅ 获取数据库连接成功");
+
+    // 没有新请求时也要让过期的请求显示为已过期
+    expire_stale_requests(&conn)?;
     
     // 解析 JSON 数据
     let req: Value = serde_json::from_str(&request_data)
@@ -817,19 +1221,31 @@ pub async fn save_request(
         .unwrap_or("pending")
         .to_string();
     let is_read = req["is_read"].as_bool().unwrap_or(false);
+    let names_resolved = req["names_resolved"].as_bool().unwrap_or(false);
     
     tracing::info!("📊 解析请求数据: id={}, flag={}, request_type={}, user_id={}, group_id={:?}, status={}, is_read={}", 
         id, flag, request_type, user_id, group_id, status, is_read);
     
-    // 插入或替换请求（基于 user_id 和 group_id 的唯一约束）
-    // 当收到同一个用户的新请求时，会自动更新旧记录
-    // 好友请求: 同一个 user_id (group_id=NULL) 只保留最新的
-    // 群请求: 同一个 (group_id, user_id) 组合只保留最新的
+    let existing_status: Option<String> = conn.query_row(
+        "SELECT status FROM requests WHERE flag = ?1",
+        params![flag],
+        |row| row.get(0),
+    )
+    .optional()
+    .map_err(|e| format!("查询请求失败: {}", e))?;
+
+    // 同一个 flag 重复收到（例如重连后补发）时只更新名称和验证消息，保留处理状态
     let result = conn.execute(
-        "INSERT OR REPLACE INTO requests (
+        "INSERT INTO requests (
             id, timestamp, request_type, sub_type, user_id, user_name, nickname,
-            comment, flag, group_id, group_name, status, is_read
-        ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
+            comment, flag, group_id, group_name, status, is_read, names_resolved
+        ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)
+        ON CONFLICT(flag) DO UPDATE SET
+            user_name = excluded.user_name,
+            nickname = excluded.nickname,
+            comment = excluded.comment,
+            group_name = excluded.group_name,
+            names_resolved = excluded.names_resolved",
         params![
             id,
             timestamp,
@@ -843,17 +1259,122 @@ pub async fn save_request(
             group_id,
             group_name,
             status,
-            is_read as i64
+            is_read as i64,
+            names_resolved as i64
         ],
     )
     .map_err(|e| format!("插入请求失败: {}", e))?;
     
     tracing::info!("✅ 成功保存/更新请求到数据库: id={}, user_id={}, group_id={:?}, 影响行数={}", 
         id, user_id, group_id, result);
+
+    if existing_status.is_none() {
+        record_request_transition(&conn, &flag, None, &status, None, Some("收到请求"))?;
+
+        // 同一用户对同一目标的新请求会让服务器上的旧请求失效
+        let superseded = query_request_flags(
+            &conn,
+            "SELECT flag FROM requests
+             WHERE status = 'pending' AND flag != ?1 AND request_type = ?2
+               AND user_id = ?3 AND group_id IS ?4 AND sub_type IS ?5",
+            params![flag, request_type, user_id, group_id, sub_type],
+        )?;
+        for old_flag in superseded {
+            transition_request(&conn, &old_flag, Some("pending"), "expired", Some("server"), Some("已被新的请求取代"))?;
+        }
+    }
+
+    expire_stale_requests(&conn)?;
     
     Ok(id)
 }
 
+/// 把超过有效期仍未处理的请求标记为 expired（服务器端已失效），返回标记的数量
+///
+/// 收到新请求、加载请求列表和连接成功后都会执行
+pub(crate) fn expire_stale_requests(conn: &Connection) -> Result<usize, String> {
+    let expired = query_request_flags(
+        conn,
+        "SELECT flag FROM requests WHERE status = 'pending' AND timestamp < ?1",
+        params![chrono::Utc::now().timestamp() - REQUEST_EXPIRE_SECS],
+    )?;
+    let mut count = 0;
+    for old_flag in expired {
+        if transition_request(conn, &old_flag, Some("pending"), "expired", Some("server"), Some("请求已过期"))? {
+            count += 1;
+        }
+    }
+    Ok(count)
+}
+
+/// 查询满足条件的请求 flag
+fn query_request_flags(conn: &Connection, sql: &str, params: impl rusqlite::Params) -> Result<Vec<String>, String> {
+    let mut stmt = conn.prepare(sql)
+        .map_err(|e| format!("准备查询失败: {}", e))?;
+    let flags = stmt.query_map(params, |row| row.get(0))
+        .map_err(|e| format!("查询请求失败: {}", e))?
+        .collect::<Result<Vec<String>, _>>()
+        .map_err(|e| format!("读取请求失败: {}", e))?;
+    Ok(flags)
+}
+
+/// 记录一次请求状态变更
+fn record_request_transition(
+    conn: &Connection,
+    flag: &str,
+    from_status: Option<&str>,
+    to_status: &str,
+    handled_by: Option<&str>,
+    note: Option<&str>,
+) -> Result<(), String> {
+    conn.execute(
+        "INSERT INTO request_transitions (flag, from_status, to_status, handled_by, note)
+         VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![flag, from_status, to_status, handled_by, note],
+    )
+    .map_err(|e| format!("记录请求状态变更失败: {}", e))?;
+    Ok(())
+}
+
+/// 更新请求状态并记录状态变更
+///
+/// from 不为空时只在当前状态等于 from 时更新（避免覆盖并发处理的结果），返回是否更新
+pub(crate) fn transition_request(
+    conn: &Connection,
+    flag: &str,
+    from: Option<&str>,
+    to: &str,
+    handled_by: Option<&str>,
+    note: Option<&str>,
+) -> Result<bool, String> {
+    let current: Option<String> = conn.query_row(
+        "SELECT status FROM requests WHERE flag = ?1",
+        params![flag],
+        |row| row.get(0),
+    )
+    .optional()
+    .map_err(|e| format!("查询请求失败: {}", e))?;
+    let Some(current) = current else {
+        return Err(format!("请求不存在: {}", flag));
+    };
+    if from.is_some_and(|from| from != current) || current == to {
+        return Ok(false);
+    }
+
+    let updated = conn.execute(
+        "UPDATE requests SET status = ?1, handled_by = ?2, handled_at = strftime('%s', 'now')
+         WHERE flag = ?3 AND status = ?4",
+        params![to, handled_by, flag, current],
+    )
+    .map_err(|e| format!("更新请求状态失败: {}", e))?;
+    if updated == 0 {
+        return Ok(false);
+    }
+    record_request_transition(conn, flag, Some(&current), to, handled_by, note)?;
+    tracing::info!("请求 {} 状态变更: {} -> {} ({:?})", flag, current, to, handled_by);
+    Ok(true)
+}
+
 /// 更新请求状态
 #[tauri::command]
 pub async fn update_request_status(
@@ -863,13 +1384,7 @@ pub async fn update_request_status(
     app: AppHandle,
 ) -> Result<(), String> {
     let conn = get_connection(&app, self_id)?;
-    
-    conn.execute(
-        "UPDATE requests SET status = ?1 WHERE flag = ?2",
-        params![status, flag],
-    )
-    .map_err(|e| format!("更新请求状态失败: {}", e))?;
-    
+    transition_request(&conn, &flag, None, &status, Some("manual"), None)?;
     Ok(())
 }
 
@@ -877,6 +1392,7 @@ pub async fn update_request_status(
 #[tauri::command]
 pub async fn get_requests(
     status: Option<String>,
+    user_id: Option<i64>,
     limit: Option<u32>,
     offset: Option<u32>,
     self_id: Option<i64>,
@@ -893,13 +1409,20 @@ pub async fn get_requests(
     let offset = offset.unwrap_or(0);
     
     let mut query = "SELECT id, timestamp, request_type, sub_type, user_id, user_name, nickname, \
-                     comment, flag, group_id, group_name, status, is_read FROM requests WHERE 1=1".to_string();
+                     comment, flag, group_id, group_name, status, is_read, \
+                     review_rule, review_action, review_error, handled_by, handled_at \
+                     FROM requests WHERE 1=1".to_string();
     let mut params: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
     
     if let Some(s) = &status {
         query.push_str(" AND status = ?");
         params.push(Box::new(s.clone()));
     }
+
+    if let Some(uid) = user_id {
+        query.push_str(" AND user_id = ?");
+        params.push(Box::new(uid));
+    }
     
     query.push_str(" ORDER BY timestamp DESC LIMIT ? OFFSET ?");
     params.push(Box::new(limit as i32));
@@ -928,6 +1451,11 @@ pub async fn get_requests(
             let group_name: Option<String> = row.get(10)?;
             let status: String = row.get(11)?;
             let is_read: i64 = row.get(12)?;
+            let review_rule: Option<String> = row.get(13)?;
+            let review_action: Option<String> = row.get(14)?;
+            let review_error: Option<String> = row.get(15)?;
+            let handled_by: Option<String> = row.get(16)?;
+            let handled_at: Option<i64> = row.get(17)?;
             
             let json = serde_json::json!({
                 "id": id,
@@ -942,7 +1470,12 @@ pub async fn get_requests(
                 "group_id": group_id,
                 "group_name": group_name,
                 "status": status,
-                "is_read": is_read != 0
+                "is_read": is_read != 0,
+                "review_rule": review_rule,
+                "review_action": review_action,
+                "review_error": review_error,
+                "handled_by": handled_by,
+                "handled_at": handled_at
             });
             
             Ok(serde_json::to_string(&json).unwrap_or_default())
@@ -960,6 +1493,37 @@ pub async fn get_requests(
     Ok(requests)
 }
 
+/// 获取请求的状态变更记录（按时间顺序）
+#[tauri::command]
+pub async fn get_request_transitions(
+    flag: String,
+    self_id: Option<i64>,
+    app: AppHandle,
+) -> Result<Vec<Value>, String> {
+    let conn = get_connection(&app, self_id)?;
+
+    let mut stmt = conn.prepare(
+        "SELECT from_status, to_status, handled_by, note, created_at
+         FROM request_transitions WHERE flag = ?1 ORDER BY id",
+    )
+    .map_err(|e| format!("准备查询失败: {}", e))?;
+
+    let transitions = stmt.query_map(params![flag], |row| {
+        Ok(serde_json::json!({
+            "from_status": row.get::<_, Option<String>>(0)?,
+            "to_status": row.get::<_, String>(1)?,
+            "handled_by": row.get::<_, Option<String>>(2)?,
+            "note": row.get::<_, Option<String>>(3)?,
+            "time": row.get::<_, i64>(4)?,
+        }))
+    })
+    .map_err(|e| format!("查询请求状态变更失败: {}", e))?
+    .collect::<Result<Vec<_>, _>>()
+    .map_err(|e| format!("读取请求状态变更失败: {}", e))?;
+
+    Ok(transitions)
+}
+
 /// 删除请求
 #[tauri::command]
 pub async fn delete_request(
@@ -974,6 +1538,12 @@ pub async fn delete_request(
         params![flag],
     )
     .map_err(|e| format!("删除请求失败: {}", e))?;
+
+    conn.execute(
+        "DELETE FROM request_transitions WHERE flag = ?1",
+        params![flag],
+    )
+    .map_err(|e| format!("删除请求状态变更记录失败: {}", e))?;
     
     Ok(())
 }
@@ -991,6 +1561,12 @@ pub async fn clear_history_requests(
         [],
     )
     .map_err(|e| format!("清空历史请求失败: {}", e))?;
+
+    conn.execute(
+        "DELETE FROM request_transitions WHERE flag NOT IN (SELECT flag FROM requests)",
+        [],
+    )
+    .map_err(|e| format!("清空请求状态变更记录失败: {}", e))?;
     
     Ok(deleted as u32)
 }
@@ -1030,3 +1606,119 @@ pub async fn get_unread_request_count(
     
     Ok(count)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn open() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        init_database(&conn).unwrap();
+        conn
+    }
+
+    fn message(local_message_id: &str, message_id: i64, text: &str) -> String {
+        json!({
+            "localMessageId": local_message_id,
+            "time": 1,
+            "post_type": "message",
+            "message_type": "private",
+            "user_id": 2,
+            "message_id": message_id,
+            "message": text,
+            "raw_message": text,
+        })
+        .to_string()
+    }
+
+    fn count(conn: &Connection) -> i64 {
+        conn.query_row("SELECT COUNT(*) FROM messages", [], |row| row.get(0)).unwrap()
+    }
+
+    #[test]
+    fn duplicate_message_id_returns_existing_record() {
+        let conn = open();
+        assert_eq!(insert_message(&conn, &message("a", 10, "hi")).unwrap(), "a");
+        assert_eq!(insert_message(&conn, &message("b", 10, "hi")).unwrap(), "a");
+        assert_eq!(count(&conn), 1);
+
+        // 同一 localMessageId 再次写入时更新内容
+        insert_message(&conn, &message("a", 10, "edited")).unwrap();
+        let content: String = conn
+            .query_row("SELECT content FROM messages WHERE local_message_id = 'a'", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(content, "edited");
+    }
+
+    #[test]
+    fn existing_duplicates_are_removed_before_indexing() {
+        let conn = Connection::open_in_memory().unwrap();
+        init_database(&conn).unwrap();
+        conn.execute_batch("DROP INDEX idx_messages_message_id").unwrap();
+        for (local_message_id, text) in [("a", "first"), ("b", "second")] {
+            conn.execute(
+                "INSERT INTO messages (local_message_id, timestamp, post_type, message_id, data)
+                 VALUES (?1, 1, 'message', 10, ?2)",
+                params![local_message_id, text],
+            )
+            .unwrap();
+            conn.execute("INSERT INTO messages_rowid_map (local_message_id) VALUES (?1)", params![local_message_id])
+                .unwrap();
+        }
+
+        init_database(&conn).unwrap();
+        let kept: String = conn.query_row("SELECT local_message_id FROM messages", [], |row| row.get(0)).unwrap();
+        assert_eq!(kept, "a");
+        let mapped: i64 = conn
+            .query_row("SELECT COUNT(*) FROM messages_rowid_map", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(mapped, 1);
+    }
+
+    #[test]
+    fn concurrent_duplicates_are_stored_once() {
+        let path = std::env::temp_dir().join(format!("runbot-dedup-{}.db", new_local_message_id()));
+        init_database(&Connection::open(&path).unwrap()).unwrap();
+
+        let handles: Vec<_> = (0..8)
+            .map(|i| {
+                let path = path.clone();
+                std::thread::spawn(move || {
+                    let conn = Connection::open(&path).unwrap();
+                    conn.busy_timeout(std::time::Duration::from_secs(5)).unwrap();
+                    insert_message(&conn, &message(&format!("local-{}", i), 42, "hi")).unwrap()
+                })
+            })
+            .collect();
+        let ids: Vec<String> = handles.into_iter().map(|handle| handle.join().unwrap()).collect();
+
+        assert!(ids.iter().all(|id| id == &ids[0]));
+        assert_eq!(count(&Connection::open(&path).unwrap()), 1);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn replay_uses_a_fresh_directory_until_released() {
+        let app = crate::test_support::TestApp::new();
+        let app = app.handle();
+        let real_dir = get_user_data_dir(app, Some(10001)).unwrap();
+        std::fs::write(real_dir.join("runbot.db"), b"").unwrap();
+
+        let first = ReplayDir::create(app, 10001).unwrap();
+        let second = ReplayDir::create(app, 10002).unwrap();
+        assert!(first.path.starts_with(app.path().app_data_dir().unwrap().join("replay")));
+        assert_ne!(first.path.file_name(), second.path.file_name());
+        assert_eq!(get_user_data_dir(app, Some(10001)).unwrap(), first.path);
+        assert_eq!(get_user_data_dir(app, Some(10002)).unwrap(), second.path);
+        assert!(!first.path.join("runbot.db").exists());
+
+        // 释放后删除临时目录，真实的数据目录不受影响
+        let path = first.path.clone();
+        drop(first);
+        assert!(!path.exists());
+        assert_eq!(get_user_data_dir(app, Some(10001)).unwrap(), real_dir);
+        assert!(real_dir.join("runbot.db").exists());
+        assert_eq!(get_user_data_dir(app, Some(10002)).unwrap(), second.path);
+    }
+}
@@ -0,0 +1,738 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use futures_util::stream::FuturesUnordered;
+use futures_util::{SinkExt, StreamExt};
+use rand::Rng;
+use runbot::onebot11_api::get_login_info::LoginInfo;
+use runbot::prelude::*;
+use tauri::Emitter;
+use crate::AppHandle;
+use tokio::net::TcpStream;
+use tokio::sync::{mpsc, oneshot, watch};
+use tokio_tungstenite::tungstenite::protocol::Message as WsMessage;
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+use crate::bridge::LoopbackBridge;
+use crate::connection::{self, ConnectionState, DisconnectReason};
+use crate::http_transport::{EventReceiver, HttpApi};
+use crate::recorder::{Direction, Recording};
+use crate::runbot::RunbotState;
+use crate::session::Session;
+
+/// 回放时两个事件之间的最长等待时间，录制中较长的空闲间隔会被压缩到这个长度
+const MAX_REPLAY_GAP: Duration = Duration::from_secs(5);
+
+/// 重连策略（指数退避 + 抖动）
+#[derive(Debug, Clone)]
+pub struct ReconnectPolicy {
+    pub initial_delay: Duration,
+    pub max_delay: Duration,
+    pub multiplier: f64,
+    /// 抖动比例，0.2 表示在 ±20% 范围内随机
+    pub jitter: f64,
+    /// 单次建立连接的超时时间
+    pub connect_timeout: Duration,
+    /// 从未连接成功时允许的最大尝试次数，超过后视为失败（通常是地址或 token 配置错误）
+    pub max_initial_attempts: u32,
+    /// 心跳超时检查间隔
+    pub heartbeat_check_interval: Duration,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            initial_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(60),
+            multiplier: 2.0,
+            jitter: 0.2,
+            connect_timeout: Duration::from_secs(10),
+            max_initial_attempts: 5,
+            heartbeat_check_interval: Duration::from_secs(5),
+        }
+    }
+}
+
+impl ReconnectPolicy {
+    /// 计算第 attempt 次重连前的等待时间（attempt 从 1 开始）
+    pub fn delay_for(&self, attempt: u32) -> Duration {
+        let exp = self.multiplier.powi(attempt.saturating_sub(1).min(32) as i32);
+        let max = self.max_delay.as_secs_f64();
+        let base = (self.initial_delay.as_secs_f64() * exp).min(max);
+        let factor = if self.jitter > 0.0 {
+            rand::thread_rng().gen_range(1.0 - self.jitter..=1.0 + self.jitter)
+        } else {
+            1.0
+        };
+        Duration::from_secs_f64((base * factor).min(max))
+    }
+}
+
+/// supervisor 句柄，用于停止后台重连循环
+#[derive(Debug)]
+pub struct SupervisorHandle {
+    cancel: watch::Sender<bool>,
+    done: watch::Receiver<bool>,
+}
+
+impl SupervisorHandle {
+    /// 通知 supervisor 停止，并等待其完成清理（关闭上游连接、shutdown BotContext）
+    pub async fn shutdown(&self) {
+        let _ = self.cancel.send(true);
+        let mut done = self.done.clone();
+        let _ = done.wait_for(|v| *v).await;
+    }
+}
+
+/// 与 OneBot 实现之间的 WebSocket 连接
+pub type UpstreamStream = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// 上游连接方式
+pub enum Upstream {
+    /// 正向 WebSocket：主动连接 OneBot 实现，断线后按重连策略重连
+    Dial { url: String },
+    /// 附加到后台服务：通过后台服务本地控制接口的 /api 调用 API，重连方式同 Dial；
+    /// 事件、发送队列和定时消息都由后台服务处理
+    Attach { url: String },
+    /// 反向 WebSocket：由反向服务器把 OneBot 实现接入的连接交给会话，断线后等待其重新接入
+    Accept {
+        incoming: tokio::sync::Mutex<mpsc::UnboundedReceiver<UpstreamStream>>,
+    },
+    /// HTTP：API 调用走 HTTP API，事件由本地 HTTP 接收器接收
+    Http {
+        api: HttpApi,
+        receiver: EventReceiver,
+        events: tokio::sync::Mutex<mpsc::UnboundedReceiver<String>>,
+    },
+    /// 回放：不连接服务器，按录制的时间间隔把事件交给 runbot，API 调用用录制的响应回复
+    Replay {
+        recording: tokio::sync::Mutex<Recording>,
+        /// 回放速度倍数
+        speed: f64,
+    },
+}
+
+impl Upstream {
+    /// 是否附加到后台服务（本地不处理事件）
+    pub fn is_attached(&self) -> bool {
+        matches!(self, Upstream::Attach { .. })
+    }
+
+    /// 是否在回放录制（数据写入临时目录，不运行会产生副作用的任务）
+    pub fn is_replay(&self) -> bool {
+        matches!(self, Upstream::Replay { .. })
+    }
+}
+
+/// 一次连接结束的原因
+enum SessionEnd {
+    Cancelled,
+    Lost { authenticated: bool, error: String },
+    /// 认证结果与会话不符（账号重复或账号变更），不再重连
+    Rejected(String),
+}
+
+/// 首次认证的结果（成功时为 self_id）
+pub type ReadyReceiver = oneshot::Receiver<Result<i64, String>>;
+
+struct Supervisor {
+    app: AppHandle,
+    registry: Arc<Mutex<RunbotState>>,
+    state: Arc<Mutex<Session>>,
+    ready: Mutex<Option<oneshot::Sender<Result<i64, String>>>>,
+    bot_ctx: Arc<BotContext>,
+    bridge: LoopbackBridge,
+    upstream: Upstream,
+    policy: ReconnectPolicy,
+}
+
+/// 启动 supervisor，由它负责会话中 BotContext 的整个生命周期
+///
+/// 首次认证成功后会把会话登记到注册表中，并通过返回的 [`ReadyReceiver`] 通知调用方
+pub fn spawn(
+    app: AppHandle,
+    registry: Arc<Mutex<RunbotState>>,
+    state: Arc<Mutex<Session>>,
+    bot_ctx: Arc<BotContext>,
+    bridge: LoopbackBridge,
+    upstream: Upstream,
+    policy: ReconnectPolicy,
+) -> (Arc<SupervisorHandle>, ReadyReceiver) {
+    let (cancel_tx, cancel_rx) = watch::channel(false);
+    let (done_tx, done_rx) = watch::channel(false);
+    let (ready_tx, ready_rx) = oneshot::channel();
+
+    let supervisor = Supervisor {
+        app,
+        registry,
+        state,
+        ready: Mutex::new(Some(ready_tx)),
+        bot_ctx,
+        bridge,
+        upstream,
+        policy,
+    };
+    tokio::spawn(async move {
+        supervisor.run(cancel_rx).await;
+        let _ = done_tx.send(true);
+    });
+
+    let handle = Arc::new(SupervisorHandle {
+        cancel: cancel_tx,
+        done: done_rx,
+    });
+    (handle, ready_rx)
+}
+
+impl Supervisor {
+    async fn run(self, mut cancel: watch::Receiver<bool>) {
+        let message = match self.upstream {
+            Upstream::Dial { .. } | Upstream::Http { .. } => "正在连接...",
+            Upstream::Attach { .. } => "正在连接后台服务...",
+            Upstream::Accept { .. } => "等待 OneBot 实现连接...",
+            Upstream::Replay { .. } => "正在加载回放...",
+        };
+        self.transition(
+            ConnectionState::Connecting { attempt: 0, retry_in_ms: None },
+            Some(message.to_string()),
+        );
+
+        // 等待 runbot 接入本地桥接，否则握手阶段的 get_login_info 会因连接未就绪而失败
+        tokio::select! {
+            _ = self.bridge.wait_attached() => {}
+            _ = cancel.changed() => {
+                self.finish(None).await;
+                return;
+            }
+        }
+
+        let mut attempt: u32 = 0;
+        let mut ever_connected = false;
+
+        loop {
+            let end = match &self.upstream {
+                Upstream::Http { api, receiver, events } => {
+                    self.run_http(api, receiver, events, &mut cancel).await
+                }
+                Upstream::Replay { recording, speed } => {
+                    self.run_replay(recording, *speed, &mut cancel).await
+                }
+                _ => match self.connect(&mut cancel).await {
+                    Ok(ws_stream) => self.run_connection(ws_stream, &mut cancel).await,
+                    Err(end) => end,
+                },
+            };
+            let error = match end {
+                SessionEnd::Cancelled => {
+                    self.finish(None).await;
+                    return;
+                }
+                SessionEnd::Rejected(error) => {
+                    tracing::error!("[supervisor] {}", error);
+                    self.finish(Some(error)).await;
+                    return;
+                }
+                SessionEnd::Lost { authenticated, error } => {
+                    if authenticated {
+                        ever_connected = true;
+                        attempt = 0;
+                    }
+                    error
+                }
+            };
+
+            tracing::warn!("[supervisor] 连接断开: {}", error);
+            connection::record_error(&self.state, error.clone());
+
+            attempt += 1;
+
+            // 反向连接由 OneBot 实现负责重连，这里只需等待下一个连接接入
+            if let Upstream::Accept { .. } = self.upstream {
+                self.transition(
+                    ConnectionState::Connecting { attempt, retry_in_ms: None },
+                    Some(format!("连接已断开，等待 OneBot 实现重新连接: {}", error)),
+                );
+                continue;
+            }
+
+            if !ever_connected && attempt >= self.policy.max_initial_attempts {
+                tracing::error!("[supervisor] 已尝试 {} 次仍无法连接，放弃重连", attempt);
+                self.finish(Some(format!("连接失败: {}", error))).await;
+                return;
+            }
+
+            let delay = self.policy.delay_for(attempt);
+            tracing::info!("[supervisor] 第 {} 次重连将在 {:?} 后进行", attempt, delay);
+            self.transition(
+                ConnectionState::Connecting {
+                    attempt,
+                    retry_in_ms: Some(delay.as_millis() as u64),
+                },
+                Some(format!("连接已断开，正在重连（第 {} 次）: {}", attempt, error)),
+            );
+
+            if !self.wait_backoff(delay, &mut cancel).await {
+                self.finish(None).await;
+                return;
+            }
+        }
+    }
+
+    /// 建立（或等待）一次上游连接
+    async fn connect(&self, cancel: &mut watch::Receiver<bool>) -> Result<UpstreamStream, SessionEnd> {
+        match &self.upstream {
+            Upstream::Dial { url } | Upstream::Attach { url } => {
+                tracing::info!("[supervisor] 正在连接上游: {}", url);
+
+                let connect = tokio::time::timeout(
+                    self.policy.connect_timeout,
+                    tokio_tungstenite::connect_async(url.as_str()),
+                );
+                tokio::select! {
+                    _ = cancel.changed() => Err(SessionEnd::Cancelled),
+                    result = connect => match result {
+                        Ok(Ok((ws_stream, _))) => Ok(ws_stream),
+                        Ok(Err(e)) => Err(SessionEnd::Lost { authenticated: false, error: e.to_string() }),
+                        Err(_) => Err(SessionEnd::Lost { authenticated: false, error: "连接超时".to_string() }),
+                    },
+                }
+            }
+            Upstream::Http { .. } => unreachable!("HTTP 模式不建立 WebSocket 连接"),
+            Upstream::Replay { .. } => unreachable!("回放模式不建立 WebSocket 连接"),
+            Upstream::Accept { incoming } => {
+                tracing::info!("[supervisor] 等待反向连接接入");
+                let mut incoming = incoming.lock().await;
+                // 等待期间的 API 调用直接以失败响应返回
+                loop {
+                    tokio::select! {
+                        _ = cancel.changed() => return Err(SessionEnd::Cancelled),
+                        ws_stream = incoming.recv() => {
+                            return ws_stream.ok_or_else(|| {
+                                SessionEnd::Rejected("反向 WebSocket 服务已停止".to_string())
+                            });
+                        }
+                        action = self.bridge.next_action() => {
+                            match action {
+                                Some(frame) => self.bridge.reject_action(&frame, "未连接到 Runbot 服务器"),
+                                None => return Err(SessionEnd::Cancelled),
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// 在上游连接存活期间转发帧
+    async fn run_connection(
+        &self,
+        ws_stream: UpstreamStream,
+        cancel: &mut watch::Receiver<bool>,
+    ) -> SessionEnd {
+        tracing::info!("[supervisor] 上游已连接，开始获取登录信息");
+        self.transition(ConnectionState::Handshaking, Some("正在获取登录信息...".to_string()));
+
+        let (mut sink, mut stream) = ws_stream.split();
+
+        // 每次（重新）连接后都重新获取登录信息
+        let login = self.login();
+        tokio::pin!(login);
+        let mut authenticated = false;
+        let mut heartbeat_check = tokio::time::interval(self.policy.heartbeat_check_interval);
+
+        loop {
+            tokio::select! {
+                _ = heartbeat_check.tick(), if authenticated => {
+                    connection::check_heartbeat(&self.state, &self.app);
+                }
+                _ = cancel.changed() => {
+                    let _ = sink.send(WsMessage::Close(None)).await;
+                    return SessionEnd::Cancelled;
+                }
+                result = &mut login, if !authenticated => {
+                    match result {
+                        Ok(login_info) => {
+                            if let Err(error) = self.on_authenticated(login_info.user_id, &login_info.nickname) {
+                                let _ = sink.send(WsMessage::Close(None)).await;
+                                return SessionEnd::Rejected(error);
+                            }
+                            authenticated = true;
+                        }
+                        Err(e) => {
+                            let _ = sink.send(WsMessage::Close(None)).await;
+                            return SessionEnd::Lost {
+                                authenticated: false,
+                                error: format!("获取登录信息失败: {}", e),
+                            };
+                        }
+                    }
+                }
+                msg = stream.next() => {
+                    match msg {
+                        Some(Ok(WsMessage::Text(text))) => self.deliver_event(text.to_string()),
+                        Some(Ok(WsMessage::Close(_))) | None => {
+                            return SessionEnd::Lost { authenticated, error: "连接被关闭".to_string() };
+                        }
+                        Some(Ok(_)) => {}
+                        Some(Err(e)) => {
+                            return SessionEnd::Lost { authenticated, error: e.to_string() };
+                        }
+                    }
+                }
+                action = self.bridge.next_action() => {
+                    let Some(frame) = action else {
+                        return SessionEnd::Cancelled;
+                    };
+                    self.record(Direction::Outbound, &frame);
+                    if let Err(e) = sink.send(WsMessage::Text(frame.into())).await {
+                        return SessionEnd::Lost { authenticated, error: e.to_string() };
+                    }
+                }
+            }
+        }
+    }
+
+    /// HTTP 模式：并发执行 API 调用，同时把接收到的事件交给 runbot
+    ///
+    /// 只有首次获取登录信息失败才视为连接失败，之后单个 API 调用失败只会返回失败响应
+    async fn run_http(
+        &self,
+        api: &HttpApi,
+        receiver: &EventReceiver,
+        events: &tokio::sync::Mutex<mpsc::UnboundedReceiver<String>>,
+        cancel: &mut watch::Receiver<bool>,
+    ) -> SessionEnd {
+        tracing::info!(
+            "[supervisor] 使用 HTTP API: {}，事件接收地址: {}",
+            api.api_url(),
+            receiver.local_addr()
+        );
+        self.transition(ConnectionState::Handshaking, Some("正在获取登录信息...".to_string()));
+
+        let mut events = events.lock().await;
+        let mut calls = FuturesUnordered::new();
+
+        let login = self.login();
+        tokio::pin!(login);
+        let mut authenticated = false;
+        let mut heartbeat_check = tokio::time::interval(self.policy.heartbeat_check_interval);
+
+        loop {
+            tokio::select! {
+                _ = heartbeat_check.tick(), if authenticated => {
+                    connection::check_heartbeat(&self.state, &self.app);
+                }
+                _ = cancel.changed() => return SessionEnd::Cancelled,
+                result = &mut login, if !authenticated => {
+                    match result {
+                        Ok(login_info) => {
+                            if let Err(error) = self.on_authenticated(login_info.user_id, &login_info.nickname) {
+                                return SessionEnd::Rejected(error);
+                            }
+                            authenticated = true;
+                        }
+                        Err(e) => {
+                            return SessionEnd::Lost {
+                                authenticated: false,
+                                error: format!("获取登录信息失败: {}", e),
+                            };
+                        }
+                    }
+                }
+                event = events.recv() => {
+                    let Some(event) = event else {
+                        return SessionEnd::Rejected("HTTP 事件接收器已停止".to_string());
+                    };
+                    self.deliver_event(event);
+                }
+                action = self.bridge.next_action() => {
+                    let Some(frame) = action else {
+                        return SessionEnd::Cancelled;
+                    };
+                    self.record(Direction::Outbound, &frame);
+                    calls.push(async move {
+                        let result = api.call(&frame).await;
+                        (frame, result)
+                    });
+                }
+                Some((frame, result)) = calls.next(), if !calls.is_empty() => {
+                    match result {
+                        Ok(response) => {
+                            self.record(Direction::Inbound, &response);
+                            self.bridge.deliver(response);
+                        }
+                        Err(e) => {
+                            tracing::warn!("[supervisor] {}", e);
+                            connection::record_error(&self.state, e.clone());
+                            self.bridge.reject_action(&frame, &e);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// 回放录制的会话：事件在登录后按录制时的间隔（除以回放速度，最长 MAX_REPLAY_GAP）依次交给 runbot
+    ///
+    /// 事件回放完后会话保持在线，直到被断开；期间的 API 调用仍用录制的响应回复
+    async fn run_replay(
+        &self,
+        recording: &tokio::sync::Mutex<Recording>,
+        speed: f64,
+        cancel: &mut watch::Receiver<bool>,
+    ) -> SessionEnd {
+        let mut recording = recording.lock().await;
+        tracing::info!("[supervisor] 开始回放: {:?}，速度 {}x", recording.path(), speed);
+        self.transition(ConnectionState::Handshaking, Some("正在获取登录信息...".to_string()));
+
+        let mut events = recording.take_events().into_iter();
+        let mut next = events.next();
+        let delay = tokio::time::sleep(Duration::ZERO);
+        tokio::pin!(delay);
+
+        let login = self.login();
+        tokio::pin!(login);
+        let mut authenticated = false;
+
+        loop {
+            tokio::select! {
+                _ = cancel.changed() => return SessionEnd::Cancelled,
+                result = &mut login, if !authenticated => {
+                    match result {
+                        Ok(login_info) => {
+                            if let Err(error) = self.on_authenticated(login_info.user_id, &login_info.nickname) {
+                                return SessionEnd::Rejected(error);
+                            }
+                            authenticated = true;
+                            delay.as_mut().reset(tokio::time::Instant::now());
+                        }
+                        Err(e) => {
+                            return SessionEnd::Rejected(format!("回放获取登录信息失败: {}", e));
+                        }
+                    }
+                }
+                _ = &mut delay, if authenticated && next.is_some() => {
+                    let Some(event) = next.take() else { continue };
+                    next = events.next();
+                    match &next {
+                        Some(following) => {
+                            let gap = Duration::from_millis((following.time - event.time).max(0) as u64)
+                                .div_f64(speed)
+                                .min(MAX_REPLAY_GAP);
+                            delay.as_mut().reset(tokio::time::Instant::now() + gap);
+                        }
+                        None => {
+                            tracing::info!("[supervisor] 回放结束: {:?}", recording.path());
+                            let account_id = self.state.lock().unwrap().account_id;
+                            self.app
+                                .emit("runbot-replay-finished", serde_json::json!({ "account_id": account_id }))
+                                .unwrap_or_default();
+                        }
+                    }
+                    self.deliver_event(event.frame);
+                }
+                action = self.bridge.next_action() => {
+                    let Some(frame) = action else {
+                        return SessionEnd::Cancelled;
+                    };
+                    match recording.respond(&frame) {
+                        Some(response) => self.bridge.deliver(response),
+                        None => self.bridge.reject_action(&frame, "录制中没有该调用的响应"),
+                    }
+                }
+            }
+        }
+    }
+
+    /// 把上游推送的帧交给 runbot；通知事件先按原始数据解析保存，避免字段被 runbot 丢弃
+    fn deliver_event(&self, frame: String) {
+        self.record(Direction::Inbound, &frame);
+        crate::notice::on_raw_event(&self.app, &frame);
+        // 回放的事件不是真实发生的，不转发给控制接口和中继的订阅者
+        if self.upstream.is_replay() {
+            self.bridge.deliver(frame);
+            return;
+        }
+        let (account_id, relay) = {
+            let session_guard = self.state.lock().unwrap();
+            (session_guard.account_id, session_guard.relay.clone())
+        };
+        if let Some(account_id) = account_id {
+            crate::control::publish_raw(&self.registry, account_id, &frame);
+        }
+        if let Some(relay) = relay {
+            relay.publish(&frame);
+        }
+        self.bridge.deliver(frame);
+    }
+
+    /// 会话正在录制时记录一帧
+    fn record(&self, direction: Direction, frame: &str) {
+        let recorder = self.state.lock().unwrap().recorder.clone();
+        if let Some(recorder) = recorder {
+            recorder.record(direction, frame);
+        }
+    }
+
+    /// 获取登录信息；runbot 刚接入桥接时自身的连接可能尚未就绪，此时稍等后重试
+    async fn login(&self) -> runbot::error::Result<LoginInfo> {
+        let mut retries = 0;
+        loop {
+            match self.bot_ctx.get_login_info().await {
+                Err(runbot::error::Error::StateError(_)) if retries < 50 => {
+                    retries += 1;
+                    tokio::time::sleep(Duration::from_millis(20)).await;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// 退避等待；期间的 API 调用直接以失败响应返回。返回 false 表示已被取消
+    async fn wait_backoff(&self, delay: Duration, cancel: &mut watch::Receiver<bool>) -> bool {
+        let sleep = tokio::time::sleep(delay);
+        tokio::pin!(sleep);
+        loop {
+            tokio::select! {
+                _ = &mut sleep => return true,
+                _ = cancel.changed() => return false,
+                action = self.bridge.next_action() => {
+                    match action {
+                        Some(frame) => self.bridge.reject_action(&frame, "未连接到 Runbot 服务器"),
+                        None => return false,
+                    }
+                }
+            }
+        }
+    }
+
+    fn on_authenticated(&self, self_id: i64, nickname: &str) -> Result<(), String> {
+        tracing::info!("[supervisor] 成功获取 self_id: {} (昵称: {})", self_id, nickname);
+
+        let account_id = self.state.lock().unwrap().account_id;
+        match account_id {
+            // 首次认证：登记到注册表
+            None => {
+                self.registry.lock().unwrap().sessions.register(&self.state, self_id)?;
+                let replay_dir = if self.upstream.is_replay() {
+                    match crate::storage::ReplayDir::create(&self.app, self_id) {
+                        Ok(dir) => Some(dir),
+                        Err(e) => {
+                            self.registry.lock().unwrap().sessions.remove(&self.state);
+                            return Err(e);
+                        }
+                    }
+                } else {
+                    None
+                };
+                let mut state_guard = self.state.lock().unwrap();
+                state_guard.account_id = Some(self_id);
+                state_guard.replay_dir = replay_dir;
+            }
+            Some(account_id) if account_id != self_id => {
+                return Err(format!("上游账号已变更: {} -> {}", account_id, self_id));
+            }
+            Some(_) => {}
+        }
+
+        self.transition(ConnectionState::Authenticated { self_id }, Some("已连接".to_string()));
+        self.app.emit("runbot-self-id", self_id).unwrap_or_default();
+        // 回放时不发送排队的消息、不运行定时任务，也不补全请求
+        if !self.upstream.is_attached() && !self.upstream.is_replay() {
+            // 发送离线期间排队的消息
+            crate::outbox::request_flush(self.app.clone(), self.registry.clone(), self_id);
+            // 启动定时消息调度
+            crate::schedule::start(self.app.clone(), self.registry.clone(), self_id);
+            // 补全之前未查到名称的请求
+            crate::request::spawn_backfill(self.app.clone(), self.registry.clone(), self_id);
+        }
+        if let Some(ready) = self.ready.lock().unwrap().take() {
+            let _ = ready.send(Ok(self_id));
+        }
+        Ok(())
+    }
+
+    /// 结束 supervisor：shutdown BotContext；error 不为空时表示放弃重连
+    async fn finish(&self, error: Option<String>) {
+        if let Err(e) = self.bot_ctx.shutdown().await {
+            tracing::warn!("[supervisor] shutdown BotContext 失败: {}", e);
+        } else {
+            tracing::info!("[supervisor] BotContext shutdown 成功");
+        }
+        // 回放结束：删除临时数据目录，该账号恢复使用真实的数据目录
+        self.state.lock().unwrap().replay_dir = None;
+
+        if let Some(error) = error {
+            self.registry.lock().unwrap().sessions.remove(&self.state);
+            {
+                let mut state_guard = self.state.lock().unwrap();
+                state_guard.bot_ctx = None;
+                state_guard.supervisor = None;
+            }
+            self.transition(
+                ConnectionState::Disconnected {
+                    reason: DisconnectReason::Failed(error.clone()),
+                },
+                Some(error.clone()),
+            );
+            if let Some(ready) = self.ready.lock().unwrap().take() {
+                let _ = ready.send(Err(error));
+            }
+        } else if let Some(ready) = self.ready.lock().unwrap().take() {
+            let _ = ready.send(Err("连接已取消".to_string()));
+        }
+    }
+
+    fn transition(&self, next: ConnectionState, message: Option<String>) {
+        connection::transition(&self.state, &self.app, next, message);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+    use crate::actions;
+    use crate::mock_onebot::{MockConfig, MockServer};
+    use crate::test_support::{self, TestApp};
+
+    #[tokio::test]
+    async fn reconnects_and_logs_in_again() {
+        let server = MockServer::bind("127.0.0.1", 0, MockConfig::default()).await.unwrap();
+        let app = TestApp::new();
+        let session = app.connect(&server).await;
+        assert_eq!(test_support::wait_for_calls(&server, "get_login_info", 1).await.len(), 1);
+
+        server.disconnect_all();
+        test_support::wait_connection(&session, false).await;
+        test_support::wait_connection(&session, true).await;
+
+        // 重连后重新获取登录信息，API 调用经本地桥接正常返回
+        assert_eq!(test_support::wait_for_calls(&server, "get_login_info", 2).await.len(), 2);
+        let status: Value = actions::call(&app.registry, server.self_id(), "get_status", &json!({}))
+            .await
+            .unwrap();
+        assert_eq!(status["online"], true);
+        assert_eq!(session.lock().unwrap().account_id, Some(server.self_id()));
+
+        test_support::shutdown(&session).await;
+    }
+
+    #[tokio::test]
+    async fn keeps_retrying_while_server_is_down() {
+        let server = MockServer::bind("127.0.0.1", 0, MockConfig::default()).await.unwrap();
+        let app = TestApp::new();
+        let session = app.connect(&server).await;
+
+        server.set_accepting(false);
+        server.disconnect_all();
+        test_support::wait_connection(&session, false).await;
+
+        // 服务器拒绝连接期间 API 调用直接失败，不会等待超时
+        let result = actions::call::<_, Value>(&app.registry, server.self_id(), "get_status", &json!({})).await;
+        assert!(matches!(result, Err(actions::ActionError::NotReady { .. })));
+
+        server.set_accepting(true);
+        test_support::wait_connection(&session, true).await;
+        assert!(test_support::wait_for_calls(&server, "get_login_info", 2).await.len() >= 2);
+
+        test_support::shutdown(&session).await;
+    }
+}
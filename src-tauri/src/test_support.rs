@@ -0,0 +1,101 @@
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tauri::Manager;
+use crate::mock_onebot::MockServer;
+use crate::runbot::{self, RunbotState};
+use crate::session::Session;
+use crate::supervisor::Upstream;
+use crate::{AppHandle, Runtime};
+
+pub const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// 测试用的应用：每个实例使用独立的应用数据目录，结束时删除
+pub struct TestApp {
+    app: tauri::App<Runtime>,
+    pub registry: Arc<Mutex<RunbotState>>,
+    data_dir: PathBuf,
+}
+
+impl TestApp {
+    pub fn new() -> Self {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+
+        let mut context = tauri::test::mock_context(tauri::test::noop_assets());
+        context.config_mut().identifier = format!(
+            "runbot-desktop-test-{}-{}",
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::SeqCst)
+        );
+        let app = tauri::test::mock_builder().build(context).unwrap();
+        app.manage(crate::storage::ReplayDirs::default());
+        let data_dir = app.path().app_data_dir().unwrap();
+        Self {
+            app,
+            registry: Arc::new(Mutex::new(RunbotState::default())),
+            data_dir,
+        }
+    }
+
+    pub fn handle(&self) -> &AppHandle {
+        self.app.handle()
+    }
+
+    /// 正向连接到模拟服务器，等待首次认证完成
+    pub async fn connect(&self, server: &MockServer) -> Arc<Mutex<Session>> {
+        let upstream = Upstream::Dial { url: server.url() };
+        let (session, ready) = runbot::start_session(self.handle(), &self.registry, upstream)
+            .await
+            .unwrap();
+        let self_id = tokio::time::timeout(TIMEOUT, ready).await.unwrap().unwrap().unwrap();
+        assert_eq!(self_id, server.self_id());
+        session
+    }
+}
+
+impl Drop for TestApp {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.data_dir);
+    }
+}
+
+/// 等待会话的连接状态满足条件
+pub async fn wait_connection(session: &Mutex<Session>, ready: bool) {
+    let wait = async {
+        while session.lock().unwrap().connection.is_ready() != ready {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    };
+    tokio::time::timeout(TIMEOUT, wait).await.unwrap();
+}
+
+/// 等待模拟服务器收到 count 次指定的 API 调用，返回这些调用的参数
+pub async fn wait_for_calls(server: &MockServer, action: &str, count: usize) -> Vec<serde_json::Value> {
+    let find = || -> Vec<serde_json::Value> {
+        server
+            .calls()
+            .into_iter()
+            .filter(|call| call["action"] == action)
+            .map(|call| call["params"].clone())
+            .collect()
+    };
+    let wait = async {
+        loop {
+            let calls = find();
+            if calls.len() >= count {
+                return calls;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    };
+    tokio::time::timeout(TIMEOUT, wait).await.unwrap()
+}
+
+/// 停止会话的 supervisor
+pub async fn shutdown(session: &Mutex<Session>) {
+    let supervisor = session.lock().unwrap().supervisor.clone();
+    if let Some(supervisor) = supervisor {
+        supervisor.shutdown().await;
+    }
+}
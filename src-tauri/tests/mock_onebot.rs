@@ -0,0 +1,285 @@
+use std::sync::Arc;
+use std::time::Duration;
+use async_trait::async_trait;
+use futures_util::{SinkExt, StreamExt};
+use runbot::prelude::*;
+use runbot_desktop_lib::mock_onebot::{Fixtures, MockConfig, MockLogin, MockServer, ScriptedEvent};
+use serde_json::{json, Value};
+use tokio::net::TcpStream;
+use tokio::sync::mpsc;
+use tokio_tungstenite::tungstenite::protocol::Message as WsMessage;
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+type Client = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+fn fixtures() -> Fixtures {
+    serde_json::from_value(json!({
+        "login": { "user_id": 20002, "nickname": "测试账号" },
+        "friends": [{ "user_id": 30003, "nickname": "好友", "remark": "" }],
+        "groups": [{ "group_id": 40004, "group_name": "测试群", "member_count": 2, "max_member_count": 200 }],
+        "members": {
+            "40004": [
+                { "group_id": 40004, "user_id": 20002, "nickname": "测试账号", "role": "owner" },
+                { "group_id": 40004, "user_id": 30003, "nickname": "好友", "role": "member" }
+            ]
+        },
+        "forwards": {
+            "fwd-1": [{ "sender": { "user_id": 30003, "nickname": "好友" }, "content": "转发内容" }]
+        }
+    }))
+    .unwrap()
+}
+
+async fn start(config: MockConfig) -> MockServer {
+    MockServer::bind("127.0.0.1", 0, config).await.unwrap()
+}
+
+/// 连接并读掉连接时推送的 lifecycle 事件
+async fn connect(server: &MockServer) -> Client {
+    let (mut client, _) = tokio_tungstenite::connect_async(server.url()).await.unwrap();
+    let lifecycle = next_frame(&mut client).await;
+    assert_eq!(lifecycle["meta_event_type"], "lifecycle");
+    assert_eq!(lifecycle["sub_type"], "connect");
+    client
+}
+
+async fn next_frame(client: &mut Client) -> Value {
+    loop {
+        let msg = tokio::time::timeout(TIMEOUT, client.next()).await.unwrap().unwrap().unwrap();
+        if let WsMessage::Text(text) = msg {
+            return serde_json::from_str(&text).unwrap();
+        }
+    }
+}
+
+async fn call(client: &mut Client, action: &str, params: Value) -> Value {
+    let echo = format!("echo-{}", action);
+    let frame = json!({ "action": action, "params": params, "echo": echo });
+    client.send(WsMessage::Text(frame.to_string().into())).await.unwrap();
+    loop {
+        let frame = next_frame(client).await;
+        if frame["echo"] == echo.as_str() {
+            return frame;
+        }
+    }
+}
+
+#[tokio::test]
+async fn answers_actions_from_fixtures() {
+    let server = start(MockConfig { fixtures: fixtures(), ..Default::default() }).await;
+    let mut client = connect(&server).await;
+
+    let login = call(&mut client, "get_login_info", Value::Null).await;
+    assert_eq!(login["status"], "ok");
+    assert_eq!(login["data"]["user_id"], 20002);
+    assert_eq!(login["data"]["nickname"], "测试账号");
+
+    let friends = call(&mut client, "get_friend_list", json!({})).await;
+    assert_eq!(friends["data"][0]["user_id"], 30003);
+
+    let groups = call(&mut client, "get_group_list", json!({})).await;
+    assert_eq!(groups["data"][0]["group_name"], "测试群");
+
+    let members = call(&mut client, "get_group_member_list", json!({ "group_id": 40004 })).await;
+    assert_eq!(members["data"].as_array().unwrap().len(), 2);
+
+    let member = call(&mut client, "get_group_member_info", json!({ "group_id": 40004, "user_id": 20002 })).await;
+    assert_eq!(member["data"]["role"], "owner");
+
+    let forward = call(&mut client, "get_forward_msg", json!({ "id": "fwd-1" })).await;
+    assert_eq!(forward["data"]["messages"][0]["content"], "转发内容");
+
+    let missing = call(&mut client, "get_group_info", json!({ "group_id": 1 })).await;
+    assert_eq!(missing["status"], "failed");
+
+    let unknown = call(&mut client, "no_such_action", json!({})).await;
+    assert_eq!(unknown["status"], "failed");
+    assert_eq!(unknown["retcode"], 1404);
+
+    let calls = server.calls();
+    assert_eq!(calls.len(), 8);
+    assert_eq!(calls[0]["action"], "get_login_info");
+}
+
+#[tokio::test]
+async fn sent_messages_can_be_fetched() {
+    let server = start(MockConfig { fixtures: fixtures(), ..Default::default() }).await;
+    let mut client = connect(&server).await;
+
+    let message = json!([{ "type": "text", "data": { "text": "你好" } }]);
+    let sent = call(&mut client, "send_group_msg", json!({ "group_id": 40004, "message": message })).await;
+    let message_id = sent["data"]["message_id"].as_i64().unwrap();
+
+    let fetched = call(&mut client, "get_msg", json!({ "message_id": message_id })).await;
+    assert_eq!(fetched["status"], "ok");
+    assert_eq!(fetched["data"]["message_type"], "group");
+    assert_eq!(fetched["data"]["group_id"], 40004);
+    assert_eq!(fetched["data"]["sender"]["user_id"], 20002);
+    assert_eq!(fetched["data"]["raw_message"], "你好");
+
+    // 推送的消息同样可以查到，message_id 不会重复
+    let pushed_id = server.push_private_message(30003, "收到");
+    assert_ne!(pushed_id, message_id);
+    let pushed = next_frame(&mut client).await;
+    assert_eq!(pushed["post_type"], "message");
+    assert_eq!(pushed["message_id"], pushed_id);
+    assert_eq!(pushed["sender"]["nickname"], "好友");
+
+    let fetched = call(&mut client, "get_msg", json!({ "message_id": pushed_id })).await;
+    assert_eq!(fetched["data"]["raw_message"], "收到");
+}
+
+#[tokio::test]
+async fn rejects_connections_without_access_token() {
+    let server = start(MockConfig {
+        access_token: Some("secret".to_string()),
+        ..Default::default()
+    })
+    .await;
+
+    assert!(tokio_tungstenite::connect_async(server.url()).await.is_err());
+
+    let url = format!("{}?access_token=secret", server.url());
+    assert!(tokio_tungstenite::connect_async(url).await.is_ok());
+}
+
+#[tokio::test]
+async fn pushes_heartbeats() {
+    let server = start(MockConfig {
+        heartbeat_interval: Some(Duration::from_millis(50)),
+        ..Default::default()
+    })
+    .await;
+    let mut client = connect(&server).await;
+
+    let heartbeat = next_frame(&mut client).await;
+    assert_eq!(heartbeat["meta_event_type"], "heartbeat");
+    assert_eq!(heartbeat["self_id"], MockLogin::default().user_id);
+    assert_eq!(heartbeat["interval"], 50);
+}
+
+/// 把收到的 Post 转交给测试
+#[derive(Debug)]
+struct Forward(mpsc::UnboundedSender<Post>);
+
+#[async_trait]
+impl PostProcessor for Forward {
+    fn id(&self) -> &'static str {
+        "forward"
+    }
+
+    async fn process_post(&self, _bot_ctx: Arc<BotContext>, post: &Post) -> anyhow::Result<bool> {
+        let _ = self.0.send(post.clone());
+        Ok(false)
+    }
+}
+
+async fn next_post(posts: &mut mpsc::UnboundedReceiver<Post>) -> Post {
+    loop {
+        let post = tokio::time::timeout(TIMEOUT, posts.recv()).await.unwrap().unwrap();
+        if !matches!(post, Post::MetaEvent(_) | Post::Response(_)) {
+            return post;
+        }
+    }
+}
+
+#[tokio::test]
+async fn runbot_client_round_trip() {
+    let server = start(MockConfig { fixtures: fixtures(), ..Default::default() }).await;
+
+    let (tx, mut posts) = mpsc::unbounded_channel();
+    let bot_ctx = BotContextBuilder::new()
+        .url(server.url())
+        .add_processor(Box::new(Forward(tx)) as Box<dyn PostProcessor>)
+        .build()
+        .unwrap();
+    tokio::spawn(loop_client(bot_ctx.clone()));
+    assert!(server.wait_connected(TIMEOUT).await);
+
+    let mut login = bot_ctx.get_login_info().await;
+    for _ in 0..50 {
+        if login.is_ok() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        login = bot_ctx.get_login_info().await;
+    }
+    assert_eq!(login.unwrap().user_id, 20002);
+
+    let sent = bot_ctx
+        .send_private_message(30003, "来自 runbot")
+        .await
+        .unwrap()
+        .wait_response()
+        .await
+        .unwrap();
+    let params = server.wait_for_call("send_private_msg", TIMEOUT).await.unwrap();
+    assert_eq!(params["user_id"], 30003);
+    assert!(sent.message_id > 0);
+
+    server
+        .run_script(vec![
+            ScriptedEvent {
+                delay_ms: 0,
+                event: server_message(&server, 30003, "脚本消息"),
+            },
+            ScriptedEvent {
+                delay_ms: 10,
+                event: json!({
+                    "post_type": "request",
+                    "request_type": "friend",
+                    "user_id": 50005,
+                    "comment": "加个好友",
+                    "flag": "flag-1",
+                }),
+            },
+        ])
+        .await;
+    server.push_group_request(40004, 60006, "add", "问题：暗号\n答案：芝麻开门", "flag-2");
+
+    match next_post(&mut posts).await {
+        Post::Message(message) => {
+            assert_eq!(message.user_id, 30003);
+            assert_eq!(message.raw_message, "脚本消息");
+            assert_eq!(message.self_id, 20002);
+        }
+        post => panic!("应收到消息事件: {:?}", post),
+    }
+    match next_post(&mut posts).await {
+        Post::Request(Request::Friend(request)) => {
+            assert_eq!(request.user_id, 50005);
+            assert_eq!(request.flag, "flag-1");
+        }
+        post => panic!("应收到好友申请: {:?}", post),
+    }
+    match next_post(&mut posts).await {
+        Post::Request(Request::Group(request)) => {
+            assert_eq!(request.group_id, 40004);
+            assert_eq!(request.flag, "flag-2");
+        }
+        post => panic!("应收到加群申请: {:?}", post),
+    }
+
+    bot_ctx.shutdown().await.unwrap();
+    server.stop().await;
+}
+
+/// 构造一条完整的私聊消息事件（脚本中的事件需要自带 runbot 解析所需的全部字段）
+fn server_message(server: &MockServer, user_id: i64, text: &str) -> Value {
+    json!({
+        "post_type": "message",
+        "message_type": "private",
+        "sub_type": "friend",
+        "self_id": server.self_id(),
+        "user_id": user_id,
+        "message_id": 9001,
+        "message_seq": 9001,
+        "sender": { "user_id": user_id, "nickname": "好友" },
+        "message": [{ "type": "text", "data": { "text": text } }],
+        "raw_message": text,
+        "font": 14,
+        "message_format": "array",
+    })
+}
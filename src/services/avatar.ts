@@ -4,6 +4,7 @@
  */
 
 import { invoke } from '@tauri-apps/api/core';
+import { getConnectionState } from '../stores/connection';
 
 /**
  * 获取用户头像 URL（带缓存）
@@ -57,3 +58,13 @@ export async function clearAvatarCache(selfId?: number): Promise<void> {
   }
 }
 
+
+/**
+ * 生成头像的 asset 协议 URL（按当前账号区分缓存目录）
+ * @param kind 头像类型：user 或 group
+ * @param id QQ 号或群号
+ */
+export function avatarUrl(kind: 'user' | 'group', id: number | string): string {
+  const selfId = getConnectionState().selfId;
+  return selfId ? `asset://avatar/${selfId}/${kind}/${id}.png` : `asset://avatar/${kind}/${id}.png`;
+}
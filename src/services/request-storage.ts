@@ -1,5 +1,16 @@
 import { invoke } from '@tauri-apps/api/core';
-import type { RequestItem } from '../stores/requests';
+import type { RequestItem, RequestStatus } from '../stores/requests';
+
+/**
+ * 请求状态变更记录
+ */
+export interface RequestTransition {
+  from_status?: RequestStatus;
+  to_status: RequestStatus;
+  handled_by?: string; // manual、rule:规则名、server
+  note?: string;
+  time: number;
+}
 
 /**
  * 保存请求到数据库
@@ -17,7 +28,7 @@ export async function saveRequest(request: RequestItem, selfId?: number): Promis
  */
 export async function updateRequestStatus(
   flag: string,
-  status: 'pending' | 'approved' | 'rejected',
+  status: RequestStatus,
   selfId?: number
 ): Promise<void> {
   await invoke('update_request_status', {
@@ -31,10 +42,11 @@ export async function updateRequestStatus(
  * 获取请求列表
  */
 export async function getRequests(
-  status?: 'pending' | 'approved' | 'rejected',
+  status?: RequestStatus,
   limit?: number,
   offset?: number,
-  selfId?: number
+  selfId?: number,
+  userId?: number
 ): Promise<RequestItem[]> {
   console.log('[request-storage] getRequests 被调用:', {
     status: status || null,
@@ -45,6 +57,7 @@ export async function getRequests(
   
   const results = await invoke<string[]>('get_requests', {
     status: status || null,
+    userId: userId || null,
     limit: limit || null,
     offset: offset || null,
     selfId: selfId || null,
@@ -58,6 +71,16 @@ export async function getRequests(
   return parsed;
 }
 
+/**
+ * 获取请求的状态变更记录
+ */
+export async function getRequestTransitions(flag: string, selfId?: number): Promise<RequestTransition[]> {
+  return await invoke<RequestTransition[]>('get_request_transitions', {
+    flag,
+    selfId: selfId || null,
+  });
+}
+
 /**
  * 删除请求
  */
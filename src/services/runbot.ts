@@ -1,9 +1,24 @@
 import { invoke } from '@tauri-apps/api/core';
 import { listen, UnlistenFn } from '@tauri-apps/api/event';
+import type { Contact, Group } from '../stores/contacts';
+import type { GroupMemberInfo } from '../stores/group-members';
+
+export type ConnectionState =
+  | { kind: 'idle' }
+  | { kind: 'connecting'; attempt: number; retry_in_ms: number | null } // attempt > 0 表示断线重连
+  | { kind: 'handshaking' }
+  | { kind: 'authenticated'; self_id: number }
+  | { kind: 'degraded'; reason: string }
+  | { kind: 'disconnected'; reason: { kind: 'requested' } | { kind: 'failed'; message: string } };
 
 export interface ConnectionStatus {
-  status: 'connected' | 'disconnected' | 'connecting' | 'error';
+  account_id?: number;   // 会话所属账号，首次认证前为空
+  state: ConnectionState;
+  status: 'connected' | 'disconnected' | 'connecting' | 'reconnecting' | 'failed'; // 兼容字段
   message?: string;
+  self_id?: number;      // 认证得到的 QQ 号
+  last_error?: string;
+  connected_at?: number; // 最近一次认证成功的时间（秒）
 }
 
 export interface OneBotMessage {
@@ -20,12 +35,6 @@ export interface OneBotMessage {
   raw_message?: string;
   sender?: any;
   raw?: any;
-  // API 响应字段
-  status?: string;
-  retcode?: number;
-  data?: any;
-  echo?: string;
-  action?: string;
   // 消息发送状态
   sendStatus?: 'sending' | 'sent' | 'failed';
   // 撤回状态
@@ -39,41 +48,545 @@ export interface OneBotMessage {
   flag?: string;         // 请求标识，用于处理请求
 }
 
+/**
+ * 通知事件（保留事件自带的时间和全部字段）
+ */
+interface NoticeBase {
+  time: number;
+  self_id: number;
+}
+
+export type NotifyNotice = NoticeBase & (
+  | { notice_type: 'notify'; sub_type: 'poke'; group_id?: number; user_id: number; target_id: number }
+  | { notice_type: 'notify'; sub_type: 'lucky_king'; group_id: number; user_id: number; target_id: number }
+  | { notice_type: 'notify'; sub_type: 'honor'; group_id: number; honor_type: 'talkative' | 'performer' | 'emotion'; user_id: number }
+);
+
+export type Notice =
+  | (NoticeBase & {
+      notice_type: 'group_upload';
+      group_id: number;
+      user_id: number;
+      file: { id: string; name: string; size: number; busid: number };
+    })
+  | (NoticeBase & { notice_type: 'group_admin'; sub_type: 'set' | 'unset'; group_id: number; user_id: number })
+  | (NoticeBase & {
+      notice_type: 'group_decrease';
+      sub_type: 'leave' | 'kick' | 'kick_me';
+      group_id: number;
+      operator_id: number;
+      user_id: number;
+    })
+  | (NoticeBase & {
+      notice_type: 'group_increase';
+      sub_type: 'approve' | 'invite';
+      group_id: number;
+      operator_id: number;
+      user_id: number;
+    })
+  | (NoticeBase & {
+      notice_type: 'group_ban';
+      sub_type: 'ban' | 'lift_ban';
+      group_id: number;
+      operator_id: number;
+      user_id: number;
+      duration: number; // 禁言秒数，解除禁言时为 0
+    })
+  | (NoticeBase & { notice_type: 'friend_add'; user_id: number })
+  | (NoticeBase & {
+      notice_type: 'group_recall';
+      group_id: number;
+      user_id: number;
+      operator_id: number;
+      message_id: number;
+    })
+  | (NoticeBase & { notice_type: 'friend_recall'; user_id: number; message_id: number })
+  | NotifyNotice
+  | (NoticeBase & { notice_type: 'unknown'; data: Record<string, any> });
+
+/**
+ * 已保存的通知
+ */
+export interface NoticeRecord {
+  id: number;
+  account_id: number;
+  notice: Notice;
+}
+
+/**
+ * 通知查询条件
+ */
+export interface NoticeQuery {
+  noticeType?: string;
+  groupId?: number;
+  userId?: number;
+  limit?: number;
+  offset?: number;
+}
+
+/**
+ * 批量处理请求时单个请求的结果
+ */
+export interface RequestResult {
+  flag: string;
+  ok: boolean;
+  error?: string;
+}
+
+/**
+ * 请求自动审核规则（所有已设置的条件都满足时命中，按顺序取第一条）
+ */
+export interface ReviewRule {
+  name: string;
+  enabled?: boolean;
+  request_type?: 'friend' | 'group';
+  sub_type?: 'add' | 'invite';
+  group_ids?: number[];
+  comment?:
+    | { kind: 'exact'; text: string; ignore_case?: boolean }
+    | { kind: 'regex'; pattern: string; ignore_case?: boolean }
+    | { kind: 'answers'; answers: string[]; ignore_case?: boolean };
+  min_age?: number;
+  max_age?: number;
+  min_level?: number;
+  decision:
+    | { action: 'approve'; remark?: string }
+    | { action: 'reject'; reason?: string }
+    | { action: 'pending' };
+}
+
+/**
+ * 自动回复规则（按 priority 从高到低匹配，第一条命中的规则回复）
+ *
+ * 回复模板变量：{sender}、{nickname}、{card}、{user_id}、{group_id}、{message}、{time}、{date}
+ */
+export interface AutoReplyRule {
+  id?: number;
+  name: string;
+  enabled?: boolean;
+  match_type: 'keyword' | 'regex' | 'at_me';
+  pattern?: string;
+  ignore_case?: boolean;
+  scope?: 'all' | 'group' | 'private';
+  target_ids?: number[];
+  reply: string;
+  at_sender?: boolean;
+  quote?: boolean;
+  cooldown_secs?: number;
+  priority?: number;
+  created_at?: number;
+  updated_at?: number;
+}
+
+/**
+ * 定时消息任务
+ *
+ * run_at（秒级时间戳）与 cron（本地时间，5 段或带秒的 6、7 段写法）二选一
+ */
+export interface ScheduledJob {
+  account_id?: number;
+  id?: number;
+  name?: string;
+  target_type: 'group' | 'private';
+  target_id: number;
+  message: string;
+  run_at?: number;
+  cron?: string;
+  misfire?: 'catch_up' | 'skip';
+  state?: 'scheduled' | 'completed' | 'missed' | 'cancelled' | 'failed';
+  next_run_at?: number;
+  last_run_at?: number;
+  last_local_message_id?: string;
+  last_error?: string;
+  run_count?: number;
+  created_at?: number;
+  updated_at?: number;
+}
+
+/**
+ * 脚本（用户数据目录 scripts 文件夹中的 *.rhai）
+ */
+export interface ScriptInfo {
+  name: string;
+  path: string;
+  error?: string;
+  timeout_ms: number;
+  handles_posts: boolean;
+}
+
+/**
+ * 脚本日志
+ */
+export interface ScriptLog {
+  account_id: number;
+  script: string;
+  level: 'info' | 'debug' | 'error';
+  message: string;
+  time: number;
+}
+
+/**
+ * 插件清单中声明的权限
+ */
+export interface PluginPermissions {
+  events: ('message' | 'message_sent' | 'notice' | 'request' | 'meta_event')[];
+  send_message?: {
+    reply: boolean;
+    groups: number[];
+    users: number[];
+  };
+  storage: boolean;
+}
+
+/**
+ * WASM 插件（用户数据目录 plugins 文件夹中的子目录）
+ */
+export interface PluginInfo {
+  /** 插件目录名，授权和存储都按它区分（清单中的 id 只用于显示） */
+  id: string;
+  dir: string;
+  manifest?: {
+    id: string;
+    name: string;
+    version: string;
+    description: string;
+    module: string;
+    permissions: PluginPermissions;
+  };
+  /** 清单和模块的 SHA-256，启用时传回 */
+  fingerprint?: string;
+  enabled: boolean;
+  /** 授权时的清单和模块与当前一致 */
+  granted: boolean;
+  loaded: boolean;
+  error?: string;
+}
+
+/**
+ * 插件日志
+ */
+export interface PluginLog {
+  account_id: number;
+  plugin: string;
+  level: 'info' | 'error';
+  message: string;
+  time: number;
+}
+
+/**
+ * 发送队列中的消息
+ */
+export interface OutboxEntry {
+  account_id: number;
+  local_message_id: string;
+  action: string;
+  params: Record<string, any>;
+  state: 'pending' | 'sending' | 'sent' | 'failed';
+  attempts: number;
+  last_error?: string;
+  message_id?: number;
+  created_at: number;
+}
+
+/**
+ * 流量录制文件
+ */
+export interface RecordingFile {
+  name: string;
+  path: string;
+  size: number;
+  modified: number;
+}
+
+/**
+ * 中继服务器的下游客户端
+ */
+export interface RelayClient {
+  id: number;
+  name: string; // 连接时的 name 参数，没有时为 User-Agent
+  addr: string;
+  role: 'universal' | 'api' | 'event';
+  connected_at: number; // 秒级时间戳
+}
+
+/**
+ * 下游客户端发起的 API 调用
+ */
+export interface RelayAction {
+  account_id: number;
+  client_id: number;
+  client_name: string;
+  action: string;
+  params: any;
+  status: string; // ok、failed 或 async
+  retcode: number;
+  message: string;
+  elapsed_ms: number;
+  time: number; // 毫秒时间戳
+}
+
+/**
+ * 中继服务器状态
+ */
+export interface RelayStatus {
+  addr: string;
+  clients: RelayClient[];
+}
+
+/**
+ * 正在运行的后台服务（以 --headless 启动）
+ */
+export interface DaemonInfo {
+  pid: number;
+  addr: string; // 本地控制接口地址
+  token: string;
+  started_at: number; // 秒级时间戳
+}
+
+/**
+ * 本地控制接口的监听信息
+ */
+export interface ControlServerInfo {
+  addr: string;
+  token: string; // 访问令牌，通过 Authorization: Bearer 或 access_token 参数传递
+}
+
+/**
+ * 消息段（OneBot v11 数组格式），参数值统一为字符串；未知类型的消息段原样保留
+ */
+export interface MessageSegment {
+  type: string;
+  data: Record<string, any>;
+}
+
+/**
+ * 类型化 OneBot API 命令的错误
+ */
+export type ActionError =
+  | { kind: 'not_ready'; message: string }
+  | { kind: 'request'; message: string }
+  | { kind: 'failed'; retcode: number; message: string; wording: string }
+  | { kind: 'invalid_response'; message: string };
+
+/**
+ * OneBot API 调用结果
+ */
+export interface ActionResponse<T = any> {
+  status: string;
+  retcode: number; // 0 表示成功
+  data: T;
+  message: string;
+  wording: string;
+}
+
+/**
+ * 把 OneBot 群成员信息转换为前端格式
+ */
+function toGroupMemberInfo(groupId: number, item: any): GroupMemberInfo {
+  return {
+    groupId,
+    userId: item.user_id,
+    nickname: item.nickname || `用户 ${item.user_id}`,
+    card: item.card,
+    role: item.role,
+    joinTime: item.join_time,
+    lastSentTime: item.last_sent_time,
+    level: item.level,
+    title: item.title,
+  };
+}
+
 class RunbotService {
   private statusListeners: UnlistenFn[] = [];
   private messageListeners: UnlistenFn[] = [];
-  private apiResponseCallbacks: Map<string, (data: any) => void> = new Map();
+  // 当前界面使用的账号（connect 成功后确定），后端按账号区分会话
+  private accountId: number | null = null;
 
   /**
    * 连接 Runbot WebSocket 服务器
    */
-  async connect(wsUrl: string, accessToken?: string): Promise<void> {
-    await invoke('connect_runbot', {
+  async connect(wsUrl: string, accessToken?: string): Promise<number> {
+    // 首次认证成功后返回账号
+    const accountId = await invoke<number>('connect_runbot', {
       wsUrl,
       accessToken: accessToken || null,
     });
+    this.accountId = accountId;
+    return accountId;
+  }
+
+  /**
+   * 通过 OneBot HTTP API 连接，事件由本地 HTTP 接收器接收
+   * @param listenHost 事件接收器监听地址（OneBot 实现的 HTTP POST 上报地址）
+   * @param secret 上报签名密钥，设置后校验 X-Signature
+   */
+  async connectHttp(
+    apiUrl: string,
+    listenHost: string,
+    listenPort: number,
+    accessToken?: string,
+    secret?: string,
+  ): Promise<number> {
+    const accountId = await invoke<number>('connect_runbot_http', {
+      apiUrl,
+      accessToken: accessToken || null,
+      listenHost,
+      listenPort,
+      secret: secret || null,
+    });
+    this.accountId = accountId;
+    return accountId;
+  }
+
+  /**
+   * 回放录制文件（不连接服务器），返回录制中的账号
+   * @param speed 回放速度倍数，默认 1
+   */
+  async replay(path: string, speed?: number): Promise<number> {
+    const accountId = await invoke<number>('replay_recording', {
+      path,
+      speed: speed ?? null,
+    });
+    this.accountId = accountId;
+    return accountId;
+  }
+
+  /**
+   * 获取正在运行的后台服务（未运行时为 null）
+   */
+  async getDaemon(): Promise<DaemonInfo | null> {
+    return await invoke<DaemonInfo | null>('get_daemon');
+  }
+
+  /**
+   * 附加到后台服务，不再自己连接 OneBot 实现；返回后台服务已连接的账号，当前账号为第一个
+   */
+  async attachDaemon(): Promise<number[]> {
+    const accounts = await invoke<number[]>('attach_daemon');
+    if (accounts.length > 0) {
+      this.accountId = accounts[0];
+    }
+    return accounts;
   }
 
   /**
-   * 断开连接
+   * 断开连接（未完成认证时取消正在进行的连接）
    */
   async disconnect(): Promise<void> {
-    await invoke('disconnect_runbot');
+    await invoke('disconnect_runbot', { accountId: this.accountId });
+    this.accountId = null;
     this.cleanupListeners();
   }
 
+  /**
+   * 启动反向 WebSocket 服务器，等待 OneBot 实现连接进来
+   * @returns 实际监听的地址
+   */
+  async startReverseServer(host: string, port: number, accessToken?: string): Promise<string> {
+    return await invoke<string>('start_reverse_server', {
+      host,
+      port,
+      accessToken: accessToken || null,
+    });
+  }
+
+  /**
+   * 停止反向 WebSocket 服务器
+   */
+  async stopReverseServer(): Promise<void> {
+    await invoke('stop_reverse_server');
+  }
+
+  /**
+   * 获取反向 WebSocket 服务器的监听地址（未启动时为 null）
+   */
+  async getReverseServerAddr(): Promise<string | null> {
+    return await invoke<string | null>('get_reverse_server_addr');
+  }
+
+  /**
+   * 启动本地控制接口（只监听 127.0.0.1），供外部工具调用
+   * @param port 端口，0 表示自动分配
+   * @param token 访问令牌，为空时随机生成
+   */
+  async startControlServer(port: number, token?: string): Promise<ControlServerInfo> {
+    return await invoke<ControlServerInfo>('start_control_server', {
+      port,
+      token: token || null,
+    });
+  }
+
+  /**
+   * 停止本地控制接口
+   */
+  async stopControlServer(): Promise<void> {
+    await invoke('stop_control_server');
+  }
+
+  /**
+   * 获取本地控制接口的监听信息（未启动时为 null）
+   */
+  async getControlServer(): Promise<ControlServerInfo | null> {
+    return await invoke<ControlServerInfo | null>('get_control_server');
+  }
+
+  /**
+   * 启动中继服务器，把当前账号作为正向 WebSocket OneBot 实现提供给下游机器人
+   * @returns 实际监听的地址
+   */
+  async startRelayServer(host: string, port: number, accessToken?: string): Promise<string> {
+    return await invoke<string>('start_relay_server', {
+      accountId: this.accountId,
+      host,
+      port,
+      accessToken: accessToken || null,
+    });
+  }
+
+  /**
+   * 停止中继服务器
+   */
+  async stopRelayServer(): Promise<void> {
+    await invoke('stop_relay_server', { accountId: this.accountId });
+  }
+
+  /**
+   * 获取中继服务器的地址和下游客户端（未启动时为 null）
+   */
+  async getRelayServer(): Promise<RelayStatus | null> {
+    return await invoke<RelayStatus | null>('get_relay_server', { accountId: this.accountId });
+  }
+
+  /**
+   * 获取下游客户端最近的 API 调用
+   */
+  async getRelayActions(): Promise<RelayAction[]> {
+    return await invoke<RelayAction[]>('get_relay_actions', { accountId: this.accountId });
+  }
+
   /**
    * 获取连接状态
    */
   async getConnectionStatus(): Promise<ConnectionStatus> {
-    return await invoke('get_runbot_status');
+    const sessions = await invoke<ConnectionStatus[]>('list_runbot_sessions');
+    const current = sessions.find((s) => s.account_id != null && s.account_id === this.accountId)
+      ?? sessions.find((s) => s.account_id != null);
+    if (!current) {
+      throw new Error('未连接');
+    }
+    return current;
   }
 
   /**
    * 获取当前 self_id（QQ 号）
    */
   async getSelfId(): Promise<number | null> {
-    return await invoke<number | null>('get_runbot_self_id');
+    if (this.accountId == null) {
+      // 页面刷新后从后端已有的会话中恢复
+      const sessions = await invoke<ConnectionStatus[]>('list_runbot_sessions');
+      this.accountId = sessions.find((s) => s.account_id != null)?.account_id ?? null;
+    }
+    return this.accountId;
   }
 
   /**
@@ -81,62 +594,44 @@ class RunbotService {
    */
   async sendMessage(action: string, params: Record<string, any>): Promise<void> {
     await invoke('send_runbot_message', {
+      accountId: this.accountId,
       action,
       params,
     });
   }
 
   /**
-   * 发送 OneBot API 请求并等待响应
+   * 调用 OneBot API 并等待响应（响应由后端按 echo 匹配）
    */
-  async sendMessageWithResponse(action: string, params: Record<string, any>, timeout = 10000): Promise<any> {
-    // 生成唯一的 echo 标识
-    const echo = `${action}-${Date.now()}-${Math.random().toString(36).substring(7)}`;
-
-    // 创建一个 Promise 来等待响应
-    return new Promise((resolve, reject) => {
-      const timeoutId = setTimeout(() => {
-        this.apiResponseCallbacks.delete(echo);
-        reject(new Error(`API 请求超时: ${action}`));
-      }, timeout);
-
-      // 注册回调
-      this.apiResponseCallbacks.set(echo, (data: any) => {
-        clearTimeout(timeoutId);
-        this.apiResponseCallbacks.delete(echo);
-        resolve(data);
-      });
-
-      // 发送请求（带 echo）
-      invoke('send_runbot_message', {
-        action,
-        params: { ...params, echo },
-      }).catch((error) => {
-        clearTimeout(timeoutId);
-        this.apiResponseCallbacks.delete(echo);
-        reject(error);
-      });
+  async callAction<T = any>(action: string, params: Record<string, any>, timeout = 10000): Promise<ActionResponse<T>> {
+    return await invoke<ActionResponse<T>>('call_action', {
+      accountId: this.accountId,
+      action,
+      params,
+      timeoutMs: timeout,
     });
   }
 
   /**
-   * 处理 API 响应（需要从外部调用）
+   * 发送 OneBot API 请求并等待响应，失败时抛出错误
+   * @returns 响应中的 data
    */
-  handleApiResponse(message: OneBotMessage): void {
-    // 尝试从 message.echo 或 message.raw.echo 获取 echo
-    const echo = message.echo || (message.raw && typeof message.raw === 'object' ? (message.raw as any).echo : null);
-    
-    console.log('[RunbotService] handleApiResponse:', { echo, hasCallback: echo ? this.apiResponseCallbacks.has(echo) : false });
-    
-    if (echo && this.apiResponseCallbacks.has(echo)) {
-      const callback = this.apiResponseCallbacks.get(echo);
-      if (callback) {
-        // 尝试从不同位置获取 data
-        const data = message.data || (message.raw && typeof message.raw === 'object' ? (message.raw as any).data : null);
-        console.log('[RunbotService] 调用 API 响应回调, echo:', echo, 'data:', data);
-        callback(data);
-      }
+  async sendMessageWithResponse<T = any>(action: string, params: Record<string, any>, timeout = 10000): Promise<T> {
+    const response = await this.callAction<T>(action, params, timeout);
+    if (response.retcode !== 0) {
+      throw new Error(`${action} 失败: ${response.wording || response.message || response.retcode}`);
     }
+    return response.data;
+  }
+
+  /**
+   * 调用类型化的 OneBot v11 API 命令（参数在 Rust 端校验，失败时抛出 ActionError）
+   */
+  private async invokeAction<T = void>(command: string, params: Record<string, any> = {}): Promise<T> {
+    return await invoke<T>(command, {
+      accountId: this.accountId,
+      params,
+    });
   }
 
   /**
@@ -145,6 +640,11 @@ class RunbotService {
   async onStatusChange(callback: (status: ConnectionStatus) => void): Promise<UnlistenFn> {
     const unlisten = await listen<ConnectionStatus>('runbot-status', (event) => {
       console.log('runbot-status', event.payload);
+      // 只处理当前账号（或尚未确定账号的连接）的状态
+      const accountId = event.payload.account_id;
+      if (accountId != null && this.accountId != null && accountId !== this.accountId) {
+        return;
+      }
       callback(event.payload);
     });
 
@@ -157,6 +657,69 @@ class RunbotService {
    */
   async onMessage(callback: (message: OneBotMessage) => void): Promise<UnlistenFn> {
     const unlisten = await listen<OneBotMessage>('runbot-message', (event) => {
+      if (this.accountId != null && event.payload.self_id !== this.accountId) {
+        return;
+      }
+      callback(event.payload);
+    });
+
+    this.messageListeners.push(unlisten);
+    return unlisten;
+  }
+
+  /**
+   * 监听发送队列中消息的状态变化
+   */
+  async onOutboxState(callback: (entry: OutboxEntry) => void): Promise<UnlistenFn> {
+    const unlisten = await listen<OutboxEntry>('outbox-state', (event) => {
+      if (this.accountId != null && event.payload.account_id !== this.accountId) {
+        return;
+      }
+      callback(event.payload);
+    });
+
+    this.messageListeners.push(unlisten);
+    return unlisten;
+  }
+
+  /**
+   * 获取发送队列（默认只包含未发送成功的消息）
+   */
+  async getOutbox(includeSent: boolean = false): Promise<OutboxEntry[]> {
+    return await invoke<OutboxEntry[]>('get_outbox', {
+      accountId: this.accountId,
+      includeSent,
+    });
+  }
+
+  /**
+   * 重新发送失败的消息
+   */
+  async retryOutboxMessage(localMessageId: string): Promise<void> {
+    await invoke('retry_outbox_message', {
+      accountId: this.accountId,
+      localMessageId,
+    });
+  }
+
+  /**
+   * 从发送队列中移除消息
+   */
+  async deleteOutboxMessage(localMessageId: string): Promise<void> {
+    await invoke('delete_outbox_message', {
+      accountId: this.accountId,
+      localMessageId,
+    });
+  }
+
+  /**
+   * 监听定时任务状态变化（保存、取消、发送、跳过）
+   */
+  async onScheduleState(callback: (job: ScheduledJob) => void): Promise<UnlistenFn> {
+    const unlisten = await listen<ScheduledJob>('schedule-state', (event) => {
+      if (this.accountId != null && event.payload.account_id !== this.accountId) {
+        return;
+      }
       callback(event.payload);
     });
 
@@ -164,6 +727,166 @@ class RunbotService {
     return unlisten;
   }
 
+  /**
+   * 获取定时任务（默认只包含等待发送的任务）
+   */
+  async getScheduledJobs(includeFinished: boolean = false): Promise<ScheduledJob[]> {
+    return await invoke<ScheduledJob[]>('get_scheduled_jobs', {
+      accountId: this.accountId,
+      includeFinished,
+    });
+  }
+
+  /**
+   * 新建（不带 id）或修改定时任务
+   */
+  async saveScheduledJob(job: ScheduledJob): Promise<ScheduledJob> {
+    return await invoke<ScheduledJob>('save_scheduled_job', { accountId: this.accountId, job });
+  }
+
+  /**
+   * 取消定时任务
+   */
+  async cancelScheduledJob(id: number): Promise<ScheduledJob> {
+    return await invoke<ScheduledJob>('cancel_scheduled_job', { accountId: this.accountId, id });
+  }
+
+  /**
+   * 监听通知事件
+   */
+  async onNotice(callback: (record: NoticeRecord) => void): Promise<UnlistenFn> {
+    const unlisten = await listen<NoticeRecord>('runbot-notice', (event) => {
+      if (this.accountId != null && event.payload.account_id !== this.accountId) {
+        return;
+      }
+      callback(event.payload);
+    });
+
+    this.messageListeners.push(unlisten);
+    return unlisten;
+  }
+
+  /**
+   * 监听请求列表更新（后端补全了请求的用户名、群名）
+   */
+  async onRequestsUpdated(callback: () => void): Promise<UnlistenFn> {
+    const unlisten = await listen<{ account_id: number }>('requests-updated', (event) => {
+      if (this.accountId != null && event.payload.account_id !== this.accountId) {
+        return;
+      }
+      callback();
+    });
+
+    this.messageListeners.push(unlisten);
+    return unlisten;
+  }
+
+  /**
+   * 监听下游客户端通过中继发起的 API 调用
+   */
+  async onRelayAction(callback: (action: RelayAction) => void): Promise<UnlistenFn> {
+    const unlisten = await listen<RelayAction>('relay-action', (event) => {
+      if (this.accountId != null && event.payload.account_id !== this.accountId) {
+        return;
+      }
+      callback(event.payload);
+    });
+
+    this.messageListeners.push(unlisten);
+    return unlisten;
+  }
+
+  /**
+   * 监听中继服务器下游客户端的连接和断开
+   */
+  async onRelayClients(callback: (clients: RelayClient[]) => void): Promise<UnlistenFn> {
+    const unlisten = await listen<{ account_id: number; clients: RelayClient[] }>('relay-clients', (event) => {
+      if (this.accountId != null && event.payload.account_id !== this.accountId) {
+        return;
+      }
+      callback(event.payload.clients);
+    });
+
+    this.messageListeners.push(unlisten);
+    return unlisten;
+  }
+
+  /**
+   * 监听脚本日志
+   */
+  async onScriptLog(callback: (log: ScriptLog) => void): Promise<UnlistenFn> {
+    const unlisten = await listen<ScriptLog>('script-log', (event) => {
+      if (this.accountId != null && event.payload.account_id !== this.accountId) {
+        return;
+      }
+      callback(event.payload);
+    });
+
+    this.messageListeners.push(unlisten);
+    return unlisten;
+  }
+
+  /**
+   * 监听插件日志
+   */
+  async onPluginLog(callback: (log: PluginLog) => void): Promise<UnlistenFn> {
+    const unlisten = await listen<PluginLog>('plugin-log', (event) => {
+      if (this.accountId != null && event.payload.account_id !== this.accountId) {
+        return;
+      }
+      callback(event.payload);
+    });
+
+    this.messageListeners.push(unlisten);
+    return unlisten;
+  }
+
+  /**
+   * 监听回放结束（所有录制的事件都已回放）
+   */
+  async onReplayFinished(callback: () => void): Promise<UnlistenFn> {
+    const unlisten = await listen<{ account_id: number }>('runbot-replay-finished', (event) => {
+      if (this.accountId != null && event.payload.account_id !== this.accountId) {
+        return;
+      }
+      callback();
+    });
+
+    this.messageListeners.push(unlisten);
+    return unlisten;
+  }
+
+  /**
+   * 开始录制当前连接的原始帧，返回录制文件路径
+   */
+  async startRecording(): Promise<string> {
+    return await invoke<string>('start_recording', { accountId: this.accountId });
+  }
+
+  /**
+   * 停止录制，返回录制文件路径（未在录制时为 null）
+   */
+  async stopRecording(): Promise<string | null> {
+    return await invoke<string | null>('stop_recording', { accountId: this.accountId });
+  }
+
+  /**
+   * 列出当前账号的录制文件
+   */
+  async listRecordings(): Promise<RecordingFile[]> {
+    return await invoke<RecordingFile[]>('list_recordings', { accountId: this.accountId });
+  }
+
+  /**
+   * 获取已保存的通知（按事件时间倒序）
+   */
+  async getNotices(query: NoticeQuery = {}): Promise<NoticeRecord[]> {
+    return await invoke<NoticeRecord[]>('get_notices', {
+      accountId: this.accountId,
+      ...query,
+    });
+  }
+
   /**
    * 监听原始消息（非标准格式）
    */
@@ -198,15 +921,15 @@ class RunbotService {
   /**
    * 获取机器人状态
    */
-  async getStatus(): Promise<void> {
-    await this.sendMessage('get_status', {});
+  async getStatus(): Promise<{ online?: boolean; good: boolean; [key: string]: any }> {
+    return await this.invokeAction('get_status');
   }
 
   /**
    * 获取用户状态（在线、隐身、离线等）
    */
-  async getUserStatus(userId: number): Promise<void> {
-    await this.sendMessage('get_user_status', {
+  async getUserStatus(userId: number): Promise<{ user_id: number; status: number }> {
+    return await this.sendMessageWithResponse('get_user_status', {
       user_id: userId,
     });
   }
@@ -295,53 +1018,70 @@ class RunbotService {
   /**
    * 获取好友列表
    */
-  async getFriendList(): Promise<void> {
-    await this.sendMessage('get_friend_list', {});
+  async getFriendList(): Promise<Contact[]> {
+    const data = await this.invokeAction<any[]>('get_friend_list');
+    return data.map((item) => ({
+      userId: item.user_id,
+      nickname: item.nickname || `用户 ${item.user_id}`,
+      remark: item.remark,
+    }));
   }
 
   /**
    * 获取群列表
    */
-  async getGroupList(): Promise<void> {
-    await this.sendMessage('get_group_list', {});
+  async getGroupList(): Promise<Group[]> {
+    const data = await this.invokeAction<any[]>('get_group_list');
+    return data.map((item) => ({
+      groupId: item.group_id,
+      groupName: item.group_name || `群组 ${item.group_id}`,
+      memberCount: item.member_count,
+    }));
   }
 
   /**
    * 获取群信息
    */
-  async getGroupInfo(groupId: number, noCache: boolean = false): Promise<void> {
-    await this.sendMessage('get_group_info', {
+  async getGroupInfo(groupId: number, noCache: boolean = false): Promise<Group> {
+    const data = await this.invokeAction<any>('get_group_info', {
       group_id: groupId,
       no_cache: noCache,
     });
+    return {
+      groupId: data.group_id,
+      groupName: data.group_name || `群组 ${data.group_id}`,
+      memberCount: data.member_count,
+    };
   }
 
   /**
    * 获取群成员列表
    */
-  async getGroupMemberList(groupId: number): Promise<void> {
+  async getGroupMemberList(groupId: number): Promise<GroupMemberInfo[]> {
     console.log(`[RunbotService] 请求获取群 ${groupId} 的成员列表`);
-    await this.sendMessage('get_group_member_list', {
+    const data = await this.invokeAction<any[]>('get_group_member_list', {
       group_id: groupId,
     });
+    return data.map((item) => toGroupMemberInfo(groupId, item));
   }
 
   /**
    * 获取群成员信息
    */
-  async getGroupMemberInfo(groupId: number, userId: number, noCache: boolean = false): Promise<void> {
-    await this.sendMessage('get_group_member_info', {
+  async getGroupMemberInfo(groupId: number, userId: number, noCache: boolean = false): Promise<GroupMemberInfo> {
+    const data = await this.invokeAction<any>('get_group_member_info', {
       group_id: groupId,
       user_id: userId,
       no_cache: noCache,
     });
+    return toGroupMemberInfo(groupId, data);
   }
 
   /**
    * 撤回消息
    */
   async deleteMessage(messageId: number): Promise<void> {
-    await this.sendMessage('delete_msg', {
+    await this.invokeAction('delete_msg', {
       message_id: messageId,
     });
   }
@@ -349,8 +1089,8 @@ class RunbotService {
   /**
    * 获取消息
    */
-  async getMsg(messageId: number): Promise<void> {
-    await this.sendMessage('get_msg', {
+  async getMsg(messageId: number): Promise<any> {
+    return await this.invokeAction<any>('get_msg', {
       message_id: messageId,
     });
   }
@@ -379,7 +1119,7 @@ class RunbotService {
    * 设置群名
    */
   async setGroupName(groupId: number, groupName: string): Promise<void> {
-    await this.sendMessage('set_group_name', {
+    await this.invokeAction('set_group_name', {
       group_id: groupId,
       group_name: groupName,
     });
@@ -389,7 +1129,7 @@ class RunbotService {
    * 设置群成员名片
    */
   async setGroupCard(groupId: number, userId: number, card: string): Promise<void> {
-    await this.sendMessage('set_group_card', {
+    await this.invokeAction('set_group_card', {
       group_id: groupId,
       user_id: userId,
       card: card,
@@ -400,7 +1140,7 @@ class RunbotService {
    * 群组踢人
    */
   async setGroupKick(groupId: number, userId: number, rejectAddRequest: boolean = false): Promise<void> {
-    await this.sendMessage('set_group_kick', {
+    await this.invokeAction('set_group_kick', {
       group_id: groupId,
       user_id: userId,
       reject_add_request: rejectAddRequest,
@@ -411,7 +1151,7 @@ class RunbotService {
    * 群组禁言
    */
   async setGroupBan(groupId: number, userId: number, duration: number = 30 * 60): Promise<void> {
-    await this.sendMessage('set_group_ban', {
+    await this.invokeAction('set_group_ban', {
       group_id: groupId,
       user_id: userId,
       duration: duration,
@@ -422,7 +1162,7 @@ class RunbotService {
    * 设置群管理员
    */
   async setGroupAdmin(groupId: number, userId: number, enable: boolean = true): Promise<void> {
-    await this.sendMessage('set_group_admin', {
+    await this.invokeAction('set_group_admin', {
       group_id: groupId,
       user_id: userId,
       enable: enable,
@@ -436,7 +1176,7 @@ class RunbotService {
    * @param remark 好友备注（仅在同意时有效）
    */
   async setFriendAddRequest(flag: string, approve: boolean, remark: string = ''): Promise<void> {
-    await this.sendMessage('set_friend_add_request', {
+    await this.invokeAction('set_friend_add_request', {
       flag: flag,
       approve: approve,
       remark: remark,
@@ -456,7 +1196,7 @@ class RunbotService {
     approve: boolean,
     reason: string = ''
   ): Promise<void> {
-    await this.sendMessage('set_group_add_request', {
+    await this.invokeAction('set_group_add_request', {
       flag: flag,
       sub_type: subType,
       approve: approve,
@@ -464,6 +1204,145 @@ class RunbotService {
     });
   }
 
+  /**
+   * 同意请求（后端调用 OneBot API 成功后更新请求状态）
+   * @param flag 请求标识
+   * @param remark 好友备注（仅好友请求有效）
+   */
+  async approveRequest(flag: string, remark?: string): Promise<void> {
+    await invoke('approve_request', { accountId: this.accountId, flag, remark });
+  }
+
+  /**
+   * 拒绝请求（后端调用 OneBot API 成功后更新请求状态）
+   * @param flag 请求标识
+   * @param reason 拒绝理由（仅群请求有效）
+   */
+  async rejectRequest(flag: string, reason?: string): Promise<void> {
+    await invoke('reject_request', { accountId: this.accountId, flag, reason });
+  }
+
+  /**
+   * 获取请求自动审核规则
+   */
+  async getReviewRules(): Promise<ReviewRule[]> {
+    return await invoke<ReviewRule[]>('get_review_rules', { accountId: this.accountId });
+  }
+
+  /**
+   * 保存请求自动审核规则（整体替换）
+   */
+  async setReviewRules(rules: ReviewRule[]): Promise<void> {
+    await invoke('set_review_rules', { accountId: this.accountId, rules });
+  }
+
+  /**
+   * 获取自动回复规则
+   */
+  async getAutoReplyRules(): Promise<AutoReplyRule[]> {
+    return await invoke<AutoReplyRule[]>('get_auto_reply_rules', { accountId: this.accountId });
+  }
+
+  /**
+   * 新建（不带 id）或更新自动回复规则
+   */
+  async saveAutoReplyRule(rule: AutoReplyRule): Promise<AutoReplyRule> {
+    return await invoke<AutoReplyRule>('save_auto_reply_rule', { accountId: this.accountId, rule });
+  }
+
+  /**
+   * 启用或停用自动回复规则
+   */
+  async setAutoReplyRuleEnabled(id: number, enabled: boolean): Promise<void> {
+    await invoke('set_auto_reply_rule_enabled', { accountId: this.accountId, id, enabled });
+  }
+
+  /**
+   * 删除自动回复规则
+   */
+  async deleteAutoReplyRule(id: number): Promise<void> {
+    await invoke('delete_auto_reply_rule', { accountId: this.accountId, id });
+  }
+
+  /**
+   * 获取脚本目录
+   */
+  async getScriptsDir(): Promise<string> {
+    return await invoke<string>('get_scripts_dir', { accountId: this.accountId });
+  }
+
+  /**
+   * 获取已加载的脚本
+   */
+  async listScripts(): Promise<ScriptInfo[]> {
+    return await invoke<ScriptInfo[]>('list_scripts', { accountId: this.accountId });
+  }
+
+  /**
+   * 重新加载全部脚本
+   */
+  async reloadScripts(): Promise<ScriptInfo[]> {
+    return await invoke<ScriptInfo[]>('reload_scripts', { accountId: this.accountId });
+  }
+
+  /**
+   * 获取最近的脚本日志
+   */
+  async getScriptLogs(): Promise<ScriptLog[]> {
+    return await invoke<ScriptLog[]>('get_script_logs', { accountId: this.accountId });
+  }
+
+  /**
+   * 获取插件目录
+   */
+  async getPluginsDir(): Promise<string> {
+    return await invoke<string>('get_plugins_dir', { accountId: this.accountId });
+  }
+
+  /**
+   * 获取插件列表
+   */
+  async listPlugins(): Promise<PluginInfo[]> {
+    return await invoke<PluginInfo[]>('list_plugins', { accountId: this.accountId });
+  }
+
+  /**
+   * 重新加载全部插件
+   */
+  async reloadPlugins(): Promise<PluginInfo[]> {
+    return await invoke<PluginInfo[]>('reload_plugins', { accountId: this.accountId });
+  }
+
+  /**
+   * 启用（同意清单中的全部权限）或停用插件
+   *
+   * 启用时需要传入界面上显示的指纹，插件内容在此期间变化时后端会拒绝启用
+   */
+  async setPluginEnabled(pluginId: string, enabled: boolean, fingerprint?: string): Promise<PluginInfo[]> {
+    return await invoke<PluginInfo[]>('set_plugin_enabled', { accountId: this.accountId, pluginId, enabled, fingerprint });
+  }
+
+  /**
+   * 获取最近的插件日志
+   */
+  async getPluginLogs(): Promise<PluginLog[]> {
+    return await invoke<PluginLog[]>('get_plugin_logs', { accountId: this.accountId });
+  }
+
+  /**
+   * 批量同意请求
+   */
+  async approveRequests(flags: string[], remark?: string): Promise<RequestResult[]> {
+    return await invoke<RequestResult[]>('approve_requests', { accountId: this.accountId, flags, remark });
+  }
+
+  /**
+   * 批量拒绝请求
+   */
+  async rejectRequests(flags: string[], reason?: string): Promise<RequestResult[]> {
+    return await invoke<RequestResult[]>('reject_requests', { accountId: this.accountId, flags, reason });
+  }
+
   /**
    * 获取合并转发消息内容
    * @param id 合并转发消息 ID
@@ -472,7 +1351,7 @@ class RunbotService {
     console.log('[RunbotService] getForwardMessage 开始, id:', id);
     try {
       // 直接调用 Rust 后端的方法
-      const result = await invoke('get_forward_message', { id });
+      const result = await invoke('get_forward_message', { accountId: this.accountId, id });
       console.log('[RunbotService] getForwardMessage 成功, result:', result);
       return result;
     } catch (error) {
@@ -489,7 +1368,7 @@ class RunbotService {
   async debugGetGroupInfo(groupId: number, noCache: boolean = false): Promise<any> {
     console.log('[RunbotService] 调用 get_group_info, groupId:', groupId, 'noCache:', noCache);
     try {
-      const result = await this.sendMessageWithResponse('get_group_info', {
+      const result = await this.invokeAction<any>('get_group_info', {
         group_id: groupId,
         no_cache: noCache,
       });
@@ -507,7 +1386,7 @@ class RunbotService {
   async debugGetGroupDetailInfo(groupId: number): Promise<any> {
     console.log('[RunbotService] 调用 get_group_detail_info, groupId:', groupId);
     try {
-      const result = await invoke('get_group_detail_info', { groupId });
+      const result = await invoke('get_group_detail_info', { accountId: this.accountId, groupId });
       console.log('[RunbotService] get_group_detail_info 返回结果:', JSON.stringify(result, null, 2));
       return result;
     } catch (error) {
@@ -522,7 +1401,7 @@ class RunbotService {
   async debugGetGroupInfoEx(groupId: number, noCache: boolean = false): Promise<any> {
     console.log('[RunbotService] 调用 get_group_info_ex, groupId:', groupId, 'noCache:', noCache);
     try {
-      const result = await invoke('get_group_info_ex', { groupId, noCache });
+      const result = await invoke('get_group_info_ex', { accountId: this.accountId, groupId, noCache });
       console.log('[RunbotService] get_group_info_ex 返回结果:', JSON.stringify(result, null, 2));
       return result;
     } catch (error) {
@@ -538,8 +1417,8 @@ class RunbotService {
    * @param groupId 群号
    * @param isDismiss 是否解散（仅群主可用）
    */
-  async setGroupLeave(groupId: number, isDismiss: boolean = false): Promise<any> {
-    return await this.sendMessageWithResponse('set_group_leave', {
+  async setGroupLeave(groupId: number, isDismiss: boolean = false): Promise<void> {
+    await this.invokeAction('set_group_leave', {
       group_id: groupId,
       is_dismiss: isDismiss,
     });
@@ -636,6 +1515,21 @@ class RunbotService {
       user_id: userId,
     });
   }
+
+  /**
+   * 将 CQ 码字符串解析为消息段数组（处理 &amp; &#91; &#93; &#44; 转义）
+   */
+  async parseCQCode(message: string): Promise<MessageSegment[]> {
+    return await invoke<MessageSegment[]>('parse_cqcode', { message });
+  }
+
+  /**
+   * 将消息渲染为 CQ 码字符串
+   * @param message 消息段数组、单个消息段或 CQ 码字符串
+   */
+  async renderCQCode(message: MessageSegment[] | MessageSegment | string): Promise<string> {
+    return await invoke<string>('render_cqcode', { message });
+  }
 }
 
 export const runbotService = new RunbotService();
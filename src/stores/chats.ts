@@ -5,10 +5,10 @@
 
 import { reactive } from 'vue';
 import { getMessages } from '../services/storage';
-import { parseCQCode } from '../utils/cqcode';
 import { getFaceDisplayText } from '../utils/qq-face';
 import { getContactName, getGroupName } from './contacts';
-import type { OneBotMessage } from '../services/runbot';
+import { runbotService, type OneBotMessage } from '../services/runbot';
+import { avatarUrl } from '../services/avatar';
 
 export interface ChatItem {
   id: string;
@@ -33,9 +33,9 @@ const state = reactive<{
 });
 
 /**
- * 格式化消息预览（将图片和表情替换为文本）
+ * 格式化消息预览（将图片和表情替换为文本，CQ 码由后端 parse_cqcode 解析）
  */
-function formatMessagePreview(message: string, fullMessage?: OneBotMessage): string {
+async function formatMessagePreview(message: string, fullMessage?: OneBotMessage): Promise<string> {
   if (!message) {
     // 如果消息为空，尝试从完整消息对象中生成预览
     if (fullMessage) {
@@ -77,12 +77,12 @@ function formatMessagePreview(message: string, fullMessage?: OneBotMessage): str
     return '';
   }
   
-  const segments = parseCQCode(message);
+  const segments = await runbotService.parseCQCode(message);
   const parts: string[] = [];
   
   for (const segment of segments) {
-    if (segment.type === 'text' && segment.text) {
-      parts.push(segment.text);
+    if (segment.type === 'text' && segment.data.text) {
+      parts.push(segment.data.text);
     } else if (segment.type === 'image') {
       // 检查是否是动画表情（sub_type=1 或有 summary）
       const subType = segment.data.sub_type || '0';
@@ -142,6 +142,8 @@ export async function updateChatList(selfId?: number): Promise<void> {
 
     // 按用户/群组分组
     const chatMap = new Map<string, ChatItem>();
+    // 每个对话的最后一条消息（分组后统一生成预览）
+    const lastMessages = new Map<string, OneBotMessage>();
 
     messages.forEach((msg) => {
       let chatId: string;
@@ -187,9 +189,9 @@ export async function updateChatList(selfId?: number): Promise<void> {
         
         // 设置头像 URL
         if (chatType === 'private' && msg.user_id) {
-          chatItem.avatar = `asset://avatar/user/${msg.user_id}.png`;
+          chatItem.avatar = avatarUrl('user', msg.user_id);
         } else if (chatType === 'group' && msg.group_id) {
-          chatItem.avatar = `asset://avatar/group/${msg.group_id}.png`;
+          chatItem.avatar = avatarUrl('group', msg.group_id);
         }
         
         chatMap.set(chatId, chatItem);
@@ -208,11 +210,15 @@ export async function updateChatList(selfId?: number): Promise<void> {
       // 更新最后一条消息和时间
       if (!chat.lastTime || msg.time > chat.lastTime) {
         chat.lastTime = msg.time;
-        const rawMessage = msg.message || msg.raw_message || '';
-        chat.lastMessage = formatMessagePreview(rawMessage, msg);
+        lastMessages.set(chatId, msg);
       }
     });
 
+    await Promise.all(Array.from(lastMessages, async ([chatId, msg]) => {
+      const rawMessage = msg.message || msg.raw_message || '';
+      chatMap.get(chatId)!.lastMessage = await formatMessagePreview(rawMessage, msg);
+    }));
+
     // 转换为数组并按时间排序
     state.chats = Array.from(chatMap.values()).sort((a, b) => {
       const timeA = a.lastTime || 0;
@@ -279,9 +285,9 @@ export function updateChatFromMessage(message: OneBotMessage, selfId?: number):
     
     // 设置头像
     if (chatType === 'private' && message.user_id) {
-      chat.avatar = `asset://avatar/user/${message.user_id}.png`;
+      chat.avatar = avatarUrl('user', message.user_id);
     } else if (chatType === 'group' && message.group_id) {
-      chat.avatar = `asset://avatar/group/${message.group_id}.png`;
+      chat.avatar = avatarUrl('group', message.group_id);
     }
     
     state.chats.push(chat);
@@ -298,7 +304,15 @@ export function updateChatFromMessage(message: OneBotMessage, selfId?: number):
   const rawMessage = message.message || message.raw_message || '';
   if (!chat.lastTime || message.time > chat.lastTime) {
     chat.lastTime = message.time;
-    chat.lastMessage = formatMessagePreview(rawMessage, message);
+    // 预览异步生成，期间如果有更新的消息则不再覆盖
+    formatMessagePreview(rawMessage, message)
+      .then((preview) => {
+        const current = getChat(chatId);
+        if (current && current.lastTime === message.time) {
+          current.lastMessage = preview;
+        }
+      })
+      .catch((error) => console.error('生成消息预览失败:', error));
   }
   
   // 如果不是自己发送的消息，增加未读数
@@ -1,6 +1,5 @@
 import { ref, computed } from 'vue';
 import { 
-  updateRequestStatus as updateRequestStatusDb, 
   getRequests as getRequestsDb, 
   clearHistoryRequests as clearHistoryRequestsDb,
   markRequestRead as markRequestReadDb,
@@ -8,6 +7,8 @@ import {
 } from '../services/request-storage';
 import { getConnectionState } from './connection';
 
+export type RequestStatus = 'pending' | 'approved' | 'rejected' | 'ignored' | 'expired';
+
 export interface RequestItem {
   id: string;
   time: number;
@@ -20,8 +21,13 @@ export interface RequestItem {
   flag: string;
   group_id?: number;
   group_name?: string;
-  status: 'pending' | 'approved' | 'rejected' | 'ignored';
+  status: RequestStatus;
   is_read?: boolean;
+  review_rule?: string;   // 自动审核命中的规则
+  review_action?: 'approve' | 'reject' | 'pending';
+  review_error?: string;  // 自动处理失败时的错误
+  handled_by?: string;    // 最后一次处理人：manual、rule:规则名、server
+  handled_at?: number;
 }
 
 const requests = ref<RequestItem[]>([]);
@@ -41,16 +47,11 @@ export const useRequestsStore = () => {
     }
   };
 
-  const updateRequestStatus = async (flag: string, status: 'approved' | 'rejected') => {
+  // 数据库中的状态由后端在处理请求时更新，这里只同步内存中的列表
+  const updateRequestStatus = (flag: string, status: 'approved' | 'rejected') => {
     const request = requests.value.find(r => r.flag === flag);
     if (request) {
       request.status = status;
-      // 更新数据库
-      try {
-        await updateRequestStatusDb(flag, status, getSelfId());
-      } catch (error) {
-        console.error('更新请求状态到数据库失败:', error);
-      }
     }
   };
 